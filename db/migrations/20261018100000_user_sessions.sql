@@ -0,0 +1,13 @@
+-- Add migration script here
+
+CREATE TABLE user_sessions (
+    user_session_id TEXT NOT NULL PRIMARY KEY,
+    user_session_user_id INTEGER NOT NULL REFERENCES users(user_id) ON DELETE CASCADE,
+    user_session_store_id TEXT,
+    user_session_created_at TEXT NOT NULL,
+    user_session_last_used TEXT NOT NULL,
+    user_session_user_agent TEXT,
+    user_session_ip_address TEXT
+);
+
+CREATE INDEX idx_user_sessions_user_id ON user_sessions(user_session_user_id);
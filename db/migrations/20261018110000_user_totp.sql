@@ -0,0 +1,17 @@
+-- Add migration script here
+
+CREATE TABLE user_totp (
+    user_totp_user_id INTEGER NOT NULL PRIMARY KEY REFERENCES users(user_id) ON DELETE CASCADE,
+    user_totp_secret TEXT NOT NULL,
+    user_totp_enabled INTEGER NOT NULL DEFAULT 0,
+    user_totp_last_used_step INTEGER,
+    user_totp_created_at TEXT NOT NULL
+);
+
+CREATE TABLE user_recovery_codes (
+    user_recovery_code_id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_recovery_code_user_id INTEGER NOT NULL REFERENCES users(user_id) ON DELETE CASCADE,
+    user_recovery_code_hash TEXT NOT NULL
+);
+
+CREATE INDEX idx_user_recovery_codes_user_id ON user_recovery_codes(user_recovery_code_user_id);
@@ -0,0 +1,20 @@
+-- Add migration script here
+
+-- A workspace is a users row flagged as Workspace, so it can own models, groups, labels and resources
+-- through the existing *_user_id columns. Members get access to everything it owns.
+CREATE TABLE workspace_members (
+    workspace_member_workspace_id INTEGER NOT NULL REFERENCES users(user_id) ON DELETE CASCADE,
+    workspace_member_user_id INTEGER NOT NULL REFERENCES users(user_id) ON DELETE CASCADE,
+    workspace_member_role INTEGER NOT NULL DEFAULT 0,
+    workspace_member_added_at TEXT NOT NULL,
+    PRIMARY KEY (workspace_member_workspace_id, workspace_member_user_id)
+);
+
+CREATE INDEX idx_workspace_members_user_id ON workspace_members(workspace_member_user_id);
+
+-- Every owner a user can reach, with the role they hold on it.
+-- 0 = viewer, 1 = editor, 2 = owner. Every user owns their own entities.
+CREATE VIEW user_access AS
+    SELECT user_id AS access_user_id, user_id AS access_owner_id, 2 AS access_role FROM users
+    UNION ALL
+    SELECT workspace_member_user_id AS access_user_id, workspace_member_workspace_id AS access_owner_id, workspace_member_role AS access_role FROM workspace_members;
@@ -0,0 +1,6 @@
+-- Add migration script here
+
+-- Models derived from another model (e.g. sliced G-code) point back at the model they were made from
+ALTER TABLE models ADD model_source_model_id INTEGER NULL REFERENCES models(model_id) ON DELETE SET NULL;
+
+CREATE INDEX idx_models_source_model_id ON models(model_source_model_id);
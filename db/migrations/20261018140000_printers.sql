@@ -0,0 +1,27 @@
+-- Add migration script here
+
+CREATE TABLE printers (
+    printer_id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    printer_user_id INTEGER NOT NULL REFERENCES users(user_id) ON DELETE CASCADE,
+    printer_name TEXT NOT NULL,
+    -- 0 = OctoPrint, 1 = Moonraker, 2 = PrusaLink
+    printer_type INTEGER NOT NULL,
+    printer_base_url TEXT NOT NULL,
+    printer_api_key TEXT NOT NULL,
+    printer_created_at TEXT NOT NULL
+);
+
+CREATE INDEX idx_printers_user_id ON printers(printer_user_id);
+
+-- Files sent to a printer, so finished prints can be traced back to the model they came from
+CREATE TABLE print_jobs (
+    print_job_id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    print_job_printer_id INTEGER NOT NULL REFERENCES printers(printer_id) ON DELETE CASCADE,
+    print_job_model_id INTEGER NULL REFERENCES models(model_id) ON DELETE SET NULL,
+    print_job_file_name TEXT NOT NULL,
+    -- 0 = Uploaded, 1 = Printing, 2 = Finished, 3 = Failed, 4 = Cancelled
+    print_job_state INTEGER NOT NULL,
+    print_job_created_at TEXT NOT NULL
+);
+
+CREATE INDEX idx_print_jobs_printer_id ON print_jobs(print_job_printer_id);
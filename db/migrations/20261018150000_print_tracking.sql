@@ -0,0 +1,10 @@
+-- Add migration script here
+
+ALTER TABLE print_jobs ADD print_job_started_at TEXT NULL;
+ALTER TABLE print_jobs ADD print_job_finished_at TEXT NULL;
+ALTER TABLE print_jobs ADD print_job_duration INTEGER NULL;
+
+-- Outcome of the most recent print of the model, uses the same states as print_job_state
+ALTER TABLE models ADD model_last_print_at TEXT NULL;
+ALTER TABLE models ADD model_last_print_duration INTEGER NULL;
+ALTER TABLE models ADD model_last_print_state INTEGER NULL;
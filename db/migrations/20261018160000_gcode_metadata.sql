@@ -0,0 +1,17 @@
+-- Add migration script here
+
+-- Metadata read from the comments slicers write into G-code, one row per blob
+CREATE TABLE gcode_metadata (
+    gcode_metadata_blob_id INTEGER PRIMARY KEY NOT NULL,
+    gcode_metadata_slicer TEXT NULL,
+    gcode_metadata_slicer_version TEXT NULL,
+    gcode_metadata_print_time INTEGER NULL,
+    gcode_metadata_filament_mm REAL NULL,
+    gcode_metadata_filament_g REAL NULL,
+    gcode_metadata_filament_cost REAL NULL,
+    gcode_metadata_nozzle_diameter REAL NULL,
+    gcode_metadata_layer_height REAL NULL,
+    gcode_metadata_printer_model TEXT NULL,
+    gcode_metadata_material_type TEXT NULL,
+    FOREIGN KEY (gcode_metadata_blob_id) REFERENCES blobs(blob_id) ON DELETE CASCADE
+);
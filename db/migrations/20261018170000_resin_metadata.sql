@@ -0,0 +1,14 @@
+-- Add migration script here
+
+-- Print settings read from the header of sliced resin files, one row per blob
+CREATE TABLE resin_metadata (
+    resin_metadata_blob_id INTEGER PRIMARY KEY NOT NULL,
+    resin_metadata_printer_model TEXT NULL,
+    resin_metadata_layer_count INTEGER NULL,
+    resin_metadata_layer_height REAL NULL,
+    resin_metadata_exposure_time REAL NULL,
+    resin_metadata_bottom_exposure_time REAL NULL,
+    resin_metadata_bottom_layer_count INTEGER NULL,
+    resin_metadata_print_time INTEGER NULL,
+    FOREIGN KEY (resin_metadata_blob_id) REFERENCES blobs(blob_id) ON DELETE CASCADE
+);
@@ -0,0 +1,8 @@
+-- Add migration script here
+
+-- Parsed 3MF project metadata as JSON, so the archive doesn't have to be read again on every request
+CREATE TABLE threemf_metadata (
+    threemf_metadata_blob_id INTEGER PRIMARY KEY NOT NULL,
+    threemf_metadata_json TEXT NOT NULL,
+    FOREIGN KEY (threemf_metadata_blob_id) REFERENCES blobs(blob_id) ON DELETE CASCADE
+);
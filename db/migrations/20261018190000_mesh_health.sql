@@ -0,0 +1,9 @@
+-- Add migration script here
+
+-- Result of the mesh analysis of a blob. The full report is kept as JSON, the status is split out so models can be filtered on it
+CREATE TABLE mesh_health (
+    mesh_health_blob_id INTEGER PRIMARY KEY NOT NULL,
+    mesh_health_status INTEGER NOT NULL,
+    mesh_health_report_json TEXT NOT NULL,
+    FOREIGN KEY (mesh_health_blob_id) REFERENCES blobs(blob_id) ON DELETE CASCADE
+);
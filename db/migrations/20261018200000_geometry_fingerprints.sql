@@ -0,0 +1,16 @@
+-- Add migration script here
+
+-- Shape of a mesh independent of its file bytes, used to find the same part imported in different files
+CREATE TABLE geometry_fingerprints (
+    fingerprint_blob_id INTEGER PRIMARY KEY NOT NULL,
+    fingerprint_hash TEXT NOT NULL,
+    fingerprint_vertex_count INTEGER NOT NULL,
+    fingerprint_volume REAL NOT NULL,
+    fingerprint_area REAL NOT NULL,
+    fingerprint_moment_1 REAL NOT NULL,
+    fingerprint_moment_2 REAL NOT NULL,
+    fingerprint_moment_3 REAL NOT NULL,
+    FOREIGN KEY (fingerprint_blob_id) REFERENCES blobs(blob_id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_geometry_fingerprints_hash ON geometry_fingerprints(fingerprint_hash);
@@ -0,0 +1,8 @@
+-- Add migration script here
+
+-- 64-bit difference hash of the thumbnail of a blob, used to find models that look alike
+CREATE TABLE thumbnail_hashes (
+    thumbnail_hash_blob_id INTEGER PRIMARY KEY NOT NULL,
+    thumbnail_hash INTEGER NOT NULL,
+    FOREIGN KEY (thumbnail_hash_blob_id) REFERENCES blobs(blob_id) ON DELETE CASCADE
+);
@@ -0,0 +1,12 @@
+-- Add migration script here
+
+-- Workspaces get an owner role above editor. The member that was added first is most likely the one that created it
+UPDATE workspace_members SET workspace_member_role = 2
+WHERE workspace_member_role = 1
+  AND workspace_member_user_id = (
+    SELECT first_member.workspace_member_user_id FROM workspace_members AS first_member
+    WHERE first_member.workspace_member_workspace_id = workspace_members.workspace_member_workspace_id
+      AND first_member.workspace_member_role >= 1
+    ORDER BY first_member.workspace_member_added_at ASC, first_member.workspace_member_user_id ASC
+    LIMIT 1
+  );
@@ -0,0 +1,4 @@
+-- Add migration script here
+
+-- Workspace names move into their own namespace in user_name, so they no longer clash with the names of users
+UPDATE users SET user_name = 'workspace:' || user_name WHERE (user_permissions & 8) != 0;
@@ -0,0 +1,172 @@
+use std::collections::HashMap;
+
+use crate::{DbError, db_context::DbContext, model::{BackupBlob, BackupGroup, BackupLabel, BackupModel, BackupResource, BackupUser, LibraryDump}};
+
+// Everything a backup needs from the database, for all users at once
+pub async fn get_library_dump(db: &DbContext) -> Result<LibraryDump, DbError> {
+    let users = sqlx::query!(
+        "SELECT user_id, user_name, user_email, user_created_at FROM users"
+    )
+    .fetch_all(db)
+    .await?
+    .into_iter()
+    .map(|row| BackupUser {
+        id: row.user_id,
+        username: row.user_name,
+        email: row.user_email,
+        created_at: row.user_created_at,
+    })
+    .collect();
+
+    let blobs = sqlx::query!(
+        "SELECT blob_id, blob_sha256, blob_filetype, blob_size, blob_added
+         FROM blobs
+         WHERE blob_id IN (SELECT model_blob_id FROM models)"
+    )
+    .fetch_all(db)
+    .await?
+    .into_iter()
+    .map(|row| BackupBlob {
+        id: row.blob_id,
+        sha256: row.blob_sha256,
+        filetype: row.blob_filetype,
+        size: row.blob_size,
+        added: row.blob_added,
+    })
+    .collect();
+
+    let resources = sqlx::query!(
+        r#"SELECT resource_id as "resource_id!", resource_user_id as "resource_user_id!", resource_name, resource_flags,
+                resource_created, resource_last_modified, resource_unique_global_id
+         FROM resources"#
+    )
+    .fetch_all(db)
+    .await?
+    .into_iter()
+    .map(|row| BackupResource {
+        id: row.resource_id,
+        user_id: row.resource_user_id,
+        name: row.resource_name,
+        flags: row.resource_flags,
+        created: row.resource_created,
+        last_modified: row.resource_last_modified,
+        unique_global_id: row.resource_unique_global_id,
+    })
+    .collect();
+
+    let groups = sqlx::query!(
+        r#"SELECT group_id as "group_id!", group_user_id as "group_user_id!", group_name, group_created, group_last_modified,
+                group_resource_id as "group_resource_id?", group_unique_global_id
+         FROM models_group"#
+    )
+    .fetch_all(db)
+    .await?
+    .into_iter()
+    .map(|row| BackupGroup {
+        id: row.group_id,
+        user_id: row.group_user_id,
+        name: row.group_name,
+        created: row.group_created,
+        last_modified: row.group_last_modified,
+        resource_id: row.group_resource_id,
+        unique_global_id: row.group_unique_global_id,
+    })
+    .collect();
+
+    let mut label_parents: HashMap<i64, Vec<i64>> = HashMap::new();
+
+    for row in sqlx::query!("SELECT child_label_id, parent_label_id FROM labels_labels").fetch_all(db).await? {
+        label_parents.entry(row.child_label_id).or_default().push(row.parent_label_id);
+    }
+
+    let mut label_keywords: HashMap<i64, Vec<String>> = HashMap::new();
+
+    for row in sqlx::query!("SELECT keyword_name, keyword_label_id FROM label_keywords").fetch_all(db).await? {
+        label_keywords.entry(row.keyword_label_id).or_default().push(row.keyword_name);
+    }
+
+    let labels = sqlx::query!(
+        r#"SELECT label_id as "label_id!", label_user_id as "label_user_id!", label_name, label_color, label_last_modified, label_unique_global_id
+         FROM labels"#
+    )
+    .fetch_all(db)
+    .await?
+    .into_iter()
+    .map(|row| BackupLabel {
+        id: row.label_id,
+        user_id: row.label_user_id,
+        name: row.label_name,
+        color: row.label_color,
+        last_modified: row.label_last_modified,
+        unique_global_id: row.label_unique_global_id,
+        parent_ids: label_parents.remove(&row.label_id).unwrap_or_default(),
+        keywords: label_keywords.remove(&row.label_id).unwrap_or_default(),
+    })
+    .collect();
+
+    let mut model_labels: HashMap<i64, Vec<i64>> = HashMap::new();
+
+    for row in sqlx::query!("SELECT label_id, model_id FROM models_labels").fetch_all(db).await? {
+        model_labels.entry(row.model_id).or_default().push(row.label_id);
+    }
+
+    let models = sqlx::query!(
+        r#"SELECT model_id as "model_id!", model_user_id as "model_user_id!", model_blob_id as "model_blob_id!", model_name, model_url, model_desc,
+                model_added, model_last_modified, model_flags, model_unique_global_id, model_group_id as "model_group_id?",
+                model_source_model_id as "model_source_model_id?"
+         FROM models"#
+    )
+    .fetch_all(db)
+    .await?
+    .into_iter()
+    .map(|row| BackupModel {
+        id: row.model_id,
+        user_id: row.model_user_id,
+        blob_id: row.model_blob_id,
+        name: row.model_name,
+        link: row.model_url,
+        description: row.model_desc,
+        added: row.model_added,
+        last_modified: row.model_last_modified,
+        flags: row.model_flags,
+        unique_global_id: row.model_unique_global_id,
+        group_id: row.model_group_id,
+        label_ids: model_labels.remove(&row.model_id).unwrap_or_default(),
+        source_model_id: row.model_source_model_id,
+    })
+    .collect();
+
+    Ok(LibraryDump {
+        users,
+        blobs,
+        resources,
+        groups,
+        labels,
+        models,
+    })
+}
+
+// Creation dates are set to now when adding rows, restores put back the original ones
+pub async fn set_model_added(db: &DbContext, model_id: i64, added: &str) -> Result<(), DbError> {
+    sqlx::query!("UPDATE models SET model_added = ? WHERE model_id = ?", added, model_id)
+        .execute(db)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn set_group_created(db: &DbContext, group_id: i64, created: &str) -> Result<(), DbError> {
+    sqlx::query!("UPDATE models_group SET group_created = ? WHERE group_id = ?", created, group_id)
+        .execute(db)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn set_resource_created(db: &DbContext, resource_id: i64, created: &str) -> Result<(), DbError> {
+    sqlx::query!("UPDATE resources SET resource_created = ? WHERE resource_id = ?", created, resource_id)
+        .execute(db)
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,75 @@
+use crate::{DbError, db_context::DbContext, model::{Blob, GcodeMetadata}};
+
+pub async fn get_gcode_metadata(db: &DbContext, blob_id: i64) -> Result<Option<GcodeMetadata>, DbError> {
+    let row = sqlx::query!(
+        "SELECT gcode_metadata_slicer, gcode_metadata_slicer_version, gcode_metadata_print_time, gcode_metadata_filament_mm,
+                gcode_metadata_filament_g, gcode_metadata_filament_cost, gcode_metadata_nozzle_diameter, gcode_metadata_layer_height,
+                gcode_metadata_printer_model, gcode_metadata_material_type
+         FROM gcode_metadata
+         WHERE gcode_metadata_blob_id = ?",
+        blob_id
+    )
+    .fetch_optional(db)
+    .await?;
+
+    Ok(row.map(|row| GcodeMetadata {
+        slicer: row.gcode_metadata_slicer,
+        slicer_version: row.gcode_metadata_slicer_version,
+        print_time_seconds: row.gcode_metadata_print_time,
+        filament_used_mm: row.gcode_metadata_filament_mm,
+        filament_used_g: row.gcode_metadata_filament_g,
+        filament_cost: row.gcode_metadata_filament_cost,
+        nozzle_diameter: row.gcode_metadata_nozzle_diameter,
+        layer_height: row.gcode_metadata_layer_height,
+        printer_model: row.gcode_metadata_printer_model,
+        material_type: row.gcode_metadata_material_type,
+    }))
+}
+
+pub async fn set_gcode_metadata(db: &DbContext, blob_id: i64, metadata: &GcodeMetadata) -> Result<(), DbError> {
+    sqlx::query!(
+        "INSERT OR REPLACE INTO gcode_metadata (gcode_metadata_blob_id, gcode_metadata_slicer, gcode_metadata_slicer_version, gcode_metadata_print_time,
+                gcode_metadata_filament_mm, gcode_metadata_filament_g, gcode_metadata_filament_cost, gcode_metadata_nozzle_diameter,
+                gcode_metadata_layer_height, gcode_metadata_printer_model, gcode_metadata_material_type)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        blob_id,
+        metadata.slicer,
+        metadata.slicer_version,
+        metadata.print_time_seconds,
+        metadata.filament_used_mm,
+        metadata.filament_used_g,
+        metadata.filament_cost,
+        metadata.nozzle_diameter,
+        metadata.layer_height,
+        metadata.printer_model,
+        metadata.material_type
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+// G-code blobs imported before metadata was extracted on import
+pub async fn get_gcode_blobs_without_metadata(db: &DbContext) -> Result<Vec<Blob>, DbError> {
+    let rows = sqlx::query!(
+        "SELECT blob_id, blob_sha256, blob_filetype, blob_size, blob_added, blob_path
+         FROM blobs
+         LEFT JOIN gcode_metadata ON blobs.blob_id = gcode_metadata.gcode_metadata_blob_id
+         WHERE blob_filetype IN ('gcode', 'gcode.zip', 'bgcode') AND gcode_metadata_blob_id IS NULL"
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| Blob {
+            id: row.blob_id,
+            sha256: row.blob_sha256,
+            filetype: row.blob_filetype,
+            size: row.blob_size,
+            added: row.blob_added,
+            disk_path: row.blob_path,
+        })
+        .collect())
+}
@@ -0,0 +1,90 @@
+use crate::{DbError, db_context::DbContext, model::{Blob, GeometryFingerprint, User}};
+
+pub async fn get_fingerprint(db: &DbContext, blob_id: i64) -> Result<Option<GeometryFingerprint>, DbError> {
+    let row = sqlx::query!(
+        "SELECT fingerprint_hash, fingerprint_vertex_count, fingerprint_volume, fingerprint_area,
+                fingerprint_moment_1, fingerprint_moment_2, fingerprint_moment_3
+         FROM geometry_fingerprints
+         WHERE fingerprint_blob_id = ?",
+        blob_id
+    )
+    .fetch_optional(db)
+    .await?;
+
+    Ok(row.map(|row| GeometryFingerprint {
+        hash: row.fingerprint_hash,
+        vertex_count: row.fingerprint_vertex_count,
+        volume: row.fingerprint_volume,
+        area: row.fingerprint_area,
+        moments: [row.fingerprint_moment_1, row.fingerprint_moment_2, row.fingerprint_moment_3],
+    }))
+}
+
+pub async fn set_fingerprint(db: &DbContext, blob_id: i64, fingerprint: &GeometryFingerprint) -> Result<(), DbError> {
+    sqlx::query!(
+        "INSERT OR REPLACE INTO geometry_fingerprints (fingerprint_blob_id, fingerprint_hash, fingerprint_vertex_count, fingerprint_volume,
+                fingerprint_area, fingerprint_moment_1, fingerprint_moment_2, fingerprint_moment_3)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+        blob_id,
+        fingerprint.hash,
+        fingerprint.vertex_count,
+        fingerprint.volume,
+        fingerprint.area,
+        fingerprint.moments[0],
+        fingerprint.moments[1],
+        fingerprint.moments[2]
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+// Fingerprints of all models the user can see, as (model id, fingerprint)
+pub async fn get_model_fingerprints(db: &DbContext, user: &User) -> Result<Vec<(i64, GeometryFingerprint)>, DbError> {
+    let rows = sqlx::query!(
+        "SELECT model_id as \"model_id!\", fingerprint_hash, fingerprint_vertex_count, fingerprint_volume, fingerprint_area,
+                fingerprint_moment_1, fingerprint_moment_2, fingerprint_moment_3
+         FROM models
+         INNER JOIN geometry_fingerprints ON models.model_blob_id = geometry_fingerprints.fingerprint_blob_id
+         WHERE models.model_user_id IN (SELECT access_owner_id FROM user_access WHERE access_user_id = ?)",
+        user.id
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| (row.model_id, GeometryFingerprint {
+            hash: row.fingerprint_hash,
+            vertex_count: row.fingerprint_vertex_count,
+            volume: row.fingerprint_volume,
+            area: row.fingerprint_area,
+            moments: [row.fingerprint_moment_1, row.fingerprint_moment_2, row.fingerprint_moment_3],
+        }))
+        .collect())
+}
+
+// Mesh blobs imported before fingerprints were computed on import
+pub async fn get_mesh_blobs_without_fingerprint(db: &DbContext) -> Result<Vec<Blob>, DbError> {
+    let rows = sqlx::query!(
+        "SELECT blob_id, blob_sha256, blob_filetype, blob_size, blob_added, blob_path
+         FROM blobs
+         LEFT JOIN geometry_fingerprints ON blobs.blob_id = geometry_fingerprints.fingerprint_blob_id
+         WHERE blob_filetype IN ('stl', 'stl.zip', 'obj', 'obj.zip', '3mf', 'step', 'step.zip') AND fingerprint_blob_id IS NULL"
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| Blob {
+            id: row.blob_id,
+            sha256: row.blob_sha256,
+            filetype: row.blob_filetype,
+            size: row.blob_size,
+            added: row.blob_added,
+            disk_path: row.blob_path,
+        })
+        .collect())
+}
@@ -186,6 +186,7 @@ pub async fn set_group_id_on_models(
     // TODO: Remove clone
     let now = time_now();
     let timestamp = update_timestamp.unwrap_or(&now);
+    model_db::ensure_models_editable(db, user, &model_ids).await?;
     let models = model_db::get_models_via_ids(db, user, model_ids.clone()).await?;
     let mut old_group_ids: Vec<i64> = models.iter().filter_map(|m| m.group.as_ref().map(|g| g.id)).unique().collect();
     let mut group_ids = get_unqiue_ids_from_group_ids(db, &old_group_ids).await?;
@@ -244,7 +245,7 @@ pub async fn edit_group(db: &DbContext, user : &User, group_id: i64, group_name:
     let timestamp = update_timestamp.unwrap_or(&now);
 
     sqlx::query!(
-        "UPDATE models_group SET group_name = ?, group_last_modified = ? WHERE group_id = ? AND group_user_id = ?",
+        "UPDATE models_group SET group_name = ?, group_last_modified = ? WHERE group_id = ? AND group_user_id IN (SELECT access_owner_id FROM user_access WHERE access_user_id = ? AND access_role >= 1)",
         group_name,
         timestamp,
         group_id,
@@ -262,7 +263,7 @@ pub async fn edit_group_global_id(db: &DbContext, user : &User, group_id: i64, u
     }
 
     sqlx::query!(
-        "UPDATE models_group SET group_unique_global_id = ? WHERE group_id = ? AND group_user_id = ?",
+        "UPDATE models_group SET group_unique_global_id = ? WHERE group_id = ? AND group_user_id IN (SELECT access_owner_id FROM user_access WHERE access_user_id = ? AND access_role >= 1)",
         unique_global_id,
         group_id,
         user.id
@@ -275,7 +276,7 @@ pub async fn edit_group_global_id(db: &DbContext, user : &User, group_id: i64, u
 
 pub async fn delete_group(db: &DbContext, user : &User, group_id: i64) -> Result<(), DbError> {
     sqlx::query!(
-        "DELETE FROM models_group WHERE group_id = ? AND group_user_id = ?",
+        "DELETE FROM models_group WHERE group_id = ? AND group_user_id IN (SELECT access_owner_id FROM user_access WHERE access_user_id = ? AND access_role >= 1)",
         group_id,
         user.id
     )
@@ -304,7 +305,7 @@ pub async fn get_group_count(db: &DbContext, user : &User, include_ungrouped_mod
     let mut group_count = 0;
 
     let group_query = sqlx::query!(
-        "SELECT COUNT(DISTINCT model_group_id) as count FROM models WHERE model_user_id = ?",
+        "SELECT COUNT(DISTINCT model_group_id) as count FROM models WHERE model_user_id IN (SELECT access_owner_id FROM user_access WHERE access_user_id = ?)",
         user.id
     )
     .fetch_one(db)
@@ -314,7 +315,7 @@ pub async fn get_group_count(db: &DbContext, user : &User, include_ungrouped_mod
 
     if include_ungrouped_models {
         let ungrouped_query = sqlx::query!(
-            "SELECT COUNT(*) as count FROM models WHERE model_user_id = ? AND model_group_id IS NULL",
+            "SELECT COUNT(*) as count FROM models WHERE model_user_id IN (SELECT access_owner_id FROM user_access WHERE access_user_id = ?) AND model_group_id IS NULL",
             user.id
         )
         .fetch_one(db)
@@ -347,7 +348,7 @@ pub async fn get_group_via_id(db: &DbContext, user : &User, group_id: i64) -> Re
 
 pub async fn set_last_updated_on_group(db: &DbContext, user: &User, group_id: i64, timestamp: &str) -> Result<(), DbError> {
     sqlx::query!(
-        "UPDATE models_group SET group_last_modified = ? WHERE group_id = ? AND group_user_id = ?",
+        "UPDATE models_group SET group_last_modified = ? WHERE group_id = ? AND group_user_id IN (SELECT access_owner_id FROM user_access WHERE access_user_id = ? AND access_role >= 1)",
         timestamp,
         group_id,
         user.id
@@ -364,7 +365,7 @@ pub async fn set_last_updated_on_groups(db: &DbContext, user: &User, group_ids:
     let formatted_query = format!(
         "UPDATE models_group
          SET group_last_modified = ?
-         WHERE group_id IN ({}) AND group_user_id = ?",
+         WHERE group_id IN ({}) AND group_user_id IN (SELECT access_owner_id FROM user_access WHERE access_user_id = ? AND access_role >= 1)",
         ids_placeholder
     );
 
@@ -62,7 +62,7 @@ pub async fn get_labels(db: &DbContext, user: &User, include_ungrouped_models :
           FROM labels as parent_labels
           LEFT JOIN labels_labels ON parent_labels.label_id = labels_labels.parent_label_id
           LEFT JOIN labels as child_labels ON labels_labels.child_label_id = child_labels.label_id
-          WHERE parent_labels.label_user_id = ?
+          WHERE parent_labels.label_user_id IN (SELECT access_owner_id FROM user_access WHERE access_user_id = ?)
           ORDER BY parent_labels.label_name ASC"
     )
     .bind(user.id)
@@ -147,7 +147,7 @@ pub async fn get_labels(db: &DbContext, user: &User, include_ungrouped_models :
 pub async fn get_unique_id_from_label_id(db: &DbContext, user: &User, label_id: i64) -> Result<String, DbError>
 {
     let row = sqlx::query!(
-        "SELECT label_unique_global_id FROM labels WHERE label_id = ? AND label_user_id = ?",
+        "SELECT label_unique_global_id FROM labels WHERE label_id = ? AND label_user_id IN (SELECT access_owner_id FROM user_access WHERE access_user_id = ? AND access_role >= 1)",
         label_id,
         user.id
     )
@@ -162,7 +162,7 @@ pub async fn get_unique_ids_from_label_ids(db: &DbContext, user: &User, label_id
     let ids_placeholder = join(label_ids.iter(), ",");
 
     let query = format!(
-        "SELECT label_id, label_unique_global_id FROM labels WHERE label_id IN ({}) AND label_user_id = ?",
+        "SELECT label_id, label_unique_global_id FROM labels WHERE label_id IN ({}) AND label_user_id IN (SELECT access_owner_id FROM user_access WHERE access_user_id = ? AND access_role >= 1)",
         ids_placeholder
     );
 
@@ -183,6 +183,8 @@ pub async fn get_unique_ids_from_label_ids(db: &DbContext, user: &User, label_id
 
 pub async fn add_labels_on_models(db: &DbContext, user: &User, label_ids: &[i64], model_ids: &[i64], update_timestamp : Option<&str>) -> Result<(), DbError>
 {
+    model_db::ensure_models_editable(db, user, model_ids).await?;
+
     for label_id in label_ids {
         // Permission check
         let _ = get_unique_id_from_label_id(db, user, *label_id).await?;
@@ -217,6 +219,8 @@ pub async fn remove_labels_from_models(db: &DbContext, user: &User, label_ids: &
         return Err(DbError::RowNotFound);
     }
 
+    model_db::ensure_models_editable(db, user, model_ids).await?;
+
     let joined_labels = join(label_ids.iter(), ",");
     let joined_models = join(model_ids.iter(), ",");
 
@@ -243,6 +247,8 @@ pub async fn remove_all_labels_from_models(db: &DbContext, user: &User, model_id
         return Err(DbError::RowNotFound);
     }
 
+    model_db::ensure_models_editable(db, user, model_ids).await?;
+
     let joined_models = join(models.iter().map(|f| f.id), ",");
 
     let formatted_query = format!(
@@ -287,7 +293,7 @@ pub async fn edit_label(db: &DbContext, user: &User, label_id: i64, name: &str,
     let timestamp = update_timestamp.unwrap_or(&now);
 
     sqlx::query!(
-        "UPDATE labels SET label_name = ?, label_color = ?, label_last_modified = ? WHERE label_id = ? AND label_user_id = ?",
+        "UPDATE labels SET label_name = ?, label_color = ?, label_last_modified = ? WHERE label_id = ? AND label_user_id IN (SELECT access_owner_id FROM user_access WHERE access_user_id = ? AND access_role >= 1)",
         name,
         color,
         timestamp,
@@ -307,7 +313,7 @@ pub async fn edit_label_global_id(db: &DbContext, user: &User, label_id: i64, un
     }
 
     sqlx::query!(
-        "UPDATE labels SET label_unique_global_id = ? WHERE label_id = ? AND label_user_id = ?",
+        "UPDATE labels SET label_unique_global_id = ? WHERE label_id = ? AND label_user_id IN (SELECT access_owner_id FROM user_access WHERE access_user_id = ? AND access_role >= 1)",
         unique_global_id,
         label_id,
         user.id
@@ -321,7 +327,7 @@ pub async fn edit_label_global_id(db: &DbContext, user: &User, label_id: i64, un
 pub async fn delete_label(db: &DbContext, user: &User, label_id: i64) -> Result<(), DbError>
 {
     sqlx::query!(
-        "DELETE FROM labels WHERE label_id = ? AND label_user_id = ?",
+        "DELETE FROM labels WHERE label_id = ? AND label_user_id IN (SELECT access_owner_id FROM user_access WHERE access_user_id = ? AND access_role >= 1)",
         label_id,
         user.id
     )
@@ -402,7 +408,7 @@ pub async fn remove_all_childs_from_label(db: &DbContext, user: &User, parent_la
 
 pub async fn set_last_updated_on_label(db: &DbContext, user: &User, label_id: i64, timestamp: &str) -> Result<(), DbError> {
     sqlx::query!(
-        "UPDATE labels SET label_last_modified = ? WHERE label_id = ? AND label_user_id = ?",
+        "UPDATE labels SET label_last_modified = ? WHERE label_id = ? AND label_user_id IN (SELECT access_owner_id FROM user_access WHERE access_user_id = ? AND access_role >= 1)",
         timestamp,
         label_id,
         user.id
@@ -417,7 +423,7 @@ pub async fn set_last_updated_on_labels(db: &DbContext, user: &User, label_ids:
     let ids_placeholder = join(label_ids.iter(), ",");
 
     let query = format!(
-        "UPDATE labels SET label_last_modified = ? WHERE label_id IN ({}) AND label_user_id = ?",
+        "UPDATE labels SET label_last_modified = ? WHERE label_id IN ({}) AND label_user_id IN (SELECT access_owner_id FROM user_access WHERE access_user_id = ? AND access_role >= 1)",
         ids_placeholder
     );
 
@@ -4,7 +4,7 @@ use crate::{DbError, db_context::DbContext, label_db::{self, set_last_updated_on
 
 pub async fn get_keywords_for_label(db: &DbContext, user: &User, label_id: i64) -> Result<Vec<LabelKeyword>, DbError> {
     let rows = sqlx::query!(
-        "SELECT keyword_id, keyword_name FROM label_keywords JOIN labels ON label_keywords.keyword_label_id = labels.label_id WHERE keyword_label_id = ? AND label_user_id = ?",
+        "SELECT keyword_id as \"keyword_id?\", keyword_name FROM label_keywords JOIN labels ON label_keywords.keyword_label_id = labels.label_id WHERE keyword_label_id = ? AND label_user_id IN (SELECT access_owner_id FROM user_access WHERE access_user_id = ?)",
         label_id,
         user.id
     )
@@ -24,7 +24,7 @@ pub async fn get_keywords_for_label(db: &DbContext, user: &User, label_id: i64)
 
 pub async fn get_all_keywords(db: &DbContext, user: &User) -> Result<IndexMap<i64, Vec<LabelKeyword>>, DbError> {
     let rows = sqlx::query!(
-        "SELECT keyword_id, keyword_name, keyword_label_id FROM label_keywords JOIN labels ON label_keywords.keyword_label_id = labels.label_id WHERE label_user_id = ?",
+        "SELECT keyword_id as \"keyword_id?\", keyword_name, keyword_label_id FROM label_keywords JOIN labels ON label_keywords.keyword_label_id = labels.label_id WHERE label_user_id IN (SELECT access_owner_id FROM user_access WHERE access_user_id = ?)",
         user.id
     )
     .fetch_all(db)
@@ -8,6 +8,17 @@ pub mod label_keyword_db;
 pub mod user_db;
 pub mod resource_db;
 pub mod share_db;
+pub mod user_session_db;
+pub mod user_totp_db;
+pub mod workspace_db;
+pub mod printer_db;
+pub mod gcode_metadata_db;
+pub mod resin_metadata_db;
+pub mod threemf_metadata_db;
+pub mod mesh_health_db;
+pub mod geometry_fingerprint_db;
+pub mod thumbnail_hash_db;
+pub mod backup_db;
 mod paginated_response;
 pub use paginated_response::PaginatedResponse;
 mod util;
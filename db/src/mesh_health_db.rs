@@ -0,0 +1,51 @@
+use crate::{DbError, db_context::DbContext, model::{Blob, MeshHealth}};
+
+pub async fn get_mesh_report_json(db: &DbContext, blob_id: i64) -> Result<Option<String>, DbError> {
+    let row = sqlx::query!(
+        "SELECT mesh_health_report_json FROM mesh_health WHERE mesh_health_blob_id = ?",
+        blob_id
+    )
+    .fetch_optional(db)
+    .await?;
+
+    Ok(row.map(|row| row.mesh_health_report_json))
+}
+
+pub async fn set_mesh_health(db: &DbContext, blob_id: i64, health: MeshHealth, report_json: &str) -> Result<(), DbError> {
+    let status = health.to_i64();
+
+    sqlx::query!(
+        "INSERT OR REPLACE INTO mesh_health (mesh_health_blob_id, mesh_health_status, mesh_health_report_json) VALUES (?, ?, ?)",
+        blob_id,
+        status,
+        report_json
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+// Mesh blobs imported before they were analysed on import
+pub async fn get_mesh_blobs_without_health(db: &DbContext) -> Result<Vec<Blob>, DbError> {
+    let rows = sqlx::query!(
+        "SELECT blob_id, blob_sha256, blob_filetype, blob_size, blob_added, blob_path
+         FROM blobs
+         LEFT JOIN mesh_health ON blobs.blob_id = mesh_health.mesh_health_blob_id
+         WHERE blob_filetype IN ('stl', 'stl.zip', 'obj', 'obj.zip', '3mf', 'step', 'step.zip') AND mesh_health_blob_id IS NULL"
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| Blob {
+            id: row.blob_id,
+            sha256: row.blob_sha256,
+            filetype: row.blob_filetype,
+            size: row.blob_size,
+            added: row.blob_added,
+            disk_path: row.blob_path,
+        })
+        .collect())
+}
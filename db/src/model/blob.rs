@@ -10,9 +10,15 @@ pub enum FileType {
     ZippedObj,
     Gcode,
     ZippedGcode,
+    Bgcode,
     Step,
     ZippedStep,
     Threemf,
+    Ctb,
+    Sl1,
+    Sl1s,
+    Goo,
+    Pwmx,
     Unknown
 }
 
@@ -47,6 +53,7 @@ impl FileType {
             f if f.ends_with("stl.zip") => FileType::ZippedStl,
             f if f.ends_with("obj") => FileType::Obj,
             f if f.ends_with("obj.zip") => FileType::ZippedObj,
+            f if f.ends_with("bgcode") => FileType::Bgcode,
             f if f.ends_with("gcode") => FileType::Gcode,
             f if f.ends_with("gcode.zip") => FileType::ZippedGcode,
             f if f.ends_with("step") => FileType::Step,
@@ -54,6 +61,11 @@ impl FileType {
             f if f.ends_with("step.zip") => FileType::ZippedStep,
             f if f.ends_with("stp.zip") => FileType::ZippedStep,
             f if f.ends_with("3mf") => FileType::Threemf,
+            f if f.ends_with("ctb") => FileType::Ctb,
+            f if f.ends_with("sl1") => FileType::Sl1,
+            f if f.ends_with("sl1s") => FileType::Sl1s,
+            f if f.ends_with("goo") => FileType::Goo,
+            f if f.ends_with("pwmx") => FileType::Pwmx,
             _ => FileType::Unknown
         }
     }
@@ -66,9 +78,15 @@ impl FileType {
             FileType::ZippedObj => "obj.zip",
             FileType::Gcode => "gcode",
             FileType::ZippedGcode => "gcode.zip",
+            FileType::Bgcode => "bgcode",
             FileType::Step => "step",
             FileType::ZippedStep => "step.zip",
             FileType::Threemf => "3mf",
+            FileType::Ctb => "ctb",
+            FileType::Sl1 => "sl1",
+            FileType::Sl1s => "sl1s",
+            FileType::Goo => "goo",
+            FileType::Pwmx => "pwmx",
             FileType::Unknown => panic!("Cannot convert Unknown FileType to extension"),
         }.to_string()
     }
@@ -118,6 +136,26 @@ impl FileType {
         match self {
             FileType::Gcode => true,
             FileType::ZippedGcode => true,
+            FileType::Bgcode => true,
+            _ => false
+        }
+    }
+
+    pub fn is_bgcode(&self) -> bool {
+        match self {
+            FileType::Bgcode => true,
+            _ => false
+        }
+    }
+
+    // Sliced resin files, these hold layer images instead of a mesh
+    pub fn is_resin(&self) -> bool {
+        match self {
+            FileType::Ctb => true,
+            FileType::Sl1 => true,
+            FileType::Sl1s => true,
+            FileType::Goo => true,
+            FileType::Pwmx => true,
             _ => false
         }
     }
@@ -167,9 +205,15 @@ impl FileType {
             FileType::ZippedObj => true,
             FileType::Gcode => true,
             FileType::ZippedGcode => true,
+            FileType::Bgcode => true,
             FileType::Step => true,
             FileType::ZippedStep => true,
             FileType::Threemf => true,
+            FileType::Ctb => true,
+            FileType::Sl1 => true,
+            FileType::Sl1s => true,
+            FileType::Goo => true,
+            FileType::Pwmx => true,
             _ => false
         }
     }
@@ -179,8 +223,14 @@ impl FileType {
             FileType::Stl => true,
             FileType::Obj => true,
             FileType::Gcode => true,
+            FileType::Bgcode => true,
             FileType::Step => true,
             FileType::Threemf => true,
+            FileType::Ctb => true,
+            FileType::Sl1 => true,
+            FileType::Sl1s => true,
+            FileType::Goo => true,
+            FileType::Pwmx => true,
             _ => false
         }
     }
@@ -0,0 +1,15 @@
+use serde::Serialize;
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct GcodeMetadata {
+    pub slicer: Option<String>,
+    pub slicer_version: Option<String>,
+    pub print_time_seconds: Option<i64>,
+    pub filament_used_mm: Option<f64>,
+    pub filament_used_g: Option<f64>,
+    pub filament_cost: Option<f64>,
+    pub nozzle_diameter: Option<f64>,
+    pub layer_height: Option<f64>,
+    pub printer_model: Option<String>,
+    pub material_type: Option<String>,
+}
@@ -0,0 +1,12 @@
+use serde::Serialize;
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct GeometryFingerprint {
+    // Hash of the welded vertex set, moved so its centre sits at the origin
+    pub hash: String,
+    pub vertex_count: i64,
+    pub volume: f64,
+    pub area: f64,
+    // Radii of gyration of the surface along its principal axes, largest first
+    pub moments: [f64; 3],
+}
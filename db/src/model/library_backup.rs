@@ -0,0 +1,82 @@
+use serde::{Deserialize, Serialize};
+
+// Rows of the library as they are written to a backup archive. Ids are the ids of the library the backup was made from,
+// they are only used to link the rows together and are replaced when restoring.
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct BackupUser {
+    pub id: i64,
+    pub username: String,
+    pub email: String,
+    pub created_at: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct BackupBlob {
+    pub id: i64,
+    pub sha256: String,
+    pub filetype: String,
+    pub size: i64,
+    pub added: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct BackupResource {
+    pub id: i64,
+    pub user_id: i64,
+    pub name: String,
+    pub flags: i64,
+    pub created: String,
+    pub last_modified: String,
+    pub unique_global_id: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct BackupGroup {
+    pub id: i64,
+    pub user_id: i64,
+    pub name: String,
+    pub created: String,
+    pub last_modified: String,
+    pub resource_id: Option<i64>,
+    pub unique_global_id: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct BackupLabel {
+    pub id: i64,
+    pub user_id: i64,
+    pub name: String,
+    pub color: i64,
+    pub last_modified: String,
+    pub unique_global_id: String,
+    pub parent_ids: Vec<i64>,
+    pub keywords: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct BackupModel {
+    pub id: i64,
+    pub user_id: i64,
+    pub blob_id: i64,
+    pub name: String,
+    pub link: Option<String>,
+    pub description: Option<String>,
+    pub added: String,
+    pub last_modified: String,
+    pub flags: i64,
+    pub unique_global_id: String,
+    pub group_id: Option<i64>,
+    pub label_ids: Vec<i64>,
+    pub source_model_id: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct LibraryDump {
+    pub users: Vec<BackupUser>,
+    pub blobs: Vec<BackupBlob>,
+    pub resources: Vec<BackupResource>,
+    pub groups: Vec<BackupGroup>,
+    pub labels: Vec<BackupLabel>,
+    pub models: Vec<BackupModel>,
+}
@@ -0,0 +1,29 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MeshHealth {
+    #[default]
+    Healthy,
+    // Issues slicers usually fix on their own, like degenerate triangles
+    Warnings,
+    // Issues that commonly break slicing, like holes or non-manifold edges
+    Problems,
+}
+
+impl MeshHealth {
+    pub fn from_i64(value: i64) -> Self {
+        match value {
+            1 => MeshHealth::Warnings,
+            2 => MeshHealth::Problems,
+            _ => MeshHealth::Healthy,
+        }
+    }
+
+    pub fn to_i64(&self) -> i64 {
+        match self {
+            MeshHealth::Healthy => 0,
+            MeshHealth::Warnings => 1,
+            MeshHealth::Problems => 2,
+        }
+    }
+}
@@ -6,6 +6,15 @@ mod resource;
 mod user;
 mod blob;
 mod share;
+mod user_session;
+mod user_totp;
+mod workspace;
+mod printer;
+mod gcode_metadata;
+mod resin_metadata;
+mod mesh_health;
+mod geometry_fingerprint;
+mod library_backup;
 
 pub use model::*;
 pub use model_group::*;
@@ -14,4 +23,13 @@ pub use label_keyword::*;
 pub use resource::*;
 pub use user::*;
 pub use blob::*;
-pub use share::*;
\ No newline at end of file
+pub use share::*;
+pub use user_session::*;
+pub use user_totp::*;
+pub use workspace::*;
+pub use printer::*;
+pub use gcode_metadata::*;
+pub use resin_metadata::*;
+pub use mesh_health::*;
+pub use geometry_fingerprint::*;
+pub use library_backup::*;
\ No newline at end of file
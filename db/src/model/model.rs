@@ -2,7 +2,7 @@
 use serde::{Deserialize, Serialize};
 use bitflags::bitflags;
 
-use crate::model::{Blob, LabelMeta, ModelGroupMeta};
+use crate::model::{Blob, LabelMeta, MeshHealth, ModelGroupMeta, PrintJobState};
 
 bitflags! {
     #[derive(Debug, Default)]
@@ -46,6 +46,13 @@ impl<'de> Deserialize<'de> for ModelFlags {
     }
 }
 
+#[derive(Serialize, Debug, Clone)]
+pub struct ModelPrint {
+    pub printed_at: String,
+    pub duration_seconds: Option<i64>,
+    pub state: PrintJobState,
+}
+
 // TODO: Change this model entirely. Discard model_user. Return group id back to model. Back this instead by a blob table.
 #[derive(Serialize)]
 pub struct Model {
@@ -60,4 +67,8 @@ pub struct Model {
     pub labels: Vec<LabelMeta>,
     pub flags: ModelFlags,
     pub unique_global_id: String,
+    pub source_model_id: Option<i64>,
+    pub last_print: Option<ModelPrint>,
+    // None until the mesh of the blob was analysed, or when the blob is not a mesh
+    pub health: Option<MeshHealth>,
 }
\ No newline at end of file
@@ -0,0 +1,87 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrinterType {
+    OctoPrint,
+    Moonraker,
+    PrusaLink,
+}
+
+impl PrinterType {
+    pub fn from_i64(value: i64) -> Self {
+        match value {
+            1 => PrinterType::Moonraker,
+            2 => PrinterType::PrusaLink,
+            _ => PrinterType::OctoPrint,
+        }
+    }
+
+    pub fn to_i64(&self) -> i64 {
+        match self {
+            PrinterType::OctoPrint => 0,
+            PrinterType::Moonraker => 1,
+            PrinterType::PrusaLink => 2,
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Printer {
+    pub id: i64,
+    #[serde(skip_serializing)]
+    pub user_id: i64,
+    pub name: String,
+    pub printer_type: PrinterType,
+    pub base_url: String,
+    #[serde(skip_serializing)]
+    pub api_key: String,
+    pub created_at: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrintJobState {
+    Uploaded,
+    Printing,
+    Finished,
+    Failed,
+    Cancelled,
+}
+
+impl PrintJobState {
+    pub fn is_done(&self) -> bool {
+        matches!(self, PrintJobState::Finished | PrintJobState::Failed | PrintJobState::Cancelled)
+    }
+
+    pub fn from_i64(value: i64) -> Self {
+        match value {
+            1 => PrintJobState::Printing,
+            2 => PrintJobState::Finished,
+            3 => PrintJobState::Failed,
+            4 => PrintJobState::Cancelled,
+            _ => PrintJobState::Uploaded,
+        }
+    }
+
+    pub fn to_i64(&self) -> i64 {
+        match self {
+            PrintJobState::Uploaded => 0,
+            PrintJobState::Printing => 1,
+            PrintJobState::Finished => 2,
+            PrintJobState::Failed => 3,
+            PrintJobState::Cancelled => 4,
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct PrintJob {
+    pub id: i64,
+    pub printer_id: i64,
+    pub model_id: Option<i64>,
+    pub file_name: String,
+    pub state: PrintJobState,
+    pub created_at: String,
+    pub started_at: Option<String>,
+    pub finished_at: Option<String>,
+    pub duration_seconds: Option<i64>,
+}
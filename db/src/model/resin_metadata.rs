@@ -0,0 +1,12 @@
+use serde::Serialize;
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct ResinMetadata {
+    pub printer_model: Option<String>,
+    pub layer_count: Option<i64>,
+    pub layer_height: Option<f64>,
+    pub exposure_time: Option<f64>,
+    pub bottom_exposure_time: Option<f64>,
+    pub bottom_layer_count: Option<i64>,
+    pub print_time_seconds: Option<i64>,
+}
@@ -9,6 +9,8 @@ bitflags! {
         const Admin = 0b00000001;
         const Sync  = 0b00000010;
         const OnlineAccount = 0b00000100;
+        // Not a login account, but a shared library owned through workspace_members
+        const Workspace = 0b00001000;
     }
 }
 
@@ -27,6 +29,9 @@ impl Serialize for UserPermissions {
         if self.contains(UserPermissions::OnlineAccount) {
             flags.push("OnlineAccount");
         }
+        if self.contains(UserPermissions::Workspace) {
+            flags.push("Workspace");
+        }
         flags.serialize(serializer)
     }
 }
@@ -43,6 +48,7 @@ impl<'de> Deserialize<'de> for UserPermissions {
                 "Admin" => result.insert(UserPermissions::Admin),
                 "Sync" => result.insert(UserPermissions::Sync),
                 "OnlineAccount" => result.insert(UserPermissions::OnlineAccount),
+                "Workspace" => result.insert(UserPermissions::Workspace),
                 _ => {}
             }
         }
@@ -98,4 +104,17 @@ impl Default for User {
 
 pub fn hash_password(password: &str) -> String {
     generate_hash(password)
-}
\ No newline at end of file
+}
+
+// Recovery codes are salted like passwords, so a code is checked against each stored hash of the user
+pub fn hash_recovery_code(code: &str) -> String {
+    generate_hash(normalize_recovery_code(code))
+}
+
+// Codes may be typed with or without the dash and in any case
+pub fn normalize_recovery_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
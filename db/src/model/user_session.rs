@@ -0,0 +1,13 @@
+use serde::Serialize;
+
+#[derive(Serialize, Debug, Clone)]
+pub struct UserSession {
+    pub id: String,
+    pub user_id: i64,
+    pub created_at: String,
+    pub last_used: String,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    #[serde(skip_serializing)]
+    pub store_id: Option<String>,
+}
@@ -0,0 +1,12 @@
+use serde::Serialize;
+
+#[derive(Serialize, Debug, Clone)]
+pub struct UserTotp {
+    pub user_id: i64,
+    #[serde(skip_serializing)]
+    pub secret: String,
+    pub enabled: bool,
+    #[serde(skip_serializing)]
+    pub last_used_step: Option<i64>,
+    pub created_at: String,
+}
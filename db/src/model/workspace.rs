@@ -0,0 +1,43 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum WorkspaceRole {
+    Viewer,
+    Editor,
+    Owner,
+}
+
+impl WorkspaceRole {
+    pub fn from_i64(value: i64) -> Self {
+        match value {
+            0 => WorkspaceRole::Viewer,
+            1 => WorkspaceRole::Editor,
+            _ => WorkspaceRole::Owner,
+        }
+    }
+
+    pub fn to_i64(&self) -> i64 {
+        match self {
+            WorkspaceRole::Viewer => 0,
+            WorkspaceRole::Editor => 1,
+            WorkspaceRole::Owner => 2,
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Workspace {
+    pub id: i64,
+    pub name: String,
+    pub created_at: String,
+    pub role: WorkspaceRole,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct WorkspaceMember {
+    pub user_id: i64,
+    pub username: String,
+    pub email: String,
+    pub role: WorkspaceRole,
+    pub added_at: String,
+}
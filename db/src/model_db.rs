@@ -1,12 +1,14 @@
+use std::collections::HashSet;
+
 use indexmap::IndexMap;
 use itertools::{Itertools, join};
 use serde::de;
 use sqlx::{Execute, QueryBuilder, query};
 use sqlx::Row;
 use strum::EnumString;
-use crate::model::{Blob, FileType};
+use crate::model::{Blob, FileType, MeshHealth};
 use crate::util::{random_hex_32, time_now};
-use crate::{DbError, PaginatedResponse, db_context::DbContext, label_db, model::{Label, LabelMeta, Model, ModelFlags, ModelGroup, ModelGroupMeta, User, convert_label_meta_list_to_map}};
+use crate::{DbError, PaginatedResponse, db_context::DbContext, label_db, model::{Label, LabelMeta, Model, ModelFlags, ModelGroup, ModelGroupMeta, ModelPrint, PrintJobState, User, convert_label_meta_list_to_map}};
 
 #[derive(Debug, PartialEq, EnumString)]
 pub enum ModelOrderBy {
@@ -44,6 +46,13 @@ pub struct ModelFilterOptions {
     pub text_search: Option<String>,
     pub model_flags: Option<ModelFlags>,
     pub file_types: Option<Vec<FileType>>,
+    // Filters on the metadata read from G-code
+    pub printer_model: Option<String>,
+    pub material_type: Option<String>,
+    pub nozzle_diameter: Option<f64>,
+    pub max_print_time_seconds: Option<i64>,
+    // Only models whose mesh analysis found problems, or only those without
+    pub has_problems: Option<bool>,
     pub page : u32,
     pub page_size : u32,
 }
@@ -52,7 +61,8 @@ pub async fn get_models(db: &DbContext, user : &User, options : ModelFilterOptio
     let offset = (options.page as i64 - 1) * options.page_size as i64;
 
     let mut query_builder = QueryBuilder::new(
-        format!("SELECT models.model_id, model_name, model_url, model_desc, model_added, model_flags, model_unique_global_id, model_last_modified,
+        format!("SELECT models.model_id, model_name, model_url, model_desc, model_added, model_flags, model_unique_global_id, model_last_modified, model_source_model_id,
+                model_last_print_at, model_last_print_duration, model_last_print_state, mesh_health_status,
 				blob_id, blob_sha256, blob_filetype, blob_size, blob_path,
                 GROUP_CONCAT(labels.label_id) AS label_ids,
                 models_group.group_id, group_name, group_created, group_resource_id, group_unique_global_id, group_last_modified
@@ -61,7 +71,9 @@ pub async fn get_models(db: &DbContext, user : &User, options : ModelFilterOptio
          LEFT JOIN labels ON models_labels.label_id = labels.label_id
          LEFT JOIN models_group ON models.model_group_id = models_group.group_id
 		 INNER JOIN blobs ON models.model_blob_id = blobs.blob_id
-         WHERE models.model_user_id = {} ", user.id)
+         LEFT JOIN gcode_metadata ON blobs.blob_id = gcode_metadata.gcode_metadata_blob_id
+         LEFT JOIN mesh_health ON blobs.blob_id = mesh_health.mesh_health_blob_id
+         WHERE models.model_user_id IN (SELECT access_owner_id FROM user_access WHERE access_user_id = {}) ", user.id)
     );
 
     let mut seperated = query_builder.separated(" AND ");
@@ -97,6 +109,39 @@ pub async fn get_models(db: &DbContext, user : &User, options : ModelFilterOptio
         seperated.push(format!("blob_filetype IN ('{}')", join(extensions.iter().unique(), "','")));
     }
 
+    if let Some(printer_model) = options.printer_model
+    {
+        seperated.push("gcode_metadata_printer_model LIKE ");
+        seperated.push_bind_unseparated(format!("%{}%", printer_model));
+    }
+
+    if let Some(material_type) = options.material_type
+    {
+        seperated.push("gcode_metadata_material_type LIKE ");
+        seperated.push_bind_unseparated(format!("%{}%", material_type));
+    }
+
+    if let Some(nozzle_diameter) = options.nozzle_diameter
+    {
+        seperated.push("ABS(gcode_metadata_nozzle_diameter - ");
+        seperated.push_bind_unseparated(nozzle_diameter);
+        seperated.push_unseparated(") < 0.001");
+    }
+
+    if let Some(max_print_time_seconds) = options.max_print_time_seconds
+    {
+        seperated.push("gcode_metadata_print_time <= ");
+        seperated.push_bind_unseparated(max_print_time_seconds);
+    }
+
+    if let Some(has_problems) = options.has_problems
+    {
+        seperated.push(match has_problems {
+            true => format!("mesh_health_status = {}", MeshHealth::Problems.to_i64()),
+            false => format!("(mesh_health_status IS NULL OR mesh_health_status < {})", MeshHealth::Problems.to_i64()),
+        });
+    }
+
     if let Some(text_search) = options.text_search
     {
         let str = format!("%{}%", text_search);
@@ -164,6 +209,16 @@ pub async fn get_models(db: &DbContext, user : &User, options : ModelFilterOptio
             },
             flags: ModelFlags::from_bits(row.get::<i64, _>("model_flags") as u32).unwrap_or(ModelFlags::empty()),
             unique_global_id: row.get("model_unique_global_id"),
+            source_model_id: row.get("model_source_model_id"),
+            last_print: match row.get::<Option<String>, _>("model_last_print_at") {
+                Some(printed_at) => Some(ModelPrint {
+                    printed_at,
+                    duration_seconds: row.get("model_last_print_duration"),
+                    state: PrintJobState::from_i64(row.get::<Option<i64>, _>("model_last_print_state").unwrap_or_default()),
+                }),
+                None => None,
+            },
+            health: row.get::<Option<i64>, _>("mesh_health_status").map(MeshHealth::from_i64),
         })
     }
 
@@ -215,7 +270,7 @@ pub async fn edit_model(db: &DbContext, user: &User, id: i64, name: &str, link:
     let timestamp = update_timestamp.unwrap_or(&now);
     let flags = flags.bits() as i64;
     sqlx::query!(
-        "UPDATE models SET model_name = ?, model_url = ?, model_desc = ?, model_flags = ?, model_last_modified = ? WHERE model_id = ? AND model_user_id = ?",
+        "UPDATE models SET model_name = ?, model_url = ?, model_desc = ?, model_flags = ?, model_last_modified = ? WHERE model_id = ? AND model_user_id IN (SELECT access_owner_id FROM user_access WHERE access_user_id = ? AND access_role >= 1)",
         name,
         link,
         description,
@@ -230,6 +285,20 @@ pub async fn edit_model(db: &DbContext, user: &User, id: i64, name: &str, link:
     Ok(())
 }
 
+pub async fn set_source_model(db: &DbContext, user: &User, id: i64, source_model_id: Option<i64>) -> Result<(), DbError>
+{
+    sqlx::query!(
+        "UPDATE models SET model_source_model_id = ? WHERE model_id = ? AND model_user_id IN (SELECT access_owner_id FROM user_access WHERE access_user_id = ? AND access_role >= 1)",
+        source_model_id,
+        id,
+        user.id
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
 pub async fn edit_model_global_id(db: &DbContext, user: &User, id: i64, unique_global_id: &str) -> Result<(), DbError>
 {
     if unique_global_id.len() != 32 {
@@ -237,7 +306,7 @@ pub async fn edit_model_global_id(db: &DbContext, user: &User, id: i64, unique_g
     }
 
     sqlx::query!(
-        "UPDATE models SET model_unique_global_id = ? WHERE model_id = ? AND model_user_id = ?",
+        "UPDATE models SET model_unique_global_id = ? WHERE model_id = ? AND model_user_id IN (SELECT access_owner_id FROM user_access WHERE access_user_id = ? AND access_role >= 1)",
         unique_global_id,
         id,
         user.id
@@ -251,7 +320,7 @@ pub async fn edit_model_global_id(db: &DbContext, user: &User, id: i64, unique_g
 pub async fn delete_model(db: &DbContext, user: &User, id: i64) -> Result<(), DbError>
 {
     sqlx::query!(
-        "DELETE FROM models WHERE model_id = ? AND model_user_id = ?",
+        "DELETE FROM models WHERE model_id = ? AND model_user_id IN (SELECT access_owner_id FROM user_access WHERE access_user_id = ? AND access_role >= 1)",
         id,
         user.id
     )
@@ -270,7 +339,7 @@ pub async fn delete_models(db: &DbContext, user: &User, ids: &[i64]) -> Result<(
     let ids_placeholder = join(ids.iter(), ",");
 
     let query = format!(
-        "DELETE FROM models WHERE model_user_id = ? AND model_id IN ({})",
+        "DELETE FROM models WHERE model_user_id IN (SELECT access_owner_id FROM user_access WHERE access_user_id = ? AND access_role >= 1) AND model_id IN ({})",
         ids_placeholder
     );
 
@@ -282,6 +351,34 @@ pub async fn delete_models(db: &DbContext, user: &User, ids: &[i64]) -> Result<(
     Ok(())
 }
 
+// get_models also returns models of workspaces the user can only view, so writes by model id check this first
+pub async fn ensure_models_editable(db: &DbContext, user: &User, ids: &[i64]) -> Result<(), DbError>
+{
+    if ids.is_empty() {
+        return Ok(());
+    }
+
+    let unique_ids: Vec<&i64> = ids.iter().unique().collect();
+
+    let query = format!(
+        "SELECT COUNT(*) as count FROM models WHERE model_id IN ({}) AND model_user_id IN (SELECT access_owner_id FROM user_access WHERE access_user_id = ? AND access_role >= 1)",
+        join(unique_ids.iter(), ",")
+    );
+
+    let row = sqlx::query(&query)
+        .bind(user.id)
+        .fetch_one(db)
+        .await?;
+
+    let count: i64 = row.get("count");
+
+    if count as usize != unique_ids.len() {
+        return Err(DbError::RowNotFound);
+    }
+
+    Ok(())
+}
+
 pub async fn get_unique_id_from_model_id(db: &DbContext, model_id: i64) -> Result<String, DbError>
 {
     let row = sqlx::query!(
@@ -318,10 +415,23 @@ pub async fn get_unique_ids_from_model_ids(db: &DbContext, model_ids: Vec<i64>)
     Ok(id_map)
 }
 
+// Every model unique id the user can see, used to tell which previously exported models were deleted since
+pub async fn get_unique_global_ids(db: &DbContext, user: &User) -> Result<HashSet<String>, DbError>
+{
+    let rows = sqlx::query!(
+        "SELECT model_unique_global_id FROM models WHERE model_user_id IN (SELECT access_owner_id FROM user_access WHERE access_user_id = ?)",
+        user.id
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(rows.into_iter().map(|row| row.model_unique_global_id).collect())
+}
+
 // TODO: Can we make a get model via sha256?
 pub async fn get_model_id_via_sha256(db: &DbContext, user : &User, sha256: &str) -> Result<Option<i64>, DbError> {
     let row = sqlx::query!(
-        "SELECT model_id FROM models INNER JOIN blobs ON models.model_blob_id = blobs.blob_id WHERE blob_sha256 = ? AND model_user_id = ?",
+        "SELECT model_id FROM models INNER JOIN blobs ON models.model_blob_id = blobs.blob_id WHERE blob_sha256 = ? AND model_user_id IN (SELECT access_owner_id FROM user_access WHERE access_user_id = ?)",
         sha256,
         user.id
     )
@@ -334,12 +444,104 @@ pub async fn get_model_id_via_sha256(db: &DbContext, user : &User, sha256: &str)
     }
 }
 
+// Same as get_model_id_via_sha256, but skips models in workspaces the user can only view
+pub async fn get_editable_model_id_via_sha256(db: &DbContext, user : &User, sha256: &str) -> Result<Option<i64>, DbError> {
+    let row = sqlx::query!(
+        "SELECT model_id FROM models INNER JOIN blobs ON models.model_blob_id = blobs.blob_id WHERE blob_sha256 = ? AND model_user_id IN (SELECT access_owner_id FROM user_access WHERE access_user_id = ? AND access_role >= 1)",
+        sha256,
+        user.id
+    )
+    .fetch_optional(db)
+    .await?;
+
+    match row {
+        Some(r) => Ok(Some(r.model_id.unwrap())),
+        None => Ok(None),
+    }
+}
+
+// Looks up a G-code model by name, used to match files on a printer back to the library
+pub async fn get_gcode_model_id_via_name(db: &DbContext, user : &User, name: &str) -> Result<Option<i64>, DbError> {
+    let row = sqlx::query!(
+        "SELECT model_id FROM models INNER JOIN blobs ON models.model_blob_id = blobs.blob_id WHERE model_name = ? AND blob_filetype IN ('gcode', 'gcode.zip', 'bgcode') AND model_user_id IN (SELECT access_owner_id FROM user_access WHERE access_user_id = ?) ORDER BY model_added DESC LIMIT 1",
+        name,
+        user.id
+    )
+    .fetch_optional(db)
+    .await?;
+
+    Ok(row.map(|r| r.model_id.unwrap()))
+}
+
+// Stores the outcome of a print on the model. A finished print also sets the printed flag
+pub async fn record_print(db: &DbContext, user: &User, id: i64, state: PrintJobState, duration_seconds: Option<i64>) -> Result<(), DbError> {
+    let now = time_now();
+    let printed_bits = match state {
+        PrintJobState::Finished => ModelFlags::Printed.bits() as i64,
+        _ => 0,
+    };
+    let state = state.to_i64();
+
+    sqlx::query!(
+        "UPDATE models SET model_last_print_at = ?, model_last_print_duration = ?, model_last_print_state = ?, model_flags = model_flags | ?, model_last_modified = ? WHERE model_id = ? AND model_user_id IN (SELECT access_owner_id FROM user_access WHERE access_user_id = ? AND access_role >= 1)",
+        now,
+        duration_seconds,
+        state,
+        printed_bits,
+        now,
+        id,
+        user.id
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+// Moves the print history and the models derived from one model over to another, used when merging duplicates.
+// The last print of the target is only replaced when the other model was printed more recently.
+pub async fn transfer_model_history(db: &DbContext, user: &User, from_id: i64, to_id: i64) -> Result<(), DbError> {
+    ensure_models_editable(db, user, &[from_id, to_id]).await?;
+
+    sqlx::query!(
+        "UPDATE print_jobs SET print_job_model_id = ? WHERE print_job_model_id = ?",
+        to_id,
+        from_id
+    )
+    .execute(db)
+    .await?;
+
+    sqlx::query!(
+        "UPDATE models SET model_source_model_id = ? WHERE model_source_model_id = ?",
+        to_id,
+        from_id
+    )
+    .execute(db)
+    .await?;
+
+    sqlx::query!(
+        "UPDATE models SET (model_last_print_at, model_last_print_duration, model_last_print_state) =
+            (SELECT model_last_print_at, model_last_print_duration, model_last_print_state FROM models WHERE model_id = ?)
+         WHERE model_id = ?
+            AND (SELECT model_last_print_at FROM models WHERE model_id = ?) IS NOT NULL
+            AND (model_last_print_at IS NULL OR model_last_print_at < (SELECT model_last_print_at FROM models WHERE model_id = ?))",
+        from_id,
+        to_id,
+        from_id,
+        from_id
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
 pub async fn get_model_count(db: &DbContext, user : &User, flags : Option<ModelFlags>) -> Result<usize, DbError> {
     let count = match flags {
         Some(f) => {
             let bits = f.bits() as i64;
             sqlx::query!(
-                "SELECT COUNT(*) as count FROM models WHERE model_user_id = ? AND (models.model_flags & ?) = ?",
+                "SELECT COUNT(*) as count FROM models WHERE model_user_id IN (SELECT access_owner_id FROM user_access WHERE access_user_id = ?) AND (models.model_flags & ?) = ?",
                 user.id,
                 bits,
                 bits
@@ -348,7 +550,7 @@ pub async fn get_model_count(db: &DbContext, user : &User, flags : Option<ModelF
             .await?.count
         },
         None => sqlx::query!(
-            "SELECT COUNT(*) as count FROM models WHERE model_user_id = ?",
+            "SELECT COUNT(*) as count FROM models WHERE model_user_id IN (SELECT access_owner_id FROM user_access WHERE access_user_id = ?)",
             user.id
         )
         .fetch_one(db)
@@ -363,6 +565,7 @@ pub struct ModelSizeResult {
     pub blob_sha256: Vec<String>,
 }
 
+// Only counts models the user owns, storage of workspaces is attributed to the workspace itself
 pub async fn get_size_of_models(db: &DbContext, user : &User) -> Result<ModelSizeResult, DbError> {
     let row = sqlx::query!(
         "SELECT SUM(blob_size) as \"total_size: i64\", GROUP_CONCAT(blob_sha256) as \"blob_sha256: String\" FROM models JOIN blobs ON models.model_blob_id = blobs.blob_id WHERE model_user_id = ?",
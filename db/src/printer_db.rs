@@ -0,0 +1,238 @@
+use crate::{DbError, db_context::DbContext, model::{PrintJob, PrintJobState, Printer, PrinterType, User}, time_now};
+
+pub async fn get_printers(db: &DbContext, user: &User) -> Result<Vec<Printer>, DbError> {
+    let rows = sqlx::query!(
+        "SELECT printer_id, printer_user_id, printer_name, printer_type, printer_base_url, printer_api_key, printer_created_at
+         FROM printers
+         WHERE printer_user_id = ?
+         ORDER BY printer_name ASC",
+        user.id
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| Printer {
+            id: row.printer_id,
+            user_id: row.printer_user_id,
+            name: row.printer_name,
+            printer_type: PrinterType::from_i64(row.printer_type),
+            base_url: row.printer_base_url,
+            api_key: row.printer_api_key,
+            created_at: row.printer_created_at,
+        })
+        .collect())
+}
+
+// Printers of every user, for the background status polling
+pub async fn get_all_printers(db: &DbContext) -> Result<Vec<Printer>, DbError> {
+    let rows = sqlx::query!(
+        "SELECT printer_id, printer_user_id, printer_name, printer_type, printer_base_url, printer_api_key, printer_created_at
+         FROM printers"
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| Printer {
+            id: row.printer_id,
+            user_id: row.printer_user_id,
+            name: row.printer_name,
+            printer_type: PrinterType::from_i64(row.printer_type),
+            base_url: row.printer_base_url,
+            api_key: row.printer_api_key,
+            created_at: row.printer_created_at,
+        })
+        .collect())
+}
+
+pub async fn get_printer_by_id(db: &DbContext, user: &User, id: i64) -> Result<Option<Printer>, DbError> {
+    let row = sqlx::query!(
+        "SELECT printer_id, printer_user_id, printer_name, printer_type, printer_base_url, printer_api_key, printer_created_at
+         FROM printers
+         WHERE printer_id = ? AND printer_user_id = ?",
+        id,
+        user.id
+    )
+    .fetch_optional(db)
+    .await?;
+
+    Ok(row.map(|row| Printer {
+        id: row.printer_id,
+        user_id: row.printer_user_id,
+        name: row.printer_name,
+        printer_type: PrinterType::from_i64(row.printer_type),
+        base_url: row.printer_base_url,
+        api_key: row.printer_api_key,
+        created_at: row.printer_created_at,
+    }))
+}
+
+pub async fn add_printer(db: &DbContext, user: &User, name: &str, printer_type: PrinterType, base_url: &str, api_key: &str) -> Result<i64, DbError> {
+    let now = time_now();
+    let printer_type = printer_type.to_i64();
+
+    let result = sqlx::query!(
+        "INSERT INTO printers (printer_user_id, printer_name, printer_type, printer_base_url, printer_api_key, printer_created_at) VALUES (?, ?, ?, ?, ?, ?)",
+        user.id,
+        name,
+        printer_type,
+        base_url,
+        api_key,
+        now
+    )
+    .execute(db)
+    .await?;
+
+    Ok(result.last_insert_rowid())
+}
+
+// An api key of None keeps the stored key, so it doesn't have to be sent back to the client
+pub async fn edit_printer(db: &DbContext, user: &User, id: i64, name: &str, printer_type: PrinterType, base_url: &str, api_key: Option<&str>) -> Result<(), DbError> {
+    let printer_type = printer_type.to_i64();
+
+    sqlx::query!(
+        "UPDATE printers SET printer_name = ?, printer_type = ?, printer_base_url = ?, printer_api_key = COALESCE(?, printer_api_key) WHERE printer_id = ? AND printer_user_id = ?",
+        name,
+        printer_type,
+        base_url,
+        api_key,
+        id,
+        user.id
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn delete_printer(db: &DbContext, user: &User, id: i64) -> Result<(), DbError> {
+    sqlx::query!(
+        "DELETE FROM printers WHERE printer_id = ? AND printer_user_id = ?",
+        id,
+        user.id
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn add_print_job(db: &DbContext, printer_id: i64, model_id: Option<i64>, file_name: &str, state: PrintJobState) -> Result<i64, DbError> {
+    let now = time_now();
+    let started_at = if state == PrintJobState::Printing { Some(now.clone()) } else { None };
+    let state = state.to_i64();
+
+    let result = sqlx::query!(
+        "INSERT INTO print_jobs (print_job_printer_id, print_job_model_id, print_job_file_name, print_job_state, print_job_created_at, print_job_started_at) VALUES (?, ?, ?, ?, ?, ?)",
+        printer_id,
+        model_id,
+        file_name,
+        state,
+        now,
+        started_at
+    )
+    .execute(db)
+    .await?;
+
+    Ok(result.last_insert_rowid())
+}
+
+pub async fn get_print_jobs(db: &DbContext, user: &User, printer_id: i64) -> Result<Vec<PrintJob>, DbError> {
+    let rows = sqlx::query!(
+        "SELECT print_job_id, print_job_printer_id, print_job_model_id, print_job_file_name, print_job_state, print_job_created_at,
+                print_job_started_at, print_job_finished_at, print_job_duration
+         FROM print_jobs
+         INNER JOIN printers ON print_jobs.print_job_printer_id = printers.printer_id
+         WHERE print_job_printer_id = ? AND printer_user_id = ?
+         ORDER BY print_job_created_at DESC",
+        printer_id,
+        user.id
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| PrintJob {
+            id: row.print_job_id,
+            printer_id: row.print_job_printer_id,
+            model_id: row.print_job_model_id,
+            file_name: row.print_job_file_name,
+            state: PrintJobState::from_i64(row.print_job_state),
+            created_at: row.print_job_created_at,
+            started_at: row.print_job_started_at,
+            finished_at: row.print_job_finished_at,
+            duration_seconds: row.print_job_duration,
+        })
+        .collect())
+}
+
+// The most recent job for this file that has not finished yet
+pub async fn get_open_print_job(db: &DbContext, printer_id: i64, file_name: &str) -> Result<Option<PrintJob>, DbError> {
+    let uploaded = PrintJobState::Uploaded.to_i64();
+    let printing = PrintJobState::Printing.to_i64();
+
+    let row = sqlx::query!(
+        "SELECT print_job_id, print_job_printer_id, print_job_model_id, print_job_file_name, print_job_state, print_job_created_at,
+                print_job_started_at, print_job_finished_at, print_job_duration
+         FROM print_jobs
+         WHERE print_job_printer_id = ? AND print_job_file_name = ? AND print_job_state IN (?, ?)
+         ORDER BY print_job_id DESC
+         LIMIT 1",
+        printer_id,
+        file_name,
+        uploaded,
+        printing
+    )
+    .fetch_optional(db)
+    .await?;
+
+    Ok(row.map(|row| PrintJob {
+        id: row.print_job_id,
+        printer_id: row.print_job_printer_id,
+        model_id: row.print_job_model_id,
+        file_name: row.print_job_file_name,
+        state: PrintJobState::from_i64(row.print_job_state),
+        created_at: row.print_job_created_at,
+        started_at: row.print_job_started_at,
+        finished_at: row.print_job_finished_at,
+        duration_seconds: row.print_job_duration,
+    }))
+}
+
+pub async fn set_print_job_printing(db: &DbContext, job_id: i64) -> Result<(), DbError> {
+    let now = time_now();
+    let printing = PrintJobState::Printing.to_i64();
+
+    sqlx::query!(
+        "UPDATE print_jobs SET print_job_state = ?, print_job_started_at = ? WHERE print_job_id = ?",
+        printing,
+        now,
+        job_id
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn finish_print_job(db: &DbContext, job_id: i64, model_id: Option<i64>, state: PrintJobState, duration_seconds: Option<i64>) -> Result<(), DbError> {
+    let now = time_now();
+    let state = state.to_i64();
+
+    sqlx::query!(
+        "UPDATE print_jobs SET print_job_state = ?, print_job_finished_at = ?, print_job_duration = ?, print_job_model_id = COALESCE(print_job_model_id, ?) WHERE print_job_id = ?",
+        state,
+        now,
+        duration_seconds,
+        model_id,
+        job_id
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
@@ -0,0 +1,43 @@
+use crate::{DbError, db_context::DbContext, model::ResinMetadata};
+
+pub async fn get_resin_metadata(db: &DbContext, blob_id: i64) -> Result<Option<ResinMetadata>, DbError> {
+    let row = sqlx::query!(
+        "SELECT resin_metadata_printer_model, resin_metadata_layer_count, resin_metadata_layer_height, resin_metadata_exposure_time,
+                resin_metadata_bottom_exposure_time, resin_metadata_bottom_layer_count, resin_metadata_print_time
+         FROM resin_metadata
+         WHERE resin_metadata_blob_id = ?",
+        blob_id
+    )
+    .fetch_optional(db)
+    .await?;
+
+    Ok(row.map(|row| ResinMetadata {
+        printer_model: row.resin_metadata_printer_model,
+        layer_count: row.resin_metadata_layer_count,
+        layer_height: row.resin_metadata_layer_height,
+        exposure_time: row.resin_metadata_exposure_time,
+        bottom_exposure_time: row.resin_metadata_bottom_exposure_time,
+        bottom_layer_count: row.resin_metadata_bottom_layer_count,
+        print_time_seconds: row.resin_metadata_print_time,
+    }))
+}
+
+pub async fn set_resin_metadata(db: &DbContext, blob_id: i64, metadata: &ResinMetadata) -> Result<(), DbError> {
+    sqlx::query!(
+        "INSERT OR REPLACE INTO resin_metadata (resin_metadata_blob_id, resin_metadata_printer_model, resin_metadata_layer_count, resin_metadata_layer_height,
+                resin_metadata_exposure_time, resin_metadata_bottom_exposure_time, resin_metadata_bottom_layer_count, resin_metadata_print_time)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+        blob_id,
+        metadata.printer_model,
+        metadata.layer_count,
+        metadata.layer_height,
+        metadata.exposure_time,
+        metadata.bottom_exposure_time,
+        metadata.bottom_layer_count,
+        metadata.print_time_seconds
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
@@ -6,7 +6,7 @@ pub async fn get_resources(db: &DbContext, user: &User) -> Result<Vec<ResourceMe
     let rows = sqlx::query!(
         "SELECT resources.resource_id, resources.resource_name, resources.resource_flags, resources.resource_created, resources.resource_unique_global_id, resources.resource_last_modified
             FROM resources
-            WHERE resources.resource_user_id = ?
+            WHERE resources.resource_user_id IN (SELECT access_owner_id FROM user_access WHERE access_user_id = ?)
             ORDER BY resources.resource_name ASC",
             user.id
     )
@@ -32,7 +32,7 @@ pub async fn get_resources(db: &DbContext, user: &User) -> Result<Vec<ResourceMe
 
 pub async fn get_groups_for_resource(db: &DbContext, user: &User, resource_id: i64) -> Result<Vec<ModelGroup>, DbError> {
     let rows = sqlx::query!(
-        "SELECT models_group.group_id FROM models_group WHERE models_group.group_resource_id = ? AND models_group.group_user_id = ?",
+        "SELECT models_group.group_id FROM models_group WHERE models_group.group_resource_id = ? AND models_group.group_user_id IN (SELECT access_owner_id FROM user_access WHERE access_user_id = ?)",
         resource_id,
         user.id
     )
@@ -55,7 +55,7 @@ pub async fn get_group_id_to_resource_map(db : &DbContext, user: &User) -> Resul
         "SELECT models_group.group_id, resources.resource_id, resources.resource_name, resources.resource_flags, resources.resource_created, resources.resource_unique_global_id, resources.resource_last_modified
             FROM models_group
             INNER JOIN resources ON models_group.group_resource_id = resources.resource_id
-            WHERE resources.resource_user_id = ?",
+            WHERE resources.resource_user_id IN (SELECT access_owner_id FROM user_access WHERE access_user_id = ?)",
             user.id
     )
     .fetch_all(db)
@@ -82,7 +82,7 @@ pub async fn get_resource_meta_by_id(db: &DbContext, user: &User, id: i64) -> Re
     let row = sqlx::query!(
         "SELECT resources.resource_id, resources.resource_name, resources.resource_flags, resources.resource_created, resources.resource_unique_global_id, resources.resource_last_modified
             FROM resources
-            WHERE resources.resource_id = ? AND resources.resource_user_id = ?",
+            WHERE resources.resource_id = ? AND resources.resource_user_id IN (SELECT access_owner_id FROM user_access WHERE access_user_id = ?)",
         id,
         user.id
     )
@@ -126,7 +126,7 @@ pub async fn add_resource(db: &DbContext, user: &User, name: &str, update_timest
 
 pub async fn get_unique_id_from_resource_id(db: &DbContext, user: &User, resource_id: i64) -> Result<String, DbError> {
     let row = sqlx::query!(
-        "SELECT resource_unique_global_id FROM resources WHERE resource_id = ? AND resource_user_id = ?",
+        "SELECT resource_unique_global_id FROM resources WHERE resource_id = ? AND resource_user_id IN (SELECT access_owner_id FROM user_access WHERE access_user_id = ? AND access_role >= 1)",
         resource_id,
         user.id
     )
@@ -138,7 +138,7 @@ pub async fn get_unique_id_from_resource_id(db: &DbContext, user: &User, resourc
 
 pub async fn delete_resource(db: &DbContext, user: &User, resource_id: i64) -> Result<(), DbError> {
     sqlx::query!(
-        "DELETE FROM resources WHERE resource_id = ? AND resource_user_id = ?",
+        "DELETE FROM resources WHERE resource_id = ? AND resource_user_id IN (SELECT access_owner_id FROM user_access WHERE access_user_id = ? AND access_role >= 1)",
         resource_id,
         user.id
     )
@@ -154,7 +154,7 @@ pub async fn edit_resource(db: &DbContext, user: &User, resource_id: i64, name:
     let timestamp = update_timestamp.unwrap_or(&current_time);
 
     sqlx::query!(
-        "UPDATE resources SET resource_name = ?, resource_flags = ?, resource_last_modified = ? WHERE resource_id = ? AND resource_user_id = ?",
+        "UPDATE resources SET resource_name = ?, resource_flags = ?, resource_last_modified = ? WHERE resource_id = ? AND resource_user_id IN (SELECT access_owner_id FROM user_access WHERE access_user_id = ? AND access_role >= 1)",
         name,
         bits,
         timestamp,
@@ -173,7 +173,7 @@ pub async fn edit_resource_global_id(db: &DbContext, user: &User, resource_id: i
     }
     
     sqlx::query!(
-        "UPDATE resources SET resource_unique_global_id = ? WHERE resource_id = ? AND resource_user_id = ?",
+        "UPDATE resources SET resource_unique_global_id = ? WHERE resource_id = ? AND resource_user_id IN (SELECT access_owner_id FROM user_access WHERE access_user_id = ? AND access_role >= 1)",
         unique_global_id,
         resource_id,
         user.id
@@ -186,7 +186,7 @@ pub async fn edit_resource_global_id(db: &DbContext, user: &User, resource_id: i
 
 pub async fn set_last_updated_on_resource(db: &DbContext, user: &User, resource_id: i64, timestamp: &str) -> Result<(), DbError> {
     sqlx::query!(
-        "UPDATE resources SET resource_last_modified = ? WHERE resource_id = ? AND resource_user_id = ?",
+        "UPDATE resources SET resource_last_modified = ? WHERE resource_id = ? AND resource_user_id IN (SELECT access_owner_id FROM user_access WHERE access_user_id = ? AND access_role >= 1)",
         timestamp,
         resource_id,
         user.id
@@ -212,7 +212,7 @@ pub async fn set_resource_on_group(db: &DbContext, user: &User, resource_id: Opt
     };
 
     sqlx::query!(
-        "UPDATE models_group SET group_resource_id = ? WHERE group_id = ? AND group_user_id = ?",
+        "UPDATE models_group SET group_resource_id = ? WHERE group_id = ? AND group_user_id IN (SELECT access_owner_id FROM user_access WHERE access_user_id = ? AND access_role >= 1)",
         resource_id,
         group_id,
         user.id
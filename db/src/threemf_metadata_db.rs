@@ -0,0 +1,24 @@
+use crate::{DbError, db_context::DbContext};
+
+pub async fn get_threemf_metadata_json(db: &DbContext, blob_id: i64) -> Result<Option<String>, DbError> {
+    let row = sqlx::query!(
+        "SELECT threemf_metadata_json FROM threemf_metadata WHERE threemf_metadata_blob_id = ?",
+        blob_id
+    )
+    .fetch_optional(db)
+    .await?;
+
+    Ok(row.map(|row| row.threemf_metadata_json))
+}
+
+pub async fn set_threemf_metadata_json(db: &DbContext, blob_id: i64, json: &str) -> Result<(), DbError> {
+    sqlx::query!(
+        "INSERT OR REPLACE INTO threemf_metadata (threemf_metadata_blob_id, threemf_metadata_json) VALUES (?, ?)",
+        blob_id,
+        json
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
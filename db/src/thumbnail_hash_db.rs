@@ -0,0 +1,69 @@
+use crate::{DbError, db_context::DbContext, model::{Blob, User}};
+
+// Hashes are u64 bit patterns, stored as i64 since that is what SQLite has
+pub async fn get_thumbnail_hash(db: &DbContext, blob_id: i64) -> Result<Option<u64>, DbError> {
+    let row = sqlx::query!(
+        "SELECT thumbnail_hash FROM thumbnail_hashes WHERE thumbnail_hash_blob_id = ?",
+        blob_id
+    )
+    .fetch_optional(db)
+    .await?;
+
+    Ok(row.map(|row| row.thumbnail_hash as u64))
+}
+
+pub async fn set_thumbnail_hash(db: &DbContext, blob_id: i64, hash: u64) -> Result<(), DbError> {
+    let hash = hash as i64;
+
+    sqlx::query!(
+        "INSERT OR REPLACE INTO thumbnail_hashes (thumbnail_hash_blob_id, thumbnail_hash) VALUES (?, ?)",
+        blob_id,
+        hash
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+// Thumbnail hashes of all models the user can see, as (model id, hash)
+pub async fn get_model_thumbnail_hashes(db: &DbContext, user: &User) -> Result<Vec<(i64, u64)>, DbError> {
+    let rows = sqlx::query!(
+        "SELECT model_id as \"model_id!\", thumbnail_hash
+         FROM models
+         INNER JOIN thumbnail_hashes ON models.model_blob_id = thumbnail_hashes.thumbnail_hash_blob_id
+         WHERE models.model_user_id IN (SELECT access_owner_id FROM user_access WHERE access_user_id = ?)",
+        user.id
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| (row.model_id, row.thumbnail_hash as u64))
+        .collect())
+}
+
+// Blobs whose thumbnail was generated before thumbnails were hashed
+pub async fn get_blobs_without_thumbnail_hash(db: &DbContext) -> Result<Vec<Blob>, DbError> {
+    let rows = sqlx::query!(
+        "SELECT blob_id, blob_sha256, blob_filetype, blob_size, blob_added, blob_path
+         FROM blobs
+         LEFT JOIN thumbnail_hashes ON blobs.blob_id = thumbnail_hashes.thumbnail_hash_blob_id
+         WHERE thumbnail_hash_blob_id IS NULL"
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| Blob {
+            id: row.blob_id,
+            sha256: row.blob_sha256,
+            filetype: row.blob_filetype,
+            size: row.blob_size,
+            added: row.blob_added,
+            disk_path: row.blob_path,
+        })
+        .collect())
+}
@@ -1,6 +1,6 @@
 use rand::Rng;
 
-use crate::{DbError, db_context::DbContext, model::{User, UserPermissions, hash_password}, random_hex_32, time_now};
+use crate::{DbError, db_context::DbContext, model::{User, UserPermissions, hash_password}, random_hex_32, time_now, user_session_db, workspace_db};
 
 struct UserDbQuery {
     user_id: i64,
@@ -18,7 +18,10 @@ impl UserDbQuery {
     fn to_user(self) -> User {
         User {
             id: self.user_id,
-            username: self.user_name,
+            username: match self.user_permissions & UserPermissions::Workspace.bits() as i64 {
+                0 => self.user_name,
+                _ => workspace_db::from_user_name(self.user_name),
+            },
             email: self.user_email,
             created_at: self.user_created_at,
             last_sync: self.user_last_sync,
@@ -31,6 +34,8 @@ impl UserDbQuery {
 }
 
 pub async fn get_users(db: &DbContext) -> Result<Vec<User>, DbError> {
+    let workspace_bit = UserPermissions::Workspace.bits() as i64;
+
     let rows = sqlx::query_as!(
         UserDbQuery,
         "SELECT user_id, 
@@ -41,7 +46,8 @@ pub async fn get_users(db: &DbContext) -> Result<Vec<User>, DbError> {
             user_sync_token,
             user_sync_url,
             user_permissions,
-            user_password_hash FROM users"
+            user_password_hash FROM users WHERE (user_permissions & ?) = 0",
+        workspace_bit
     )
     .fetch_all(db)
     .await?;
@@ -90,12 +96,16 @@ pub async fn add_user(db: &DbContext, username: &str, email: &str, password: &st
     Ok(user_id)
 }
 
+// The account functions below leave workspaces alone, those are managed through workspace_db
 pub async fn edit_user_min(db: &DbContext, user_id: i64, username: &str, email: &str) -> Result<(), DbError> {
+    let workspace_bit = UserPermissions::Workspace.bits() as i64;
+
     sqlx::query!(
-        "UPDATE users SET user_name = ?, user_email = ? WHERE user_id = ?",
+        "UPDATE users SET user_name = ?, user_email = ? WHERE user_id = ? AND (user_permissions & ?) = 0",
         username,
         email,
-        user_id
+        user_id,
+        workspace_bit
     )
     .execute(db)
     .await?;
@@ -174,16 +184,20 @@ pub async fn scramble_validity_token(db: &DbContext, user_id: i64) -> Result<(),
     .execute(db)
     .await?;
 
+    user_session_db::delete_sessions_for_user(db, user_id).await?;
+
     Ok(())
 }
 
 pub async fn scramble_login_token(db: &DbContext, user_id: i64) -> Result<(), DbError> {
     let random = random_hex_32();
+    let workspace_bit = UserPermissions::Workspace.bits() as i64;
 
     sqlx::query!(
-        "UPDATE users SET user_sync_token = ? WHERE user_id = ?",
+        "UPDATE users SET user_sync_token = ? WHERE user_id = ? AND (user_permissions & ?) = 0",
         random,
-        user_id
+        user_id,
+        workspace_bit
     )
     .execute(db)
     .await?;
@@ -193,10 +207,13 @@ pub async fn scramble_login_token(db: &DbContext, user_id: i64) -> Result<(), Db
 
 pub async fn edit_user_password(db: &DbContext, user_id: i64, password: &str) -> Result<(), DbError> {
     let password = hash_password(password);
+    let workspace_bit = UserPermissions::Workspace.bits() as i64;
+
     sqlx::query!(
-        "UPDATE users SET user_password_hash = ? WHERE user_id = ?",
+        "UPDATE users SET user_password_hash = ? WHERE user_id = ? AND (user_permissions & ?) = 0",
         password,
-        user_id
+        user_id,
+        workspace_bit
     )
     .execute(db)
     .await?;
@@ -205,11 +222,15 @@ pub async fn edit_user_password(db: &DbContext, user_id: i64, password: &str) ->
 }
 
 pub async fn set_user_permissions(db: &DbContext, user_id: i64, permissions: UserPermissions) -> Result<(), DbError> {
-    let bits = permissions.bits() as i64;
+    // An account can't be turned into a workspace
+    let bits = (permissions - UserPermissions::Workspace).bits() as i64;
+    let workspace_bit = UserPermissions::Workspace.bits() as i64;
+
     sqlx::query!(
-        "UPDATE users SET user_permissions = ? WHERE user_id = ?",
+        "UPDATE users SET user_permissions = ? WHERE user_id = ? AND (user_permissions & ?) = 0",
         bits,
-        user_id
+        user_id,
+        workspace_bit
     )
     .execute(db)
     .await?;
@@ -218,9 +239,12 @@ pub async fn set_user_permissions(db: &DbContext, user_id: i64, permissions: Use
 }
 
 pub async fn delete_user(db: &DbContext, user_id: i64) -> Result<(), DbError> {
+    let workspace_bit = UserPermissions::Workspace.bits() as i64;
+
     sqlx::query!(
-        "DELETE FROM users WHERE user_id = ?",
-        user_id
+        "DELETE FROM users WHERE user_id = ? AND (user_permissions & ?) = 0",
+        user_id,
+        workspace_bit
     )
     .execute(db)
     .await?;
@@ -248,6 +272,26 @@ pub async fn get_user_by_email(db: &DbContext, email: &str) -> Result<Option<Use
     Ok(row.map(|r| r.to_user()))
 }
 
+pub async fn get_user_by_name(db: &DbContext, username: &str) -> Result<Option<User>, DbError> {
+    let row = sqlx::query_as!(
+        UserDbQuery,
+        "SELECT user_id, 
+            user_name, 
+            user_email, 
+            user_created_at, 
+            user_last_sync, 
+            user_sync_token,
+            user_sync_url,
+            user_permissions,
+            user_password_hash FROM users WHERE user_name = ?",
+        username
+    )
+    .fetch_optional(db)
+    .await?;
+
+    Ok(row.map(|r| r.to_user()))
+}
+
 pub async fn get_user_by_sync_token(db: &DbContext, sync_token: &str) -> Result<Option<User>, DbError> {
     let row = sqlx::query_as!(
         UserDbQuery,
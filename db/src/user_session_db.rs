@@ -0,0 +1,141 @@
+use crate::{DbError, db_context::DbContext, model::UserSession, random_hex_32, time_now};
+
+struct UserSessionDbQuery {
+    user_session_id: String,
+    user_session_user_id: i64,
+    user_session_store_id: Option<String>,
+    user_session_created_at: String,
+    user_session_last_used: String,
+    user_session_user_agent: Option<String>,
+    user_session_ip_address: Option<String>,
+}
+
+impl UserSessionDbQuery {
+    fn to_user_session(self) -> UserSession {
+        UserSession {
+            id: self.user_session_id,
+            user_id: self.user_session_user_id,
+            store_id: self.user_session_store_id,
+            created_at: self.user_session_created_at,
+            last_used: self.user_session_last_used,
+            user_agent: self.user_session_user_agent,
+            ip_address: self.user_session_ip_address,
+        }
+    }
+}
+
+pub async fn get_sessions_for_user(db: &DbContext, user_id: i64) -> Result<Vec<UserSession>, DbError> {
+    let rows = sqlx::query_as!(
+        UserSessionDbQuery,
+        "SELECT user_session_id,
+            user_session_user_id,
+            user_session_store_id,
+            user_session_created_at,
+            user_session_last_used,
+            user_session_user_agent,
+            user_session_ip_address FROM user_sessions WHERE user_session_user_id = ?
+            ORDER BY user_session_last_used DESC",
+        user_id
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(rows.into_iter().map(|r| r.to_user_session()).collect())
+}
+
+pub async fn get_session_via_id(db: &DbContext, session_id: &str) -> Result<Option<UserSession>, DbError> {
+    let row = sqlx::query_as!(
+        UserSessionDbQuery,
+        "SELECT user_session_id,
+            user_session_user_id,
+            user_session_store_id,
+            user_session_created_at,
+            user_session_last_used,
+            user_session_user_agent,
+            user_session_ip_address FROM user_sessions WHERE user_session_id = ?",
+        session_id
+    )
+    .fetch_optional(db)
+    .await?;
+
+    Ok(row.map(|r| r.to_user_session()))
+}
+
+pub async fn add_session(db: &DbContext, user_id: i64, store_id: Option<&str>, user_agent: Option<&str>, ip_address: Option<&str>) -> Result<String, DbError> {
+    let id = random_hex_32();
+    let now = time_now();
+
+    sqlx::query!(
+        "INSERT INTO user_sessions (user_session_id, user_session_user_id, user_session_store_id, user_session_created_at, user_session_last_used, user_session_user_agent, user_session_ip_address)
+         VALUES (?, ?, ?, ?, ?, ?, ?)",
+        id,
+        user_id,
+        store_id,
+        now,
+        now,
+        user_agent,
+        ip_address
+    )
+    .execute(db)
+    .await?;
+
+    Ok(id)
+}
+
+// Only writes when the last recorded use is older than a minute, to avoid a write on every request
+pub async fn touch_session(db: &DbContext, session_id: &str, store_id: Option<&str>, ip_address: Option<&str>) -> Result<(), DbError> {
+    let now = time_now();
+    let threshold = (chrono::Utc::now() - chrono::Duration::seconds(60))
+        .to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+
+    sqlx::query!(
+        "UPDATE user_sessions
+         SET user_session_last_used = ?, user_session_store_id = COALESCE(?, user_session_store_id), user_session_ip_address = COALESCE(?, user_session_ip_address)
+         WHERE user_session_id = ? AND user_session_last_used < ?",
+        now,
+        store_id,
+        ip_address,
+        session_id,
+        threshold
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn delete_session(db: &DbContext, user_id: i64, session_id: &str) -> Result<(), DbError> {
+    sqlx::query!(
+        "DELETE FROM user_sessions WHERE user_session_id = ? AND user_session_user_id = ?",
+        session_id,
+        user_id
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn delete_sessions_for_user(db: &DbContext, user_id: i64) -> Result<(), DbError> {
+    sqlx::query!(
+        "DELETE FROM user_sessions WHERE user_session_user_id = ?",
+        user_id
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+// The tower_sessions table is created by the session store at runtime, so it is not known to the query macros
+pub async fn delete_stale_sessions(db: &DbContext) -> Result<(), DbError> {
+    sqlx::query(
+        "DELETE FROM user_sessions
+         WHERE user_session_store_id IS NOT NULL
+           AND user_session_store_id NOT IN (SELECT id FROM tower_sessions)"
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
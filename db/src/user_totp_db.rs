@@ -0,0 +1,159 @@
+use crate::{DbError, db_context::DbContext, model::UserTotp, time_now};
+
+struct UserTotpDbQuery {
+    user_totp_user_id: i64,
+    user_totp_secret: String,
+    user_totp_enabled: i64,
+    user_totp_last_used_step: Option<i64>,
+    user_totp_created_at: String,
+}
+
+impl UserTotpDbQuery {
+    fn to_user_totp(self) -> UserTotp {
+        UserTotp {
+            user_id: self.user_totp_user_id,
+            secret: self.user_totp_secret,
+            enabled: self.user_totp_enabled != 0,
+            last_used_step: self.user_totp_last_used_step,
+            created_at: self.user_totp_created_at,
+        }
+    }
+}
+
+pub async fn get_totp(db: &DbContext, user_id: i64) -> Result<Option<UserTotp>, DbError> {
+    let row = sqlx::query_as!(
+        UserTotpDbQuery,
+        "SELECT user_totp_user_id,
+            user_totp_secret,
+            user_totp_enabled,
+            user_totp_last_used_step,
+            user_totp_created_at FROM user_totp WHERE user_totp_user_id = ?",
+        user_id
+    )
+    .fetch_optional(db)
+    .await?;
+
+    Ok(row.map(|r| r.to_user_totp()))
+}
+
+// Replaces any unconfirmed secret. The secret only takes effect once enable_totp is called
+pub async fn set_pending_totp_secret(db: &DbContext, user_id: i64, secret: &str) -> Result<(), DbError> {
+    let now = time_now();
+
+    sqlx::query!(
+        "INSERT INTO user_totp (user_totp_user_id, user_totp_secret, user_totp_enabled, user_totp_created_at)
+         VALUES (?, ?, 0, ?)
+         ON CONFLICT(user_totp_user_id) DO UPDATE SET user_totp_secret = excluded.user_totp_secret, user_totp_enabled = 0, user_totp_last_used_step = NULL, user_totp_created_at = excluded.user_totp_created_at",
+        user_id,
+        secret,
+        now
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn enable_totp(db: &DbContext, user_id: i64, last_used_step: i64) -> Result<(), DbError> {
+    sqlx::query!(
+        "UPDATE user_totp SET user_totp_enabled = 1, user_totp_last_used_step = ? WHERE user_totp_user_id = ?",
+        last_used_step,
+        user_id
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+// Only moves forward, so a code cannot be replayed within its validity window
+pub async fn set_totp_last_used_step(db: &DbContext, user_id: i64, step: i64) -> Result<bool, DbError> {
+    let result = sqlx::query!(
+        "UPDATE user_totp SET user_totp_last_used_step = ?
+         WHERE user_totp_user_id = ? AND (user_totp_last_used_step IS NULL OR user_totp_last_used_step < ?)",
+        step,
+        user_id,
+        step
+    )
+    .execute(db)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn delete_totp(db: &DbContext, user_id: i64) -> Result<(), DbError> {
+    sqlx::query!(
+        "DELETE FROM user_totp WHERE user_totp_user_id = ?",
+        user_id
+    )
+    .execute(db)
+    .await?;
+
+    sqlx::query!(
+        "DELETE FROM user_recovery_codes WHERE user_recovery_code_user_id = ?",
+        user_id
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn set_recovery_codes(db: &DbContext, user_id: i64, code_hashes: &[String]) -> Result<(), DbError> {
+    sqlx::query!(
+        "DELETE FROM user_recovery_codes WHERE user_recovery_code_user_id = ?",
+        user_id
+    )
+    .execute(db)
+    .await?;
+
+    for code_hash in code_hashes {
+        sqlx::query!(
+            "INSERT INTO user_recovery_codes (user_recovery_code_user_id, user_recovery_code_hash) VALUES (?, ?)",
+            user_id,
+            code_hash
+        )
+        .execute(db)
+        .await?;
+    }
+
+    Ok(())
+}
+
+// Unused recovery codes of the user, as (recovery code id, hash)
+pub async fn get_recovery_codes(db: &DbContext, user_id: i64) -> Result<Vec<(i64, String)>, DbError> {
+    let rows = sqlx::query!(
+        "SELECT user_recovery_code_id as \"user_recovery_code_id!\", user_recovery_code_hash FROM user_recovery_codes WHERE user_recovery_code_user_id = ?",
+        user_id
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| (row.user_recovery_code_id, row.user_recovery_code_hash))
+        .collect())
+}
+
+// Consumes the recovery code. Returns false if it was already used
+pub async fn use_recovery_code(db: &DbContext, recovery_code_id: i64) -> Result<bool, DbError> {
+    let result = sqlx::query!(
+        "DELETE FROM user_recovery_codes WHERE user_recovery_code_id = ?",
+        recovery_code_id
+    )
+    .execute(db)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn get_recovery_code_count(db: &DbContext, user_id: i64) -> Result<i64, DbError> {
+    let row = sqlx::query!(
+        "SELECT COUNT(*) as count FROM user_recovery_codes WHERE user_recovery_code_user_id = ?",
+        user_id
+    )
+    .fetch_one(db)
+    .await?;
+
+    Ok(row.count)
+}
@@ -0,0 +1,249 @@
+use itertools::join;
+
+use crate::{DbError, db_context::DbContext, model::{User, UserPermissions, Workspace, WorkspaceMember, WorkspaceRole}, model_db, random_hex_32, time_now, user_db};
+
+// Workspaces are rows in users. Their names are stored with this prefix, so they can only clash with other workspaces
+const WORKSPACE_NAME_PREFIX: &str = "workspace:";
+
+fn to_user_name(name: &str) -> String {
+    format!("{}{}", WORKSPACE_NAME_PREFIX, name)
+}
+
+pub(crate) fn from_user_name(user_name: String) -> String {
+    match user_name.strip_prefix(WORKSPACE_NAME_PREFIX) {
+        Some(name) => name.to_string(),
+        None => user_name,
+    }
+}
+
+// User names in the workspace namespace would block creating a workspace with that name
+pub fn is_reserved_user_name(name: &str) -> bool {
+    name.starts_with(WORKSPACE_NAME_PREFIX)
+}
+
+pub async fn is_workspace_name_taken(db: &DbContext, name: &str, workspace_id: Option<i64>) -> Result<bool, DbError> {
+    let user_name = to_user_name(name);
+
+    let row = sqlx::query!(
+        "SELECT user_id FROM users WHERE user_name = ?",
+        user_name
+    )
+    .fetch_optional(db)
+    .await?;
+
+    Ok(row.is_some_and(|r| Some(r.user_id) != workspace_id))
+}
+
+pub async fn get_workspaces(db: &DbContext, user: &User) -> Result<Vec<Workspace>, DbError> {
+    let rows = sqlx::query!(
+        "SELECT user_id, user_name, user_created_at, workspace_member_role
+         FROM workspace_members
+         INNER JOIN users ON workspace_members.workspace_member_workspace_id = users.user_id
+         WHERE workspace_member_user_id = ?
+         ORDER BY user_name ASC",
+        user.id
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| Workspace {
+            id: row.user_id,
+            name: from_user_name(row.user_name),
+            created_at: row.user_created_at,
+            role: WorkspaceRole::from_i64(row.workspace_member_role),
+        })
+        .collect())
+}
+
+pub async fn get_workspace_role(db: &DbContext, user: &User, workspace_id: i64) -> Result<Option<WorkspaceRole>, DbError> {
+    let row = sqlx::query!(
+        "SELECT workspace_member_role FROM workspace_members WHERE workspace_member_workspace_id = ? AND workspace_member_user_id = ?",
+        workspace_id,
+        user.id
+    )
+    .fetch_optional(db)
+    .await?;
+
+    Ok(row.map(|r| WorkspaceRole::from_i64(r.workspace_member_role)))
+}
+
+// Returns the workspace as a user, so it can be passed to the other *_db functions to create entities inside it.
+// None if the user is not a member with at least the given role
+pub async fn get_workspace_identity(db: &DbContext, user: &User, workspace_id: i64, min_role: WorkspaceRole) -> Result<Option<User>, DbError> {
+    match get_workspace_role(db, user, workspace_id).await? {
+        Some(role) if role >= min_role => {},
+        _ => return Ok(None),
+    };
+
+    let workspace = user_db::get_user_by_id(db, workspace_id).await?;
+
+    Ok(workspace.filter(|w| w.permissions.contains(UserPermissions::Workspace)))
+}
+
+pub async fn add_workspace(db: &DbContext, user: &User, name: &str) -> Result<i64, DbError> {
+    let now = time_now();
+    let permissions = UserPermissions::Workspace.bits() as i64;
+    // Workspaces can't log in. The email only has to satisfy the unique constraint on users
+    let email = format!("workspace-{}@workspace.invalid", random_hex_32());
+    let validity_token = random_hex_32();
+    let name = to_user_name(name);
+
+    let result = sqlx::query!(
+        "INSERT INTO users (user_name, user_email, user_password_hash, user_created_at, user_permissions, user_sync_url) VALUES (?, ?, '', ?, ?, ?)",
+        name,
+        email,
+        now,
+        permissions,
+        validity_token
+    )
+    .execute(db)
+    .await?;
+
+    let workspace_id = result.last_insert_rowid();
+
+    set_workspace_member(db, workspace_id, user.id, WorkspaceRole::Owner).await?;
+
+    Ok(workspace_id)
+}
+
+pub async fn edit_workspace(db: &DbContext, workspace_id: i64, name: &str) -> Result<(), DbError> {
+    let workspace_bit = UserPermissions::Workspace.bits() as i64;
+    let name = to_user_name(name);
+
+    sqlx::query!(
+        "UPDATE users SET user_name = ? WHERE user_id = ? AND (user_permissions & ?) != 0",
+        name,
+        workspace_id,
+        workspace_bit
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+// Deletes the workspace and, through the cascade on *_user_id, everything it owns
+pub async fn delete_workspace(db: &DbContext, workspace_id: i64) -> Result<(), DbError> {
+    let workspace_bit = UserPermissions::Workspace.bits() as i64;
+
+    sqlx::query!(
+        "DELETE FROM users WHERE user_id = ? AND (user_permissions & ?) != 0",
+        workspace_id,
+        workspace_bit
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn get_workspace_members(db: &DbContext, workspace_id: i64) -> Result<Vec<WorkspaceMember>, DbError> {
+    let rows = sqlx::query!(
+        "SELECT user_id, user_name, user_email, workspace_member_role, workspace_member_added_at
+         FROM workspace_members
+         INNER JOIN users ON workspace_members.workspace_member_user_id = users.user_id
+         WHERE workspace_member_workspace_id = ?
+         ORDER BY user_name ASC",
+        workspace_id
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| WorkspaceMember {
+            user_id: row.user_id,
+            username: row.user_name,
+            email: row.user_email,
+            role: WorkspaceRole::from_i64(row.workspace_member_role),
+            added_at: row.workspace_member_added_at,
+        })
+        .collect())
+}
+
+pub async fn set_workspace_member(db: &DbContext, workspace_id: i64, user_id: i64, role: WorkspaceRole) -> Result<(), DbError> {
+    let now = time_now();
+    let role = role.to_i64();
+
+    sqlx::query!(
+        "INSERT INTO workspace_members (workspace_member_workspace_id, workspace_member_user_id, workspace_member_role, workspace_member_added_at)
+         VALUES (?, ?, ?, ?)
+         ON CONFLICT(workspace_member_workspace_id, workspace_member_user_id) DO UPDATE SET workspace_member_role = excluded.workspace_member_role",
+        workspace_id,
+        user_id,
+        role,
+        now
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn remove_workspace_member(db: &DbContext, workspace_id: i64, user_id: i64) -> Result<(), DbError> {
+    sqlx::query!(
+        "DELETE FROM workspace_members WHERE workspace_member_workspace_id = ? AND workspace_member_user_id = ?",
+        workspace_id,
+        user_id
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn get_owner_count(db: &DbContext, workspace_id: i64) -> Result<i64, DbError> {
+    let owner_role = WorkspaceRole::Owner.to_i64();
+
+    let row = sqlx::query!(
+        "SELECT COUNT(*) as count FROM workspace_members WHERE workspace_member_workspace_id = ? AND workspace_member_role >= ?",
+        workspace_id,
+        owner_role
+    )
+    .fetch_one(db)
+    .await?;
+
+    Ok(row.count)
+}
+
+// Moves models to another owner (a workspace, or back to a personal library).
+// Groups move along once all of their models belong to the new owner
+pub async fn move_models_to_owner(db: &DbContext, user: &User, owner: &User, model_ids: &[i64]) -> Result<(), DbError> {
+    if model_ids.is_empty() {
+        return Ok(());
+    }
+
+    model_db::ensure_models_editable(db, user, model_ids).await?;
+
+    let ids_placeholder = join(model_ids.iter(), ",");
+    let now = time_now();
+
+    let query = format!(
+        "UPDATE models SET model_user_id = ?, model_last_modified = ? WHERE model_id IN ({})",
+        ids_placeholder
+    );
+
+    sqlx::query(&query)
+        .bind(owner.id)
+        .bind(&now)
+        .execute(db)
+        .await?;
+
+    let query = format!(
+        "UPDATE models_group SET group_user_id = ?, group_last_modified = ?
+         WHERE group_id IN (SELECT model_group_id FROM models WHERE model_id IN ({}))
+           AND NOT EXISTS (SELECT 1 FROM models WHERE model_group_id = models_group.group_id AND model_user_id != ?)",
+        ids_placeholder
+    );
+
+    sqlx::query(&query)
+        .bind(owner.id)
+        .bind(&now)
+        .bind(owner.id)
+        .execute(db)
+        .await?;
+
+    Ok(())
+}
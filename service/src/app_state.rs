@@ -36,6 +36,18 @@ impl AppState {
         path_buff
     }
 
+    // Blobs converted to another mesh format on export, named after the blob's hash and the target format
+    pub fn get_conversion_dir(&self) -> PathBuf {
+        let mut path_buff = PathBuf::from(self.app_data_path.clone());
+        path_buff.push("conversions");
+
+        if !path_buff.exists() {
+            std::fs::create_dir_all(path_buff.clone()).expect("Failed to create conversion directory");
+        }
+
+        path_buff
+    }
+
     pub fn get_resources_dir(&self) -> PathBuf {
         let mut path_buff = PathBuf::from(self.get_configuration().data_path.clone());
         path_buff.push("resources");
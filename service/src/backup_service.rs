@@ -0,0 +1,581 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Component, Path, PathBuf};
+
+use async_zip::tokio::read::seek::ZipFileReader;
+use async_zip::tokio::write::ZipFileWriter;
+use async_zip::{Compression, ZipEntryBuilder};
+use chrono::Utc;
+use db::model::{BackupBlob, Blob, FileType, LibraryDump, ModelFlags, ResourceFlags, User};
+use db::{backup_db, blob_db, group_db, label_db, label_keyword_db, model_db, resource_db, user_db};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::fs::File;
+use tokio::io::{AsyncWrite, BufReader, DuplexStream};
+use tokio_util::compat::{FuturesAsyncReadCompatExt, TokioAsyncReadCompatExt};
+
+use crate::app_state::AppState;
+use crate::export_service::{get_bytes_from_blob, get_image_path_for_blob, get_model_path_for_blob};
+use crate::service_error::ServiceError;
+
+const BACKUP_FORMAT: &str = "mesh-organiser-backup";
+// Bump when the layout of the archive or library.json changes in a way older versions can't read
+const BACKUP_VERSION: u32 = 1;
+const MANIFEST_ENTRY: &str = "manifest.json";
+const LIBRARY_ENTRY: &str = "library.json";
+const PIPE_SIZE: usize = 256 * 1024;
+
+// A backup is a zip archive with:
+// - manifest.json: format, version and counts
+// - library.json: the database rows of all users, see LibraryDump
+// - blobs/<sha256>.<filetype>: model files, stored as they are on disk
+// - images/<sha256>.png: thumbnails
+// - resources/<resource unique_global_id>/...: resource folders
+#[derive(Serialize, Deserialize)]
+pub struct BackupManifest {
+    pub format: String,
+    pub version: u32,
+    pub created: String,
+    pub users: usize,
+    pub models: usize,
+    pub blobs: usize,
+    pub groups: usize,
+    pub labels: usize,
+    pub resources: usize,
+}
+
+#[derive(Deserialize, Default)]
+pub struct RestoreOptions {
+    // Archive user id to local user id. Users that are not mapped are matched on email,
+    // and otherwise restored into the user running the restore
+    #[serde(default)]
+    pub user_mapping: HashMap<i64, i64>,
+}
+
+#[derive(Serialize, Default)]
+pub struct RestoreResult {
+    pub models: usize,
+    pub groups: usize,
+    pub labels: usize,
+    pub resources: usize,
+    // Rows the target user already had, matched on unique_global_id. These are left as they are
+    pub existing: usize,
+    // Rows whose unique_global_id is used by another user, they were given a new one
+    pub remapped_ids: usize,
+    pub missing_blobs: usize,
+}
+
+// Writes a backup of the whole library to the path. Entries are streamed into the archive one file at a time
+pub async fn create_backup(path: &PathBuf, app_state: &AppState) -> Result<BackupManifest, ServiceError> {
+    let dump = backup_db::get_library_dump(&app_state.db).await?;
+    let file = File::create(path).await?;
+
+    write_backup(file, dump, app_state).await
+}
+
+// Streams a backup of the whole library without writing the archive to disk. The size isn't known up front, as entries are deflated while streaming.
+// The library is read before this returns, so a failing database is still reported as an error instead of a broken download
+pub async fn stream_backup(app_state: &AppState) -> Result<DuplexStream, ServiceError> {
+    let dump = backup_db::get_library_dump(&app_state.db).await?;
+    let (writer, reader) = tokio::io::duplex(PIPE_SIZE);
+    let app_state = app_state.clone();
+
+    tokio::spawn(async move {
+        // The response ends early when this fails, which the client sees as a broken download
+        if let Err(e) = write_backup(writer, dump, &app_state).await {
+            println!("Failed to stream backup: {}", e);
+        }
+    });
+
+    Ok(reader)
+}
+
+async fn write_backup<W: AsyncWrite + Unpin>(writer: W, dump: LibraryDump, app_state: &AppState) -> Result<BackupManifest, ServiceError> {
+    let manifest = BackupManifest {
+        format: String::from(BACKUP_FORMAT),
+        version: BACKUP_VERSION,
+        created: Utc::now().to_rfc3339(),
+        users: dump.users.len(),
+        models: dump.models.len(),
+        blobs: dump.blobs.len(),
+        groups: dump.groups.len(),
+        labels: dump.labels.len(),
+        resources: dump.resources.len(),
+    };
+
+    let mut writer = ZipFileWriter::with_tokio(writer);
+
+    write_json_entry(&mut writer, MANIFEST_ENTRY, &manifest).await?;
+    write_json_entry(&mut writer, LIBRARY_ENTRY, &dump).await?;
+
+    let blob_ids: HashSet<i64> = dump.blobs.iter().map(|blob| blob.id).collect();
+    let blobs = blob_db::get_blobs(&app_state.db).await?;
+
+    for blob in blobs.iter().filter(|blob| blob_ids.contains(&blob.id)) {
+        let model_path = get_model_path_for_blob(blob, app_state);
+
+        if !model_path.exists() {
+            println!("Blob {} is missing on disk, leaving it out of the backup", blob.sha256);
+            continue;
+        }
+
+        // Zipped blobs are already compressed
+        let compression = match blob.to_file_type().is_zipped() {
+            true => Compression::Stored,
+            false => Compression::Deflate,
+        };
+
+        write_file_entry(&mut writer, &format!("blobs/{}.{}", blob.sha256, blob.filetype), &model_path, compression).await?;
+
+        let image_path = get_image_path_for_blob(blob, app_state);
+
+        if image_path.exists() {
+            write_file_entry(&mut writer, &format!("images/{}.png", blob.sha256), &image_path, Compression::Stored).await?;
+        }
+    }
+
+    let resources_dir = app_state.get_resources_dir();
+
+    for resource in &dump.resources {
+        let resource_dir = resources_dir.join(format!("{}_{}", resource.id, resource.user_id));
+
+        for file_path in list_files(&resource_dir) {
+            let relative = match file_path.strip_prefix(&resource_dir) {
+                Ok(relative) => relative,
+                Err(_) => continue,
+            };
+            let relative = relative.components().map(|c| c.as_os_str().to_string_lossy()).collect::<Vec<_>>().join("/");
+
+            write_file_entry(&mut writer, &format!("resources/{}/{}", resource.unique_global_id, relative), &file_path, Compression::Deflate).await?;
+        }
+    }
+
+    writer.close().await?;
+
+    Ok(manifest)
+}
+
+async fn write_json_entry<T: Serialize, W: AsyncWrite + Unpin>(writer: &mut ZipFileWriter<W>, name: &str, value: &T) -> Result<(), ServiceError> {
+    let buffer = serde_json::to_vec_pretty(value)?;
+    let builder = ZipEntryBuilder::new(name.into(), Compression::Deflate);
+    writer.write_entry_whole(builder, &buffer).await?;
+
+    Ok(())
+}
+
+async fn write_file_entry<W: AsyncWrite + Unpin>(writer: &mut ZipFileWriter<W>, name: &str, path: &PathBuf, compression: Compression) -> Result<(), ServiceError> {
+    let builder = ZipEntryBuilder::new(name.into(), compression);
+    let mut stream_writer = writer.write_entry_stream(builder).await?;
+    let mut file = File::open(path).await?.compat();
+
+    futures::io::copy(&mut file, &mut stream_writer).await?;
+    stream_writer.close().await?;
+
+    Ok(())
+}
+
+fn list_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return files,
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+
+        if path.is_dir() {
+            files.extend(list_files(&path));
+        } else if path.is_file() {
+            files.push(path);
+        }
+    }
+
+    files
+}
+
+// Entry names come from the archive, only plain relative paths are extracted
+fn safe_relative_path(name: &str) -> Option<PathBuf> {
+    let path = PathBuf::from(name);
+
+    match path.components().all(|component| matches!(component, Component::Normal(_))) {
+        true => Some(path),
+        false => None,
+    }
+}
+
+// Blob file names are built from the archive's library.json, so the hash and file type are checked before they become a path
+fn safe_blob_file_name(blob: &BackupBlob) -> Option<PathBuf> {
+    let is_hash = matches!(blob.sha256.len(), 32 | 64) && blob.sha256.chars().all(|c| matches!(c, '0'..='9' | 'a'..='f'));
+    let file_type = FileType::from_extension(&blob.filetype);
+
+    if !is_hash || file_type == FileType::Unknown || file_type.to_extension() != blob.filetype {
+        return None;
+    }
+
+    safe_relative_path(&format!("{}.{}", blob.sha256, blob.filetype))
+}
+
+// The archive could contain anything under a blob's name, so the hash of the extracted file is checked against the one it claims
+async fn is_extracted_blob_valid(blob: &BackupBlob, app_state: &AppState) -> bool {
+    let local_blob = Blob {
+        id: blob.id,
+        sha256: blob.sha256.clone(),
+        filetype: blob.filetype.clone(),
+        size: blob.size,
+        added: blob.added.clone(),
+        disk_path: None,
+    };
+
+    let bytes = match get_bytes_from_blob(&local_blob, app_state).await {
+        Ok(bytes) => bytes,
+        Err(_) => return false,
+    };
+
+    format!("{:x}", Sha256::digest(&bytes)).starts_with(&blob.sha256)
+}
+
+struct BackupReader {
+    zip: ZipFileReader<BufReader<File>>,
+    entries: HashMap<String, usize>,
+}
+
+impl BackupReader {
+    async fn open(path: &PathBuf) -> Result<Self, ServiceError> {
+        let file = File::open(path).await?;
+        let zip = ZipFileReader::with_tokio(BufReader::new(file)).await?;
+
+        let mut entries = HashMap::new();
+
+        for (index, entry) in zip.file().entries().iter().enumerate() {
+            if let Ok(name) = entry.filename().as_str() {
+                entries.insert(name.to_string(), index);
+            }
+        }
+
+        Ok(Self { zip, entries })
+    }
+
+    async fn read(&mut self, name: &str) -> Result<Option<Vec<u8>>, ServiceError> {
+        let index = match self.entries.get(name) {
+            Some(index) => *index,
+            None => return Ok(None),
+        };
+
+        let reader = self.zip.reader_with_entry(index).await?;
+        let mut buffer = Vec::new();
+        tokio::io::copy(&mut reader.compat(), &mut buffer).await?;
+
+        Ok(Some(buffer))
+    }
+
+    async fn extract(&mut self, name: &str, path: &PathBuf) -> Result<bool, ServiceError> {
+        let index = match self.entries.get(name) {
+            Some(index) => *index,
+            None => return Ok(false),
+        };
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let reader = self.zip.reader_with_entry(index).await?;
+        let mut file = File::create(path).await?;
+        tokio::io::copy(&mut reader.compat(), &mut file).await?;
+
+        Ok(true)
+    }
+
+    fn names_with_prefix(&self, prefix: &str) -> Vec<String> {
+        self.entries.keys().filter(|name| name.starts_with(prefix) && !name.ends_with('/')).cloned().collect()
+    }
+}
+
+// Merges a backup into this library. Rows the target user already has (same unique_global_id) are kept as they are,
+// everything else is added. A unique_global_id that is taken by another user is replaced with a new one.
+pub async fn restore_backup(path: &PathBuf, options: RestoreOptions, user: &User, app_state: &AppState) -> Result<RestoreResult, ServiceError> {
+    let db = &app_state.db;
+    let mut archive = BackupReader::open(path).await?;
+
+    let manifest: BackupManifest = match archive.read(MANIFEST_ENTRY).await? {
+        Some(bytes) => serde_json::from_slice(&bytes)?,
+        None => return Err(ServiceError::InternalError("Archive is not a Mesh Organiser backup".into())),
+    };
+
+    if manifest.format != BACKUP_FORMAT {
+        return Err(ServiceError::InternalError("Archive is not a Mesh Organiser backup".into()));
+    }
+
+    if manifest.version > BACKUP_VERSION {
+        return Err(ServiceError::InternalError(format!(
+            "Backup was made with a newer version (format version {}), update to restore it",
+            manifest.version
+        )));
+    }
+
+    let dump: LibraryDump = match archive.read(LIBRARY_ENTRY).await? {
+        Some(bytes) => serde_json::from_slice(&bytes)?,
+        None => return Err(ServiceError::InternalError("Backup does not contain library.json".into())),
+    };
+
+    let local = backup_db::get_library_dump(db).await?;
+    let local_users = user_db::get_users(db).await?;
+    let mut used_global_ids: HashSet<String> = local.models.iter().map(|m| m.unique_global_id.clone())
+        .chain(local.groups.iter().map(|g| g.unique_global_id.clone()))
+        .chain(local.labels.iter().map(|l| l.unique_global_id.clone()))
+        .chain(local.resources.iter().map(|r| r.unique_global_id.clone()))
+        .collect();
+    let mut used_keywords: HashSet<String> = local.labels.iter().flat_map(|l| l.keywords.iter().cloned()).collect();
+
+    let mut users: HashMap<i64, User> = HashMap::new();
+
+    for archive_user in &dump.users {
+        let local_user_id = options.user_mapping.get(&archive_user.id).copied()
+            .or_else(|| local_users.iter().find(|u| u.email == archive_user.email).map(|u| u.id))
+            .unwrap_or(user.id);
+
+        let local_user = match local_users.iter().find(|u| u.id == local_user_id) {
+            Some(local_user) => local_user.clone(),
+            None => return Err(ServiceError::InternalError(format!("User {} does not exist", local_user_id))),
+        };
+
+        users.insert(archive_user.id, local_user);
+    }
+
+    let user_for = |archive_user_id: i64| users.get(&archive_user_id).unwrap_or(user);
+    let mut result = RestoreResult::default();
+
+    let mut blob_ids: HashMap<i64, i64> = HashMap::new();
+
+    for blob in &dump.blobs {
+        if let Some(existing) = blob_db::get_blob_via_sha256(db, &blob.sha256).await? {
+            blob_ids.insert(blob.id, existing.id);
+            continue;
+        }
+
+        let (file_name, image_name) = match (safe_blob_file_name(blob), safe_relative_path(&format!("{}.png", blob.sha256))) {
+            (Some(file_name), Some(image_name)) => (file_name, image_name),
+            _ => {
+                println!("Blob {} in the backup has an invalid hash or file type, leaving it out", blob.id);
+                result.missing_blobs += 1;
+                continue;
+            }
+        };
+
+        let model_path = app_state.get_model_dir().join(&file_name);
+
+        if !archive.extract(&format!("blobs/{}", file_name.to_string_lossy()), &model_path).await? {
+            result.missing_blobs += 1;
+            continue;
+        }
+
+        let image_path = app_state.get_image_dir().join(&image_name);
+        let extracted_image = !image_path.exists() && archive.extract(&format!("images/{}", image_name.to_string_lossy()), &image_path).await?;
+
+        if !is_extracted_blob_valid(blob, app_state).await {
+            println!("Blob {} in the backup does not match its hash, leaving it out", blob.sha256);
+            let _ = std::fs::remove_file(&model_path);
+
+            if extracted_image {
+                let _ = std::fs::remove_file(&image_path);
+            }
+
+            result.missing_blobs += 1;
+            continue;
+        }
+
+        if extracted_image && image::image_dimensions(&image_path).is_err() {
+            let _ = std::fs::remove_file(&image_path);
+        }
+
+        blob_ids.insert(blob.id, blob_db::add_blob(db, &blob.sha256, &blob.filetype, blob.size, None).await?);
+    }
+
+    let resources_dir = app_state.get_resources_dir();
+    let mut resource_ids: HashMap<i64, i64> = HashMap::new();
+    let mut new_resources = Vec::new();
+
+    for resource in &dump.resources {
+        let target = user_for(resource.user_id);
+
+        let resource_id = match local.resources.iter().find(|r| r.user_id == target.id && r.unique_global_id == resource.unique_global_id) {
+            Some(existing) => {
+                result.existing += 1;
+                existing.id
+            }
+            None => {
+                let resource_id = resource_db::add_resource(db, target, &resource.name, Some(&resource.last_modified)).await?;
+                let flags = ResourceFlags::from_bits_truncate(resource.flags as u32);
+                resource_db::edit_resource(db, target, resource_id, &resource.name, flags, Some(&resource.last_modified)).await?;
+                backup_db::set_resource_created(db, resource_id, &resource.created).await?;
+
+                match used_global_ids.insert(resource.unique_global_id.clone()) {
+                    true => resource_db::edit_resource_global_id(db, target, resource_id, &resource.unique_global_id).await?,
+                    false => result.remapped_ids += 1,
+                }
+
+                new_resources.push((resource_id, resource));
+                result.resources += 1;
+                resource_id
+            }
+        };
+
+        resource_ids.insert(resource.id, resource_id);
+
+        // Files are merged into existing resource folders, files that are already there are kept
+        let prefix = format!("resources/{}/", resource.unique_global_id);
+        let resource_dir = resources_dir.join(format!("{}_{}", resource_id, target.id));
+
+        for name in archive.names_with_prefix(&prefix) {
+            let relative = match safe_relative_path(&name[prefix.len()..]) {
+                Some(relative) => relative,
+                None => continue,
+            };
+
+            let file_path = resource_dir.join(relative);
+
+            if !file_path.exists() {
+                archive.extract(&name, &file_path).await?;
+            }
+        }
+    }
+
+    let mut group_ids: HashMap<i64, i64> = HashMap::new();
+    let mut new_groups = Vec::new();
+
+    for group in &dump.groups {
+        let target = user_for(group.user_id);
+
+        if let Some(existing) = local.groups.iter().find(|g| g.user_id == target.id && g.unique_global_id == group.unique_global_id) {
+            result.existing += 1;
+            group_ids.insert(group.id, existing.id);
+            continue;
+        }
+
+        let group_id = group_db::add_empty_group(db, target, &group.name, Some(&group.last_modified)).await?;
+        backup_db::set_group_created(db, group_id, &group.created).await?;
+
+        match used_global_ids.insert(group.unique_global_id.clone()) {
+            true => group_db::edit_group_global_id(db, target, group_id, &group.unique_global_id).await?,
+            false => result.remapped_ids += 1,
+        }
+
+        group_ids.insert(group.id, group_id);
+        new_groups.push((group_id, group));
+        result.groups += 1;
+    }
+
+    let mut label_ids: HashMap<i64, i64> = HashMap::new();
+    let mut new_labels = Vec::new();
+
+    for label in &dump.labels {
+        let target = user_for(label.user_id);
+
+        if let Some(existing) = local.labels.iter().find(|l| l.user_id == target.id && l.unique_global_id == label.unique_global_id) {
+            result.existing += 1;
+            label_ids.insert(label.id, existing.id);
+            continue;
+        }
+
+        let label_id = label_db::add_label(db, target, &label.name, label.color, Some(&label.last_modified)).await?;
+
+        match used_global_ids.insert(label.unique_global_id.clone()) {
+            true => label_db::edit_label_global_id(db, target, label_id, &label.unique_global_id).await?,
+            false => result.remapped_ids += 1,
+        }
+
+        // Keywords are unique over all users, ones that are taken stay with the label that has them
+        let keywords: Vec<String> = label.keywords.iter().filter(|keyword| used_keywords.insert(keyword.to_string())).cloned().collect();
+
+        if !keywords.is_empty() {
+            label_keyword_db::set_keywords_for_label(db, target, label_id, keywords, Some(&label.last_modified)).await?;
+        }
+
+        label_ids.insert(label.id, label_id);
+        new_labels.push((label_id, label));
+        result.labels += 1;
+    }
+
+    // Only new labels get their parents, existing labels keep the hierarchy they have
+    for (label_id, label) in &new_labels {
+        let target = user_for(label.user_id);
+
+        for parent_id in label.parent_ids.iter().filter_map(|id| label_ids.get(id)) {
+            label_db::add_childs_to_label(db, target, *parent_id, vec![*label_id], Some(&label.last_modified)).await?;
+        }
+    }
+
+    let mut model_ids: HashMap<i64, i64> = HashMap::new();
+    let mut new_models = Vec::new();
+    let mut filled_groups: HashSet<i64> = HashSet::new();
+
+    for model in &dump.models {
+        let target = user_for(model.user_id);
+
+        if let Some(existing) = local.models.iter().find(|m| m.user_id == target.id && m.unique_global_id == model.unique_global_id) {
+            result.existing += 1;
+            model_ids.insert(model.id, existing.id);
+            continue;
+        }
+
+        let blob_id = match blob_ids.get(&model.blob_id) {
+            Some(blob_id) => *blob_id,
+            None => continue,
+        };
+
+        let model_id = model_db::add_model(db, target, &model.name, blob_id, model.link.as_deref(), Some(&model.last_modified)).await?;
+        let flags = ModelFlags::from_bits_truncate(model.flags as u32);
+        model_db::edit_model(db, target, model_id, &model.name, model.link.as_deref(), model.description.as_deref(), flags, Some(&model.last_modified)).await?;
+        backup_db::set_model_added(db, model_id, &model.added).await?;
+
+        match used_global_ids.insert(model.unique_global_id.clone()) {
+            true => model_db::edit_model_global_id(db, target, model_id, &model.unique_global_id).await?,
+            false => result.remapped_ids += 1,
+        }
+
+        if let Some(group_id) = model.group_id.and_then(|id| group_ids.get(&id)) {
+            group_db::set_group_id_on_models(db, target, Some(*group_id), vec![model_id], Some(&model.last_modified)).await?;
+            filled_groups.insert(*group_id);
+        }
+
+        let labels: Vec<i64> = model.label_ids.iter().filter_map(|id| label_ids.get(id)).copied().collect();
+
+        if !labels.is_empty() {
+            label_db::add_labels_on_models(db, target, &labels, &[model_id], Some(&model.last_modified)).await?;
+        }
+
+        model_ids.insert(model.id, model_id);
+        new_models.push((model_id, model));
+        result.models += 1;
+    }
+
+    for (model_id, model) in &new_models {
+        if let Some(source_model_id) = model.source_model_id.and_then(|id| model_ids.get(&id)) {
+            model_db::set_source_model(db, user_for(model.user_id), *model_id, Some(*source_model_id)).await?;
+        }
+    }
+
+    // Resources can only be set on groups with models in them
+    for (group_id, group) in &new_groups {
+        let target = user_for(group.user_id);
+
+        if let Some(resource_id) = group.resource_id.and_then(|id| resource_ids.get(&id)) {
+            if filled_groups.contains(group_id) {
+                resource_db::set_resource_on_group(db, target, Some(*resource_id), *group_id, Some(&group.last_modified)).await?;
+            }
+        }
+
+        group_db::set_last_updated_on_group(db, target, *group_id, &group.last_modified).await?;
+    }
+
+    // Adding models and children bumps the timestamps of labels and resources, put back the ones from the backup
+    for (label_id, label) in &new_labels {
+        label_db::set_last_updated_on_label(db, user_for(label.user_id), *label_id, &label.last_modified).await?;
+    }
+
+    for (resource_id, resource) in &new_resources {
+        resource_db::set_last_updated_on_resource(db, user_for(resource.user_id), *resource_id, &resource.last_modified).await?;
+    }
+
+    Ok(result)
+}
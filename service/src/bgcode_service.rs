@@ -0,0 +1,592 @@
+use std::io::Read;
+use std::path::PathBuf;
+
+use flate2::read::ZlibDecoder;
+use image::{DynamicImage, ImageFormat};
+
+use crate::service_error::ServiceError;
+
+// Prusa binary G-code, see https://github.com/prusa3d/libbgcode/blob/main/doc/specifications.md
+const MAGIC: &[u8; 4] = b"GCDE";
+const FILE_HEADER_SIZE: usize = 10;
+
+const BLOCK_FILE_METADATA: u16 = 0;
+const BLOCK_GCODE: u16 = 1;
+const BLOCK_SLICER_METADATA: u16 = 2;
+const BLOCK_PRINTER_METADATA: u16 = 3;
+const BLOCK_PRINT_METADATA: u16 = 4;
+const BLOCK_THUMBNAIL: u16 = 5;
+
+const COMPRESSION_NONE: u16 = 0;
+const COMPRESSION_DEFLATE: u16 = 1;
+const COMPRESSION_HEATSHRINK_11_4: u16 = 2;
+const COMPRESSION_HEATSHRINK_12_4: u16 = 3;
+
+const CHECKSUM_CRC32: u16 = 1;
+
+const ENCODING_INI: u16 = 0;
+const ENCODING_MEATPACK: u16 = 1;
+const ENCODING_MEATPACK_COMMENTS: u16 = 2;
+
+const THUMBNAIL_PNG: u16 = 0;
+const THUMBNAIL_JPG: u16 = 1;
+const THUMBNAIL_QOI: u16 = 2;
+
+struct Block<'a> {
+    block_type: u16,
+    compression: u16,
+    uncompressed_size: usize,
+    parameters: &'a [u8],
+    data: &'a [u8],
+}
+
+impl Block<'_> {
+    fn encoding(&self) -> u16 {
+        read_u16(self.parameters, 0).unwrap_or(0)
+    }
+}
+
+pub fn is_bgcode(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+// Decodes all G-code blocks back into plain text G-code
+pub fn decode_gcode(bytes: &[u8]) -> Result<String, ServiceError> {
+    let mut gcode = String::new();
+
+    for block in read_blocks(bytes)?.iter().filter(|b| b.block_type == BLOCK_GCODE) {
+        let data = decompress(block)?;
+
+        match block.encoding() {
+            ENCODING_MEATPACK | ENCODING_MEATPACK_COMMENTS => gcode.push_str(&unmeatpack(&data)),
+            _ => gcode.push_str(&String::from_utf8_lossy(&data)),
+        }
+    }
+
+    Ok(gcode)
+}
+
+pub fn decode_to_file(path: &PathBuf, gcode_path: &PathBuf) -> Result<(), ServiceError> {
+    let bytes = std::fs::read(path)?;
+    let gcode = decode_gcode(&bytes)?;
+
+    std::fs::write(gcode_path, gcode)?;
+
+    Ok(())
+}
+
+// Writes the metadata blocks as the "; key = value" comments a text G-code file would have, so they can be parsed the same way
+pub fn metadata_as_gcode_comments(bytes: &[u8]) -> Result<String, ServiceError> {
+    let mut lines = vec![];
+
+    for block in read_blocks(bytes)? {
+        let is_metadata = matches!(
+            block.block_type,
+            BLOCK_FILE_METADATA | BLOCK_SLICER_METADATA | BLOCK_PRINTER_METADATA | BLOCK_PRINT_METADATA
+        );
+
+        if !is_metadata || block.encoding() != ENCODING_INI {
+            continue;
+        }
+
+        let data = decompress(&block)?;
+
+        for line in String::from_utf8_lossy(&data).lines() {
+            let (key, value) = match line.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => continue,
+            };
+
+            // The file metadata names the slicer that produced the file, e.g. "Producer=PrusaSlicer 2.8.1"
+            if block.block_type == BLOCK_FILE_METADATA && key.eq_ignore_ascii_case("producer") {
+                lines.push(format!("; generated by {}", value));
+            } else {
+                lines.push(format!("; {} = {}", key, value));
+            }
+        }
+    }
+
+    Ok(lines.join("\n"))
+}
+
+// Returns the largest embedded thumbnail
+pub fn read_thumbnail(path: &PathBuf) -> Result<Option<DynamicImage>, ServiceError> {
+    let bytes = std::fs::read(path)?;
+
+    let thumbnail = read_blocks(&bytes)?
+        .into_iter()
+        .filter(|b| b.block_type == BLOCK_THUMBNAIL)
+        .max_by_key(|b| {
+            let width = read_u16(b.parameters, 2).unwrap_or(0) as u32;
+            let height = read_u16(b.parameters, 4).unwrap_or(0) as u32;
+            width * height
+        });
+
+    let block = match thumbnail {
+        Some(block) => block,
+        None => return Ok(None),
+    };
+
+    let format = match read_u16(block.parameters, 0) {
+        Some(THUMBNAIL_PNG) => ImageFormat::Png,
+        Some(THUMBNAIL_JPG) => ImageFormat::Jpeg,
+        Some(THUMBNAIL_QOI) => ImageFormat::Qoi,
+        _ => return Ok(None),
+    };
+
+    let data = decompress(&block)?;
+
+    Ok(Some(image::load_from_memory_with_format(&data, format)?))
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> Option<u16> {
+    bytes
+        .get(offset..offset + 2)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]))
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    bytes
+        .get(offset..offset + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+fn corrupt() -> ServiceError {
+    ServiceError::InternalError(String::from("Binary G-code file is corrupt"))
+}
+
+fn read_blocks(bytes: &[u8]) -> Result<Vec<Block<'_>>, ServiceError> {
+    if !is_bgcode(bytes) || bytes.len() < FILE_HEADER_SIZE {
+        return Err(ServiceError::InternalError(String::from(
+            "Not a binary G-code file",
+        )));
+    }
+
+    let checksum_size = match read_u16(bytes, 8) {
+        Some(CHECKSUM_CRC32) => 4,
+        _ => 0,
+    };
+
+    let mut blocks = vec![];
+    let mut offset = FILE_HEADER_SIZE;
+
+    while offset < bytes.len() {
+        let block_type = read_u16(bytes, offset).ok_or_else(corrupt)?;
+        let compression = read_u16(bytes, offset + 2).ok_or_else(corrupt)?;
+        let uncompressed_size = read_u32(bytes, offset + 4).ok_or_else(corrupt)? as usize;
+        offset += 8;
+
+        let data_size = if compression == COMPRESSION_NONE {
+            uncompressed_size
+        } else {
+            let compressed_size = read_u32(bytes, offset).ok_or_else(corrupt)? as usize;
+            offset += 4;
+            compressed_size
+        };
+
+        // Thumbnails carry their format and size, every other block only its encoding
+        let parameters_size = if block_type == BLOCK_THUMBNAIL { 6 } else { 2 };
+
+        let parameters = bytes.get(offset..offset + parameters_size).ok_or_else(corrupt)?;
+        offset += parameters_size;
+
+        let data = bytes.get(offset..offset + data_size).ok_or_else(corrupt)?;
+        offset += data_size + checksum_size;
+
+        blocks.push(Block {
+            block_type,
+            compression,
+            uncompressed_size,
+            parameters,
+            data,
+        });
+    }
+
+    Ok(blocks)
+}
+
+fn decompress(block: &Block) -> Result<Vec<u8>, ServiceError> {
+    match block.compression {
+        COMPRESSION_NONE => Ok(block.data.to_vec()),
+        COMPRESSION_DEFLATE => {
+            let mut data = Vec::with_capacity(block.uncompressed_size);
+            ZlibDecoder::new(block.data).read_to_end(&mut data)?;
+            Ok(data)
+        }
+        COMPRESSION_HEATSHRINK_11_4 => Ok(unheatshrink(block.data, 11, 4, block.uncompressed_size)),
+        COMPRESSION_HEATSHRINK_12_4 => Ok(unheatshrink(block.data, 12, 4, block.uncompressed_size)),
+        _ => Err(ServiceError::InternalError(String::from(
+            "Unsupported binary G-code compression",
+        ))),
+    }
+}
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl BitReader<'_> {
+    fn read(&mut self, count: u32) -> Option<usize> {
+        if self.position + count as usize > self.data.len() * 8 {
+            return None;
+        }
+
+        let mut value = 0;
+
+        for _ in 0..count {
+            let bit = (self.data[self.position / 8] >> (7 - self.position % 8)) & 1;
+            value = (value << 1) | bit as usize;
+            self.position += 1;
+        }
+
+        Some(value)
+    }
+}
+
+// Heatshrink is LZSS: a set bit is followed by a literal byte, a cleared bit by a back reference into the output.
+// The leftover bits of the last byte are padding
+fn unheatshrink(data: &[u8], window_bits: u32, lookahead_bits: u32, expected_size: usize) -> Vec<u8> {
+    let mut reader = BitReader { data, position: 0 };
+    let mut output = Vec::with_capacity(expected_size);
+
+    while output.len() < expected_size {
+        match reader.read(1) {
+            Some(1) => match reader.read(8) {
+                Some(byte) => output.push(byte as u8),
+                None => break,
+            },
+            Some(_) => {
+                let (index, count) = match (reader.read(window_bits), reader.read(lookahead_bits)) {
+                    (Some(index), Some(count)) => (index + 1, count + 1),
+                    _ => break,
+                };
+
+                for _ in 0..count.min(expected_size - output.len()) {
+                    // The window starts out zeroed, so references before the start of the output read zeroes
+                    let byte = if index > output.len() { 0 } else { output[output.len() - index] };
+                    output.push(byte);
+                }
+            }
+            None => break,
+        }
+    }
+
+    output
+}
+
+const MEATPACK_COMMAND_BYTE: u8 = 0xFF;
+const MEATPACK_ENABLE_PACKING: u8 = 251;
+const MEATPACK_DISABLE_PACKING: u8 = 250;
+const MEATPACK_RESET_ALL: u8 = 249;
+const MEATPACK_ENABLE_NO_SPACES: u8 = 247;
+const MEATPACK_DISABLE_NO_SPACES: u8 = 246;
+// A nibble of all ones means the character did not fit in 4 bits and follows as a full byte
+const MEATPACK_FULL_CHAR: u8 = 0b1111;
+
+#[derive(Default)]
+struct MeatPackDecoder {
+    packing: bool,
+    no_spaces: bool,
+    command_pending: bool,
+    command_bytes: usize,
+    full_chars_pending: usize,
+    buffered_char: Option<u8>,
+    output: Vec<u8>,
+}
+
+impl MeatPackDecoder {
+    fn unpack_nibble(&self, nibble: u8) -> u8 {
+        match nibble {
+            0..=9 => b'0' + nibble,
+            10 => b'.',
+            11 if self.no_spaces => b'E',
+            11 => b' ',
+            12 => b'\n',
+            13 => b'G',
+            _ => b'X',
+        }
+    }
+
+    fn handle_command(&mut self, command: u8) {
+        match command {
+            MEATPACK_ENABLE_PACKING => self.packing = true,
+            MEATPACK_DISABLE_PACKING | MEATPACK_RESET_ALL => self.packing = false,
+            MEATPACK_ENABLE_NO_SPACES => self.no_spaces = true,
+            MEATPACK_DISABLE_NO_SPACES => self.no_spaces = false,
+            _ => {}
+        }
+    }
+
+    fn push(&mut self, byte: u8) {
+        if byte == MEATPACK_COMMAND_BYTE {
+            if self.command_bytes > 0 {
+                self.command_pending = true;
+                self.command_bytes = 0;
+            } else {
+                self.command_bytes += 1;
+            }
+        } else if self.command_pending {
+            self.handle_command(byte);
+            self.command_pending = false;
+        } else {
+            // A single 0xFF was data, not the start of a command
+            if self.command_bytes > 0 {
+                self.push_data(MEATPACK_COMMAND_BYTE);
+                self.command_bytes = 0;
+            }
+
+            self.push_data(byte);
+        }
+    }
+
+    fn push_data(&mut self, byte: u8) {
+        if !self.packing {
+            self.output.push(byte);
+            return;
+        }
+
+        if self.full_chars_pending > 0 {
+            self.output.push(byte);
+
+            if let Some(buffered) = self.buffered_char.take() {
+                self.output.push(buffered);
+            }
+
+            self.full_chars_pending -= 1;
+            return;
+        }
+
+        let low = byte & 0x0F;
+        let high = byte >> 4;
+
+        if low == MEATPACK_FULL_CHAR {
+            self.full_chars_pending += 1;
+
+            if high == MEATPACK_FULL_CHAR {
+                self.full_chars_pending += 1;
+            } else {
+                self.buffered_char = Some(self.unpack_nibble(high));
+            }
+        } else {
+            let first = self.unpack_nibble(low);
+            self.output.push(first);
+
+            // A newline ends the pair, the other half is padding
+            if first != b'\n' {
+                if high == MEATPACK_FULL_CHAR {
+                    self.full_chars_pending += 1;
+                } else {
+                    self.output.push(self.unpack_nibble(high));
+                }
+            }
+        }
+    }
+}
+
+fn unmeatpack(data: &[u8]) -> String {
+    let mut decoder = MeatPackDecoder::default();
+
+    for byte in data {
+        decoder.push(*byte);
+    }
+
+    add_omitted_spaces(&decoder.output)
+}
+
+// MeatPack drops the spaces between G-code parameters, put them back so the result is readable as normal G-code.
+// M commands can carry free text (M117), so there a space is only added where a number ends. Comments are left as they are
+fn add_omitted_spaces(decoded: &[u8]) -> String {
+    let mut output = String::with_capacity(decoded.len() + decoded.len() / 4);
+    let mut previous: Option<char> = None;
+    let mut line_command: Option<char> = None;
+
+    for c in decoded.iter().map(|c| *c as char) {
+        if (c == 'G' || c == 'M') && (previous.is_none() || previous == Some('\n')) {
+            line_command = Some(c);
+        } else if c == '\n' || c == ';' {
+            line_command = None;
+        }
+
+        let needs_space = match line_command {
+            Some('G') => previous.is_some_and(|p| p != ' '),
+            Some('M') => previous.is_some_and(|p| p.is_ascii_digit() || p == '.'),
+            _ => false,
+        };
+
+        if needs_space && "XYZEFIJRPWHCAS".contains(c) {
+            output.push(' ');
+            previous = Some(' ');
+        }
+
+        // Empty lines are dropped
+        if c != '\n' || previous.is_some_and(|p| p != '\n') {
+            output.push(c);
+            previous = Some(c);
+        }
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{Compression, write::ZlibEncoder};
+    use std::io::Write;
+
+    #[derive(Default)]
+    struct BitWriter {
+        bytes: Vec<u8>,
+        position: usize,
+    }
+
+    impl BitWriter {
+        fn write(&mut self, value: usize, count: u32) {
+            for i in (0..count).rev() {
+                if self.position.is_multiple_of(8) {
+                    self.bytes.push(0);
+                }
+
+                let bit = ((value >> i) & 1) as u8;
+                *self.bytes.last_mut().unwrap() |= bit << (7 - self.position % 8);
+                self.position += 1;
+            }
+        }
+    }
+
+    fn block(block_type: u16, compression: u16, parameters: &[u8], data: &[u8], uncompressed_size: usize) -> Vec<u8> {
+        let mut bytes = vec![];
+        bytes.extend_from_slice(&block_type.to_le_bytes());
+        bytes.extend_from_slice(&compression.to_le_bytes());
+        bytes.extend_from_slice(&(uncompressed_size as u32).to_le_bytes());
+
+        if compression != COMPRESSION_NONE {
+            bytes.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        }
+
+        bytes.extend_from_slice(parameters);
+        bytes.extend_from_slice(data);
+        bytes
+    }
+
+    fn file(checksum: u16, blocks: &[Vec<u8>]) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&1u32.to_le_bytes());
+        bytes.extend_from_slice(&checksum.to_le_bytes());
+
+        for block in blocks {
+            bytes.extend_from_slice(block);
+
+            // The checksum isn't verified, only skipped
+            if checksum == CHECKSUM_CRC32 {
+                bytes.extend_from_slice(&[0xAA; 4]);
+            }
+        }
+
+        bytes
+    }
+
+    fn meatpack(data: &[u8]) -> String {
+        let mut bytes = vec![MEATPACK_COMMAND_BYTE, MEATPACK_COMMAND_BYTE, MEATPACK_ENABLE_PACKING];
+        bytes.extend_from_slice(data);
+        unmeatpack(&bytes)
+    }
+
+    #[test]
+    fn unheatshrink_expands_literals_and_back_references() {
+        let mut writer = BitWriter::default();
+        writer.write(1, 1);
+        writer.write(b'a' as usize, 8);
+        writer.write(1, 1);
+        writer.write(b'b' as usize, 8);
+        // Copy 4 bytes starting 2 back, overlapping the output that is being written
+        writer.write(0, 1);
+        writer.write(2 - 1, 11);
+        writer.write(4 - 1, 4);
+
+        assert_eq!(unheatshrink(&writer.bytes, 11, 4, 6), b"ababab");
+        // Padding bits at the end don't produce output past the expected size
+        assert_eq!(unheatshrink(&writer.bytes, 11, 4, 3), b"aba");
+    }
+
+    #[test]
+    fn unheatshrink_reads_zeroes_before_the_output_start() {
+        let mut writer = BitWriter::default();
+        writer.write(0, 1);
+        writer.write(8 - 1, 12);
+        writer.write(2 - 1, 4);
+
+        assert_eq!(unheatshrink(&writer.bytes, 12, 4, 2), vec![0, 0]);
+    }
+
+    #[test]
+    fn meatpack_unpacks_nibble_pairs() {
+        // "G1", " X", "1\n"
+        assert_eq!(meatpack(&[0x1D, 0xEB, 0xC1]), "G1 X1\n");
+    }
+
+    #[test]
+    fn meatpack_without_spaces_decodes_e_and_restores_spaces() {
+        let data = [MEATPACK_COMMAND_BYTE, MEATPACK_COMMAND_BYTE, MEATPACK_ENABLE_NO_SPACES, 0x1D, 0x1E, 0x2B, 0x0C];
+
+        assert_eq!(meatpack(&data), "G1 X1 E2\n");
+    }
+
+    #[test]
+    fn meatpack_full_characters_follow_as_bytes() {
+        // "M" + "1", "04", "S" + "2", "15", "\n"
+        let data = [0x1F, b'M', 0x40, 0x2F, b'S', 0x51, 0x0C];
+
+        assert_eq!(meatpack(&data), "M104 S215\n");
+    }
+
+    #[test]
+    fn omitted_spaces_are_added_to_g_and_m_lines() {
+        let decoded = b"G1X10.5Y-2F3000\n\nM104S215\nM73P10R5\nM117 Print done\nG4S1\n;comment X\n";
+
+        assert_eq!(
+            add_omitted_spaces(decoded),
+            "G1 X10.5 Y-2 F3000\nM104 S215\nM73 P10 R5\nM117 Print done\nG4 S1\n;comment X\n"
+        );
+    }
+
+    #[test]
+    fn trailing_comments_are_left_alone() {
+        let decoded = b"G1X1Y2;PAUSE\nM106S255;FAN SPEED\nM600;PAUSE\n";
+
+        assert_eq!(
+            add_omitted_spaces(decoded),
+            "G1 X1 Y2;PAUSE\nM106 S255;FAN SPEED\nM600;PAUSE\n"
+        );
+    }
+
+    #[test]
+    fn decode_gcode_reads_deflated_blocks() {
+        let gcode = b"G28\nG1 X10 Y10\n";
+        let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+        encoder.write_all(gcode).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let bytes = file(CHECKSUM_CRC32, &[
+            block(BLOCK_FILE_METADATA, COMPRESSION_NONE, &ENCODING_INI.to_le_bytes(), b"Producer=PrusaSlicer 2.8.1\n", 27),
+            block(BLOCK_GCODE, COMPRESSION_DEFLATE, &ENCODING_INI.to_le_bytes(), &compressed, gcode.len()),
+            block(BLOCK_PRINT_METADATA, COMPRESSION_NONE, &ENCODING_INI.to_le_bytes(), b"layer_height=0.2\n", 17),
+        ]);
+
+        assert!(is_bgcode(&bytes));
+        assert_eq!(decode_gcode(&bytes).unwrap(), "G28\nG1 X10 Y10\n");
+        assert_eq!(
+            metadata_as_gcode_comments(&bytes).unwrap(),
+            "; generated by PrusaSlicer 2.8.1\n; layer_height = 0.2"
+        );
+    }
+
+    #[test]
+    fn truncated_files_are_rejected() {
+        let bytes = file(0, &[block(BLOCK_GCODE, COMPRESSION_NONE, &ENCODING_INI.to_le_bytes(), b"G28\n", 4)]);
+
+        assert!(decode_gcode(&bytes[..bytes.len() - 1]).is_err());
+        assert!(decode_gcode(b"G28\n").is_err());
+    }
+}
@@ -37,6 +37,7 @@ pub struct StoredConfiguration {
     pub max_size_model_step_preview: Option<u32>,
     pub only_show_single_image_in_groups: Option<bool>,
     pub custom_slicer_path: Option<String>,
+    pub slicer_executable: Option<String>,
     pub elegoo_deep_link: Option<bool>,
     pub group_split_view: Option<String>,
     pub label_exported_model_as_printed: Option<bool>,
@@ -53,6 +54,8 @@ pub struct StoredConfiguration {
     pub thumbnail_rotation: Option<[i16; 3]>,
     pub watch_downloads_folder: Option<bool>,
     pub startup_page: Option<String>,
+    pub combined_3mf_bed_size: Option<[u32; 2]>,
+    pub export_folder_template: Option<String>,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -85,6 +88,8 @@ pub struct Configuration {
     pub max_size_model_obj_preview: u32,
     pub only_show_single_image_in_groups: bool,
     pub custom_slicer_path: String,
+    // Which install of the selected slicer to open. Empty uses the first one found
+    pub slicer_executable: String,
     pub elegoo_deep_link: bool,
     pub group_split_view: String,
     pub label_exported_model_as_printed: bool,
@@ -102,6 +107,10 @@ pub struct Configuration {
     pub watch_downloads_folder: bool,
     pub startup_page: String,
     pub max_size_model_step_preview: u32,
+    // Bed size in mm used to arrange models when combining them into a single 3MF
+    pub combined_3mf_bed_size: [u32; 2],
+    // Path template used when exporting models to a folder tree, see folder_export_service
+    pub export_folder_template: String,
 }
 
 pub fn stored_to_configuration(configuration: StoredConfiguration) -> Configuration {
@@ -188,6 +197,9 @@ pub fn stored_to_configuration(configuration: StoredConfiguration) -> Configurat
         custom_slicer_path: configuration
             .custom_slicer_path
             .unwrap_or(default.custom_slicer_path),
+        slicer_executable: configuration
+            .slicer_executable
+            .unwrap_or(default.slicer_executable),
         elegoo_deep_link: configuration
             .elegoo_deep_link
             .unwrap_or(default.elegoo_deep_link),
@@ -231,6 +243,12 @@ pub fn stored_to_configuration(configuration: StoredConfiguration) -> Configurat
         max_size_model_step_preview: configuration
             .max_size_model_step_preview
             .unwrap_or(default.max_size_model_step_preview),
+        combined_3mf_bed_size: configuration
+            .combined_3mf_bed_size
+            .unwrap_or(default.combined_3mf_bed_size),
+        export_folder_template: configuration
+            .export_folder_template
+            .unwrap_or(default.export_folder_template),
     }
 }
 
@@ -276,6 +294,7 @@ impl Default for Configuration {
             max_size_model_step_preview: 10,
             only_show_single_image_in_groups: true,
             custom_slicer_path: String::new(),
+            slicer_executable: String::new(),
             elegoo_deep_link: false,
             group_split_view: String::from("split-left-right"),
             label_exported_model_as_printed: false,
@@ -292,6 +311,8 @@ impl Default for Configuration {
             thumbnail_rotation: [35, 30, 0],
             watch_downloads_folder: false,
             startup_page: String::from(""),
+            combined_3mf_bed_size: [256, 256],
+            export_folder_template: String::from("{group}/{name}.{ext}"),
         }
     }
 }
@@ -0,0 +1,78 @@
+use std::path::PathBuf;
+
+use chrono::Utc;
+use db::model::{Blob, FileType};
+use strum::IntoEnumIterator;
+use tokio::task::spawn_blocking;
+
+use crate::app_state::AppState;
+use crate::export_service::get_bytes_from_blob;
+use crate::mesh_service::{self, MeshFormat};
+use crate::service_error::ServiceError;
+
+pub fn can_convert(file_type: &FileType) -> bool {
+    matches!(file_type.from_zip(), FileType::Stl | FileType::Obj | FileType::Threemf | FileType::Step)
+}
+
+// True when the blob has to be converted to end up in the requested format
+pub fn needs_conversion(blob: &Blob, format: Option<MeshFormat>) -> bool {
+    match format {
+        Some(format) => MeshFormat::from_file_type(&blob.to_file_type()) != Some(format),
+        None => false,
+    }
+}
+
+pub fn get_conversion_path(sha256: &str, format: MeshFormat, app_state: &AppState) -> PathBuf {
+    app_state
+        .get_conversion_dir()
+        .join(format!("{}.{}", sha256, format.to_extension()))
+}
+
+// Returns the path of the blob converted to the requested format, converting it on first use
+pub async fn get_converted_path(blob: &Blob, format: MeshFormat, app_state: &AppState) -> Result<PathBuf, ServiceError> {
+    let file_type = blob.to_file_type();
+
+    if !can_convert(&file_type) {
+        return Err(ServiceError::InternalError(format!(
+            "Cannot convert {} to {}",
+            file_type.from_zip().to_extension(),
+            format.to_extension()
+        )));
+    }
+
+    let path = get_conversion_path(&blob.sha256, format, app_state);
+
+    if path.exists() {
+        return Ok(path);
+    }
+
+    let bytes = get_bytes_from_blob(blob, app_state).await?;
+    let name = blob.sha256.clone();
+    let final_path = path.clone();
+
+    spawn_blocking(move || -> Result<(), ServiceError> {
+        let meshes = mesh_service::read_meshes(&bytes, &file_type, &name)?;
+
+        // Written next to the final path first, so an interrupted conversion never ends up in the cache
+        let temp_path = final_path.with_extension(format!("{}.{}.tmp", format.to_extension(), Utc::now().timestamp_nanos_opt().unwrap()));
+        mesh_service::write_meshes(&meshes, format, &[], &temp_path)?;
+        std::fs::rename(&temp_path, &final_path)?;
+
+        Ok(())
+    })
+    .await??;
+
+    Ok(path)
+}
+
+pub fn delete_conversions(sha256: &str, app_state: &AppState) {
+    for format in MeshFormat::iter() {
+        let path = get_conversion_path(sha256, format, app_state);
+
+        if path.exists() {
+            if let Err(e) = std::fs::remove_file(path) {
+                eprintln!("Failed to remove converted blob file: {}", e);
+            }
+        }
+    }
+}
@@ -0,0 +1,351 @@
+use std::collections::HashMap;
+use std::f64::consts::PI;
+
+use db::geometry_fingerprint_db;
+use db::model::{Blob, FileType, GeometryFingerprint, Model, ModelFlags, User};
+use db::{label_db, model_db};
+use itertools::Itertools;
+use sha2::{Digest, Sha256};
+use tokio::task::spawn_blocking;
+
+use crate::app_state::AppState;
+use crate::export_service::get_bytes_from_blob;
+use crate::mesh_analysis_service::{UnionFind, cross, dot, signed_volume, sub, weld_vertices};
+use crate::mesh_service::{self, Mesh};
+use crate::service_error::ServiceError;
+
+// Vertices are rounded to this grid (in mm) before hashing, so ASCII and binary exports of the same part hash the same
+const HASH_GRID: f64 = 1e-3;
+// Relative difference in volume, area and moments below which two meshes are considered the same shape
+const SHAPE_TOLERANCE: f64 = 0.005;
+
+// Computes the fingerprints of meshes imported before fingerprints were computed on import
+pub async fn fingerprint_missing(app_state: &AppState) -> Result<(), ServiceError> {
+    let blobs = geometry_fingerprint_db::get_mesh_blobs_without_fingerprint(&app_state.db).await?;
+
+    for blob in blobs {
+        if let Err(e) = fingerprint_blob(&blob, app_state).await {
+            println!("Failed to fingerprint mesh of blob {}: {}", blob.id, e);
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn fingerprint_blob(blob: &Blob, app_state: &AppState) -> Result<GeometryFingerprint, ServiceError> {
+    let bytes = get_bytes_from_blob(blob, app_state).await?;
+
+    store_fingerprint_from_bytes(blob.id, &blob.to_file_type(), bytes, app_state).await
+}
+
+pub async fn store_fingerprint_from_bytes(blob_id: i64, file_type: &FileType, bytes: Vec<u8>, app_state: &AppState) -> Result<GeometryFingerprint, ServiceError> {
+    let file_type = file_type.from_zip();
+
+    let fingerprint = spawn_blocking(move || -> Result<GeometryFingerprint, ServiceError> {
+        let meshes = mesh_service::read_meshes(&bytes, &file_type, "")?;
+        Ok(compute_fingerprint(&meshes))
+    }).await??;
+
+    geometry_fingerprint_db::set_fingerprint(&app_state.db, blob_id, &fingerprint).await?;
+
+    Ok(fingerprint)
+}
+
+// Describes the shape independent of file format, triangle order and position.
+// Volume, area and moments also don't change with rotation, the hash does.
+pub fn compute_fingerprint(meshes: &[Mesh]) -> GeometryFingerprint {
+    let mut positions = Vec::new();
+    let mut triangles = Vec::new();
+
+    for mesh in meshes {
+        let (welded, remap) = weld_vertices(&mesh.vertices);
+        let offset = positions.len();
+        positions.extend(welded);
+
+        triangles.extend(
+            mesh.triangles
+                .iter()
+                .filter(|triangle| triangle.iter().all(|index| *index < remap.len()))
+                .map(|[a, b, c]| [remap[*a] + offset, remap[*b] + offset, remap[*c] + offset]),
+        );
+    }
+
+    let mut centre = [0.0; 3];
+
+    for vertex in &positions {
+        for axis in 0..3 {
+            centre[axis] += vertex[axis] / positions.len() as f64;
+        }
+    }
+
+    let mut grid: Vec<[i64; 3]> = positions
+        .iter()
+        .map(|vertex| {
+            let relative = sub(vertex, &centre);
+            [
+                (relative[0] / HASH_GRID).round() as i64,
+                (relative[1] / HASH_GRID).round() as i64,
+                (relative[2] / HASH_GRID).round() as i64,
+            ]
+        })
+        .collect();
+
+    grid.sort_unstable();
+    grid.dedup();
+
+    let mut hasher = Sha256::new();
+
+    for vertex in &grid {
+        for value in vertex {
+            hasher.update(value.to_le_bytes());
+        }
+    }
+
+    let hash = format!("{:x}", hasher.finalize())[0..32].to_string();
+
+    let mut volume = 0.0;
+    let mut area = 0.0;
+    let mut surface_centre = [0.0; 3];
+
+    for [a, b, c] in &triangles {
+        let (a, b, c) = (&positions[*a], &positions[*b], &positions[*c]);
+        let triangle_area = triangle_area(a, b, c);
+
+        volume += signed_volume(a, b, c);
+        area += triangle_area;
+
+        for axis in 0..3 {
+            surface_centre[axis] += triangle_area * (a[axis] + b[axis] + c[axis]) / 3.0;
+        }
+    }
+
+    if area > 0.0 {
+        for value in surface_centre.iter_mut() {
+            *value /= area;
+        }
+    }
+
+    // Area weighted covariance of the triangle centres, its eigenvalues don't depend on how the mesh is rotated
+    let mut covariance = [[0.0; 3]; 3];
+
+    for [a, b, c] in &triangles {
+        let (a, b, c) = (&positions[*a], &positions[*b], &positions[*c]);
+        let weight = triangle_area(a, b, c);
+        let centroid = [(a[0] + b[0] + c[0]) / 3.0, (a[1] + b[1] + c[1]) / 3.0, (a[2] + b[2] + c[2]) / 3.0];
+        let offset = sub(&centroid, &surface_centre);
+
+        for row in 0..3 {
+            for column in 0..3 {
+                covariance[row][column] += weight * offset[row] * offset[column];
+            }
+        }
+    }
+
+    if area > 0.0 {
+        for row in covariance.iter_mut() {
+            for value in row.iter_mut() {
+                *value /= area;
+            }
+        }
+    }
+
+    let eigenvalues = symmetric_eigenvalues(&covariance);
+
+    GeometryFingerprint {
+        hash,
+        vertex_count: positions.len() as i64,
+        volume: volume.abs(),
+        area,
+        moments: eigenvalues.map(|value| value.max(0.0).sqrt()),
+    }
+}
+
+fn triangle_area(a: &[f64; 3], b: &[f64; 3], c: &[f64; 3]) -> f64 {
+    let normal = cross(&sub(b, a), &sub(c, a));
+    dot(&normal, &normal).sqrt() / 2.0
+}
+
+// Closed form eigenvalues of a symmetric 3x3 matrix, largest first
+fn symmetric_eigenvalues(m: &[[f64; 3]; 3]) -> [f64; 3] {
+    let off_diagonal = m[0][1].powi(2) + m[0][2].powi(2) + m[1][2].powi(2);
+
+    let mut values = if off_diagonal == 0.0 {
+        [m[0][0], m[1][1], m[2][2]]
+    } else {
+        let q = (m[0][0] + m[1][1] + m[2][2]) / 3.0;
+        let p = (((m[0][0] - q).powi(2) + (m[1][1] - q).powi(2) + (m[2][2] - q).powi(2) + 2.0 * off_diagonal) / 6.0).sqrt();
+
+        let mut b = *m;
+
+        for (index, row) in b.iter_mut().enumerate() {
+            row[index] -= q;
+
+            for value in row.iter_mut() {
+                *value /= p;
+            }
+        }
+
+        let determinant = b[0][0] * (b[1][1] * b[2][2] - b[1][2] * b[2][1])
+            - b[0][1] * (b[1][0] * b[2][2] - b[1][2] * b[2][0])
+            + b[0][2] * (b[1][0] * b[2][1] - b[1][1] * b[2][0]);
+
+        let phi = (determinant / 2.0).clamp(-1.0, 1.0).acos() / 3.0;
+        let largest = q + 2.0 * p * phi.cos();
+        let smallest = q + 2.0 * p * (phi + 2.0 * PI / 3.0).cos();
+
+        [largest, 3.0 * q - largest - smallest, smallest]
+    };
+
+    values.sort_by(|a, b| b.total_cmp(a));
+    values
+}
+
+fn relative_difference(a: f64, b: f64) -> f64 {
+    let largest = a.abs().max(b.abs());
+
+    if largest == 0.0 {
+        return 0.0;
+    }
+
+    (a - b).abs() / largest
+}
+
+pub fn is_same_shape(a: &GeometryFingerprint, b: &GeometryFingerprint) -> bool {
+    if a.hash == b.hash {
+        return true;
+    }
+
+    if a.area <= 0.0 || b.area <= 0.0 {
+        return false;
+    }
+
+    relative_difference(a.volume, b.volume) < SHAPE_TOLERANCE
+        && relative_difference(a.area, b.area) < SHAPE_TOLERANCE
+        && (0..3).all(|axis| relative_difference(a.moments[axis], b.moments[axis]) < SHAPE_TOLERANCE)
+}
+
+// Groups the models of the user that likely contain the same part. Each group is ordered oldest first
+pub async fn find_duplicates(user: &User, app_state: &AppState) -> Result<Vec<Vec<Model>>, ServiceError> {
+    let mut fingerprints = geometry_fingerprint_db::get_model_fingerprints(&app_state.db, user).await?;
+
+    // Sorted by area, so only neighbours within the tolerance have to be compared
+    fingerprints.sort_by(|(_, a), (_, b)| a.area.total_cmp(&b.area));
+
+    let mut groups = UnionFind::new(fingerprints.len());
+    let mut by_hash: HashMap<&str, usize> = HashMap::new();
+
+    for (index, (_, fingerprint)) in fingerprints.iter().enumerate() {
+        match by_hash.get(fingerprint.hash.as_str()) {
+            Some(first) => groups.union(index, *first),
+            None => {
+                by_hash.insert(&fingerprint.hash, index);
+            }
+        }
+
+        for (other, (_, candidate)) in fingerprints.iter().enumerate().skip(index + 1) {
+            if relative_difference(fingerprint.area, candidate.area) >= SHAPE_TOLERANCE {
+                break;
+            }
+
+            if is_same_shape(fingerprint, candidate) {
+                groups.union(index, other);
+            }
+        }
+    }
+
+    let model_groups: Vec<Vec<i64>> = (0..fingerprints.len())
+        .map(|index| (groups.find(index), fingerprints[index].0))
+        .into_group_map()
+        .into_values()
+        .filter(|model_ids| model_ids.len() > 1)
+        .collect();
+
+    let models = model_db::get_models_via_ids(&app_state.db, user, model_groups.iter().flatten().copied().collect()).await?;
+    let mut models: HashMap<i64, Model> = models.into_iter().map(|model| (model.id, model)).collect();
+
+    let mut duplicates: Vec<Vec<Model>> = model_groups
+        .into_iter()
+        .map(|model_ids| {
+            let mut group: Vec<Model> = model_ids.iter().filter_map(|id| models.remove(id)).collect();
+            group.sort_by(|a, b| a.added.cmp(&b.added).then(a.id.cmp(&b.id)));
+            group
+        })
+        .filter(|group| group.len() > 1)
+        .collect();
+
+    duplicates.sort_by(|a, b| a[0].name.cmp(&b[0].name));
+
+    Ok(duplicates)
+}
+
+// Resolves the models to merge into the kept model. Returns None when one of them is not in the same duplicate group
+pub async fn get_mergeable_duplicates(keep: &Model, duplicate_model_ids: &[i64], user: &User, app_state: &AppState) -> Result<Option<Vec<Model>>, ServiceError> {
+    let group = find_duplicates(user, app_state)
+        .await?
+        .into_iter()
+        .find(|group| group.iter().any(|model| model.id == keep.id))
+        .unwrap_or_default();
+
+    if duplicate_model_ids.iter().any(|id| *id != keep.id && !group.iter().any(|model| model.id == *id)) {
+        return Ok(None);
+    }
+
+    Ok(Some(
+        group
+            .into_iter()
+            .filter(|model| model.id != keep.id && duplicate_model_ids.contains(&model.id))
+            .collect(),
+    ))
+}
+
+// Folds the duplicates into the kept model and deletes them. Labels, flags, print history and derived models move over,
+// the description and link are only taken from a duplicate when the kept model has none.
+pub async fn merge_duplicates(keep: &Model, duplicates: &[Model], user: &User, app_state: &AppState) -> Result<(), ServiceError> {
+    let duplicates: Vec<&Model> = duplicates.iter().filter(|model| model.id != keep.id).collect();
+
+    if duplicates.is_empty() {
+        return Ok(());
+    }
+
+    let duplicate_ids: Vec<i64> = duplicates.iter().map(|model| model.id).collect();
+    model_db::ensure_models_editable(&app_state.db, user, &duplicate_ids).await?;
+
+    let label_ids: Vec<i64> = duplicates
+        .iter()
+        .flat_map(|model| model.labels.iter().map(|label| label.id))
+        .filter(|id| !keep.labels.iter().any(|label| label.id == *id))
+        .unique()
+        .collect();
+
+    if !label_ids.is_empty() {
+        label_db::add_labels_on_models(&app_state.db, user, &label_ids, &[keep.id], None).await?;
+    }
+
+    let mut flags = keep.flags.bits();
+    let mut description = keep.description.clone().filter(|d| !d.trim().is_empty());
+    let mut link = keep.link.clone().filter(|l| !l.trim().is_empty());
+
+    for duplicate in &duplicates {
+        flags |= duplicate.flags.bits();
+        description = description.or_else(|| duplicate.description.clone().filter(|d| !d.trim().is_empty()));
+        link = link.or_else(|| duplicate.link.clone().filter(|l| !l.trim().is_empty()));
+
+        model_db::transfer_model_history(&app_state.db, user, duplicate.id, keep.id).await?;
+    }
+
+    model_db::edit_model(
+        &app_state.db,
+        user,
+        keep.id,
+        &keep.name,
+        link.as_deref(),
+        description.as_deref(),
+        ModelFlags::from_bits_truncate(flags),
+        None,
+    )
+    .await?;
+
+    model_db::delete_models(&app_state.db, user, &duplicate_ids).await?;
+
+    Ok(())
+}
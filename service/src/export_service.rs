@@ -1,4 +1,6 @@
 use crate::ASYNC_MULT;
+use crate::conversion_service;
+use crate::mesh_service::{self, MeshFormat};
 use crate::util::cleanse_evil_from_name;
 use crate::service_error::ServiceError;
 use async_zip::{Compression, ZipEntryBuilder};
@@ -56,6 +58,7 @@ pub async fn export_to_temp_folder(
     app_state: &AppState,
     lazy: bool,
     action: &str,
+    format: Option<MeshFormat>,
 ) -> Result<(PathBuf, Vec<PathBuf>), ServiceError> {
     let configuration = app_state.get_configuration();
     let temp_dir = get_temp_dir(action);
@@ -83,7 +86,7 @@ pub async fn export_to_temp_folder(
 
         futures.spawn(async move { 
             let model = model;
-            get_path_from_model(&temp_dir, &model, &app_state, lazy).await
+            get_path_from_model_as(&temp_dir, &model, format, &app_state, lazy).await
         });
 
         if active >= max {
@@ -107,7 +110,7 @@ pub async fn export_to_temp_folder(
     Ok((temp_dir, paths))
 }
 
-fn name_collection_of_models(models : &[Model]) -> String {
+pub fn name_collection_of_models(models : &[Model]) -> String {
     let set : Vec<i64> = models.iter().map(|m| m.group.as_ref().map(|g| g.id).unwrap_or(-1)).unique().collect();
 
     if set.len() == 1 && set[0] > 0 {
@@ -117,6 +120,90 @@ fn name_collection_of_models(models : &[Model]) -> String {
     cleanse_evil_from_name(&format!("{}{}", models.iter().take(5).map(|m| &m.name).join("+"), if models.len() > 5 { format!("+{} more...", models.len() - 5) } else { "".to_string() }))
 }
 
+// Spacing in mm kept between models when arranging them on the bed
+const COMBINED_3MF_SPACING: f64 = 5.0;
+
+// Packs the models into one 3MF project, with one object per model arranged next to each other on the bed
+pub async fn export_combined_3mf(
+    models: Vec<Model>,
+    app_state: &AppState,
+) -> Result<(PathBuf, PathBuf), ServiceError> {
+    let configuration = app_state.get_configuration();
+    let temp_dir = get_temp_dir("combine");
+    let title = name_collection_of_models(&models);
+    let path = ensure_unique_file(&temp_dir, &title, "3mf");
+    let mut meshes = Vec::with_capacity(models.len());
+
+    for model in &models {
+        let file_type = model.blob.to_file_type();
+
+        if !conversion_service::can_convert(&file_type) {
+            println!("Skipping model {} while combining, {} is not a mesh", model.name, file_type.to_extension());
+            continue;
+        }
+
+        let bytes = get_bytes_from_blob(&model.blob, app_state).await?;
+        let name = model.name.clone();
+
+        let mesh = tokio::task::spawn_blocking(move || -> Result<mesh_service::Mesh, ServiceError> {
+            let meshes = mesh_service::read_meshes(&bytes, &file_type, &name)?;
+            Ok(mesh_service::merge_meshes(meshes, &name))
+        }).await??;
+
+        meshes.push(mesh);
+    }
+
+    if meshes.is_empty() {
+        return Err(ServiceError::InternalError("None of the selected models can be combined into a 3MF".into()));
+    }
+
+    let bed_size = configuration.combined_3mf_bed_size.map(|size| size.max(1) as f64);
+    let metadata = combined_3mf_metadata(&models);
+    let write_path = path.clone();
+
+    tokio::task::spawn_blocking(move || -> Result<(), ServiceError> {
+        mesh_service::arrange_on_bed(&mut meshes, bed_size, COMBINED_3MF_SPACING);
+        mesh_service::write_3mf(&meshes, &metadata, &write_path)
+    }).await??;
+
+    Ok((temp_dir, path))
+}
+
+fn combined_3mf_metadata(models: &[Model]) -> Vec<(String, String)> {
+    let groups: Vec<&str> = models.iter().filter_map(|m| m.group.as_ref().map(|g| g.name.as_str())).unique().collect();
+    let title = match groups.as_slice() {
+        [group] if models.iter().all(|m| m.group.is_some()) => group.to_string(),
+        _ => models.iter().map(|m| &m.name).join(", "),
+    };
+
+    let descriptions: Vec<&Model> = models.iter().filter(|m| m.description.as_ref().is_some_and(|d| !d.trim().is_empty())).collect();
+    let description = match descriptions.as_slice() {
+        [model] => model.description.clone().unwrap(),
+        _ => descriptions.iter().map(|m| format!("{}: {}", m.name, m.description.as_ref().unwrap().trim())).join("\n\n"),
+    };
+
+    let source = models.iter().filter_map(|m| m.link.as_deref()).filter(|l| !l.is_empty()).unique().join("\n");
+
+    let mut metadata = vec![
+        (String::from("Title"), title),
+        (String::from("CreationDate"), Utc::now().format("%Y-%m-%d").to_string()),
+    ];
+
+    if !groups.is_empty() {
+        metadata.push((String::from("Group"), groups.join(", ")));
+    }
+
+    if !description.is_empty() {
+        metadata.push((String::from("Description"), description));
+    }
+
+    if !source.is_empty() {
+        metadata.push((String::from("Source"), source));
+    }
+
+    metadata
+}
+
 pub struct ExportZipResult {
     pub temp_dir: PathBuf,
     pub zip_path: PathBuf,
@@ -125,6 +212,7 @@ pub struct ExportZipResult {
 pub async fn export_zip_to_temp_folder(
     models: Vec<Model>,
     app_state: &AppState,
+    format: Option<MeshFormat>,
 ) -> Result<ExportZipResult, ServiceError> {
     let configuration = app_state.get_configuration();
     let temp_dir = get_temp_dir("export_zip");
@@ -146,10 +234,24 @@ pub async fn export_zip_to_temp_folder(
     for model in models {
         let cleansed_name = cleanse_evil_from_name(&model.name);
         let file_type = model.blob.to_file_type();
-        let uncompressed_file_type = file_type.from_zip();
-        let builder = ZipEntryBuilder::new(format!("{}.{}", cleansed_name, uncompressed_file_type.to_extension()).into(), Compression::Deflate);
+        let convert_to = format.filter(|_| conversion_service::needs_conversion(&model.blob, format) && conversion_service::can_convert(&file_type));
+        let extension = match convert_to {
+            Some(format) => format.to_extension().to_string(),
+            None => file_type.from_zip().to_extension(),
+        };
+        let builder = ZipEntryBuilder::new(format!("{}.{}", cleansed_name, extension).into(), Compression::Deflate);
         let mut stream_writer = writer.write_entry_stream(builder).await?;
 
+        if let Some(format) = convert_to {
+            let converted_path = conversion_service::get_converted_path(&model.blob, format, app_state).await?;
+            let mut converted_file = File::open(converted_path).await?.compat();
+
+            futures::io::copy(&mut converted_file, &mut stream_writer).await?;
+            stream_writer.close().await?;
+            println!("Added model {} to zip as {}", model.name, extension);
+            continue;
+        }
+
         // TODO: Find a way to reuse this
         let src_file_path = get_model_path_for_blob(&model.blob, app_state);
         let model_file = File::open(src_file_path).await?;
@@ -225,12 +327,82 @@ pub fn ensure_unique_file(base_path: &PathBuf, file_name: &str, extension: &str)
     return new_file_name;
 }
 
+// Extension a model is exported with, after unzipping it and converting it to the given format where possible
+pub fn get_export_extension(blob: &Blob, format: Option<MeshFormat>) -> String {
+    let file_type = blob.to_file_type();
+
+    match format.filter(|_| conversion_service::needs_conversion(blob, format) && conversion_service::can_convert(&file_type)) {
+        Some(format) => format.to_extension().to_string(),
+        None => file_type.from_zip().to_extension(),
+    }
+}
+
+// Writes the model to the given path, unzipped and converted to the given format where possible
+pub async fn write_model_to_path(
+    model: &Model,
+    format: Option<MeshFormat>,
+    dst_file_path: &PathBuf,
+    app_state: &AppState,
+) -> Result<(), ServiceError> {
+    let file_type = model.blob.to_file_type();
+
+    if let Some(format) = format.filter(|_| conversion_service::needs_conversion(&model.blob, format) && conversion_service::can_convert(&file_type)) {
+        let converted_path = conversion_service::get_converted_path(&model.blob, format, app_state).await?;
+        tokio::fs::copy(&converted_path, dst_file_path).await?;
+        return Ok(());
+    }
+
+    let src_file_path = get_model_path_for_blob(&model.blob, app_state);
+
+    if file_type.is_zipped() {
+        let zip_file = File::open(src_file_path).await?;
+        let mut buffered_reader = BufReader::new(zip_file);
+        let mut zip = ZipFileReader::with_tokio(&mut buffered_reader).await?;
+        let file = zip.reader_with_entry(0).await?;
+        let mut file_compat = file.compat();
+
+        let mut dst_file = File::create(dst_file_path).await?;
+
+        tokio::io::copy(&mut file_compat, &mut dst_file).await?;
+    } else {
+        tokio::fs::copy(&src_file_path, dst_file_path).await?;
+    }
+
+    Ok(())
+}
+
 pub async fn get_path_from_model(
     temp_dir: &PathBuf,
     model: &Model,
     app_state: &AppState,
     lazy: bool,
 ) -> Result<PathBuf, ServiceError> {
+    get_path_from_model_as(temp_dir, model, None, app_state, lazy).await
+}
+
+// Like get_path_from_model, but converts mesh models to the given format first.
+// Models that can't be converted, like G-code, are exported as they are.
+pub async fn get_path_from_model_as(
+    temp_dir: &PathBuf,
+    model: &Model,
+    format: Option<MeshFormat>,
+    app_state: &AppState,
+    lazy: bool,
+) -> Result<PathBuf, ServiceError> {
+    if let Some(format) = format {
+        if conversion_service::needs_conversion(&model.blob, Some(format)) && conversion_service::can_convert(&model.blob.to_file_type()) {
+            let converted_path = conversion_service::get_converted_path(&model.blob, format, app_state).await?;
+
+            if lazy {
+                return Ok(converted_path);
+            }
+
+            let dst_file_path = ensure_unique_file(temp_dir, &cleanse_evil_from_name(&model.name), format.to_extension());
+            tokio::fs::copy(&converted_path, &dst_file_path).await?;
+            return Ok(dst_file_path);
+        }
+    }
+
     let src_file_path = get_model_path_for_blob(&model.blob, app_state);
     let cleansed_name = cleanse_evil_from_name(&model.name);
     let file_type = model.blob.to_file_type();
@@ -316,6 +488,8 @@ pub async fn delete_dead_blobs(
                 eprintln!("Failed to remove dead blob image file: {}", e);
             }
         }
+
+        conversion_service::delete_conversions(&blob.sha256, app_state);
     }
     Ok(())
 }
\ No newline at end of file
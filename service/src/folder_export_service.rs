@@ -0,0 +1,279 @@
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+
+use chrono::Utc;
+use db::model::{LabelMeta, Model, User};
+use db::{model_db, resource_db};
+use serde::{Deserialize, Serialize};
+
+use crate::app_state::AppState;
+use crate::export_service::{ensure_unique_file_full_filename, get_export_extension, write_model_to_path};
+use crate::mesh_service::MeshFormat;
+use crate::service_error::ServiceError;
+use crate::util::cleanse_evil_from_name;
+
+const MANIFEST_FILE_NAME: &str = "manifest.json";
+
+#[derive(Serialize, Deserialize)]
+pub struct FolderExportManifestEntry {
+    pub model_id: i64,
+    pub model_unique_global_id: String,
+    // Only set when the template contains {label}, as a model is then exported once per label
+    pub label_unique_global_id: Option<String>,
+    pub name: String,
+    pub group: Option<String>,
+    pub resource: Option<String>,
+    pub labels: Vec<String>,
+    pub link: Option<String>,
+    pub description: Option<String>,
+    pub sha256: String,
+    pub format: Option<MeshFormat>,
+    // Path the template rendered to, before a number was added to make it unique
+    pub template_path: String,
+    pub path: String,
+    pub size: u64,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct FolderExportManifest {
+    pub template: String,
+    pub exported: String,
+    pub entries: Vec<FolderExportManifestEntry>,
+}
+
+#[derive(Serialize)]
+pub struct FolderExportResult {
+    pub written: usize,
+    pub skipped: usize,
+    pub failed: usize,
+}
+
+// Values that can be used in a folder export template. Each path segment is cleansed after filling them in,
+// so a name containing a slash doesn't create extra folders.
+struct TemplateValues<'a> {
+    name: &'a str,
+    ext: &'a str,
+    id: i64,
+    group: &'a str,
+    label: &'a str,
+    resource: &'a str,
+}
+
+fn render_template(template: &str, values: &TemplateValues) -> Vec<String> {
+    template
+        .split(['/', '\\'])
+        .filter(|segment| !segment.trim().is_empty())
+        .map(|segment| {
+            let mut rendered = String::new();
+            let mut rest = segment;
+
+            while let Some(start) = rest.find('{') {
+                rendered.push_str(&rest[..start]);
+                rest = &rest[start..];
+
+                let end = match rest.find('}') {
+                    Some(end) => end,
+                    None => break,
+                };
+
+                match &rest[1..end] {
+                    "name" => rendered.push_str(values.name),
+                    "ext" => rendered.push_str(values.ext),
+                    "id" => rendered.push_str(&values.id.to_string()),
+                    "group" => rendered.push_str(values.group),
+                    "label" => rendered.push_str(values.label),
+                    "resource" => rendered.push_str(values.resource),
+                    _ => rendered.push_str(&rest[..=end]),
+                }
+
+                rest = &rest[end + 1..];
+            }
+
+            rendered.push_str(rest);
+
+            match cleanse_evil_from_name(&rendered).as_str() {
+                "" | "." | ".." => String::from("_"),
+                cleansed => cleansed.to_string(),
+            }
+        })
+        .collect()
+}
+
+fn read_manifest(path: &PathBuf) -> FolderExportManifest {
+    std::fs::File::open(path)
+        .ok()
+        .and_then(|file| serde_json::from_reader(file).ok())
+        .unwrap_or_default()
+}
+
+fn file_size(directory: &PathBuf, path: &str) -> Option<u64> {
+    std::fs::metadata(directory.join(path)).ok().map(|metadata| metadata.len())
+}
+
+// Removes a previously exported file along with the folders it leaves empty. Paths come from the manifest on disk,
+// so anything pointing outside the export directory is left alone
+fn remove_exported_file(directory: &PathBuf, path: &str) {
+    let relative = Path::new(path);
+
+    if !relative.components().all(|component| matches!(component, Component::Normal(_))) {
+        return;
+    }
+
+    let _ = std::fs::remove_file(directory.join(relative));
+
+    for folder in relative.ancestors().skip(1) {
+        if folder.as_os_str().is_empty() || std::fs::remove_dir(directory.join(folder)).is_err() {
+            break;
+        }
+    }
+}
+
+// Exports the models into a folder tree below the directory, laid out by a template like `{resource}/{group}/{name}.{ext}`.
+// A manifest.json is kept next to the files. Exporting into the same directory again only writes models that changed since the last export.
+pub async fn export_to_folder(
+    models: &[Model],
+    directory: &PathBuf,
+    template: &str,
+    format: Option<MeshFormat>,
+    user: &User,
+    app_state: &AppState,
+) -> Result<FolderExportResult, ServiceError> {
+    // The file name has to end in the extension, conflicts are resolved by numbering the part before it
+    let template = match template.trim().ends_with(".{ext}") {
+        true => template.trim().to_string(),
+        false => format!("{}.{{ext}}", template.trim()),
+    };
+
+    if template.trim_end_matches(".{ext}").is_empty() {
+        return Err(ServiceError::InternalError("Export template does not contain a file name".into()));
+    }
+
+    std::fs::create_dir_all(directory)?;
+
+    let manifest_path = directory.join(MANIFEST_FILE_NAME);
+    let mut previous_entries: HashMap<(String, Option<String>), FolderExportManifestEntry> = read_manifest(&manifest_path)
+        .entries
+        .into_iter()
+        .map(|entry| ((entry.model_unique_global_id.clone(), entry.label_unique_global_id.clone()), entry))
+        .collect();
+
+    // Models deleted since the last export are dropped from the manifest, together with their files
+    let existing_models = model_db::get_unique_global_ids(&app_state.db, user).await?;
+
+    previous_entries.retain(|(model_unique_global_id, _), entry| {
+        let exists = existing_models.contains(model_unique_global_id);
+
+        if !exists {
+            remove_exported_file(directory, &entry.path);
+        }
+
+        exists
+    });
+
+    let resources: HashMap<i64, String> = resource_db::get_resources(&app_state.db, user)
+        .await?
+        .into_iter()
+        .map(|resource| (resource.id, resource.name))
+        .collect();
+
+    let per_label = template.contains("{label}");
+    let mut entries = Vec::new();
+    let mut result = FolderExportResult {
+        written: 0,
+        skipped: 0,
+        failed: 0,
+    };
+
+    for model in models {
+        let labels: Vec<Option<&LabelMeta>> = match per_label && !model.labels.is_empty() {
+            true => model.labels.iter().map(Some).collect(),
+            false => vec![None],
+        };
+
+        let extension = get_export_extension(&model.blob, format);
+        let group = model.group.as_ref();
+        let resource = group.and_then(|group| group.resource_id).and_then(|id| resources.get(&id));
+
+        for label in labels {
+            let segments = render_template(&template, &TemplateValues {
+                name: &model.name,
+                ext: &extension,
+                id: model.id,
+                group: group.map(|group| group.name.as_str()).unwrap_or("Ungrouped"),
+                label: label.map(|label| label.name.as_str()).unwrap_or("Unlabelled"),
+                resource: resource.map(|resource| resource.as_str()).unwrap_or("No resource"),
+            });
+
+            let template_path = segments.join("/");
+            let key = (model.unique_global_id.clone(), label.map(|label| label.unique_global_id.clone()));
+
+            let mut entry = FolderExportManifestEntry {
+                model_id: model.id,
+                model_unique_global_id: key.0.clone(),
+                label_unique_global_id: key.1.clone(),
+                name: model.name.clone(),
+                group: group.map(|group| group.name.clone()),
+                resource: resource.cloned(),
+                labels: model.labels.iter().map(|label| label.name.clone()).collect(),
+                link: model.link.clone(),
+                description: model.description.clone(),
+                sha256: model.blob.sha256.clone(),
+                format,
+                template_path,
+                path: String::new(),
+                size: 0,
+            };
+
+            if let Some(previous) = previous_entries.remove(&key) {
+                let unchanged = previous.template_path == entry.template_path
+                    && previous.sha256 == entry.sha256
+                    && previous.format == entry.format
+                    && file_size(directory, &previous.path) == Some(previous.size);
+
+                if unchanged {
+                    entry.path = previous.path;
+                    entry.size = previous.size;
+                    entries.push(entry);
+                    result.skipped += 1;
+                    continue;
+                }
+
+                // The old copy is out of date, don't leave it behind next to the new one
+                remove_exported_file(directory, &previous.path);
+            }
+
+            let (file_name, folders) = segments.split_last().unwrap();
+            let folder = folders.iter().fold(directory.clone(), |path, segment| path.join(segment));
+            std::fs::create_dir_all(&folder)?;
+
+            let dst_file_path = ensure_unique_file_full_filename(&folder, file_name);
+
+            if let Err(e) = write_model_to_path(model, format, &dst_file_path, app_state).await {
+                println!("Failed to export model {} to {:?}: {}", model.name, dst_file_path, e);
+                result.failed += 1;
+                continue;
+            }
+
+            let written_name = dst_file_path.file_name().unwrap().to_string_lossy().to_string();
+            entry.path = folders.iter().chain(std::iter::once(&written_name)).cloned().collect::<Vec<String>>().join("/");
+            entry.size = file_size(directory, &entry.path).unwrap_or(0);
+            entries.push(entry);
+            result.written += 1;
+        }
+    }
+
+    // Models that were exported before but are not part of this export stay in the manifest, so exporting a subset doesn't forget about them
+    entries.extend(previous_entries.into_values());
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+
+    let manifest = FolderExportManifest {
+        template,
+        exported: Utc::now().to_rfc3339(),
+        entries,
+    };
+
+    let manifest_file = std::fs::File::create(&manifest_path)?;
+    serde_json::to_writer_pretty(manifest_file, &manifest)?;
+
+    Ok(result)
+}
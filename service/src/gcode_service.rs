@@ -0,0 +1,480 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use async_zip::tokio::read::seek::ZipFileReader;
+use db::gcode_metadata_db;
+use db::model::{Blob, FileType, GcodeMetadata, Model};
+use itertools::Itertools;
+use regex::Regex;
+use tokio::io::{AsyncReadExt, BufReader};
+use tokio::task::spawn_blocking;
+use tokio_util::compat::FuturesAsyncReadCompatExt;
+
+use crate::app_state::AppState;
+use crate::bgcode_service;
+use crate::export_service::get_model_path_for_blob;
+use crate::service_error::ServiceError;
+
+// Slicers write their metadata as comments at the start (Orca, Bambu, Cura) or the end (Prusa) of the G-code
+const SCAN_BYTES: usize = 256 * 1024;
+
+// Returns the stored metadata of a G-code model, reading it from the file if it was never extracted
+pub async fn get_metadata(model: &Model, app_state: &AppState) -> Result<GcodeMetadata, ServiceError> {
+    if !model.blob.to_file_type().is_gcode() {
+        return Err(ServiceError::InternalError(String::from(
+            "Model is not G-code",
+        )));
+    }
+
+    if let Some(metadata) = gcode_metadata_db::get_gcode_metadata(&app_state.db, model.blob.id).await? {
+        return Ok(metadata);
+    }
+
+    extract_blob_metadata(&model.blob, app_state).await
+}
+
+// Fills in the metadata of G-code imported before it was extracted on import
+pub async fn extract_missing_metadata(app_state: &AppState) -> Result<(), ServiceError> {
+    let blobs = gcode_metadata_db::get_gcode_blobs_without_metadata(&app_state.db).await?;
+
+    for blob in blobs {
+        if let Err(e) = extract_blob_metadata(&blob, app_state).await {
+            println!("Failed to read G-code metadata of blob {}: {}", blob.id, e);
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn store_metadata_from_bytes(blob_id: i64, gcode: &[u8], app_state: &AppState) -> Result<GcodeMetadata, ServiceError> {
+    let metadata = parse_metadata(&metadata_text_of_bytes(gcode)?);
+
+    gcode_metadata_db::set_gcode_metadata(&app_state.db, blob_id, &metadata).await?;
+
+    Ok(metadata)
+}
+
+async fn extract_blob_metadata(blob: &Blob, app_state: &AppState) -> Result<GcodeMetadata, ServiceError> {
+    let path = get_model_path_for_blob(blob, app_state);
+
+    let file_type = blob.to_file_type();
+
+    let text = if file_type.is_bgcode() {
+        bgcode_service::metadata_as_gcode_comments(&tokio::fs::read(&path).await?)?
+    } else if file_type.is_zipped() {
+        read_zipped_head_and_tail(&path).await?
+    } else {
+        spawn_blocking(move || read_head_and_tail(&path)).await??
+    };
+
+    let metadata = parse_metadata(&text);
+
+    gcode_metadata_db::set_gcode_metadata(&app_state.db, blob.id, &metadata).await?;
+
+    Ok(metadata)
+}
+
+pub async fn read_metadata_from_path(path: &Path) -> Result<GcodeMetadata, ServiceError> {
+    let path = path.to_path_buf();
+
+    let text = if FileType::from_pathbuf(&path).is_bgcode() {
+        bgcode_service::metadata_as_gcode_comments(&tokio::fs::read(&path).await?)?
+    } else {
+        spawn_blocking(move || read_head_and_tail(&path)).await??
+    };
+
+    Ok(parse_metadata(&text))
+}
+
+fn read_head_and_tail(path: &PathBuf) -> Result<String, ServiceError> {
+    let mut file = fs::File::open(path)?;
+    let size = file.metadata()?.len() as usize;
+
+    if size <= SCAN_BYTES * 2 {
+        let mut buffer = Vec::with_capacity(size);
+        file.read_to_end(&mut buffer)?;
+        return Ok(String::from_utf8_lossy(&buffer).to_string());
+    }
+
+    let mut head = vec![0u8; SCAN_BYTES];
+    file.read_exact(&mut head)?;
+
+    let mut tail = Vec::with_capacity(SCAN_BYTES);
+    file.seek(SeekFrom::End(-(SCAN_BYTES as i64)))?;
+    file.read_to_end(&mut tail)?;
+
+    Ok(format!("{}\n{}", String::from_utf8_lossy(&head), String::from_utf8_lossy(&tail)))
+}
+
+// Zip entries can't seek, so the whole entry is streamed while only its start and end are kept
+async fn read_zipped_head_and_tail(path: &PathBuf) -> Result<String, ServiceError> {
+    let zip_file = tokio::fs::File::open(path).await?;
+    let mut buffered_reader = BufReader::new(zip_file);
+    let mut zip = ZipFileReader::with_tokio(&mut buffered_reader).await?;
+    let mut reader = zip.reader_with_entry(0).await?.compat();
+
+    let mut head = Vec::with_capacity(SCAN_BYTES);
+    let mut tail = Vec::with_capacity(SCAN_BYTES * 2);
+    let mut chunk = vec![0u8; 64 * 1024];
+
+    loop {
+        let read = reader.read(&mut chunk).await?;
+
+        if read == 0 {
+            break;
+        }
+
+        let mut data = &chunk[..read];
+
+        if head.len() < SCAN_BYTES {
+            let take = data.len().min(SCAN_BYTES - head.len());
+            head.extend_from_slice(&data[..take]);
+            data = &data[take..];
+        }
+
+        tail.extend_from_slice(data);
+
+        if tail.len() > SCAN_BYTES * 2 {
+            tail.drain(..tail.len() - SCAN_BYTES);
+        }
+    }
+
+    if tail.len() > SCAN_BYTES {
+        tail.drain(..tail.len() - SCAN_BYTES);
+    }
+
+    Ok(format!("{}\n{}", String::from_utf8_lossy(&head), String::from_utf8_lossy(&tail)))
+}
+
+fn metadata_text_of_bytes(gcode: &[u8]) -> Result<String, ServiceError> {
+    if bgcode_service::is_bgcode(gcode) {
+        return bgcode_service::metadata_as_gcode_comments(gcode);
+    }
+
+    if gcode.len() <= SCAN_BYTES * 2 {
+        return Ok(String::from_utf8_lossy(gcode).to_string());
+    }
+
+    Ok(format!(
+        "{}\n{}",
+        String::from_utf8_lossy(&gcode[..SCAN_BYTES]),
+        String::from_utf8_lossy(&gcode[gcode.len() - SCAN_BYTES..])
+    ))
+}
+
+pub fn parse_metadata(gcode: &str) -> GcodeMetadata {
+    let values = parse_comment_values(gcode);
+    let (slicer, slicer_version) = parse_slicer(gcode);
+
+    GcodeMetadata {
+        slicer,
+        slicer_version,
+        print_time_seconds: parse_print_time(gcode, &values),
+        filament_used_mm: first_value(&values, &["filament used [mm]", "total filament length [mm]"])
+            .and_then(sum_values)
+            // Cura: ";Filament used: 1.23456m"
+            .or_else(|| first_value(&values, &["filament used"]).and_then(|v| sum_values(&v.replace('m', ""))).map(|m| m * 1000.0)),
+        filament_used_g: first_value(&values, &["total filament used [g]", "filament used [g]", "total filament weight [g]"])
+            .and_then(sum_values),
+        filament_cost: first_value(&values, &["total filament cost", "filament cost"]).and_then(sum_values),
+        nozzle_diameter: first_value(&values, &["nozzle_diameter", "extruder_train.0.nozzle.diameter"])
+            .and_then(|v| v.split(',').next().and_then(|n| n.trim().parse::<f64>().ok())),
+        layer_height: first_value(&values, &["layer_height", "layer height"]).and_then(|v| v.parse::<f64>().ok()),
+        printer_model: first_value(&values, &["printer_model", "printer_settings_id", "target_machine.name", "machine_name"])
+            .map(|v| v.to_string()),
+        // Multi material prints list one type per extruder
+        material_type: first_value(&values, &["filament_type"]).map(|v| {
+            v.split([';', ','])
+                .map(|t| t.trim())
+                .filter(|t| !t.is_empty())
+                .unique()
+                .join(", ")
+        }),
+    }
+}
+
+// Collects "; key = value" and ";KEY:value" comments, the first occurrence of a key wins
+fn parse_comment_values(gcode: &str) -> HashMap<String, String> {
+    let mut values = HashMap::new();
+
+    for line in gcode.lines() {
+        let comment = match line.strip_prefix(';') {
+            Some(comment) => comment.trim(),
+            None => continue,
+        };
+
+        let separator = comment.find('=').or_else(|| comment.find(':'));
+
+        if let Some(index) = separator {
+            let key = comment[..index].trim().to_lowercase();
+            let value = comment[index + 1..].trim().trim_matches('"').to_string();
+
+            if !key.is_empty() && !value.is_empty() {
+                values.entry(key).or_insert(value);
+            }
+        }
+    }
+
+    values
+}
+
+fn first_value<'a>(values: &'a HashMap<String, String>, keys: &[&str]) -> Option<&'a str> {
+    keys.iter().find_map(|key| values.get(*key)).map(|v| v.as_str())
+}
+
+fn parse_slicer(gcode: &str) -> (Option<String>, Option<String>) {
+    // PrusaSlicer, SuperSlicer, OrcaSlicer: "; generated by PrusaSlicer 2.8.1+win64 on 2024-..."
+    // Cura: ";Generated with Cura_SteamEngine 5.7.1"
+    let generated_regex = Regex::new(r"(?im)^;\s*generated (?:by|with) ([A-Za-z0-9_ -]+?)[ _]v?(\d+(?:\.\d+)+)").unwrap();
+    // Bambu Studio: "; BambuStudio 01.09.00.70"
+    let bambu_regex = Regex::new(r"(?m)^;\s*(BambuStudio)\s+v?(\d+(?:\.\d+)+)").unwrap();
+
+    match generated_regex.captures(gcode).or_else(|| bambu_regex.captures(gcode)) {
+        Some(captures) => {
+            let name = match &captures[1] {
+                "Cura_SteamEngine" => "Cura",
+                "BambuStudio" => "Bambu Studio",
+                name => name,
+            };
+
+            (Some(name.to_string()), Some(captures[2].to_string()))
+        }
+        None => (None, None),
+    }
+}
+
+fn parse_print_time(gcode: &str, values: &HashMap<String, String>) -> Option<i64> {
+    // OrcaSlicer, Bambu Studio: "; model printing time: 1h 2m 3s; total estimated time: 1h 5m 3s"
+    let total_time_regex = Regex::new(r"total estimated time:\s*([0-9dhms ]+)").unwrap();
+
+    if let Some(seconds) = total_time_regex.captures(gcode).and_then(|c| parse_duration(&c[1])) {
+        return Some(seconds);
+    }
+
+    // PrusaSlicer: "; estimated printing time (normal mode) = 1h 2m 3s", Simplify3D: ";   Build time: 2 hours 3 minutes"
+    if let Some(seconds) = first_value(values, &["estimated printing time (normal mode)", "estimated printing time", "build time"]).and_then(parse_duration) {
+        return Some(seconds);
+    }
+
+    // Cura: ";TIME:3723" in seconds
+    first_value(values, &["time", "print.time"]).and_then(|v| v.parse::<f64>().ok()).map(|v| v as i64)
+}
+
+// Parses durations like "1d 2h 3m 4s"
+fn parse_duration(text: &str) -> Option<i64> {
+    let part_regex = Regex::new(r"(\d+)\s*([dhms])").unwrap();
+    let mut seconds = 0;
+    let mut found = false;
+
+    for captures in part_regex.captures_iter(text) {
+        let value: i64 = captures[1].parse().ok()?;
+        found = true;
+
+        seconds += match &captures[2] {
+            "d" => value * 86400,
+            "h" => value * 3600,
+            "m" => value * 60,
+            _ => value,
+        };
+    }
+
+    if found { Some(seconds) } else { None }
+}
+
+// Multi extruder prints list one value per extruder, separated by commas
+fn sum_values(text: &str) -> Option<f64> {
+    let values: Vec<f64> = text
+        .split(',')
+        .filter_map(|v| v.trim().parse::<f64>().ok())
+        .collect();
+
+    if values.is_empty() {
+        return None;
+    }
+
+    Some(values.iter().sum())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PRUSASLICER: &str = "; generated by PrusaSlicer 2.8.1+linux-x64-GTK3 on 2024-10-01 at 12:00:00 UTC
+G1 X10 Y10
+; filament used [mm] = 1234.56
+; filament used [cm3] = 2.97
+; filament used [g] = 3.68
+; filament cost = 0.09
+; total filament used [g] = 3.68
+; total filament cost = 0.09
+; estimated printing time (normal mode) = 1h 2m 3s
+; estimated printing time (silent mode) = 1h 5m 0s
+; filament_type = PETG
+; layer_height = 0.2
+; nozzle_diameter = 0.4
+; printer_model = MK4S
+";
+
+    const ORCASLICER: &str = "; HEADER_BLOCK_START
+; generated by OrcaSlicer 2.1.1 on 2024-08-01 at 10:00:00
+; model printing time: 1d 2h 3m 4s; total estimated time: 1d 2h 10m 4s
+; total layer number: 250
+; HEADER_BLOCK_END
+G1 X10 Y10
+; filament used [mm] = 5000.00, 250.50
+; filament used [g] = 14.91, 0.75
+; total filament used [g] = 15.66
+; total filament cost = 0.40
+; filament_type = PLA;PLA;PETG
+; layer_height = 0.16
+; nozzle_diameter = 0.4,0.4
+; printer_settings_id = Voron 2.4 350 0.4 nozzle
+";
+
+    const BAMBUSTUDIO: &str = "; HEADER_BLOCK_START
+; BambuStudio 01.09.00.70
+; model printing time: 42m 7s; total estimated time: 48m 30s
+; total layer number: 120
+; HEADER_BLOCK_END
+G1 X10 Y10
+; total filament used [g] = 12.30
+; filament_type = PLA
+; layer_height = 0.2
+; nozzle_diameter = 0.4
+; printer_model = Bambu Lab X1 Carbon
+";
+
+    const CURA: &str = ";START_OF_HEADER
+;HEADER_VERSION:0.1
+;FLAVOR:Griffin
+;GENERATOR.NAME:Cura_SteamEngine
+;GENERATOR.VERSION:5.7.1
+;TARGET_MACHINE.NAME:Ultimaker S5
+;EXTRUDER_TRAIN.0.NOZZLE.DIAMETER:0.4
+;EXTRUDER_TRAIN.0.MATERIAL.VOLUME_USED:2970
+;PRINT.TIME:3723
+;END_OF_HEADER
+;Generated with Cura_SteamEngine 5.7.1
+;Filament used: 1.23456m
+;Layer height: 0.2
+G1 X10 Y10
+";
+
+    const SIMPLIFY3D: &str = "; G-Code generated by Simplify3D(R) Version 4.1.2
+;   layerHeight,0.2
+G1 X10 Y10
+; Build Summary
+;   Build time: 2 hours 3 minutes
+;   Filament length: 1234.5 mm (1.23 m)
+";
+
+    fn assert_close(actual: Option<f64>, expected: f64) {
+        let actual = actual.expect("value is missing");
+        assert!((actual - expected).abs() < 1e-6, "{} != {}", actual, expected);
+    }
+
+    #[test]
+    fn parses_prusaslicer_comments() {
+        let metadata = parse_metadata(PRUSASLICER);
+
+        assert_eq!(metadata.slicer.as_deref(), Some("PrusaSlicer"));
+        assert_eq!(metadata.slicer_version.as_deref(), Some("2.8.1"));
+        assert_eq!(metadata.print_time_seconds, Some(3723));
+        assert_close(metadata.filament_used_mm, 1234.56);
+        assert_close(metadata.filament_used_g, 3.68);
+        assert_close(metadata.filament_cost, 0.09);
+        assert_close(metadata.nozzle_diameter, 0.4);
+        assert_close(metadata.layer_height, 0.2);
+        assert_eq!(metadata.printer_model.as_deref(), Some("MK4S"));
+        assert_eq!(metadata.material_type.as_deref(), Some("PETG"));
+    }
+
+    #[test]
+    fn parses_orcaslicer_comments_with_multiple_extruders() {
+        let metadata = parse_metadata(ORCASLICER);
+
+        assert_eq!(metadata.slicer.as_deref(), Some("OrcaSlicer"));
+        assert_eq!(metadata.slicer_version.as_deref(), Some("2.1.1"));
+        assert_eq!(metadata.print_time_seconds, Some(86400 + 2 * 3600 + 10 * 60 + 4));
+        assert_close(metadata.filament_used_mm, 5250.5);
+        assert_close(metadata.filament_used_g, 15.66);
+        assert_close(metadata.filament_cost, 0.4);
+        assert_close(metadata.nozzle_diameter, 0.4);
+        assert_close(metadata.layer_height, 0.16);
+        assert_eq!(metadata.printer_model.as_deref(), Some("Voron 2.4 350 0.4 nozzle"));
+        assert_eq!(metadata.material_type.as_deref(), Some("PLA, PETG"));
+    }
+
+    #[test]
+    fn parses_bambustudio_comments() {
+        let metadata = parse_metadata(BAMBUSTUDIO);
+
+        assert_eq!(metadata.slicer.as_deref(), Some("Bambu Studio"));
+        assert_eq!(metadata.slicer_version.as_deref(), Some("01.09.00.70"));
+        assert_eq!(metadata.print_time_seconds, Some(48 * 60 + 30));
+        assert_close(metadata.filament_used_g, 12.3);
+        assert_eq!(metadata.printer_model.as_deref(), Some("Bambu Lab X1 Carbon"));
+        assert_eq!(metadata.filament_used_mm, None);
+    }
+
+    #[test]
+    fn parses_cura_comments_with_time_in_seconds() {
+        let metadata = parse_metadata(CURA);
+
+        assert_eq!(metadata.slicer.as_deref(), Some("Cura"));
+        assert_eq!(metadata.slicer_version.as_deref(), Some("5.7.1"));
+        assert_eq!(metadata.print_time_seconds, Some(3723));
+        assert_close(metadata.filament_used_mm, 1234.56);
+        assert_close(metadata.nozzle_diameter, 0.4);
+        assert_close(metadata.layer_height, 0.2);
+        assert_eq!(metadata.printer_model.as_deref(), Some("Ultimaker S5"));
+        assert_eq!(metadata.filament_used_g, None);
+    }
+
+    #[test]
+    fn parses_simplify3d_build_time() {
+        let metadata = parse_metadata(SIMPLIFY3D);
+
+        assert_eq!(metadata.print_time_seconds, Some(2 * 3600 + 3 * 60));
+    }
+
+    #[test]
+    fn missing_values_stay_empty() {
+        let metadata = parse_metadata("G28\nG1 X10 Y10\n; just a comment\n");
+
+        assert_eq!(metadata.slicer, None);
+        assert_eq!(metadata.slicer_version, None);
+        assert_eq!(metadata.print_time_seconds, None);
+        assert_eq!(metadata.filament_used_mm, None);
+        assert_eq!(metadata.printer_model, None);
+        assert_eq!(metadata.material_type, None);
+    }
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration("1d 2h 3m 4s"), Some(93784));
+        assert_eq!(parse_duration("2h 0m 5s"), Some(7205));
+        assert_eq!(parse_duration("45s"), Some(45));
+        assert_eq!(parse_duration("3723"), None);
+        assert_eq!(parse_duration(""), None);
+    }
+
+    #[test]
+    fn plain_seconds_are_used_when_no_duration_is_given() {
+        let values = parse_comment_values(";TIME:3723.6\n");
+
+        assert_eq!(parse_print_time("", &values), Some(3723));
+        assert_eq!(parse_print_time("", &HashMap::new()), None);
+    }
+
+    #[test]
+    fn first_occurrence_of_a_key_wins() {
+        let values = parse_comment_values("; layer_height = 0.2\n;LAYER_HEIGHT:0.3\n;not a value\n; empty =\n");
+
+        assert_eq!(values.get("layer_height").map(|v| v.as_str()), Some("0.2"));
+        assert!(!values.contains_key("empty"));
+        assert!(!values.contains_key("not a value"));
+    }
+}
@@ -1,6 +1,11 @@
 use super::app_state::AppState;
 use crate::ASYNC_MULT;
 use crate::configuration::Configuration;
+use crate::conversion_service;
+use crate::duplicate_service;
+use crate::gcode_service;
+use crate::mesh_analysis_service;
+use crate::resin_service;
 use crate::import_state::{ImportState, ImportStatus, ImportedModelsSet};
 use crate::util::{self, read_file_as_text};
 use async_zip::ZipEntryBuilder;
@@ -8,11 +13,11 @@ use async_zip::tokio::read;
 use async_zip::tokio::read::seek::ZipFileReader;
 use async_zip::tokio::write::ZipFileWriter;
 use db::{blob_db, label_db, label_keyword_db, model_db};
-use db::model::{FileType, Model, User};
+use db::model::{FileType, LabelMeta, Model, ModelFlags, User};
 use db::model_db::ModelFilterOptions;
 use indexmap::IndexMap;
 use itertools::Itertools;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use tokio::fs::File;
 use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt, BufReader};
@@ -53,6 +58,11 @@ pub async fn import_path(
             import_state.update_status(ImportStatus::FinishedModels);
             import_state.create_groups_from_all_sets(app_state).await?;
 
+            let background_app_state = app_state.clone();
+            tokio::spawn(async move {
+                let _ = mesh_analysis_service::analyse_missing(&background_app_state).await;
+            });
+
             let import_state = {
                 let fake = ImportState::new(None, false, false, false, User::default());
                 std::mem::replace(&mut *import_state, fake)
@@ -133,7 +143,7 @@ pub async fn import_path_inner(
                 None
             };
 
-            let id = import_single_model(
+            let imported = import_single_model(
                 &mut file,
                 extension,
                 size,
@@ -143,7 +153,7 @@ pub async fn import_path_inner(
                 &import_state.user,
                 permanent_disk_path,
             ).await?;
-            import_state.add_model_id_to_current_set(id);
+            import_state.add_model_id_to_current_set(imported.id);
         }
 
         if import_state.delete_after_import {
@@ -296,13 +306,13 @@ async fn import_models_from_dir_inner(
        None
     };
 
-    let id = import_single_model(
+    let imported = import_single_model(
         &mut file, extension, file_size, &file_name, link.clone(), app_state, user, permanent_disk_path
     ).await?;
 
     {
         let import_state = &mut import_state_mutex.lock().await;
-        import_state.add_model_id_to_current_set(id);
+        import_state.add_model_id_to_current_set(imported.id);
     }
 
     if delete_after_import {
@@ -391,6 +401,9 @@ async fn import_models_from_zip(
         )));
     }
 
+    let mut metadata: Option<Vec<ExportedModelMetadata>> = None;
+    let mut imported: Vec<(String, i64)> = Vec::new();
+
     {
         let zip_file = File::open(path).await?;
         let buffered_reader = BufReader::new(zip_file);
@@ -407,13 +420,18 @@ async fn import_models_from_zip(
             }
 
             let path = PathBuf::from(file.entry().filename().as_str()?);
-            /* -- Revist this at some point
-            if path.file_name().take().unwrap() == ".link" {
-                let mut file_contents: Vec<u8> = Vec::new();
-                file_compat.read_to_end(&mut file_contents).await?;
-                let temp_str = String::from_utf8(file_contents).unwrap();
-                link.replace(temp_str);
-            }*/
+
+            if path.as_os_str() == ZIP_METADATA_FILE_NAME {
+                let mut contents = Vec::new();
+                file.compat().read_to_end(&mut contents).await?;
+
+                match serde_json::from_slice(&contents) {
+                    Ok(parsed) => metadata = Some(parsed),
+                    Err(e) => println!("Ignoring metadata.json in {}: {}", path.to_string_lossy(), e),
+                }
+
+                continue;
+            }
 
             if !is_supported_extension(&path) {
                 continue;
@@ -424,14 +442,25 @@ async fn import_models_from_zip(
             let file_size = file.entry().uncompressed_size() as usize;
             let mut file_compat = file.compat();
 
-            let id = import_single_model(
+            let file_stem = path.file_stem().unwrap().to_string_lossy().to_string();
+
+            let model = import_single_model(
                     &mut file_compat, extension, file_size, &file_name, link, app_state, &import_state.user, None
             ).await?;
 
-            import_state.add_model_id_to_current_set(id);
+            import_state.add_model_id_to_current_set(model.id);
+
+            // Models that were already in the library keep their own name, labels and group
+            if model.is_new {
+                imported.push((file_stem, model.id));
+            }
         }
     }
 
+    if let Some(metadata) = metadata {
+        apply_zip_metadata(&metadata, &imported, app_state, &mut import_state).await?;
+    }
+
     if import_state.delete_after_import {
         let _ = fs::remove_file(path);
     }
@@ -439,6 +468,136 @@ async fn import_models_from_zip(
     Ok(())
 }
 
+const ZIP_METADATA_FILE_NAME: &str = "metadata.json";
+
+// The part of a serialized Model that export_zip_to_temp_folder writes to metadata.json and that is restored on import
+#[derive(Deserialize)]
+struct ExportedModelMetadata {
+    name: String,
+    blob: ExportedBlobMetadata,
+    link: Option<String>,
+    description: Option<String>,
+    #[serde(default)]
+    flags: ModelFlags,
+    group: Option<ExportedGroupMetadata>,
+    #[serde(default)]
+    labels: Vec<ExportedLabelMetadata>,
+}
+
+#[derive(Deserialize)]
+struct ExportedBlobMetadata {
+    sha256: String,
+}
+
+#[derive(Deserialize)]
+struct ExportedGroupMetadata {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct ExportedLabelMetadata {
+    name: String,
+    color: i64,
+}
+
+// Puts the names, descriptions, links, flags, groups and labels from metadata.json back on the models the zip import created.
+// Models are matched on their hash, or on their file name when they were converted to another format on export.
+// Labels are matched on name and created when missing, groups are created like any other import group.
+async fn apply_zip_metadata(
+    metadata: &[ExportedModelMetadata],
+    imported: &[(String, i64)],
+    app_state: &AppState,
+    import_state: &mut ImportState,
+) -> Result<(), ServiceError> {
+    let db = &app_state.db;
+    let user = import_state.user.clone();
+    let model_ids: Vec<i64> = imported.iter().map(|(_, id)| *id).unique().collect();
+    let models = model_db::get_models_via_ids(db, &user, model_ids).await?;
+    let mut labels: Vec<LabelMeta> = label_db::get_labels(db, &user, false)
+        .await?
+        .into_iter()
+        .map(|label| label.meta)
+        .collect();
+
+    let mut label_models: IndexMap<i64, Vec<i64>> = IndexMap::new();
+    let mut group_models: IndexMap<Option<String>, Vec<i64>> = IndexMap::new();
+
+    for (file_stem, model_id) in imported.iter().unique_by(|(_, id)| *id) {
+        let model = match models.iter().find(|model| model.id == *model_id) {
+            Some(model) => model,
+            None => continue,
+        };
+
+        let exported = metadata
+            .iter()
+            .find(|exported| exported.blob.sha256 == model.blob.sha256)
+            .or_else(|| metadata.iter().find(|exported| util::cleanse_evil_from_name(&exported.name) == *file_stem));
+
+        let exported = match exported {
+            Some(exported) => exported,
+            None => continue,
+        };
+
+        model_db::edit_model(
+            db,
+            &user,
+            model.id,
+            &exported.name,
+            exported.link.as_deref().or(model.link.as_deref()),
+            exported.description.as_deref().or(model.description.as_deref()),
+            ModelFlags::from_bits_truncate(model.flags.bits() | exported.flags.bits()),
+            None,
+        )
+        .await?;
+
+        for exported_label in &exported.labels {
+            let label_id = match labels.iter().find(|label| label.name.to_lowercase() == exported_label.name.to_lowercase()) {
+                Some(label) => label.id,
+                None => {
+                    let label_id = label_db::add_label(db, &user, &exported_label.name, exported_label.color, None).await?;
+                    labels.push(LabelMeta {
+                        id: label_id,
+                        name: exported_label.name.clone(),
+                        color: exported_label.color,
+                        unique_global_id: String::new(),
+                        last_modified: String::new(),
+                    });
+                    label_id
+                }
+            };
+
+            if !model.labels.iter().any(|label| label.id == label_id) {
+                let models = label_models.entry(label_id).or_default();
+
+                if !models.contains(&model.id) {
+                    models.push(model.id);
+                }
+            }
+        }
+
+        group_models
+            .entry(exported.group.as_ref().map(|group| group.name.clone()))
+            .or_default()
+            .push(model.id);
+    }
+
+    for (label_id, model_ids) in label_models {
+        label_db::add_labels_on_models(db, &user, &[label_id], &model_ids, None).await?;
+    }
+
+    for (group_name, model_ids) in group_models {
+        import_state.move_models_to_new_set(&model_ids, group_name);
+    }
+
+    Ok(())
+}
+
+struct ImportedModel {
+    id: i64,
+    // False when the file was already in the library and the existing model was returned
+    is_new: bool,
+}
+
 async fn import_single_model<W>(
     reader: &mut W,
     file_type: &str,
@@ -448,7 +607,7 @@ async fn import_single_model<W>(
     app_state: &AppState,
     user: &User,
     permanent_disk_path: Option<PathBuf>,
-) -> Result<i64, ServiceError>
+) -> Result<ImportedModel, ServiceError>
 where
     W: AsyncRead + Unpin,
 {
@@ -467,19 +626,17 @@ where
 
     reader.read_to_end(&mut file_contents).await?;
 
-    let mut hasher = Sha256::new();
-    hasher.update(&file_contents);
-    let bytes = hasher.finalize();
-    let hash = String::from(&format!("{:x}", bytes)[0..32]);
+    let hash = hash_file_contents(&file_contents);
 
-    let existing_id = model_db::get_model_id_via_sha256(&app_state.db, user, &hash)
+    let existing_id = model_db::get_editable_model_id_via_sha256(&app_state.db, user, &hash)
             .await?;
     
     if let Some(id) = existing_id {
-        return Ok(id);
+        return Ok(ImportedModel { id, is_new: false });
     }
 
     let blob_id_optional = blob_db::get_blob_via_sha256(&app_state.db, &hash).await?;
+    let is_new_blob = blob_id_optional.is_none();
 
     let blob_id;
 
@@ -508,6 +665,25 @@ where
         blob_id = blob_db::add_blob(&app_state.db, &hash, &compressed_file_type.to_extension(), file_size as i64, None).await?;
     }
 
+    if is_new_blob && (file_type == FileType::Gcode || file_type == FileType::Bgcode) {
+        if let Err(e) = gcode_service::store_metadata_from_bytes(blob_id, &file_contents, app_state).await {
+            println!("Failed to read G-code metadata of {}: {}", name, e);
+        }
+    }
+
+    if is_new_blob && file_type.is_resin() {
+        if let Err(e) = resin_service::store_metadata_from_bytes(blob_id, &file_type, &file_contents, app_state).await {
+            println!("Failed to read resin metadata of {}: {}", name, e);
+        }
+    }
+
+    // Mesh analysis runs in the background once the import finishes. STEP files are converted before they can be fingerprinted, which is too slow to do during import. They are picked up on the next start instead
+    if is_new_blob && conversion_service::can_convert(&file_type) && !file_type.is_step() {
+        if let Err(e) = duplicate_service::store_fingerprint_from_bytes(blob_id, &file_type, file_contents, app_state).await {
+            println!("Failed to fingerprint mesh of {}: {}", name, e);
+        }
+    }
+
     let id = model_db::add_model(
             &app_state.db,
             user,
@@ -518,7 +694,15 @@ where
         )
         .await?;
 
-    return Ok(id);
+    return Ok(ImportedModel { id, is_new: true });
+}
+
+// Models and blobs are deduplicated on the first half of the sha256 of the file
+pub fn hash_file_contents(file_contents: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(file_contents);
+    let bytes = hasher.finalize();
+    String::from(&format!("{:x}", bytes)[0..32])
 }
 
 pub fn is_supported_extension(path: &PathBuf) -> bool {
@@ -635,4 +819,52 @@ pub async fn expand_paths(paths: &[PathBuf], recursive: bool) -> Result<Vec<Dire
     }
 
     Ok(models)
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use db::db_context;
+    use std::future::Future;
+
+    fn run<F: Future>(future: F) -> F::Output {
+        tokio::runtime::Runtime::new().unwrap().block_on(future)
+    }
+
+    async fn app_state_in(dir: &Path) -> AppState {
+        let db = db_context::setup_db(&dir.join("db.sqlite"), &dir.join("backups")).await;
+
+        AppState {
+            db: Arc::new(db),
+            configuration: std::sync::Mutex::new(Configuration {
+                data_path: dir.to_str().unwrap().to_string(),
+                ..Configuration::default()
+            }),
+            import_mutex: Arc::new(tokio::sync::Mutex::new(())),
+            app_data_path: dir.to_str().unwrap().to_string(),
+        }
+    }
+
+    #[test]
+    fn truncated_bgcode_is_imported_without_metadata() {
+        let dir = std::env::temp_dir().join(format!("meshorganiser_import_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        let path = dir.join("truncated.bgcode");
+        fs::write(&path, b"GCDE\x01\x00\x00").unwrap();
+
+        let import_state = run(async {
+            let app_state = app_state_in(&dir).await;
+            let import_state = ImportState::new(None, false, false, false, User::default());
+
+            import_path(path.to_str().unwrap(), &app_state, import_state).await
+        });
+
+        let _ = fs::remove_dir_all(&dir);
+
+        let import_state = import_state.unwrap();
+        assert_eq!(import_state.imported_models_count, 1);
+        assert_eq!(import_state.imported_models[0].model_ids.len(), 1);
+    }
+}
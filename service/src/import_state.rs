@@ -15,6 +15,7 @@ pub struct ImportedModelsSet {
 #[derive(Serialize, Debug, Display)]
 pub enum ImportStatus {
     Idle,
+    Slicing,
     ProcessingModels,
     FinishedModels,
     ProcessingThumbnails,
@@ -164,6 +165,21 @@ impl ImportState {
         self.emitter.model_count_event(self);
     }
 
+    // Moves models that were already added to a set into a set of their own, to group them differently than the folder or zip they came from
+    pub fn move_models_to_new_set(&mut self, model_ids: &[i64], group_name: Option<String>) {
+        for set in self.imported_models.iter_mut() {
+            set.model_ids.retain(|id| !model_ids.contains(id));
+        }
+
+        self.imported_models.push(ImportedModelsSet {
+            group_id: None,
+            group_name,
+            model_ids: model_ids.to_vec(),
+        });
+
+        self.emitter.model_group_event(self);
+    }
+
     pub fn get_last_group_name(&self) -> Option<String> {
         if let Some(last) = self.imported_models.last() {
             return last.group_name.clone();
@@ -1,11 +1,25 @@
+pub mod backup_service;
+pub mod bgcode_service;
+pub mod conversion_service;
 pub mod download_file_service;
+pub mod duplicate_service;
 pub mod export_service;
+pub mod folder_export_service;
+pub mod gcode_service;
 pub mod import_service;
 pub mod import_state;
+pub mod mesh_analysis_service;
+pub mod mesh_repair_service;
+pub mod mesh_service;
+pub mod printer_service;
+pub mod resin_service;
 pub mod resource_service;
+pub mod similarity_service;
 pub mod slicer_service;
+pub mod slicing_service;
 pub mod threemf_service;
 pub mod thumbnail_service;
+pub mod zip_stream_service;
 mod util;
 mod configuration;
 mod service_error;
@@ -0,0 +1,446 @@
+use std::collections::{HashMap, HashSet};
+
+use db::mesh_health_db;
+use db::model::{Blob, FileType, MeshHealth, Model};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+use tokio::task::spawn_blocking;
+
+use crate::app_state::AppState;
+use crate::conversion_service;
+use crate::export_service::get_bytes_from_blob;
+use crate::mesh_service::{self, Mesh};
+use crate::service_error::ServiceError;
+
+// Vertices closer than this (in mm) are treated as the same vertex
+const WELD_TOLERANCE: f64 = 1e-5;
+// Triangles with a smaller area (in mm²) are degenerate
+const DEGENERATE_AREA: f64 = 1e-10;
+// Upper bound of triangle pairs tested for self-intersections, larger meshes only get an estimate
+const MAX_INTERSECTION_TESTS: usize = 20_000_000;
+// Triangles spanning more grid cells than this are only put in the cell they start in, which makes the count an estimate
+const MAX_CELLS_PER_TRIANGLE: usize = 512;
+
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+#[serde(default)]
+pub struct MeshReport {
+    pub health: MeshHealth,
+    pub vertex_count: usize,
+    pub triangle_count: usize,
+    pub shell_count: usize,
+    pub is_manifold: bool,
+    // Edges used by a single triangle
+    pub boundary_edges: usize,
+    // Closed loops of boundary edges
+    pub hole_count: usize,
+    // Edges shared by more than two triangles
+    pub non_manifold_edges: usize,
+    // Edges where both triangles run in the same direction, meaning one of them is flipped
+    pub inconsistent_winding_edges: usize,
+    // Closed shells with a negative volume, meaning all of their normals point inwards
+    pub inverted_shells: usize,
+    pub degenerate_triangles: usize,
+    // Pairs of intersecting triangles that don't share a vertex
+    pub self_intersections: usize,
+    // True when not every triangle pair could be tested, the count is then a lower bound
+    pub self_intersections_estimated: bool,
+}
+
+// Returns the stored report of a model, analysing its mesh if it was never analysed
+pub async fn get_report(model: &Model, app_state: &AppState) -> Result<MeshReport, ServiceError> {
+    if !conversion_service::can_convert(&model.blob.to_file_type()) {
+        return Err(ServiceError::InternalError(String::from(
+            "Model is not a mesh",
+        )));
+    }
+
+    if let Some(json) = mesh_health_db::get_mesh_report_json(&app_state.db, model.blob.id).await? {
+        return Ok(serde_json::from_str(&json)?);
+    }
+
+    analyse_blob(&model.blob, app_state).await
+}
+
+// Only one pass runs at a time, a pass started during another one waits and then picks up whatever is still missing
+static ANALYSE_MISSING_LOCK: Mutex<()> = Mutex::const_new(());
+
+// Analyses every mesh without a report. Runs on start and in the background after each import
+pub async fn analyse_missing(app_state: &AppState) -> Result<(), ServiceError> {
+    let _lock = ANALYSE_MISSING_LOCK.lock().await;
+    let blobs = mesh_health_db::get_mesh_blobs_without_health(&app_state.db).await?;
+
+    for blob in blobs {
+        if let Err(e) = analyse_blob(&blob, app_state).await {
+            println!("Failed to analyse mesh of blob {}: {}", blob.id, e);
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn analyse_blob(blob: &Blob, app_state: &AppState) -> Result<MeshReport, ServiceError> {
+    let bytes = get_bytes_from_blob(blob, app_state).await?;
+
+    store_report_from_bytes(blob.id, &blob.to_file_type(), bytes, app_state).await
+}
+
+pub async fn store_report_from_bytes(blob_id: i64, file_type: &FileType, bytes: Vec<u8>, app_state: &AppState) -> Result<MeshReport, ServiceError> {
+    let file_type = file_type.from_zip();
+
+    let report = spawn_blocking(move || -> Result<MeshReport, ServiceError> {
+        let meshes = mesh_service::read_meshes(&bytes, &file_type, "")?;
+        Ok(analyse_meshes(&meshes))
+    }).await??;
+
+    mesh_health_db::set_mesh_health(&app_state.db, blob_id, report.health, &serde_json::to_string(&report)?).await?;
+
+    Ok(report)
+}
+
+pub fn analyse_meshes(meshes: &[Mesh]) -> MeshReport {
+    let mut report = MeshReport::default();
+    let (positions, triangles) = weld(meshes);
+
+    report.vertex_count = positions.len();
+    report.triangle_count = triangles.len();
+
+    let valid: Vec<[usize; 3]> = triangles
+        .iter()
+        .filter(|triangle| !is_degenerate(&positions, triangle))
+        .copied()
+        .collect();
+
+    report.degenerate_triangles = triangles.len() - valid.len();
+
+    // Per undirected edge: number of triangles using it, and how many of those run from the lower to the higher vertex
+    let mut edges: HashMap<(usize, usize), (usize, usize)> = HashMap::with_capacity(valid.len() * 3 / 2);
+
+    for [a, b, c] in &valid {
+        for (from, to) in [(*a, *b), (*b, *c), (*c, *a)] {
+            let entry = edges.entry((from.min(to), from.max(to))).or_insert((0, 0));
+            entry.0 += 1;
+
+            if from < to {
+                entry.1 += 1;
+            }
+        }
+    }
+
+    let mut shells = UnionFind::new(positions.len());
+    let mut holes = UnionFind::new(positions.len());
+    let mut boundary_vertices = Vec::new();
+
+    for [a, b, c] in &valid {
+        shells.union(*a, *b);
+        shells.union(*b, *c);
+    }
+
+    let mut open_shells = HashSet::new();
+
+    for ((from, to), (count, forward)) in &edges {
+        match count {
+            1 => {
+                report.boundary_edges += 1;
+                holes.union(*from, *to);
+                boundary_vertices.push(*from);
+                open_shells.insert(shells.find(*from));
+            }
+            2 if *forward != 1 => report.inconsistent_winding_edges += 1,
+            2 => {}
+            _ => {
+                report.non_manifold_edges += 1;
+                open_shells.insert(shells.find(*from));
+            }
+        }
+    }
+
+    let mut hole_roots: Vec<usize> = boundary_vertices.iter().map(|vertex| holes.find(*vertex)).collect();
+    hole_roots.sort_unstable();
+    hole_roots.dedup();
+    report.hole_count = hole_roots.len();
+
+    // Signed volume per shell, only meaningful for closed shells
+    let mut volumes: HashMap<usize, f64> = HashMap::new();
+
+    for [a, b, c] in &valid {
+        *volumes.entry(shells.find(*a)).or_insert(0.0) += signed_volume(&positions[*a], &positions[*b], &positions[*c]);
+    }
+
+    report.shell_count = volumes.len();
+    report.inverted_shells = volumes
+        .iter()
+        .filter(|(root, volume)| !open_shells.contains(*root) && **volume < 0.0)
+        .count();
+
+    let (self_intersections, estimated) = count_self_intersections(&positions, &valid);
+    report.self_intersections = self_intersections;
+    report.self_intersections_estimated = estimated;
+
+    report.is_manifold = report.boundary_edges == 0 && report.non_manifold_edges == 0;
+    report.health = if valid.is_empty()
+        || !report.is_manifold
+        || report.inconsistent_winding_edges > 0
+        || report.inverted_shells > 0
+        || report.self_intersections > 0
+    {
+        MeshHealth::Problems
+    } else if report.degenerate_triangles > 0 {
+        MeshHealth::Warnings
+    } else {
+        MeshHealth::Healthy
+    };
+
+    report
+}
+
+// Merges vertices that share a position, so triangles written with their own copy of each vertex (like STL) connect.
+// Vertices of different meshes are never merged, separate objects touching each other are not an error.
+fn weld(meshes: &[Mesh]) -> (Vec<[f64; 3]>, Vec<[usize; 3]>) {
+    let mut positions = Vec::new();
+    let mut triangles = Vec::new();
+
+    for mesh in meshes {
+        let (welded, remap) = weld_vertices(&mesh.vertices);
+        let offset = positions.len();
+        positions.extend(welded);
+
+        triangles.extend(
+            mesh.triangles
+                .iter()
+                .filter(|triangle| triangle.iter().all(|index| *index < remap.len()))
+                .map(|[a, b, c]| [remap[*a] + offset, remap[*b] + offset, remap[*c] + offset]),
+        );
+    }
+
+    (positions, triangles)
+}
+
+// Returns the unique positions, and for every input vertex the index of its position
+pub fn weld_vertices(vertices: &[[f64; 3]]) -> (Vec<[f64; 3]>, Vec<usize>) {
+    let mut positions = Vec::new();
+    let mut lookup: HashMap<(i64, i64, i64), usize> = HashMap::with_capacity(vertices.len());
+
+    let remap = vertices
+        .iter()
+        .map(|vertex| {
+            let key = (
+                (vertex[0] / WELD_TOLERANCE).round() as i64,
+                (vertex[1] / WELD_TOLERANCE).round() as i64,
+                (vertex[2] / WELD_TOLERANCE).round() as i64,
+            );
+
+            *lookup.entry(key).or_insert_with(|| {
+                positions.push(*vertex);
+                positions.len() - 1
+            })
+        })
+        .collect();
+
+    (positions, remap)
+}
+
+pub fn sub(a: &[f64; 3], b: &[f64; 3]) -> [f64; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+pub fn cross(a: &[f64; 3], b: &[f64; 3]) -> [f64; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+pub fn dot(a: &[f64; 3], b: &[f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+pub fn is_degenerate(positions: &[[f64; 3]], [a, b, c]: &[usize; 3]) -> bool {
+    if a == b || b == c || a == c {
+        return true;
+    }
+
+    let normal = cross(&sub(&positions[*b], &positions[*a]), &sub(&positions[*c], &positions[*a]));
+
+    dot(&normal, &normal).sqrt() / 2.0 < DEGENERATE_AREA
+}
+
+pub fn signed_volume(a: &[f64; 3], b: &[f64; 3], c: &[f64; 3]) -> f64 {
+    dot(a, &cross(b, c)) / 6.0
+}
+
+// Buckets triangles in a uniform grid and tests the triangles sharing a cell against each other.
+// A pair is only tested in the first cell both of them overlap, so no pair is tested twice.
+fn count_self_intersections(positions: &[[f64; 3]], triangles: &[[usize; 3]]) -> (usize, bool) {
+    if triangles.len() < 2 {
+        return (0, false);
+    }
+
+    let bounds: Vec<([f64; 3], [f64; 3])> = triangles
+        .iter()
+        .map(|[a, b, c]| {
+            let (a, b, c) = (&positions[*a], &positions[*b], &positions[*c]);
+            (
+                [a[0].min(b[0]).min(c[0]), a[1].min(b[1]).min(c[1]), a[2].min(b[2]).min(c[2])],
+                [a[0].max(b[0]).max(c[0]), a[1].max(b[1]).max(c[1]), a[2].max(b[2]).max(c[2])],
+            )
+        })
+        .collect();
+
+    let mut min = [f64::MAX; 3];
+    let mut max = [f64::MIN; 3];
+
+    for (low, high) in &bounds {
+        for axis in 0..3 {
+            min[axis] = min[axis].min(low[axis]);
+            max[axis] = max[axis].max(high[axis]);
+        }
+    }
+
+    // Aim for roughly one triangle per cell
+    let size = sub(&max, &min);
+    let volume = size.iter().map(|s| s.max(1e-6)).product::<f64>();
+    let cell_size = (volume / triangles.len() as f64).cbrt().max(1e-6);
+    let cell_of = |value: f64, axis: usize| ((value - min[axis]) / cell_size).floor() as i64;
+
+    let mut grid: HashMap<(i64, i64, i64), Vec<usize>> = HashMap::new();
+    let mut estimated = false;
+
+    for (index, (low, high)) in bounds.iter().enumerate() {
+        let start = [cell_of(low[0], 0), cell_of(low[1], 1), cell_of(low[2], 2)];
+        let mut end = [cell_of(high[0], 0), cell_of(high[1], 1), cell_of(high[2], 2)];
+        let cells = (0..3).map(|axis| (end[axis] - start[axis] + 1) as usize).product::<usize>();
+
+        if cells > MAX_CELLS_PER_TRIANGLE {
+            end = start;
+            estimated = true;
+        }
+
+        for x in start[0]..=end[0] {
+            for y in start[1]..=end[1] {
+                for z in start[2]..=end[2] {
+                    grid.entry((x, y, z)).or_default().push(index);
+                }
+            }
+        }
+    }
+
+    let mut intersections = 0;
+    let mut tests = 0;
+
+    for ((x, y, z), members) in &grid {
+        for (i, first) in members.iter().enumerate() {
+            for second in &members[i + 1..] {
+                let (low_a, high_a) = &bounds[*first];
+                let (low_b, high_b) = &bounds[*second];
+
+                if (0..3).any(|axis| low_a[axis] > high_b[axis] || low_b[axis] > high_a[axis]) {
+                    continue;
+                }
+
+                let first_shared = (
+                    cell_of(low_a[0].max(low_b[0]), 0).max(*x),
+                    cell_of(low_a[1].max(low_b[1]), 1).max(*y),
+                    cell_of(low_a[2].max(low_b[2]), 2).max(*z),
+                );
+
+                if first_shared != (*x, *y, *z) {
+                    continue;
+                }
+
+                let a = &triangles[*first];
+                let b = &triangles[*second];
+
+                // Neighbouring triangles always touch at their shared vertex
+                if a.iter().any(|vertex| b.contains(vertex)) {
+                    continue;
+                }
+
+                tests += 1;
+
+                if tests > MAX_INTERSECTION_TESTS {
+                    return (intersections, true);
+                }
+
+                if triangles_intersect(positions, a, b) {
+                    intersections += 1;
+                }
+            }
+        }
+    }
+
+    (intersections, estimated)
+}
+
+// Two triangles intersect when an edge of one passes through the other. Coplanar overlaps are not detected.
+fn triangles_intersect(positions: &[[f64; 3]], a: &[usize; 3], b: &[usize; 3]) -> bool {
+    let a = [positions[a[0]], positions[a[1]], positions[a[2]]];
+    let b = [positions[b[0]], positions[b[1]], positions[b[2]]];
+
+    (0..3).any(|i| segment_hits_triangle(&a[i], &a[(i + 1) % 3], &b))
+        || (0..3).any(|i| segment_hits_triangle(&b[i], &b[(i + 1) % 3], &a))
+}
+
+// Möller–Trumbore, limited to the segment between start and end
+fn segment_hits_triangle(start: &[f64; 3], end: &[f64; 3], triangle: &[[f64; 3]; 3]) -> bool {
+    const EPSILON: f64 = 1e-9;
+
+    let direction = sub(end, start);
+    let edge1 = sub(&triangle[1], &triangle[0]);
+    let edge2 = sub(&triangle[2], &triangle[0]);
+    let p = cross(&direction, &edge2);
+    let determinant = dot(&edge1, &p);
+
+    if determinant.abs() < EPSILON {
+        return false;
+    }
+
+    let inverse = 1.0 / determinant;
+    let s = sub(start, &triangle[0]);
+    let u = dot(&s, &p) * inverse;
+
+    if u <= EPSILON || u >= 1.0 - EPSILON {
+        return false;
+    }
+
+    let q = cross(&s, &edge1);
+    let v = dot(&direction, &q) * inverse;
+
+    if v <= EPSILON || u + v >= 1.0 - EPSILON {
+        return false;
+    }
+
+    let t = dot(&edge2, &q) * inverse;
+
+    t > EPSILON && t < 1.0 - EPSILON
+}
+
+pub struct UnionFind {
+    parents: Vec<usize>,
+}
+
+impl UnionFind {
+    pub fn new(size: usize) -> Self {
+        UnionFind {
+            parents: (0..size).collect(),
+        }
+    }
+
+    pub fn find(&mut self, mut index: usize) -> usize {
+        while self.parents[index] != index {
+            self.parents[index] = self.parents[self.parents[index]];
+            index = self.parents[index];
+        }
+
+        index
+    }
+
+    pub fn union(&mut self, a: usize, b: usize) {
+        let a = self.find(a);
+        let b = self.find(b);
+
+        if a != b {
+            self.parents[a] = b;
+        }
+    }
+}
@@ -0,0 +1,479 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::Path;
+
+use db::model::{FileType, Model};
+use db::{group_db, model_db};
+use serde::Serialize;
+use tokio::task::spawn_blocking;
+
+use crate::app_state::AppState;
+use crate::export_service::{get_bytes_from_blob, get_temp_dir};
+use crate::import_service;
+use crate::import_state::ImportState;
+use crate::mesh_analysis_service::{self, MeshReport, UnionFind, is_degenerate, signed_volume, weld_vertices};
+use crate::mesh_service::{self, MeshFormat};
+use crate::service_error::ServiceError;
+use crate::util::cleanse_evil_from_name;
+
+// Holes with more edges than this are left open, a fan over a large opening rarely matches the intended surface
+const MAX_HOLE_EDGES: usize = 256;
+// Shells smaller than this fraction of the bounding box diagonal of the whole mesh are dropped as loose debris
+const LOOSE_SHELL_RATIO: f64 = 0.01;
+
+#[derive(Serialize, Clone, Default, Debug)]
+pub struct MeshRepairChanges {
+    pub welded_vertices: usize,
+    pub removed_degenerate_triangles: usize,
+    pub removed_duplicate_triangles: usize,
+    pub removed_shells: usize,
+    pub filled_holes: usize,
+    pub flipped_triangles: usize,
+}
+
+impl MeshRepairChanges {
+    pub fn is_empty(&self) -> bool {
+        self.welded_vertices == 0
+            && self.removed_degenerate_triangles == 0
+            && self.removed_duplicate_triangles == 0
+            && self.removed_shells == 0
+            && self.filled_holes == 0
+            && self.flipped_triangles == 0
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct MeshRepairReport {
+    pub before: MeshReport,
+    pub after: MeshReport,
+    pub changes: MeshRepairChanges,
+}
+
+// A triangle together with its multi material painting, so both stay in sync while triangles are removed or added
+type PaintedTriangle = ([usize; 3], Option<String>);
+
+// Repairs the mesh of the model and imports the result as a new model that points back at the original.
+// Nothing is imported when the mesh needed no repairs
+pub async fn repair_model(
+    model: &Model,
+    app_state: &AppState,
+    import_state: ImportState,
+) -> Result<(ImportState, MeshRepairReport), ServiceError> {
+    let file_type = model.blob.to_file_type().from_zip();
+
+    let format = match MeshFormat::from_file_type(&file_type) {
+        Some(format @ (MeshFormat::Stl | MeshFormat::Obj | MeshFormat::Threemf)) => format,
+        _ => {
+            return Err(ServiceError::InternalError(String::from(
+                "Only STL, OBJ and 3MF models can be repaired",
+            )));
+        }
+    };
+
+    let temp_dir = get_temp_dir("repair");
+    let result = repair_model_in(model, file_type, format, &temp_dir, app_state, import_state).await;
+    let _ = std::fs::remove_dir_all(&temp_dir);
+
+    result
+}
+
+async fn repair_model_in(
+    model: &Model,
+    file_type: FileType,
+    format: MeshFormat,
+    temp_dir: &Path,
+    app_state: &AppState,
+    import_state: ImportState,
+) -> Result<(ImportState, MeshRepairReport), ServiceError> {
+    let bytes = get_bytes_from_blob(&model.blob, app_state).await?;
+    let repaired_name = format!("{} (repaired)", model.name);
+    let path = temp_dir.join(format!("{}.{}", cleanse_evil_from_name(&repaired_name), format.to_extension()));
+    let write_path = path.clone();
+    let name = model.name.clone();
+
+    let report = spawn_blocking(move || -> Result<MeshRepairReport, ServiceError> {
+        let mut meshes = mesh_service::read_meshes(&bytes, &file_type, &name)?;
+        let before = mesh_analysis_service::analyse_meshes(&meshes);
+        let mut changes = MeshRepairChanges::default();
+
+        for mesh in meshes.iter_mut() {
+            repair_mesh(mesh, &mut changes);
+        }
+
+        meshes.retain(|mesh| !mesh.triangles.is_empty());
+
+        if meshes.is_empty() {
+            return Err(ServiceError::InternalError(String::from(
+                "Nothing is left of the mesh after repairing it",
+            )));
+        }
+
+        let after = mesh_analysis_service::analyse_meshes(&meshes);
+        mesh_service::write_meshes(&meshes, format, &[], &write_path)?;
+
+        Ok(MeshRepairReport { before, after, changes })
+    })
+    .await??;
+
+    if report.changes.is_empty() {
+        return Ok((import_state, report));
+    }
+
+    // An identical mesh would be deduplicated into the existing model, which must not be renamed or re-linked
+    let hash = import_service::hash_file_contents(&tokio::fs::read(&path).await?);
+
+    if let Some(id) = model_db::get_editable_model_id_via_sha256(&app_state.db, &import_state.user, &hash).await? {
+        return Err(ServiceError::InternalError(format!(
+            "The repaired mesh already exists as model {}",
+            id
+        )));
+    }
+
+    let import_state = import_service::import_path(&path.to_string_lossy(), app_state, import_state).await?;
+
+    let user = &import_state.user;
+    let new_model_id = match import_state.imported_models.iter().flat_map(|set| set.model_ids.iter()).next() {
+        Some(id) if *id != model.id => *id,
+        _ => {
+            return Err(ServiceError::InternalError(String::from(
+                "Repaired model was not imported",
+            )));
+        }
+    };
+
+    model_db::set_source_model(&app_state.db, user, new_model_id, Some(model.id)).await?;
+
+    if let Some(repaired_model) = model_db::get_models_via_ids(&app_state.db, user, vec![new_model_id]).await?.pop() {
+        model_db::edit_model(
+            &app_state.db,
+            user,
+            new_model_id,
+            &repaired_name,
+            model.link.as_deref(),
+            Some(&describe_repair(model, &report)),
+            repaired_model.flags,
+            None,
+        )
+        .await?;
+    }
+
+    if let Some(group) = &model.group {
+        group_db::set_group_id_on_models(&app_state.db, user, Some(group.id), vec![new_model_id], None).await?;
+    }
+
+    Ok((import_state, report))
+}
+
+fn describe_repair(model: &Model, report: &MeshRepairReport) -> String {
+    let changes = &report.changes;
+    let mut lines = vec![format!("Repaired copy of {}.", model.name)];
+
+    for (count, change) in [
+        (changes.welded_vertices, "duplicate vertices welded"),
+        (changes.removed_degenerate_triangles, "degenerate triangles removed"),
+        (changes.removed_duplicate_triangles, "duplicate triangles removed"),
+        (changes.removed_shells, "loose shells removed"),
+        (changes.filled_holes, "holes filled"),
+        (changes.flipped_triangles, "triangles flipped"),
+    ] {
+        if count > 0 {
+            lines.push(format!("{} {}", count, change));
+        }
+    }
+
+    lines.push(format!("Before: {}", describe_report(&report.before)));
+    lines.push(format!("After: {}", describe_report(&report.after)));
+
+    lines.join("\n")
+}
+
+fn describe_report(report: &MeshReport) -> String {
+    let problems: Vec<String> = [
+        (report.hole_count, "holes"),
+        (report.non_manifold_edges, "non-manifold edges"),
+        (report.inconsistent_winding_edges, "flipped edges"),
+        (report.inverted_shells, "inverted shells"),
+        (report.degenerate_triangles, "degenerate triangles"),
+        (report.self_intersections, "self-intersections"),
+    ]
+    .iter()
+    .filter(|(count, _)| *count > 0)
+    .map(|(count, problem)| format!("{} {}", count, problem))
+    .collect();
+
+    match problems.is_empty() {
+        true => String::from("no problems found"),
+        false => problems.join(", "),
+    }
+}
+
+fn repair_mesh(mesh: &mut mesh_service::Mesh, changes: &mut MeshRepairChanges) {
+    let painted = mesh.has_paint();
+    let (mut positions, remap) = weld_vertices(&mesh.vertices);
+    changes.welded_vertices += mesh.vertices.len() - positions.len();
+
+    let mut triangles: Vec<PaintedTriangle> = Vec::with_capacity(mesh.triangles.len());
+    let mut seen = HashSet::with_capacity(mesh.triangles.len());
+
+    for (index, [a, b, c]) in mesh.triangles.iter().enumerate() {
+        if [a, b, c].iter().any(|vertex| **vertex >= remap.len()) {
+            continue;
+        }
+
+        let triangle = [remap[*a], remap[*b], remap[*c]];
+
+        if is_degenerate(&positions, &triangle) {
+            changes.removed_degenerate_triangles += 1;
+            continue;
+        }
+
+        let mut key = triangle;
+        key.sort_unstable();
+
+        if !seen.insert(key) {
+            changes.removed_duplicate_triangles += 1;
+            continue;
+        }
+
+        triangles.push((triangle, mesh.paint.get(index).cloned().flatten()));
+    }
+
+    remove_loose_shells(&positions, &mut triangles, changes);
+    fill_holes(&mut positions, &mut triangles, changes);
+    unify_orientation(&positions, &mut triangles, changes);
+
+    // Only keep the vertices still used by a triangle
+    let mut new_index = vec![usize::MAX; positions.len()];
+    let mut vertices = Vec::new();
+
+    for (triangle, _) in triangles.iter_mut() {
+        for vertex in triangle.iter_mut() {
+            if new_index[*vertex] == usize::MAX {
+                new_index[*vertex] = vertices.len();
+                vertices.push(positions[*vertex]);
+            }
+
+            *vertex = new_index[*vertex];
+        }
+    }
+
+    let (triangles, paint): (Vec<[usize; 3]>, Vec<Option<String>>) = triangles.into_iter().unzip();
+
+    mesh.vertices = vertices;
+    mesh.triangles = triangles;
+    mesh.paint = if painted { paint } else { Vec::new() };
+}
+
+fn diagonal((min, max): &([f64; 3], [f64; 3])) -> f64 {
+    ((max[0] - min[0]).powi(2) + (max[1] - min[1]).powi(2) + (max[2] - min[2]).powi(2)).sqrt()
+}
+
+fn remove_loose_shells(positions: &[[f64; 3]], triangles: &mut Vec<PaintedTriangle>, changes: &mut MeshRepairChanges) {
+    let mut shells = UnionFind::new(positions.len());
+
+    for ([a, b, c], _) in triangles.iter() {
+        shells.union(*a, *b);
+        shells.union(*b, *c);
+    }
+
+    let mut bounds: HashMap<usize, ([f64; 3], [f64; 3])> = HashMap::new();
+
+    for (triangle, _) in triangles.iter() {
+        let (min, max) = bounds.entry(shells.find(triangle[0])).or_insert(([f64::MAX; 3], [f64::MIN; 3]));
+
+        for vertex in triangle {
+            for axis in 0..3 {
+                min[axis] = min[axis].min(positions[*vertex][axis]);
+                max[axis] = max[axis].max(positions[*vertex][axis]);
+            }
+        }
+    }
+
+    if bounds.len() < 2 {
+        return;
+    }
+
+    let mut overall = ([f64::MAX; 3], [f64::MIN; 3]);
+
+    for (min, max) in bounds.values() {
+        for axis in 0..3 {
+            overall.0[axis] = overall.0[axis].min(min[axis]);
+            overall.1[axis] = overall.1[axis].max(max[axis]);
+        }
+    }
+
+    let threshold = diagonal(&overall) * LOOSE_SHELL_RATIO;
+    let loose: HashSet<usize> = bounds
+        .iter()
+        .filter(|(_, shell_bounds)| diagonal(shell_bounds) < threshold)
+        .map(|(root, _)| *root)
+        .collect();
+
+    if loose.is_empty() || loose.len() == bounds.len() {
+        return;
+    }
+
+    changes.removed_shells += loose.len();
+    triangles.retain(|(triangle, _)| !loose.contains(&shells.find(triangle[0])));
+}
+
+// Closes simple holes, a single loop of boundary edges, with a fan of triangles around the centre of the hole
+fn fill_holes(positions: &mut Vec<[f64; 3]>, triangles: &mut Vec<PaintedTriangle>, changes: &mut MeshRepairChanges) {
+    let mut edge_counts: HashMap<(usize, usize), usize> = HashMap::new();
+
+    for ([a, b, c], _) in triangles.iter() {
+        for (from, to) in [(*a, *b), (*b, *c), (*c, *a)] {
+            *edge_counts.entry((from.min(to), from.max(to))).or_insert(0) += 1;
+        }
+    }
+
+    // The hole runs against the winding of the triangles around it, a boundary edge a -> b is walked as b -> a
+    let mut next: HashMap<usize, Vec<usize>> = HashMap::new();
+
+    for ([a, b, c], _) in triangles.iter() {
+        for (from, to) in [(*a, *b), (*b, *c), (*c, *a)] {
+            if edge_counts[&(from.min(to), from.max(to))] == 1 {
+                next.entry(to).or_default().push(from);
+            }
+        }
+    }
+
+    let mut starts: Vec<usize> = next.keys().copied().collect();
+    starts.sort_unstable();
+
+    let mut visited = HashSet::new();
+
+    for start in starts {
+        if visited.contains(&start) {
+            continue;
+        }
+
+        let mut hole = vec![start];
+        let mut current = start;
+
+        let closed = loop {
+            // Vertices where several holes meet are not simple holes
+            let following = match next.get(&current) {
+                Some(candidates) if candidates.len() == 1 => candidates[0],
+                _ => break false,
+            };
+
+            if following == start {
+                break true;
+            }
+
+            if hole.len() >= MAX_HOLE_EDGES || hole.contains(&following) {
+                break false;
+            }
+
+            hole.push(following);
+            current = following;
+        };
+
+        visited.extend(hole.iter().copied());
+
+        if !closed || hole.len() < 3 {
+            continue;
+        }
+
+        if hole.len() == 3 {
+            triangles.push(([hole[0], hole[1], hole[2]], None));
+        } else {
+            let mut centre = [0.0; 3];
+
+            for vertex in &hole {
+                for axis in 0..3 {
+                    centre[axis] += positions[*vertex][axis] / hole.len() as f64;
+                }
+            }
+
+            positions.push(centre);
+            let centre_index = positions.len() - 1;
+
+            for i in 0..hole.len() {
+                triangles.push(([hole[i], hole[(i + 1) % hole.len()], centre_index], None));
+            }
+        }
+
+        changes.filled_holes += 1;
+    }
+}
+
+// Walks every shell from one triangle, flipping neighbours that are wound the other way.
+// Closed shells that end up with a negative volume are inside out and get flipped as a whole.
+fn unify_orientation(positions: &[[f64; 3]], triangles: &mut [PaintedTriangle], changes: &mut MeshRepairChanges) {
+    let mut edge_triangles: HashMap<(usize, usize), Vec<usize>> = HashMap::new();
+
+    for (index, ([a, b, c], _)) in triangles.iter().enumerate() {
+        for (from, to) in [(*a, *b), (*b, *c), (*c, *a)] {
+            edge_triangles.entry((from.min(to), from.max(to))).or_default().push(index);
+        }
+    }
+
+    let oriented = |triangle: &[usize; 3], flip: bool| match flip {
+        true => [triangle[0], triangle[2], triangle[1]],
+        false => *triangle,
+    };
+
+    let mut flipped = vec![false; triangles.len()];
+    let mut visited = vec![false; triangles.len()];
+
+    for seed in 0..triangles.len() {
+        if visited[seed] {
+            continue;
+        }
+
+        visited[seed] = true;
+        let mut queue = VecDeque::from([seed]);
+        let mut shell = Vec::new();
+        let mut closed = true;
+
+        while let Some(current) = queue.pop_front() {
+            shell.push(current);
+            let [a, b, c] = oriented(&triangles[current].0, flipped[current]);
+
+            for (from, to) in [(a, b), (b, c), (c, a)] {
+                let neighbours = &edge_triangles[&(from.min(to), from.max(to))];
+
+                if neighbours.len() != 2 {
+                    closed = false;
+                    continue;
+                }
+
+                let other = if neighbours[0] == current { neighbours[1] } else { neighbours[0] };
+
+                if visited[other] {
+                    continue;
+                }
+
+                // A consistently wound neighbour runs through the shared edge in the opposite direction
+                let [x, y, z] = triangles[other].0;
+                flipped[other] = [(x, y), (y, z), (z, x)].contains(&(from, to));
+                visited[other] = true;
+                queue.push_back(other);
+            }
+        }
+
+        if closed {
+            let volume: f64 = shell
+                .iter()
+                .map(|index| {
+                    let [a, b, c] = oriented(&triangles[*index].0, flipped[*index]);
+                    signed_volume(&positions[a], &positions[b], &positions[c])
+                })
+                .sum();
+
+            if volume < 0.0 {
+                for index in &shell {
+                    flipped[*index] = !flipped[*index];
+                }
+            }
+        }
+    }
+
+    for (index, (triangle, _)) in triangles.iter_mut().enumerate() {
+        if flipped[index] {
+            triangle.swap(1, 2);
+            changes.flipped_triangles += 1;
+        }
+    }
+}
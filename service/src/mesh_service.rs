@@ -0,0 +1,943 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{BufRead, BufReader, BufWriter, Cursor, Write};
+use std::path::Path;
+
+use async_zip::base::read::mem::ZipFileReader;
+use async_zip::base::write::ZipFileWriter;
+use async_zip::{Compression, ZipEntryBuilder};
+use db::model::FileType;
+use futures::executor::block_on;
+use serde::{Deserialize, Serialize};
+use strum::EnumIter;
+use stl_io::Vector;
+
+use crate::service_error::ServiceError;
+
+// 3MF row-major affine transform: "m00 m01 m02 m10 m11 m12 m20 m21 m22 m30 m31 m32"
+pub type Transform = [f64; 12];
+
+pub const IDENTITY: Transform = [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0];
+
+// Nested components deeper than this are treated as a reference loop
+const MAX_COMPONENT_DEPTH: usize = 32;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq, Debug, EnumIter)]
+#[serde(rename_all = "lowercase")]
+pub enum MeshFormat {
+    #[default]
+    Stl,
+    Obj,
+    #[serde(rename = "3mf")]
+    Threemf,
+    Ply,
+}
+
+impl MeshFormat {
+    pub fn to_extension(&self) -> &'static str {
+        match self {
+            MeshFormat::Stl => "stl",
+            MeshFormat::Obj => "obj",
+            MeshFormat::Threemf => "3mf",
+            MeshFormat::Ply => "ply",
+        }
+    }
+
+    // The format a blob is already in, zipped blobs count as their plain type
+    pub fn from_file_type(file_type: &FileType) -> Option<MeshFormat> {
+        match file_type.from_zip() {
+            FileType::Stl => Some(MeshFormat::Stl),
+            FileType::Obj => Some(MeshFormat::Obj),
+            FileType::Threemf => Some(MeshFormat::Threemf),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct Mesh {
+    pub name: String,
+    // Id of the 3MF object the mesh was read from
+    pub object_id: u32,
+    pub vertices: Vec<[f64; 3]>,
+    pub triangles: Vec<[usize; 3]>,
+    // Per triangle multi material painting, either empty or one entry per triangle
+    pub paint: Vec<Option<String>>,
+    pub extruder: Option<u32>,
+}
+
+impl Mesh {
+    pub fn has_paint(&self) -> bool {
+        self.paint.iter().any(|paint| paint.is_some())
+    }
+}
+
+struct RawObject {
+    name: Option<String>,
+    vertices: Vec<[f64; 3]>,
+    triangles: Vec<[usize; 3]>,
+    paint: Vec<Option<String>>,
+    components: Vec<RawReference>,
+}
+
+struct RawReference {
+    path: String,
+    object_id: u32,
+    transform: Transform,
+}
+
+// Applies a then b
+pub fn combine_transforms(a: &Transform, b: &Transform) -> Transform {
+    let mut result = [0.0; 12];
+
+    for row in 0..4 {
+        for column in 0..3 {
+            let mut value = if row == 3 { b[9 + column] } else { 0.0 };
+
+            for k in 0..3 {
+                value += a[row * 3 + k] * b[k * 3 + column];
+            }
+
+            result[row * 3 + column] = value;
+        }
+    }
+
+    result
+}
+
+pub fn apply_transform(transform: &Transform, vertex: &[f64; 3]) -> [f64; 3] {
+    let [x, y, z] = *vertex;
+
+    [
+        x * transform[0] + y * transform[3] + z * transform[6] + transform[9],
+        x * transform[1] + y * transform[4] + z * transform[7] + transform[10],
+        x * transform[2] + y * transform[5] + z * transform[8] + transform[11],
+    ]
+}
+
+pub fn parse_transform(value: &str) -> Option<Transform> {
+    let values: Vec<f64> = value
+        .split_whitespace()
+        .filter_map(|v| v.parse::<f64>().ok())
+        .collect();
+
+    values.try_into().ok()
+}
+
+// Reads every printable mesh of a 3MF file in millimeters, with the build item and component transforms applied.
+// A build item referencing components results in one mesh per component.
+pub fn read_3mf(path: &Path) -> Result<Vec<Mesh>, ServiceError> {
+    read_3mf_from(fs::read(path)?)
+}
+
+// Mesh reading runs on blocking threads, so the archive is read in memory and driven to completion here
+fn read_3mf_from(bytes: Vec<u8>) -> Result<Vec<Mesh>, ServiceError> {
+    let archive = block_on(ZipFileReader::new(bytes))?;
+    let mut objects: HashMap<(String, u32), RawObject> = HashMap::new();
+    let mut build_items = Vec::new();
+    let mut scale = 1.0;
+
+    let model_files: Vec<(usize, String)> = archive
+        .file()
+        .entries()
+        .iter()
+        .enumerate()
+        .filter_map(|(i, e)| e.filename().as_str().ok().map(|name| (i, name.to_string())))
+        .filter(|(_, name)| name.to_lowercase().ends_with(".model"))
+        .collect();
+
+    for (index, model_file) in model_files {
+        let mut contents = String::new();
+        block_on(async { archive.reader_with_entry(index).await?.read_to_string_checked(&mut contents).await })?;
+
+        let is_root = model_file.to_lowercase().ends_with("3dmodel.model");
+        let file_scale = parse_model_file(&contents, &model_file, &mut objects, if is_root { Some(&mut build_items) } else { None });
+
+        if is_root {
+            scale = file_scale;
+        }
+    }
+
+    let scale_transform = [scale, 0.0, 0.0, 0.0, scale, 0.0, 0.0, 0.0, scale, 0.0, 0.0, 0.0];
+    let mut meshes = Vec::new();
+
+    for item in build_items {
+        let transform = combine_transforms(&item.transform, &scale_transform);
+        resolve_object(&objects, &item.path, item.object_id, &transform, 0, &mut meshes);
+    }
+
+    Ok(meshes)
+}
+
+// Reads the meshes of an unzipped STL, OBJ, 3MF or STEP file
+pub fn read_meshes(bytes: &[u8], file_type: &FileType, name: &str) -> Result<Vec<Mesh>, ServiceError> {
+    let mut meshes = match file_type.from_zip() {
+        FileType::Stl => vec![read_stl(bytes)?],
+        FileType::Obj => vec![read_obj(bytes)?],
+        FileType::Threemf => read_3mf_from(bytes.to_vec())?,
+        FileType::Step => {
+            let stl = crate::thumbnail_service::convert_step_to_stl(bytes)
+                .map_err(|e| ServiceError::InternalError(format!("Failed to convert STEP to STL: {}", e)))?;
+
+            vec![read_stl(&stl)?]
+        }
+        _ => return Err(ServiceError::InternalError(String::from("File type does not contain a mesh"))),
+    };
+
+    if meshes.len() == 1 {
+        meshes[0].name = name.to_string();
+    }
+
+    Ok(meshes)
+}
+
+pub fn read_stl(bytes: &[u8]) -> Result<Mesh, ServiceError> {
+    let indexed = stl_io::read_stl(&mut Cursor::new(bytes))?;
+
+    Ok(Mesh {
+        vertices: indexed.vertices.iter().map(|v| [v[0] as f64, v[1] as f64, v[2] as f64]).collect(),
+        triangles: indexed.faces.iter().map(|face| face.vertices).collect(),
+        ..Default::default()
+    })
+}
+
+// Polygons are fanned into triangles, texture coordinates and normals are dropped
+pub fn read_obj(bytes: &[u8]) -> Result<Mesh, ServiceError> {
+    let mut mesh = Mesh::default();
+
+    for line in BufReader::new(bytes).lines() {
+        let line = line?;
+        let mut parts = line.split_whitespace();
+
+        match parts.next() {
+            Some("v") => {
+                let values: Vec<f64> = parts.take(3).filter_map(|v| v.parse::<f64>().ok()).collect();
+
+                if values.len() == 3 {
+                    mesh.vertices.push([values[0], values[1], values[2]]);
+                }
+            }
+            Some("f") => {
+                let vertex_count = mesh.vertices.len() as i64;
+                let indices: Vec<usize> = parts
+                    .filter_map(|part| part.split('/').next()?.parse::<i64>().ok())
+                    // Negative indices count back from the last vertex
+                    .map(|index| if index < 0 { vertex_count + index } else { index - 1 })
+                    .filter(|index| *index >= 0 && *index < vertex_count)
+                    .map(|index| index as usize)
+                    .collect();
+
+                for i in 1..indices.len().saturating_sub(1) {
+                    mesh.triangles.push([indices[0], indices[i], indices[i + 1]]);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(mesh)
+}
+
+fn resolve_object(objects: &HashMap<(String, u32), RawObject>, path: &str, object_id: u32, transform: &Transform, depth: usize, meshes: &mut Vec<Mesh>) {
+    if depth > MAX_COMPONENT_DEPTH {
+        return;
+    }
+
+    let object = match objects.get(&(path.to_string(), object_id)) {
+        Some(object) => object,
+        None => return,
+    };
+
+    if !object.triangles.is_empty() {
+        meshes.push(Mesh {
+            name: object.name.clone().unwrap_or_else(|| format!("object_{}", object_id)),
+            object_id,
+            vertices: object.vertices.iter().map(|vertex| apply_transform(transform, vertex)).collect(),
+            triangles: object.triangles.clone(),
+            paint: object.paint.clone(),
+            extruder: None,
+        });
+    }
+
+    for component in &object.components {
+        let component_transform = combine_transforms(&component.transform, transform);
+        resolve_object(objects, &component.path, component.object_id, &component_transform, depth + 1, meshes);
+    }
+}
+
+// Walks the tags of a .model file, model files can be hundreds of megabytes so no DOM is built.
+// Returns the scale from the file's unit to millimeters.
+fn parse_model_file(data: &str, file_name: &str, objects: &mut HashMap<(String, u32), RawObject>, mut build_items: Option<&mut Vec<RawReference>>) -> f64 {
+    let file_path = file_name.trim_start_matches('/').to_string();
+    let mut current: Option<(u32, RawObject)> = None;
+    let mut scale = 1.0;
+    let mut rest = data;
+
+    while let Some(start) = rest.find('<') {
+        let end = match rest[start..].find('>') {
+            Some(end) => start + end,
+            None => break,
+        };
+
+        let tag = &rest[start + 1..end];
+        rest = &rest[end + 1..];
+
+        if tag.starts_with('?') || tag.starts_with('!') {
+            continue;
+        }
+
+        let is_closing = tag.starts_with('/');
+        let tag_name = tag
+            .trim_start_matches('/')
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .next()
+            .unwrap_or("");
+
+        match (tag_name, is_closing) {
+            ("model", false) => {
+                scale = match attribute(tag, "unit") {
+                    Some("micron") => 0.001,
+                    Some("centimeter") => 10.0,
+                    Some("inch") => 25.4,
+                    Some("foot") => 304.8,
+                    Some("meter") => 1000.0,
+                    _ => 1.0,
+                };
+            }
+            ("object", false) => {
+                let id = attribute(tag, "id").and_then(|id| id.parse::<u32>().ok()).unwrap_or(u32::MAX);
+
+                current = Some((id, RawObject {
+                    name: attribute(tag, "name").map(unescape_xml),
+                    vertices: Vec::new(),
+                    triangles: Vec::new(),
+                    paint: Vec::new(),
+                    components: Vec::new(),
+                }));
+            }
+            ("object", true) => {
+                if let Some((id, mut object)) = current.take() {
+                    if !object.paint.iter().any(|paint| paint.is_some()) {
+                        object.paint.clear();
+                    }
+
+                    objects.insert((file_path.clone(), id), object);
+                }
+            }
+            ("vertex", false) => {
+                if let Some((_, object)) = current.as_mut() {
+                    let coordinate = |name: &str| attribute(tag, name).and_then(|v| v.parse::<f64>().ok()).unwrap_or(0.0);
+                    object.vertices.push([coordinate("x"), coordinate("y"), coordinate("z")]);
+                }
+            }
+            ("triangle", false) => {
+                if let Some((_, object)) = current.as_mut() {
+                    let index = |name: &str| attribute(tag, name).and_then(|v| v.parse::<usize>().ok());
+
+                    if let (Some(v1), Some(v2), Some(v3)) = (index("v1"), index("v2"), index("v3")) {
+                        if v1 < object.vertices.len() && v2 < object.vertices.len() && v3 < object.vertices.len() {
+                            object.triangles.push([v1, v2, v3]);
+                            object.paint.push(
+                                attribute(tag, "paint_color")
+                                    .or_else(|| attribute(tag, "slic3rpe:mmu_segmentation"))
+                                    .map(|paint| paint.to_string()),
+                            );
+                        }
+                    }
+                }
+            }
+            ("component", false) => {
+                if let Some((_, object)) = current.as_mut() {
+                    if let Some(reference) = parse_reference(tag, &file_path) {
+                        object.components.push(reference);
+                    }
+                }
+            }
+            ("item", false) => {
+                if let Some(build_items) = build_items.as_mut() {
+                    if let Some(reference) = parse_reference(tag, &file_path) {
+                        build_items.push(reference);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    scale
+}
+
+fn parse_reference(tag: &str, file_path: &str) -> Option<RawReference> {
+    // Production extension: the object can live in another model file
+    let path = attribute(tag, "p:path")
+        .map(|path| path.trim_start_matches('/').to_string())
+        .unwrap_or_else(|| file_path.to_string());
+
+    Some(RawReference {
+        path,
+        object_id: attribute(tag, "objectid")?.parse::<u32>().ok()?,
+        transform: attribute(tag, "transform").and_then(parse_transform).unwrap_or(IDENTITY),
+    })
+}
+
+fn attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let mut search = tag;
+
+    while let Some(position) = search.find(name) {
+        let preceded_by_space = position == 0 || search[..position].ends_with(char::is_whitespace);
+        let after = &search[position + name.len()..];
+
+        if preceded_by_space {
+            if let Some(value) = after.trim_start().strip_prefix('=') {
+                let value = value.trim_start();
+                let quote = value.chars().next()?;
+
+                if quote == '"' || quote == '\'' {
+                    let value = &value[1..];
+                    return value.find(quote).map(|end| &value[..end]);
+                }
+            }
+        }
+
+        search = after;
+    }
+
+    None
+}
+
+fn unescape_xml(value: &str) -> String {
+    value
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+fn escape_xml(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+// Decodes the painting of a triangle as written by PrusaSlicer, Bambu Studio and OrcaSlicer.
+// The hex string is read back to front as a tree of nibbles, split triangles list their children and
+// leaves hold the extruder (0 for the object's own extruder). Returns the extruder covering most of the triangle.
+pub fn paint_extruder(paint: &str) -> u32 {
+    let mut nibbles = paint.chars().rev().filter_map(|c| c.to_digit(16));
+    let mut counts: HashMap<u32, f64> = HashMap::new();
+
+    fn read_node(nibbles: &mut impl Iterator<Item = u32>, area: f64, counts: &mut HashMap<u32, f64>) -> Option<()> {
+        let code = nibbles.next()?;
+        let split_sides = code & 0b11;
+
+        if split_sides != 0 {
+            let children = split_sides + 1;
+
+            for _ in 0..children {
+                read_node(nibbles, area / children as f64, counts)?;
+            }
+
+            return Some(());
+        }
+
+        let mut state = code >> 2;
+
+        if state == 0b11 {
+            state = nibbles.next()? + 3;
+        }
+
+        *counts.entry(state).or_insert(0.0) += area;
+        Some(())
+    }
+
+    let _ = read_node(&mut nibbles, 1.0, &mut counts);
+
+    counts
+        .into_iter()
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(state, _)| state)
+        .unwrap_or(0)
+}
+
+// Parses "#RRGGBB" or "#RRGGBBAA" into 0..1 floats
+pub fn parse_colour(colour: &str) -> Option<[f32; 3]> {
+    let hex = colour.trim().trim_start_matches('#');
+
+    if hex.len() < 6 {
+        return None;
+    }
+
+    let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok().map(|v| v as f32 / 255.0);
+
+    Some([channel(0)?, channel(2)?, channel(4)?])
+}
+
+// Joins several meshes into a single mesh, used to turn a multi part file into one object
+pub fn merge_meshes(meshes: Vec<Mesh>, name: &str) -> Mesh {
+    let painted = meshes.iter().any(|mesh| mesh.has_paint());
+    let mut merged = Mesh {
+        name: name.to_string(),
+        extruder: meshes.first().and_then(|mesh| mesh.extruder),
+        ..Default::default()
+    };
+
+    for mesh in meshes {
+        let offset = merged.vertices.len();
+        let triangle_count = mesh.triangles.len();
+
+        merged.vertices.extend(mesh.vertices);
+        merged.triangles.extend(mesh.triangles.into_iter().map(|[a, b, c]| [a + offset, b + offset, c + offset]));
+
+        if painted {
+            let mut paint = mesh.paint;
+            paint.resize(triangle_count, None);
+            merged.paint.extend(paint);
+        }
+    }
+
+    merged
+}
+
+pub fn bounding_box(mesh: &Mesh) -> ([f64; 3], [f64; 3]) {
+    let mut min = [f64::MAX; 3];
+    let mut max = [f64::MIN; 3];
+
+    for vertex in &mesh.vertices {
+        for axis in 0..3 {
+            min[axis] = min[axis].min(vertex[axis]);
+            max[axis] = max[axis].max(vertex[axis]);
+        }
+    }
+
+    if mesh.vertices.is_empty() {
+        return ([0.0; 3], [0.0; 3]);
+    }
+
+    (min, max)
+}
+
+pub fn translate(mesh: &mut Mesh, offset: [f64; 3]) {
+    for vertex in &mut mesh.vertices {
+        for axis in 0..3 {
+            vertex[axis] += offset[axis];
+        }
+    }
+}
+
+// Lays the meshes out in rows on the bed, tallest footprint first, and drops them onto z = 0.
+// Rows that no longer fit continue past the bed, so meshes never overlap. The layout is centered on the bed when it fits.
+pub fn arrange_on_bed(meshes: &mut [Mesh], bed_size: [f64; 2], spacing: f64) {
+    let boxes: Vec<([f64; 3], [f64; 3])> = meshes.iter().map(bounding_box).collect();
+    let mut order: Vec<usize> = (0..meshes.len()).collect();
+    order.sort_by(|a, b| {
+        let depth_a = boxes[*a].1[1] - boxes[*a].0[1];
+        let depth_b = boxes[*b].1[1] - boxes[*b].0[1];
+        depth_b.total_cmp(&depth_a)
+    });
+
+    let mut positions = vec![[0.0; 2]; meshes.len()];
+    let mut x = 0.0;
+    let mut y = 0.0;
+    let mut row_depth: f64 = 0.0;
+    let mut used = [0.0f64; 2];
+
+    for index in order {
+        let (min, max) = boxes[index];
+        let width = max[0] - min[0];
+        let depth = max[1] - min[1];
+
+        if x > 0.0 && x + width > bed_size[0] {
+            x = 0.0;
+            y += row_depth + spacing;
+            row_depth = 0.0;
+        }
+
+        positions[index] = [x, y];
+        used[0] = used[0].max(x + width);
+        used[1] = used[1].max(y + depth);
+        row_depth = row_depth.max(depth);
+        x += width + spacing;
+    }
+
+    let margin = [
+        ((bed_size[0] - used[0]) / 2.0).max(0.0),
+        ((bed_size[1] - used[1]) / 2.0).max(0.0),
+    ];
+
+    for (index, mesh) in meshes.iter_mut().enumerate() {
+        let (min, _) = boxes[index];
+        translate(mesh, [
+            margin[0] + positions[index][0] - min[0],
+            margin[1] + positions[index][1] - min[1],
+            -min[2],
+        ]);
+    }
+}
+
+fn triangle_normal(a: &[f64; 3], b: &[f64; 3], c: &[f64; 3]) -> [f32; 3] {
+    let edge1 = [b[0] - a[0], b[1] - a[1], b[2] - a[2]];
+    let edge2 = [c[0] - a[0], c[1] - a[1], c[2] - a[2]];
+    let normal = [
+        edge1[1] * edge2[2] - edge1[2] * edge2[1],
+        edge1[2] * edge2[0] - edge1[0] * edge2[2],
+        edge1[0] * edge2[1] - edge1[1] * edge2[0],
+    ];
+
+    let length = (normal[0] * normal[0] + normal[1] * normal[1] + normal[2] * normal[2]).sqrt();
+
+    if length == 0.0 {
+        return [0.0, 0.0, 0.0];
+    }
+
+    [(normal[0] / length) as f32, (normal[1] / length) as f32, (normal[2] / length) as f32]
+}
+
+pub fn write_stl(meshes: &[Mesh], path: &Path) -> Result<(), ServiceError> {
+    let triangles: Vec<stl_io::Triangle> = meshes
+        .iter()
+        .flat_map(|mesh| {
+            mesh.triangles.iter().map(|triangle| {
+                let [a, b, c] = triangle.map(|index| mesh.vertices[index]);
+                let vertex = |v: [f64; 3]| Vector([v[0] as f32, v[1] as f32, v[2] as f32]);
+
+                stl_io::Triangle {
+                    normal: Vector(triangle_normal(&a, &b, &c)),
+                    vertices: [vertex(a), vertex(b), vertex(c)],
+                }
+            })
+        })
+        .collect();
+
+    let mut file = BufWriter::new(fs::File::create(path)?);
+    stl_io::write_stl(&mut file, triangles.iter())?;
+
+    Ok(())
+}
+
+// OBJ has no per face colours, so painted meshes are written with per vertex colours ("v x y z r g b"),
+// giving every triangle its own vertices. Colours come from the filament colours of the project.
+pub fn write_obj(meshes: &[Mesh], colours: &[String], path: &Path) -> Result<(), ServiceError> {
+    let mut file = BufWriter::new(fs::File::create(path)?);
+    let mut vertex_offset = 1;
+
+    let extruder_colour = |extruder: u32| -> Option<[f32; 3]> {
+        colours.get((extruder.max(1) - 1) as usize).and_then(|colour| parse_colour(colour))
+    };
+
+    for mesh in meshes {
+        writeln!(file, "o {}", mesh.name)?;
+
+        let default_extruder = mesh.extruder.unwrap_or(1);
+
+        if mesh.has_paint() {
+            for (i, triangle) in mesh.triangles.iter().enumerate() {
+                let extruder = match mesh.paint.get(i).and_then(|paint| paint.as_deref()).map(paint_extruder) {
+                    Some(0) | None => default_extruder,
+                    Some(extruder) => extruder,
+                };
+                let [r, g, b] = extruder_colour(extruder).unwrap_or([0.8, 0.8, 0.8]);
+
+                for index in triangle {
+                    let [x, y, z] = mesh.vertices[*index];
+                    writeln!(file, "v {} {} {} {} {} {}", x as f32, y as f32, z as f32, r, g, b)?;
+                }
+
+                writeln!(file, "f {} {} {}", vertex_offset, vertex_offset + 1, vertex_offset + 2)?;
+                vertex_offset += 3;
+            }
+
+            continue;
+        }
+
+        let colour = if colours.is_empty() { None } else { extruder_colour(default_extruder) };
+
+        for [x, y, z] in &mesh.vertices {
+            match colour {
+                Some([r, g, b]) => writeln!(file, "v {} {} {} {} {} {}", *x as f32, *y as f32, *z as f32, r, g, b)?,
+                None => writeln!(file, "v {} {} {}", *x as f32, *y as f32, *z as f32)?,
+            }
+        }
+
+        for [a, b, c] in &mesh.triangles {
+            writeln!(file, "f {} {} {}", a + vertex_offset, b + vertex_offset, c + vertex_offset)?;
+        }
+
+        vertex_offset += mesh.vertices.len();
+    }
+
+    file.flush()?;
+
+    Ok(())
+}
+
+// Writes a plain 3MF with one object per mesh. Painting is kept in both the Bambu Studio / OrcaSlicer and PrusaSlicer attribute.
+pub fn write_3mf(meshes: &[Mesh], metadata: &[(String, String)], path: &Path) -> Result<(), ServiceError> {
+    let mut model = String::new();
+    model.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    model.push_str("<model unit=\"millimeter\" xml:lang=\"en-US\" xmlns=\"http://schemas.microsoft.com/3dmanufacturing/core/2015/02\" xmlns:slic3rpe=\"http://schemas.slic3r.org/3mf/2017/06\">\n");
+    model.push_str(" <metadata name=\"Application\">Mesh Organiser</metadata>\n");
+
+    for (name, value) in metadata {
+        model.push_str(&format!(" <metadata name=\"{}\">{}</metadata>\n", escape_xml(name), escape_xml(value)));
+    }
+
+    model.push_str(" <resources>\n");
+
+    for (i, mesh) in meshes.iter().enumerate() {
+        model.push_str(&format!("  <object id=\"{}\" name=\"{}\" type=\"model\">\n   <mesh>\n    <vertices>\n", i + 1, escape_xml(&mesh.name)));
+
+        for [x, y, z] in &mesh.vertices {
+            model.push_str(&format!("     <vertex x=\"{}\" y=\"{}\" z=\"{}\"/>\n", *x as f32, *y as f32, *z as f32));
+        }
+
+        model.push_str("    </vertices>\n    <triangles>\n");
+
+        for (j, [v1, v2, v3]) in mesh.triangles.iter().enumerate() {
+            match mesh.paint.get(j).and_then(|paint| paint.as_deref()) {
+                Some(paint) => model.push_str(&format!(
+                    "     <triangle v1=\"{}\" v2=\"{}\" v3=\"{}\" paint_color=\"{}\" slic3rpe:mmu_segmentation=\"{}\"/>\n",
+                    v1, v2, v3, paint, paint
+                )),
+                None => model.push_str(&format!("     <triangle v1=\"{}\" v2=\"{}\" v3=\"{}\"/>\n", v1, v2, v3)),
+            }
+        }
+
+        model.push_str("    </triangles>\n   </mesh>\n  </object>\n");
+    }
+
+    model.push_str(" </resources>\n <build>\n");
+
+    for i in 0..meshes.len() {
+        model.push_str(&format!("  <item objectid=\"{}\"/>\n", i + 1));
+    }
+
+    model.push_str(" </build>\n</model>\n");
+
+    let archive = block_on(async {
+        let mut writer = ZipFileWriter::new(futures::io::Cursor::new(Vec::new()));
+
+        for (name, contents) in [("[Content_Types].xml", CONTENT_TYPES), ("_rels/.rels", RELATIONSHIPS), ("3D/3dmodel.model", model.as_str())] {
+            writer.write_entry_whole(ZipEntryBuilder::new(name.into(), Compression::Deflate), contents.as_bytes()).await?;
+        }
+
+        writer.close().await
+    })?;
+
+    fs::write(path, archive.into_inner())?;
+
+    Ok(())
+}
+
+pub fn write_ply(meshes: &[Mesh], path: &Path) -> Result<(), ServiceError> {
+    let vertex_count: usize = meshes.iter().map(|mesh| mesh.vertices.len()).sum();
+    let face_count: usize = meshes.iter().map(|mesh| mesh.triangles.len()).sum();
+
+    let mut file = BufWriter::new(fs::File::create(path)?);
+
+    write!(
+        file,
+        "ply\nformat binary_little_endian 1.0\ncomment Mesh Organiser\nelement vertex {}\nproperty float x\nproperty float y\nproperty float z\nelement face {}\nproperty list uchar uint vertex_indices\nend_header\n",
+        vertex_count, face_count
+    )?;
+
+    for mesh in meshes {
+        for vertex in &mesh.vertices {
+            for value in vertex {
+                file.write_all(&(*value as f32).to_le_bytes())?;
+            }
+        }
+    }
+
+    let mut vertex_offset = 0;
+
+    for mesh in meshes {
+        for triangle in &mesh.triangles {
+            file.write_all(&[3u8])?;
+
+            for index in triangle {
+                file.write_all(&((index + vertex_offset) as u32).to_le_bytes())?;
+            }
+        }
+
+        vertex_offset += mesh.vertices.len();
+    }
+
+    file.flush()?;
+
+    Ok(())
+}
+
+pub fn write_meshes(meshes: &[Mesh], format: MeshFormat, colours: &[String], path: &Path) -> Result<(), ServiceError> {
+    match format {
+        MeshFormat::Stl => write_stl(meshes, path),
+        MeshFormat::Obj => write_obj(meshes, colours, path),
+        MeshFormat::Threemf => write_3mf(meshes, &[], path),
+        MeshFormat::Ply => write_ply(meshes, path),
+    }
+}
+
+const CONTENT_TYPES: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types">
+ <Default Extension="rels" ContentType="application/vnd.openxmlformats-package.relationships+xml"/>
+ <Default Extension="model" ContentType="application/vnd.ms-package.3dmanufacturing-3dmodel+xml"/>
+ <Default Extension="png" ContentType="image/png"/>
+</Types>
+"#;
+
+const RELATIONSHIPS: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">
+ <Relationship Target="/3D/3dmodel.model" Id="rel0" Type="http://schemas.microsoft.com/3dmanufacturing/2013/01/3dmodel"/>
+</Relationships>
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRIANGLE: &str = "<mesh><vertices><vertex x=\"0\" y=\"0\" z=\"0\"/><vertex x=\"1\" y=\"0\" z=\"0\"/><vertex x=\"0\" y=\"1\" z=\"0\"/></vertices><triangles><triangle v1=\"0\" v2=\"1\" v3=\"2\"/></triangles></mesh>";
+
+    fn threemf(files: &[(&str, String)]) -> Vec<u8> {
+        block_on(async {
+            let mut writer = ZipFileWriter::new(futures::io::Cursor::new(Vec::new()));
+
+            for (name, contents) in files {
+                writer.write_entry_whole(ZipEntryBuilder::new(name.to_string().into(), Compression::Deflate), contents.as_bytes()).await.unwrap();
+            }
+
+            writer.close().await.unwrap().into_inner()
+        })
+    }
+
+    fn model(unit: &str, resources: &str, build: &str) -> String {
+        format!("<?xml version=\"1.0\"?><model unit=\"{}\" xmlns:p=\"http://schemas.microsoft.com/3dmanufacturing/production/2015/06\"><resources>{}</resources><build>{}</build></model>", unit, resources, build)
+    }
+
+    fn assert_vertex(actual: [f64; 3], expected: [f64; 3]) {
+        for i in 0..3 {
+            assert!((actual[i] - expected[i]).abs() < 1e-9, "{:?} != {:?}", actual, expected);
+        }
+    }
+
+    #[test]
+    fn transforms_combine_in_order() {
+        let translate = [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 1.0, 0.0, 0.0];
+        let scale = [2.0, 0.0, 0.0, 0.0, 2.0, 0.0, 0.0, 0.0, 2.0, 0.0, 0.0, 0.0];
+
+        assert_vertex(apply_transform(&combine_transforms(&translate, &scale), &[0.0, 0.0, 0.0]), [2.0, 0.0, 0.0]);
+        assert_vertex(apply_transform(&combine_transforms(&scale, &translate), &[0.0, 0.0, 0.0]), [1.0, 0.0, 0.0]);
+        assert_eq!(combine_transforms(&IDENTITY, &translate), translate);
+    }
+
+    #[test]
+    fn rotation_is_row_major() {
+        // 90 degrees around z, x maps onto y
+        let rotate = parse_transform("0 1 0 -1 0 0 0 0 1 0 0 5").unwrap();
+
+        assert_vertex(apply_transform(&rotate, &[1.0, 0.0, 0.0]), [0.0, 1.0, 5.0]);
+        assert_vertex(apply_transform(&rotate, &[0.0, 1.0, 0.0]), [-1.0, 0.0, 5.0]);
+    }
+
+    #[test]
+    fn transforms_need_twelve_values() {
+        assert!(parse_transform("1 0 0 0 1 0 0 0 1 0 0").is_none());
+        assert!(parse_transform("1 0 0 0 1 0 0 0 1 0 0 0 0").is_none());
+        assert_eq!(parse_transform(" 1 0 0  0 1 0 0 0 1 0 0 0 "), Some(IDENTITY));
+    }
+
+    #[test]
+    fn build_item_transform_is_applied() {
+        let bytes = threemf(&[(
+            "3D/3dmodel.model",
+            model("millimeter", &format!("<object id=\"1\" name=\"part\">{}</object>", TRIANGLE), "<item objectid=\"1\" transform=\"1 0 0 0 1 0 0 0 1 10 20 30\"/>"),
+        )]);
+
+        let meshes = read_3mf_from(bytes).unwrap();
+
+        assert_eq!(meshes.len(), 1);
+        assert_eq!(meshes[0].name, "part");
+        assert_vertex(meshes[0].vertices[1], [11.0, 20.0, 30.0]);
+    }
+
+    #[test]
+    fn component_transform_is_applied_before_build_item() {
+        let resources = format!(
+            "<object id=\"1\">{}</object><object id=\"2\"><components><component objectid=\"1\" transform=\"2 0 0 0 2 0 0 0 2 0 0 0\"/><component objectid=\"1\" transform=\"1 0 0 0 1 0 0 0 1 0 0 1\"/></components></object>",
+            TRIANGLE
+        );
+
+        let bytes = threemf(&[("3D/3dmodel.model", model("millimeter", &resources, "<item objectid=\"2\" transform=\"1 0 0 0 1 0 0 0 1 5 0 0\"/>"))]);
+        let meshes = read_3mf_from(bytes).unwrap();
+
+        assert_eq!(meshes.len(), 2);
+        assert_vertex(meshes[0].vertices[1], [7.0, 0.0, 0.0]);
+        assert_vertex(meshes[1].vertices[1], [6.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn unit_is_converted_to_millimeters() {
+        let bytes = threemf(&[(
+            "3D/3dmodel.model",
+            model("centimeter", &format!("<object id=\"1\">{}</object>", TRIANGLE), "<item objectid=\"1\" transform=\"1 0 0 0 1 0 0 0 1 1 0 0\"/>"),
+        )]);
+
+        let meshes = read_3mf_from(bytes).unwrap();
+
+        assert_vertex(meshes[0].vertices[0], [10.0, 0.0, 0.0]);
+        assert_vertex(meshes[0].vertices[1], [20.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn components_in_other_model_files_are_resolved() {
+        let bytes = threemf(&[
+            ("3D/Objects/part.model", model("millimeter", &format!("<object id=\"1\" name=\"external\">{}</object>", TRIANGLE), "")),
+            (
+                "3D/3dmodel.model",
+                model("millimeter", "<object id=\"1\"><components><component p:path=\"/3D/Objects/part.model\" objectid=\"1\" transform=\"1 0 0 0 1 0 0 0 1 0 3 0\"/></components></object>", "<item objectid=\"1\"/>"),
+            ),
+        ]);
+
+        let meshes = read_3mf_from(bytes).unwrap();
+
+        assert_eq!(meshes.len(), 1);
+        assert_eq!(meshes[0].name, "external");
+        assert_vertex(meshes[0].vertices[2], [0.0, 4.0, 0.0]);
+    }
+
+    #[test]
+    fn component_loops_terminate() {
+        let resources = format!(
+            "<object id=\"1\">{}<components><component objectid=\"2\"/></components></object><object id=\"2\"><components><component objectid=\"1\"/></components></object>",
+            TRIANGLE
+        );
+
+        let bytes = threemf(&[("3D/3dmodel.model", model("millimeter", &resources, "<item objectid=\"1\"/>"))]);
+        let meshes = read_3mf_from(bytes).unwrap();
+
+        assert_eq!(meshes.len(), MAX_COMPONENT_DEPTH / 2 + 1);
+    }
+
+    #[test]
+    fn written_3mf_reads_back() {
+        let mesh = Mesh {
+            name: String::from("cube & co"),
+            vertices: vec![[0.0, 0.0, 0.0], [1.5, 0.0, 0.0], [0.0, 2.5, 0.0]],
+            triangles: vec![[0, 1, 2]],
+            ..Default::default()
+        };
+
+        let path = std::env::temp_dir().join(format!("mesh_service_test_{}.3mf", std::process::id()));
+        write_3mf(&[mesh], &[], &path).unwrap();
+        let meshes = read_3mf(&path);
+        let _ = fs::remove_file(&path);
+        let meshes = meshes.unwrap();
+
+        assert_eq!(meshes.len(), 1);
+        assert_eq!(meshes[0].name, "cube & co");
+        assert_eq!(meshes[0].triangles, vec![[0, 1, 2]]);
+        assert_vertex(meshes[0].vertices[2], [0.0, 2.5, 0.0]);
+    }
+}
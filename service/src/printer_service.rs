@@ -0,0 +1,611 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use db::model::{Model, PrintJobState, Printer, PrinterType, User};
+use db::{model_db, printer_db, user_db};
+use reqwest::multipart::{Form, Part};
+use reqwest::{RequestBuilder, Response};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use urlencoding::encode;
+
+use crate::app_state::AppState;
+use crate::export_service::{get_path_from_model, get_temp_dir};
+use crate::service_error::ServiceError;
+use crate::util::cleanse_evil_from_name;
+
+const PRINTER_POLL_INTERVAL_SECS: u64 = 30;
+
+#[derive(Debug, Clone)]
+pub struct PrinterStatus {
+    // Uploaded means the printer is idle
+    pub state: PrintJobState,
+    pub file_name: Option<String>,
+    pub duration_seconds: Option<i64>,
+}
+
+// Printers are reached over the local network, so only plain http(s) urls make sense
+pub fn normalize_base_url(base_url: &str) -> Result<String, ServiceError> {
+    let base_url = base_url.trim().trim_end_matches('/');
+
+    if !base_url.starts_with("http://") && !base_url.starts_with("https://") {
+        return Err(ServiceError::InternalError(String::from(
+            "Printer url has to start with http:// or https://",
+        )));
+    }
+
+    Ok(base_url.to_string())
+}
+
+fn with_api_key(request: RequestBuilder, printer: &Printer) -> RequestBuilder {
+    // Moonraker can run without authentication, in which case there is no key to send
+    if printer.api_key.is_empty() {
+        return request;
+    }
+
+    request.header("X-Api-Key", &printer.api_key)
+}
+
+fn ensure_success(response: Response, printer: &Printer) -> Result<Response, ServiceError> {
+    if !response.status().is_success() {
+        return Err(ServiceError::InternalError(format!(
+            "Printer {} responded with status code {}.",
+            printer.name,
+            response.status()
+        )));
+    }
+
+    Ok(response)
+}
+
+pub async fn test_connection(printer: &Printer) -> Result<(), ServiceError> {
+    let path = match printer.printer_type {
+        PrinterType::OctoPrint => "/api/version",
+        PrinterType::Moonraker => "/server/info",
+        PrinterType::PrusaLink => "/api/version",
+    };
+
+    let client = reqwest::Client::new();
+    let request = with_api_key(client.get(format!("{}{}", printer.base_url, path)), printer);
+
+    ensure_success(request.send().await?, printer)?;
+
+    Ok(())
+}
+
+// Uploads the G-code of the model and optionally starts printing it. Returns the id of the recorded print job
+pub async fn send_model_to_printer(
+    app_state: &AppState,
+    printer: &Printer,
+    model: &Model,
+    start_print: bool,
+) -> Result<i64, ServiceError> {
+    let file_type = model.blob.to_file_type();
+
+    if !file_type.is_gcode() {
+        return Err(ServiceError::InternalError(String::from(
+            "Only G-code can be sent to a printer",
+        )));
+    }
+
+    if file_type.is_bgcode() && printer.printer_type != PrinterType::PrusaLink {
+        return Err(ServiceError::InternalError(String::from(
+            "Binary G-code can only be sent to PrusaLink printers",
+        )));
+    }
+
+    let temp_dir = get_temp_dir("print");
+    let path = get_path_from_model(&temp_dir, model, app_state, true).await?;
+    let contents = tokio::fs::read(&path).await?;
+    let _ = tokio::fs::remove_dir_all(&temp_dir).await;
+
+    let file_name = format!("{}.{}", cleanse_evil_from_name(&model.name), file_type.from_zip().to_extension());
+
+    upload_gcode(printer, &file_name, contents, start_print).await?;
+
+    let state = if start_print {
+        PrintJobState::Printing
+    } else {
+        PrintJobState::Uploaded
+    };
+
+    let job_id = printer_db::add_print_job(&app_state.db, printer.id, Some(model.id), &file_name, state).await?;
+
+    Ok(job_id)
+}
+
+async fn upload_gcode(printer: &Printer, file_name: &str, contents: Vec<u8>, start_print: bool) -> Result<(), ServiceError> {
+    let client = reqwest::Client::new();
+
+    let request = match printer.printer_type {
+        PrinterType::OctoPrint => {
+            let form = Form::new()
+                .part("file", Part::bytes(contents).file_name(file_name.to_string()))
+                .text("select", start_print.to_string())
+                .text("print", start_print.to_string());
+
+            client
+                .post(format!("{}/api/files/local", printer.base_url))
+                .multipart(form)
+        }
+        PrinterType::Moonraker => {
+            let form = Form::new()
+                .part("file", Part::bytes(contents).file_name(file_name.to_string()))
+                .text("root", "gcodes")
+                .text("print", start_print.to_string());
+
+            client
+                .post(format!("{}/server/files/upload", printer.base_url))
+                .multipart(form)
+        }
+        PrinterType::PrusaLink => {
+            // PrusaLink takes the raw file, with the options as structured header booleans
+            client
+                .put(format!("{}/api/v1/files/usb/{}", printer.base_url, encode(file_name)))
+                .header("Content-Type", "text/x.gcode")
+                .header("Overwrite", "?1")
+                .header("Print-After-Upload", if start_print { "?1" } else { "?0" })
+                .body(contents)
+        }
+    };
+
+    ensure_success(with_api_key(request, printer).send().await?, printer)?;
+
+    Ok(())
+}
+
+async fn get_json(printer: &Printer, path: &str) -> Result<Option<Value>, ServiceError> {
+    let client = reqwest::Client::new();
+    let request = with_api_key(client.get(format!("{}{}", printer.base_url, path)), printer);
+    let response = ensure_success(request.send().await?, printer)?;
+
+    // PrusaLink answers with 204 when there is no job
+    if response.status() == reqwest::StatusCode::NO_CONTENT {
+        return Ok(None);
+    }
+
+    let body = response.text().await?;
+    Ok(Some(serde_json::from_str(&body)?))
+}
+
+pub async fn get_printer_status(printer: &Printer) -> Result<PrinterStatus, ServiceError> {
+    match printer.printer_type {
+        PrinterType::OctoPrint => get_octoprint_status(printer).await,
+        PrinterType::Moonraker => get_moonraker_status(printer).await,
+        PrinterType::PrusaLink => get_prusalink_status(printer).await,
+    }
+}
+
+async fn get_octoprint_status(printer: &Printer) -> Result<PrinterStatus, ServiceError> {
+    let job = get_json(printer, "/api/job").await?.unwrap_or_default();
+    let state_text = job["state"].as_str().unwrap_or_default();
+    let completion = job["progress"]["completion"].as_f64();
+
+    // OctoPrint has no finished state, a job that went back to operational either completed or was cancelled
+    let state = match state_text {
+        "Printing" | "Pausing" | "Paused" | "Resuming" | "Cancelling" | "Starting" | "Finishing" => PrintJobState::Printing,
+        "Operational" if completion.is_some_and(|c| c >= 100.0) => PrintJobState::Finished,
+        "Operational" if completion.is_some() => PrintJobState::Cancelled,
+        s if s.starts_with("Error") || s.starts_with("Offline after error") => PrintJobState::Failed,
+        _ => PrintJobState::Uploaded,
+    };
+
+    Ok(PrinterStatus {
+        state,
+        file_name: job["job"]["file"]["name"].as_str().map(|s| s.to_string()),
+        duration_seconds: job["progress"]["printTime"].as_f64().map(|t| t as i64),
+    })
+}
+
+async fn get_moonraker_status(printer: &Printer) -> Result<PrinterStatus, ServiceError> {
+    let response = get_json(printer, "/printer/objects/query?print_stats").await?.unwrap_or_default();
+    let print_stats = &response["result"]["status"]["print_stats"];
+
+    let state = match print_stats["state"].as_str().unwrap_or_default() {
+        "printing" | "paused" => PrintJobState::Printing,
+        "complete" => PrintJobState::Finished,
+        "cancelled" => PrintJobState::Cancelled,
+        "error" => PrintJobState::Failed,
+        _ => PrintJobState::Uploaded,
+    };
+
+    Ok(PrinterStatus {
+        state,
+        file_name: print_stats["filename"].as_str().filter(|s| !s.is_empty()).map(|s| s.to_string()),
+        duration_seconds: print_stats["print_duration"].as_f64().map(|t| t as i64),
+    })
+}
+
+async fn get_prusalink_status(printer: &Printer) -> Result<PrinterStatus, ServiceError> {
+    let status = get_json(printer, "/api/v1/status").await?.unwrap_or_default();
+
+    let state = match status["printer"]["state"].as_str().unwrap_or_default() {
+        "PRINTING" | "PAUSED" | "ATTENTION" => PrintJobState::Printing,
+        "FINISHED" => PrintJobState::Finished,
+        "STOPPED" => PrintJobState::Cancelled,
+        "ERROR" => PrintJobState::Failed,
+        _ => PrintJobState::Uploaded,
+    };
+
+    if state == PrintJobState::Uploaded {
+        return Ok(PrinterStatus {
+            state,
+            file_name: None,
+            duration_seconds: None,
+        });
+    }
+
+    let job = get_json(printer, "/api/v1/job").await?.unwrap_or_default();
+
+    // Files on USB storage have a short 8.3 name, the display name is the one it was uploaded with
+    let file_name = job["file"]["display_name"]
+        .as_str()
+        .or(job["file"]["name"].as_str())
+        .map(|s| s.to_string());
+
+    Ok(PrinterStatus {
+        state,
+        file_name,
+        duration_seconds: job["time_printing"].as_i64(),
+    })
+}
+
+async fn download_printer_file(printer: &Printer, file_name: &str) -> Result<Vec<u8>, ServiceError> {
+    let path = match printer.printer_type {
+        PrinterType::OctoPrint => format!("/downloads/files/local/{}", encode(file_name)),
+        PrinterType::Moonraker => format!("/server/files/gcodes/{}", encode(file_name)),
+        PrinterType::PrusaLink => {
+            // The file info links to where the contents can be downloaded
+            let info = get_json(printer, &format!("/api/v1/files/usb/{}", encode(file_name))).await?.unwrap_or_default();
+
+            match info["refs"]["download"].as_str() {
+                Some(download) => download.to_string(),
+                None => {
+                    return Err(ServiceError::InternalError(String::from(
+                        "Printer did not provide a download for the file",
+                    )));
+                }
+            }
+        }
+    };
+
+    let client = reqwest::Client::new();
+    let request = with_api_key(client.get(format!("{}{}", printer.base_url, path)), printer);
+    let response = ensure_success(request.send().await?, printer)?;
+
+    Ok(response.bytes().await?.to_vec())
+}
+
+// Matches a file on the printer that wasn't sent by us to a model, first by name and otherwise by its contents
+async fn find_model_for_file(app_state: &AppState, printer: &Printer, user: &User, file_name: &str) -> Result<Option<i64>, ServiceError> {
+    let name = file_name
+        .rsplit_once('.')
+        .map(|(name, _)| name)
+        .unwrap_or(file_name);
+
+    if let Some(id) = model_db::get_gcode_model_id_via_name(&app_state.db, user, name).await? {
+        return Ok(Some(id));
+    }
+
+    let contents = download_printer_file(printer, file_name).await?;
+    let mut hasher = Sha256::new();
+    hasher.update(&contents);
+    let hash = String::from(&format!("{:x}", hasher.finalize())[0..32]);
+
+    Ok(model_db::get_model_id_via_sha256(&app_state.db, user, &hash).await?)
+}
+
+fn seconds_since(timestamp: &str) -> Option<i64> {
+    let started = DateTime::parse_from_rfc3339(timestamp).ok()?;
+
+    Some((Utc::now() - started.with_timezone(&Utc)).num_seconds())
+}
+
+async fn poll_printer(app_state: &AppState, printer: &Printer) -> Result<(), ServiceError> {
+    let status = get_printer_status(printer).await?;
+
+    let file_name = match &status.file_name {
+        Some(file_name) => file_name,
+        None => return Ok(()),
+    };
+
+    let job = printer_db::get_open_print_job(&app_state.db, printer.id, file_name).await?;
+
+    match job {
+        // Started from the printer itself, or uploaded by us without starting it
+        None if status.state == PrintJobState::Printing => {
+            printer_db::add_print_job(&app_state.db, printer.id, None, file_name, PrintJobState::Printing).await?;
+        }
+        Some(job) if status.state == PrintJobState::Printing && job.state == PrintJobState::Uploaded => {
+            printer_db::set_print_job_printing(&app_state.db, job.id).await?;
+        }
+        // Only prints that were seen running are recorded, the printer keeps reporting the last job while it is idle
+        Some(job) if status.state.is_done() && job.state == PrintJobState::Printing => {
+            let user = user_db::get_user_by_id(&app_state.db, printer.user_id).await?.unwrap_or_default();
+
+            let model_id = match job.model_id {
+                Some(id) => Some(id),
+                None => find_model_for_file(app_state, printer, &user, file_name).await.unwrap_or(None),
+            };
+
+            let duration = status
+                .duration_seconds
+                .or_else(|| job.started_at.as_deref().and_then(seconds_since));
+
+            printer_db::finish_print_job(&app_state.db, job.id, model_id, status.state, duration).await?;
+
+            if let Some(model_id) = model_id {
+                model_db::record_print(&app_state.db, &user, model_id, status.state, duration).await?;
+            }
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+pub async fn poll_printers(app_state: &AppState) -> Result<(), ServiceError> {
+    for printer in printer_db::get_all_printers(&app_state.db).await? {
+        // An unreachable printer is usually just turned off
+        if let Err(e) = poll_printer(app_state, &printer).await {
+            println!("Failed to poll printer {}: {}", printer.name, e);
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn watch_printers(app_state: AppState) {
+    loop {
+        if let Err(e) = poll_printers(&app_state).await {
+            println!("Failed to poll printers: {}", e);
+        }
+
+        tokio::time::sleep(Duration::from_secs(PRINTER_POLL_INTERVAL_SECS)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    #[derive(Debug, Clone)]
+    struct RecordedRequest {
+        method: String,
+        path: String,
+        headers: Vec<(String, String)>,
+        body: Vec<u8>,
+    }
+
+    impl RecordedRequest {
+        fn header(&self, name: &str) -> Option<&str> {
+            self.headers
+                .iter()
+                .find(|(key, _)| key.eq_ignore_ascii_case(name))
+                .map(|(_, value)| value.as_str())
+        }
+    }
+
+    type Responder = fn(&str, &str) -> (u16, &'static str);
+
+    // Stands in for a printer: answers every request through the responder and records what it received
+    async fn start_printer(responder: Responder) -> (String, Arc<Mutex<Vec<RecordedRequest>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let recorded = requests.clone();
+
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut data = Vec::new();
+                let mut buffer = [0u8; 4096];
+
+                let header_end = loop {
+                    let read = stream.read(&mut buffer).await.unwrap();
+                    data.extend_from_slice(&buffer[..read]);
+
+                    if let Some(pos) = data.windows(4).position(|w| w == b"\r\n\r\n") {
+                        break pos + 4;
+                    }
+                };
+
+                let head = String::from_utf8_lossy(&data[..header_end]).to_string();
+                let mut lines = head.split("\r\n");
+                let mut request_line = lines.next().unwrap().split(' ');
+                let method = request_line.next().unwrap().to_string();
+                let path = request_line.next().unwrap().to_string();
+
+                let headers: Vec<(String, String)> = lines
+                    .filter_map(|line| line.split_once(": "))
+                    .map(|(key, value)| (key.to_string(), value.to_string()))
+                    .collect();
+
+                let content_length = headers
+                    .iter()
+                    .find(|(key, _)| key.eq_ignore_ascii_case("content-length"))
+                    .map(|(_, value)| value.parse::<usize>().unwrap())
+                    .unwrap_or(0);
+
+                while data.len() < header_end + content_length {
+                    let read = stream.read(&mut buffer).await.unwrap();
+                    data.extend_from_slice(&buffer[..read]);
+                }
+
+                let (status, body) = responder(&method, &path);
+
+                recorded.lock().unwrap().push(RecordedRequest {
+                    method,
+                    path,
+                    headers,
+                    body: data[header_end..header_end + content_length].to_vec(),
+                });
+
+                let response = format!(
+                    "HTTP/1.1 {} Stand-in\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+
+                stream.write_all(response.as_bytes()).await.unwrap();
+                let _ = stream.shutdown().await;
+            }
+        });
+
+        (base_url, requests)
+    }
+
+    fn printer(printer_type: PrinterType, base_url: &str, api_key: &str) -> Printer {
+        Printer {
+            id: 1,
+            user_id: 1,
+            name: String::from("Stand-in"),
+            printer_type,
+            base_url: base_url.to_string(),
+            api_key: api_key.to_string(),
+            created_at: String::new(),
+        }
+    }
+
+    fn run<F: Future>(future: F) -> F::Output {
+        tokio::runtime::Runtime::new().unwrap().block_on(future)
+    }
+
+    #[test]
+    fn normalize_base_url_requires_http() {
+        assert_eq!(normalize_base_url(" http://printer.local:5000/ ").unwrap(), "http://printer.local:5000");
+        assert!(normalize_base_url("file:///etc/passwd").is_err());
+        assert!(normalize_base_url("printer.local").is_err());
+    }
+
+    #[test]
+    fn test_connection_sends_api_key_to_version_endpoint() {
+        run(async {
+            let (base_url, requests) = start_printer(|_, _| (200, "{}")).await;
+
+            test_connection(&printer(PrinterType::OctoPrint, &base_url, "secret")).await.unwrap();
+            test_connection(&printer(PrinterType::Moonraker, &base_url, "")).await.unwrap();
+
+            let requests = requests.lock().unwrap();
+            assert_eq!(requests[0].path, "/api/version");
+            assert_eq!(requests[0].header("X-Api-Key"), Some("secret"));
+            assert_eq!(requests[1].path, "/server/info");
+            assert_eq!(requests[1].header("X-Api-Key"), None);
+        });
+    }
+
+    #[test]
+    fn test_connection_fails_on_error_status() {
+        run(async {
+            let (base_url, _) = start_printer(|_, _| (401, "{}")).await;
+
+            assert!(test_connection(&printer(PrinterType::PrusaLink, &base_url, "wrong")).await.is_err());
+        });
+    }
+
+    #[test]
+    fn octoprint_operational_after_full_completion_is_finished() {
+        run(async {
+            let (base_url, _) = start_printer(|_, path| match path {
+                "/api/job" => (200, r#"{"state":"Operational","job":{"file":{"name":"benchy.gcode"}},"progress":{"completion":100.0,"printTime":3600.5}}"#),
+                _ => (404, "{}"),
+            })
+            .await;
+
+            let status = get_printer_status(&printer(PrinterType::OctoPrint, &base_url, "key")).await.unwrap();
+
+            assert_eq!(status.state, PrintJobState::Finished);
+            assert_eq!(status.file_name.as_deref(), Some("benchy.gcode"));
+            assert_eq!(status.duration_seconds, Some(3600));
+        });
+    }
+
+    #[test]
+    fn moonraker_printing_state_is_read_from_print_stats() {
+        run(async {
+            let (base_url, _) = start_printer(|_, path| match path {
+                "/printer/objects/query?print_stats" => (200, r#"{"result":{"status":{"print_stats":{"state":"printing","filename":"cube.gcode","print_duration":12.0}}}}"#),
+                _ => (404, "{}"),
+            })
+            .await;
+
+            let status = get_printer_status(&printer(PrinterType::Moonraker, &base_url, "")).await.unwrap();
+
+            assert_eq!(status.state, PrintJobState::Printing);
+            assert_eq!(status.file_name.as_deref(), Some("cube.gcode"));
+            assert_eq!(status.duration_seconds, Some(12));
+        });
+    }
+
+    #[test]
+    fn prusalink_idle_printer_skips_job_lookup() {
+        run(async {
+            let (base_url, requests) = start_printer(|_, path| match path {
+                "/api/v1/status" => (200, r#"{"printer":{"state":"IDLE"}}"#),
+                _ => (404, "{}"),
+            })
+            .await;
+
+            let status = get_printer_status(&printer(PrinterType::PrusaLink, &base_url, "key")).await.unwrap();
+
+            assert_eq!(status.state, PrintJobState::Uploaded);
+            assert_eq!(status.file_name, None);
+            assert_eq!(requests.lock().unwrap().len(), 1);
+        });
+    }
+
+    #[test]
+    fn prusalink_job_uses_display_name() {
+        run(async {
+            let (base_url, _) = start_printer(|_, path| match path {
+                "/api/v1/status" => (200, r#"{"printer":{"state":"PRINTING"}}"#),
+                "/api/v1/job" => (200, r#"{"file":{"name":"BENCHY~1.BGC","display_name":"benchy.bgcode"},"time_printing":90}"#),
+                _ => (404, "{}"),
+            })
+            .await;
+
+            let status = get_printer_status(&printer(PrinterType::PrusaLink, &base_url, "key")).await.unwrap();
+
+            assert_eq!(status.state, PrintJobState::Printing);
+            assert_eq!(status.file_name.as_deref(), Some("benchy.bgcode"));
+            assert_eq!(status.duration_seconds, Some(90));
+        });
+    }
+
+    #[test]
+    fn prusalink_upload_puts_raw_file() {
+        run(async {
+            let (base_url, requests) = start_printer(|_, _| (201, "{}")).await;
+
+            upload_gcode(&printer(PrinterType::PrusaLink, &base_url, "key"), "my part.gcode", b"G28\n".to_vec(), true).await.unwrap();
+
+            let requests = requests.lock().unwrap();
+            assert_eq!(requests[0].method, "PUT");
+            assert_eq!(requests[0].path, "/api/v1/files/usb/my%20part.gcode");
+            assert_eq!(requests[0].header("Print-After-Upload"), Some("?1"));
+            assert_eq!(requests[0].header("X-Api-Key"), Some("key"));
+            assert_eq!(requests[0].body, b"G28\n");
+        });
+    }
+
+    #[test]
+    fn octoprint_upload_posts_multipart_form() {
+        run(async {
+            let (base_url, requests) = start_printer(|_, _| (201, "{}")).await;
+
+            upload_gcode(&printer(PrinterType::OctoPrint, &base_url, "key"), "part.gcode", b"G28\n".to_vec(), false).await.unwrap();
+
+            let requests = requests.lock().unwrap();
+            let body = String::from_utf8_lossy(&requests[0].body);
+            assert_eq!(requests[0].method, "POST");
+            assert_eq!(requests[0].path, "/api/files/local");
+            assert!(requests[0].header("Content-Type").unwrap().starts_with("multipart/form-data"));
+            assert!(body.contains("filename=\"part.gcode\""));
+            assert!(body.contains("name=\"print\"\r\n\r\nfalse"));
+        });
+    }
+}
@@ -0,0 +1,659 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use async_zip::base::read::mem::ZipFileReader;
+use db::model::{FileType, Model, ResinMetadata};
+use futures::executor::block_on;
+use db::resin_metadata_db;
+use image::{DynamicImage, RgbImage};
+
+use crate::app_state::AppState;
+use crate::export_service::get_model_path_for_blob;
+use crate::service_error::ServiceError;
+
+// Chitubox .ctb (and the older .cbddlp). Chitubox 1.9 and later encrypt the header, those files are stored without metadata
+const CTB_MAGIC: u32 = 0x12FD0086;
+const CBDDLP_MAGIC: u32 = 0x12FD0019;
+const CTB_ENCRYPTED_MAGIC: u32 = 0x12FD0107;
+
+const GOO_MAGIC: &[u8; 8] = &[0x07, 0x00, 0x00, 0x00, 0x44, 0x4C, 0x50, 0x00];
+const GOO_SMALL_PREVIEW_SIZE: usize = 116;
+const GOO_BIG_PREVIEW_SIZE: usize = 290;
+
+const ANYCUBIC_MAGIC: &[u8; 8] = b"ANYCUBIC";
+
+// Reads the little (Chitubox, Anycubic) or big (Elegoo) endian headers field by field
+struct HeaderReader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+    big_endian: bool,
+}
+
+impl<'a> HeaderReader<'a> {
+    fn new(bytes: &'a [u8], offset: usize, big_endian: bool) -> Self {
+        HeaderReader { bytes, offset, big_endian }
+    }
+
+    fn take(&mut self, length: usize) -> Result<&'a [u8], ServiceError> {
+        let slice = self
+            .bytes
+            .get(self.offset..self.offset + length)
+            .ok_or_else(|| ServiceError::InternalError(String::from("Resin file header is truncated")))?;
+
+        self.offset += length;
+        Ok(slice)
+    }
+
+    fn skip(&mut self, length: usize) -> Result<(), ServiceError> {
+        self.take(length).map(|_| ())
+    }
+
+    fn u32(&mut self) -> Result<u32, ServiceError> {
+        let b = self.take(4)?;
+        let b = [b[0], b[1], b[2], b[3]];
+        Ok(if self.big_endian { u32::from_be_bytes(b) } else { u32::from_le_bytes(b) })
+    }
+
+    fn f32(&mut self) -> Result<f32, ServiceError> {
+        Ok(f32::from_bits(self.u32()?))
+    }
+
+    fn string(&mut self, length: usize) -> Result<String, ServiceError> {
+        let b = self.take(length)?;
+        Ok(String::from_utf8_lossy(b).trim_matches(char::from(0)).trim().to_string())
+    }
+}
+
+// Returns the stored metadata of a resin model, reading it from the file if it was never extracted
+pub async fn get_metadata(model: &Model, app_state: &AppState) -> Result<ResinMetadata, ServiceError> {
+    let file_type = model.blob.to_file_type();
+
+    if !file_type.is_resin() {
+        return Err(ServiceError::InternalError(String::from(
+            "Model is not a sliced resin file",
+        )));
+    }
+
+    if let Some(metadata) = resin_metadata_db::get_resin_metadata(&app_state.db, model.blob.id).await? {
+        return Ok(metadata);
+    }
+
+    let bytes = tokio::fs::read(get_model_path_for_blob(&model.blob, app_state)).await?;
+
+    store_metadata_from_bytes(model.blob.id, &file_type, &bytes, app_state).await
+}
+
+pub async fn store_metadata_from_bytes(blob_id: i64, file_type: &FileType, bytes: &[u8], app_state: &AppState) -> Result<ResinMetadata, ServiceError> {
+    let metadata = parse_metadata(file_type, bytes)?;
+
+    resin_metadata_db::set_resin_metadata(&app_state.db, blob_id, &metadata).await?;
+
+    Ok(metadata)
+}
+
+pub fn parse_metadata(file_type: &FileType, bytes: &[u8]) -> Result<ResinMetadata, ServiceError> {
+    match file_type {
+        FileType::Ctb => parse_ctb_metadata(bytes),
+        FileType::Sl1 | FileType::Sl1s => parse_sl1_metadata(bytes),
+        FileType::Goo => parse_goo_metadata(bytes),
+        FileType::Pwmx => parse_pwmx_metadata(bytes),
+        _ => Err(ServiceError::InternalError(String::from(
+            "Model is not a sliced resin file",
+        ))),
+    }
+}
+
+// Returns the largest embedded preview image
+pub fn read_preview(path: &PathBuf) -> Result<Option<DynamicImage>, ServiceError> {
+    let bytes = std::fs::read(path)?;
+
+    match FileType::from_pathbuf(path) {
+        FileType::Ctb => read_ctb_preview(&bytes),
+        FileType::Sl1 | FileType::Sl1s => read_sl1_preview(&bytes),
+        FileType::Goo => read_goo_preview(&bytes),
+        FileType::Pwmx => read_pwmx_preview(&bytes),
+        _ => Ok(None),
+    }
+}
+
+fn read_ctb_magic(bytes: &[u8]) -> Result<Option<u32>, ServiceError> {
+    let magic = HeaderReader::new(bytes, 0, false).u32()?;
+
+    match magic {
+        CTB_MAGIC | CBDDLP_MAGIC => Ok(Some(magic)),
+        CTB_ENCRYPTED_MAGIC => Ok(None),
+        _ => Err(ServiceError::InternalError(String::from(
+            "Not a Chitubox file",
+        ))),
+    }
+}
+
+fn parse_ctb_metadata(bytes: &[u8]) -> Result<ResinMetadata, ServiceError> {
+    if read_ctb_magic(bytes)?.is_none() {
+        return Ok(ResinMetadata::default());
+    }
+
+    let mut header = HeaderReader::new(bytes, 4, false);
+    header.skip(4)?; // Version
+    header.skip(12)?; // Bed size x, y, z
+    header.skip(8)?;
+    header.skip(4)?; // Total height
+    let layer_height = header.f32()?;
+    let exposure_time = header.f32()?;
+    let bottom_exposure_time = header.f32()?;
+    header.skip(4)?; // Light off time
+    let bottom_layer_count = header.u32()?;
+    header.skip(8)?; // Resolution x, y
+    header.skip(8)?; // Large preview and layer definition offsets
+    let layer_count = header.u32()?;
+    header.skip(4)?; // Small preview offset
+    let print_time = header.u32()?;
+    header.skip(4 + 8 + 4 + 4 + 4)?; // Projector type, print parameters, anti aliasing, light pwm, encryption key
+    let slicer_offset = header.u32()? as usize;
+    let slicer_size = header.u32()? as usize;
+
+    // The slicer info (version 3 and up) points at the machine name after 7 lift and retract settings
+    let printer_model = if slicer_offset > 0 && slicer_size >= 36 {
+        let mut slicer = HeaderReader::new(bytes, slicer_offset + 28, false);
+        let name_offset = slicer.u32()? as usize;
+        let name_size = slicer.u32()? as usize;
+
+        HeaderReader::new(bytes, name_offset, false).string(name_size).ok()
+    } else {
+        None
+    };
+
+    Ok(ResinMetadata {
+        printer_model: printer_model.filter(|m| !m.is_empty()),
+        layer_count: Some(layer_count as i64),
+        layer_height: Some(layer_height as f64),
+        exposure_time: Some(exposure_time as f64),
+        bottom_exposure_time: Some(bottom_exposure_time as f64),
+        bottom_layer_count: Some(bottom_layer_count as i64),
+        print_time_seconds: Some(print_time as i64),
+    })
+}
+
+fn read_ctb_preview(bytes: &[u8]) -> Result<Option<DynamicImage>, ServiceError> {
+    if read_ctb_magic(bytes)?.is_none() {
+        return Ok(None);
+    }
+
+    let mut header = HeaderReader::new(bytes, 60, false);
+    let large_preview_offset = header.u32()? as usize;
+    header.skip(8)?;
+    let small_preview_offset = header.u32()? as usize;
+
+    for offset in [large_preview_offset, small_preview_offset] {
+        if offset == 0 {
+            continue;
+        }
+
+        let mut preview = HeaderReader::new(bytes, offset, false);
+        let width = preview.u32()? as usize;
+        let height = preview.u32()? as usize;
+        let image_offset = preview.u32()? as usize;
+        let image_length = preview.u32()? as usize;
+
+        let data = HeaderReader::new(bytes, image_offset, false).take(image_length)?;
+
+        if let Some(image) = decode_ctb_rle(data, width, height) {
+            return Ok(Some(image));
+        }
+    }
+
+    Ok(None)
+}
+
+// 15 bit colours where bit 5 marks a run, the run length follows in the low 12 bits of the next 16 bits
+fn decode_ctb_rle(data: &[u8], width: usize, height: usize) -> Option<DynamicImage> {
+    let pixel_count = width * height;
+    let mut pixels = Vec::with_capacity(pixel_count * 3);
+    let mut index = 0;
+
+    while index + 1 < data.len() && pixels.len() < pixel_count * 3 {
+        let dot = u16::from_le_bytes([data[index], data[index + 1]]);
+        index += 2;
+
+        let red = (((dot >> 11) & 0x1F) << 3) as u8;
+        let green = (((dot >> 6) & 0x1F) << 3) as u8;
+        let blue = ((dot & 0x1F) << 3) as u8;
+
+        let mut repeat = 1;
+
+        if dot & 0x0020 == 0x0020 && index + 1 < data.len() {
+            repeat += (data[index] as usize) | (((data[index + 1] & 0x0F) as usize) << 8);
+            index += 2;
+        }
+
+        for _ in 0..repeat {
+            pixels.extend_from_slice(&[red, green, blue]);
+        }
+    }
+
+    pixels.resize(pixel_count * 3, 0);
+
+    RgbImage::from_raw(width as u32, height as u32, pixels).map(DynamicImage::ImageRgb8)
+}
+
+fn decode_rgb565(data: &[u8], width: usize, height: usize, big_endian: bool) -> Option<DynamicImage> {
+    let pixels: Vec<u8> = data
+        .chunks_exact(2)
+        .take(width * height)
+        .flat_map(|b| {
+            let pixel = if big_endian { u16::from_be_bytes([b[0], b[1]]) } else { u16::from_le_bytes([b[0], b[1]]) };
+
+            [
+                (((pixel >> 11) & 0x1F) << 3) as u8,
+                (((pixel >> 5) & 0x3F) << 2) as u8,
+                ((pixel & 0x1F) << 3) as u8,
+            ]
+        })
+        .collect();
+
+    RgbImage::from_raw(width as u32, height as u32, pixels).map(DynamicImage::ImageRgb8)
+}
+
+fn goo_header(bytes: &[u8]) -> Result<HeaderReader<'_>, ServiceError> {
+    let mut header = HeaderReader::new(bytes, 0, true);
+    header.skip(4)?; // Version
+
+    if header.take(8)? != GOO_MAGIC {
+        return Err(ServiceError::InternalError(String::from(
+            "Not an Elegoo .goo file",
+        )));
+    }
+
+    Ok(header)
+}
+
+fn parse_goo_metadata(bytes: &[u8]) -> Result<ResinMetadata, ServiceError> {
+    let mut header = goo_header(bytes)?;
+    header.skip(32 + 24 + 24)?; // Software name, software version, creation time
+    let machine_name = header.string(32)?;
+    header.skip(32 + 32)?; // Machine type, profile name
+    header.skip(6)?; // Anti aliasing, grey and blur level
+    header.skip(GOO_SMALL_PREVIEW_SIZE * GOO_SMALL_PREVIEW_SIZE * 2 + 2)?;
+    header.skip(GOO_BIG_PREVIEW_SIZE * GOO_BIG_PREVIEW_SIZE * 2 + 2)?;
+    let layer_count = header.u32()?;
+    header.skip(4 + 2)?; // Resolution x, y and mirroring
+    header.skip(12)?; // Size x, y, z
+    let layer_height = header.f32()?;
+    let exposure_time = header.f32()?;
+    header.skip(1 + 4 * 7)?; // Exposure delay mode, light off and lift/retract wait times
+    let bottom_exposure_time = header.f32()?;
+    let bottom_layer_count = header.u32()?;
+    header.skip(4 * 16 + 2 + 2 + 1)?; // Lift and retract distances and speeds, light pwm, per layer settings
+    let print_time = header.u32()?;
+
+    Ok(ResinMetadata {
+        printer_model: Some(machine_name).filter(|m| !m.is_empty()),
+        layer_count: Some(layer_count as i64),
+        layer_height: Some(layer_height as f64),
+        exposure_time: Some(exposure_time as f64),
+        bottom_exposure_time: Some(bottom_exposure_time as f64),
+        bottom_layer_count: Some(bottom_layer_count as i64),
+        print_time_seconds: Some(print_time as i64),
+    })
+}
+
+fn read_goo_preview(bytes: &[u8]) -> Result<Option<DynamicImage>, ServiceError> {
+    let mut header = goo_header(bytes)?;
+    header.skip(32 + 24 + 24 + 32 + 32 + 32 + 6)?;
+    header.skip(GOO_SMALL_PREVIEW_SIZE * GOO_SMALL_PREVIEW_SIZE * 2 + 2)?;
+    let data = header.take(GOO_BIG_PREVIEW_SIZE * GOO_BIG_PREVIEW_SIZE * 2)?;
+
+    Ok(decode_rgb565(data, GOO_BIG_PREVIEW_SIZE, GOO_BIG_PREVIEW_SIZE, true))
+}
+
+// Anycubic files start with a table of section addresses, each section starts with a 12 byte name and its length
+fn pwmx_section<'a>(bytes: &'a [u8], address_offset: usize, name: &str) -> Result<HeaderReader<'a>, ServiceError> {
+    if !bytes.starts_with(ANYCUBIC_MAGIC) {
+        return Err(ServiceError::InternalError(String::from(
+            "Not an Anycubic file",
+        )));
+    }
+
+    let address = HeaderReader::new(bytes, address_offset, false).u32()? as usize;
+    let mut section = HeaderReader::new(bytes, address, false);
+
+    if section.string(12)? != name {
+        return Err(ServiceError::InternalError(format!(
+            "Anycubic file is missing its {} section",
+            name
+        )));
+    }
+
+    section.skip(4)?; // Length
+    Ok(section)
+}
+
+fn parse_pwmx_metadata(bytes: &[u8]) -> Result<ResinMetadata, ServiceError> {
+    let mut header = pwmx_section(bytes, 20, "HEADER")?;
+    header.skip(4)?; // Pixel size
+    let layer_height = header.f32()?;
+    let exposure_time = header.f32()?;
+    header.skip(4)?; // Wait time before cure
+    let bottom_exposure_time = header.f32()?;
+    let bottom_layer_count = header.f32()?;
+    header.skip(4 * 4)?; // Lift height, lift speed, retract speed, volume
+    header.skip(4 * 3)?; // Anti aliasing, resolution x, y
+    header.skip(4 * 4)?; // Weight, price, currency, per layer override
+    let print_time = header.u32()?;
+
+    let layer_count = pwmx_section(bytes, 36, "LAYERDEF")?.u32()?;
+
+    // Only newer files (version 516 and up) have a machine section, it starts with a 96 byte machine name
+    let printer_model = pwmx_section(bytes, 44, "MACHINE")
+        .and_then(|mut machine| machine.string(96))
+        .ok();
+
+    Ok(ResinMetadata {
+        printer_model: printer_model.filter(|m| !m.is_empty()),
+        layer_count: Some(layer_count as i64),
+        layer_height: Some(layer_height as f64),
+        exposure_time: Some(exposure_time as f64),
+        bottom_exposure_time: Some(bottom_exposure_time as f64),
+        bottom_layer_count: Some(bottom_layer_count as i64),
+        print_time_seconds: Some(print_time as i64),
+    })
+}
+
+fn read_pwmx_preview(bytes: &[u8]) -> Result<Option<DynamicImage>, ServiceError> {
+    let mut preview = pwmx_section(bytes, 28, "PREVIEW")?;
+    let width = preview.u32()? as usize;
+    preview.skip(4)?; // 'x'
+    let height = preview.u32()? as usize;
+    let data = preview.take(width * height * 2)?;
+
+    Ok(decode_rgb565(data, width, height, false))
+}
+
+// Prusa SL1 archives are zips with a config.ini next to the layer images and thumbnails.
+// Previews are read from sync code, so the archive is read in memory and driven to completion here
+fn parse_sl1_metadata(bytes: &[u8]) -> Result<ResinMetadata, ServiceError> {
+    let archive = open_sl1(bytes)?;
+
+    let index = archive
+        .file()
+        .entries()
+        .iter()
+        .position(|e| e.filename().as_str().map(|name| name == "config.ini").unwrap_or(false))
+        .ok_or_else(|| ServiceError::InternalError(String::from("SL1 archive is missing its config.ini")))?;
+
+    let config = String::from_utf8_lossy(&read_sl1_entry(&archive, index)?).to_string();
+
+    let values: HashMap<&str, &str> = config
+        .lines()
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, value)| (key.trim(), value.trim()))
+        .collect();
+
+    let int = |key: &str| values.get(key).and_then(|v| v.parse::<i64>().ok());
+    let float = |key: &str| values.get(key).and_then(|v| v.parse::<f64>().ok());
+
+    let layer_count = match (int("numFast"), int("numSlow")) {
+        (None, None) => None,
+        (fast, slow) => Some(fast.unwrap_or(0) + slow.unwrap_or(0)),
+    };
+
+    Ok(ResinMetadata {
+        printer_model: values.get("printerModel").map(|v| v.to_string()),
+        layer_count,
+        layer_height: float("layerHeight"),
+        exposure_time: float("expTime"),
+        bottom_exposure_time: float("expTimeFirst"),
+        bottom_layer_count: None,
+        print_time_seconds: float("printTime").map(|v| v as i64),
+    })
+}
+
+fn read_sl1_preview(bytes: &[u8]) -> Result<Option<DynamicImage>, ServiceError> {
+    let archive = open_sl1(bytes)?;
+
+    let largest = archive
+        .file()
+        .entries()
+        .iter()
+        .enumerate()
+        .filter_map(|(i, e)| e.filename().as_str().ok().map(|name| (i, name.to_string(), e.uncompressed_size())))
+        .filter(|(_, name, _)| name.starts_with("thumbnail/") && name.to_lowercase().ends_with(".png"))
+        .max_by_key(|(_, _, size)| *size);
+
+    let index = match largest {
+        Some((index, _, _)) => index,
+        None => return Ok(None),
+    };
+
+    let data = read_sl1_entry(&archive, index)?;
+
+    Ok(Some(image::load_from_memory_with_format(&data, image::ImageFormat::Png)?))
+}
+
+fn open_sl1(bytes: &[u8]) -> Result<ZipFileReader, ServiceError> {
+    block_on(ZipFileReader::new(bytes.to_vec()))
+        .map_err(|_| ServiceError::InternalError(String::from("Not an SL1 archive")))
+}
+
+fn read_sl1_entry(archive: &ZipFileReader, index: usize) -> Result<Vec<u8>, ServiceError> {
+    block_on(async {
+        let mut data = vec![];
+        archive.reader_with_entry(index).await?.read_to_end_checked(&mut data).await?;
+        Ok(data)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_zip::base::write::ZipFileWriter;
+    use async_zip::{Compression, ZipEntryBuilder};
+
+    #[derive(Default)]
+    struct HeaderWriter {
+        bytes: Vec<u8>,
+        big_endian: bool,
+    }
+
+    impl HeaderWriter {
+        fn u32(&mut self, value: u32) -> &mut Self {
+            let b = if self.big_endian { value.to_be_bytes() } else { value.to_le_bytes() };
+            self.bytes.extend_from_slice(&b);
+            self
+        }
+
+        fn f32(&mut self, value: f32) -> &mut Self {
+            self.u32(value.to_bits())
+        }
+
+        fn string(&mut self, value: &str, length: usize) -> &mut Self {
+            let mut b = value.as_bytes().to_vec();
+            b.resize(length, 0);
+            self.bytes.extend_from_slice(&b);
+            self
+        }
+
+        fn zeroes(&mut self, length: usize) -> &mut Self {
+            self.bytes.resize(self.bytes.len() + length, 0);
+            self
+        }
+
+        fn set_u32(&mut self, offset: usize, value: u32) {
+            let b = if self.big_endian { value.to_be_bytes() } else { value.to_le_bytes() };
+            self.bytes[offset..offset + 4].copy_from_slice(&b);
+        }
+    }
+
+    fn ctb(magic: u32, with_slicer_info: bool) -> Vec<u8> {
+        let mut w = HeaderWriter::default();
+        w.u32(magic).u32(4).zeroes(12).zeroes(8).f32(20.0);
+        w.f32(0.05).f32(2.5).f32(30.0).f32(0.5).u32(6);
+        w.zeroes(8).zeroes(8).u32(400).zeroes(4).u32(3600);
+        w.zeroes(24).u32(0).u32(0);
+
+        if with_slicer_info {
+            let slicer_offset = w.bytes.len();
+            w.set_u32(104, slicer_offset as u32);
+            w.set_u32(108, 36);
+            w.zeroes(28).u32(slicer_offset as u32 + 36).u32(12).string("Saturn 3", 12);
+        }
+
+        w.bytes
+    }
+
+    #[test]
+    fn ctb_header_is_parsed() {
+        let metadata = parse_metadata(&FileType::Ctb, &ctb(CTB_MAGIC, true)).unwrap();
+
+        assert_eq!(metadata.printer_model.as_deref(), Some("Saturn 3"));
+        assert_eq!(metadata.layer_count, Some(400));
+        assert_eq!(metadata.layer_height, Some(0.05f32 as f64));
+        assert_eq!(metadata.exposure_time, Some(2.5));
+        assert_eq!(metadata.bottom_exposure_time, Some(30.0));
+        assert_eq!(metadata.bottom_layer_count, Some(6));
+        assert_eq!(metadata.print_time_seconds, Some(3600));
+    }
+
+    #[test]
+    fn ctb_without_slicer_info_has_no_printer_model() {
+        let metadata = parse_metadata(&FileType::Ctb, &ctb(CBDDLP_MAGIC, false)).unwrap();
+
+        assert_eq!(metadata.printer_model, None);
+        assert_eq!(metadata.layer_count, Some(400));
+    }
+
+    #[test]
+    fn encrypted_ctb_is_stored_without_metadata() {
+        let metadata = parse_metadata(&FileType::Ctb, &ctb(CTB_ENCRYPTED_MAGIC, false)).unwrap();
+
+        assert_eq!(metadata.layer_count, None);
+        assert_eq!(metadata.printer_model, None);
+    }
+
+    #[test]
+    fn truncated_and_unknown_headers_fail() {
+        let bytes = ctb(CTB_MAGIC, false);
+
+        assert!(parse_metadata(&FileType::Ctb, &bytes[..50]).is_err());
+        assert!(parse_metadata(&FileType::Ctb, &[0u8; 200]).is_err());
+        assert!(parse_metadata(&FileType::Goo, &bytes).is_err());
+        assert!(parse_metadata(&FileType::Pwmx, &bytes).is_err());
+        assert!(parse_metadata(&FileType::Stl, &bytes).is_err());
+    }
+
+    #[test]
+    fn goo_header_is_parsed_big_endian() {
+        let mut w = HeaderWriter { big_endian: true, ..Default::default() };
+        w.u32(3);
+        w.bytes.extend_from_slice(GOO_MAGIC);
+        w.zeroes(32 + 24 + 24).string("Mars 4 Ultra", 32).zeroes(32 + 32 + 6);
+        w.zeroes(GOO_SMALL_PREVIEW_SIZE * GOO_SMALL_PREVIEW_SIZE * 2 + 2);
+        w.zeroes(GOO_BIG_PREVIEW_SIZE * GOO_BIG_PREVIEW_SIZE * 2 + 2);
+        w.u32(250).zeroes(4 + 2).zeroes(12).f32(0.03).f32(1.5);
+        w.zeroes(1 + 4 * 7).f32(25.0).u32(4);
+        w.zeroes(4 * 16 + 2 + 2 + 1).u32(1800);
+
+        let metadata = parse_metadata(&FileType::Goo, &w.bytes).unwrap();
+
+        assert_eq!(metadata.printer_model.as_deref(), Some("Mars 4 Ultra"));
+        assert_eq!(metadata.layer_count, Some(250));
+        assert_eq!(metadata.layer_height, Some(0.03f32 as f64));
+        assert_eq!(metadata.exposure_time, Some(1.5));
+        assert_eq!(metadata.bottom_exposure_time, Some(25.0));
+        assert_eq!(metadata.bottom_layer_count, Some(4));
+        assert_eq!(metadata.print_time_seconds, Some(1800));
+
+        let preview = read_goo_preview(&w.bytes).unwrap().unwrap();
+        assert_eq!(preview.width(), GOO_BIG_PREVIEW_SIZE as u32);
+    }
+
+    fn pwmx(machine_name: Option<&str>) -> Vec<u8> {
+        let mut w = HeaderWriter::default();
+        w.string("ANYCUBIC", 12).u32(if machine_name.is_some() { 516 } else { 1 }).u32(8);
+        w.zeroes(4 * 8);
+
+        let header = w.bytes.len();
+        w.set_u32(20, header as u32);
+        w.string("HEADER", 12).u32(80);
+        w.f32(50.0).f32(0.05).f32(2.0).f32(0.0).f32(35.0).f32(5.0);
+        w.zeroes(4 * 4).zeroes(4 * 3).zeroes(4 * 4).u32(5400);
+
+        let preview = w.bytes.len();
+        w.set_u32(28, preview as u32);
+        w.string("PREVIEW", 12).u32(28).u32(2).string("x", 4).u32(1);
+        w.bytes.extend_from_slice(&[0x00, 0xF8, 0xE0, 0x07]);
+
+        let layer_definition = w.bytes.len();
+        w.set_u32(36, layer_definition as u32);
+        w.string("LAYERDEF", 12).u32(4).u32(320);
+
+        if let Some(machine_name) = machine_name {
+            let machine = w.bytes.len();
+            w.set_u32(44, machine as u32);
+            w.string("MACHINE", 12).u32(96).string(machine_name, 96);
+        }
+
+        w.bytes
+    }
+
+    #[test]
+    fn pwmx_header_is_parsed() {
+        let metadata = parse_metadata(&FileType::Pwmx, &pwmx(Some("Photon Mono M5s"))).unwrap();
+
+        assert_eq!(metadata.printer_model.as_deref(), Some("Photon Mono M5s"));
+        assert_eq!(metadata.layer_count, Some(320));
+        assert_eq!(metadata.layer_height, Some(0.05f32 as f64));
+        assert_eq!(metadata.exposure_time, Some(2.0));
+        assert_eq!(metadata.bottom_exposure_time, Some(35.0));
+        assert_eq!(metadata.bottom_layer_count, Some(5));
+        assert_eq!(metadata.print_time_seconds, Some(5400));
+    }
+
+    #[test]
+    fn pwmx_without_machine_section_has_no_printer_model() {
+        let metadata = parse_metadata(&FileType::Pwmx, &pwmx(None)).unwrap();
+
+        assert_eq!(metadata.printer_model, None);
+        assert_eq!(metadata.layer_count, Some(320));
+    }
+
+    #[test]
+    fn pwmx_preview_is_decoded() {
+        let preview = read_pwmx_preview(&pwmx(None)).unwrap().unwrap().to_rgb8();
+
+        assert_eq!((preview.width(), preview.height()), (2, 1));
+        assert_eq!(preview.get_pixel(0, 0).0, [248, 0, 0]);
+        assert_eq!(preview.get_pixel(1, 0).0, [0, 252, 0]);
+    }
+
+    #[test]
+    fn ctb_rle_runs_are_expanded() {
+        // One white pixel followed by a run of 2 more, then a single black pixel
+        let data = [0xFF, 0xFF, 0x02, 0x00, 0x00, 0x00];
+        let image = decode_ctb_rle(&data, 2, 2).unwrap().to_rgb8();
+
+        assert_eq!(image.get_pixel(1, 1).0, [0, 0, 0]);
+        assert_eq!(image.get_pixel(0, 1).0, [248, 248, 248]);
+    }
+
+    #[test]
+    fn sl1_config_is_parsed() {
+        let config = "printerModel = SL1S\nlayerHeight = 0.05\nexpTime = 2.0\nexpTimeFirst = 35\nnumFast = 100\nnumSlow = 20\nprintTime = 1234.5\n";
+
+        let bytes = block_on(async {
+            let mut writer = ZipFileWriter::new(futures::io::Cursor::new(Vec::new()));
+            writer.write_entry_whole(ZipEntryBuilder::new("config.ini".into(), Compression::Deflate), config.as_bytes()).await.unwrap();
+            writer.close().await.unwrap().into_inner()
+        });
+
+        let metadata = parse_metadata(&FileType::Sl1s, &bytes).unwrap();
+
+        assert_eq!(metadata.printer_model.as_deref(), Some("SL1S"));
+        assert_eq!(metadata.layer_count, Some(120));
+        assert_eq!(metadata.layer_height, Some(0.05));
+        assert_eq!(metadata.exposure_time, Some(2.0));
+        assert_eq!(metadata.bottom_exposure_time, Some(35.0));
+        assert_eq!(metadata.print_time_seconds, Some(1234));
+        assert!(read_sl1_preview(&bytes).unwrap().is_none());
+    }
+}
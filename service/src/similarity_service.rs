@@ -0,0 +1,68 @@
+use std::collections::HashMap;
+
+use db::model::{Model, User};
+use db::{model_db, thumbnail_hash_db};
+use serde::Serialize;
+use tokio::task::spawn_blocking;
+
+use crate::app_state::AppState;
+use crate::export_service::get_image_path_for_blob;
+use crate::service_error::ServiceError;
+use crate::thumbnail_service;
+
+// Out of 64 bits. Thumbnails further apart than this rarely show the same kind of object
+const MAX_SIMILAR_DISTANCE: u32 = 16;
+
+#[derive(Serialize)]
+pub struct SimilarModel {
+    pub model: Model,
+    pub distance: u32,
+}
+
+async fn get_or_compute_hash(model: &Model, app_state: &AppState) -> Result<Option<u64>, ServiceError> {
+    if let Some(hash) = thumbnail_hash_db::get_thumbnail_hash(&app_state.db, model.blob.id).await? {
+        return Ok(Some(hash));
+    }
+
+    let image_path = get_image_path_for_blob(&model.blob, app_state);
+
+    if !image_path.exists() {
+        return Ok(None);
+    }
+
+    let hash = spawn_blocking(move || thumbnail_service::perceptual_hash(&image_path)).await??;
+    thumbnail_hash_db::set_thumbnail_hash(&app_state.db, model.blob.id, hash).await?;
+
+    Ok(Some(hash))
+}
+
+// Models of the user whose thumbnail looks most like the thumbnail of the given model, closest first
+pub async fn find_similar_models(model: &Model, user: &User, limit: usize, app_state: &AppState) -> Result<Vec<SimilarModel>, ServiceError> {
+    let hash = match get_or_compute_hash(model, app_state).await? {
+        Some(hash) => hash,
+        None => return Ok(Vec::new()),
+    };
+
+    let mut neighbours: Vec<(i64, u32)> = thumbnail_hash_db::get_model_thumbnail_hashes(&app_state.db, user)
+        .await?
+        .into_iter()
+        .filter(|(model_id, _)| *model_id != model.id)
+        .map(|(model_id, other)| (model_id, (hash ^ other).count_ones()))
+        .filter(|(_, distance)| *distance <= MAX_SIMILAR_DISTANCE)
+        .collect();
+
+    neighbours.sort_by_key(|(model_id, distance)| (*distance, *model_id));
+    neighbours.truncate(limit);
+
+    if neighbours.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let models = model_db::get_models_via_ids(&app_state.db, user, neighbours.iter().map(|(model_id, _)| *model_id).collect()).await?;
+    let mut models: HashMap<i64, Model> = models.into_iter().map(|model| (model.id, model)).collect();
+
+    Ok(neighbours
+        .into_iter()
+        .filter_map(|(model_id, distance)| models.remove(&model_id).map(|model| SimilarModel { model, distance }))
+        .collect())
+}
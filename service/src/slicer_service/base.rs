@@ -1,6 +1,11 @@
+use std::path::PathBuf;
+
+use db::model::FileType;
 use serde::{Deserialize, Serialize};
 use strum::EnumIter;
 
+use crate::{app_state::AppState, service_error::ServiceError};
+
 // TODO: Make all of this async
 #[derive(Clone, Serialize, Deserialize, EnumIter)]
 pub enum Slicer {
@@ -8,5 +13,102 @@ pub enum Slicer {
     OrcaSlicer,
     Cura,
     BambuStudio,
+    SuperSlicer,
+    CrealityPrint,
+    ElegooSlicer,
+    IdeaMaker,
+    Lychee,
+    Chitubox,
     Custom,
 }
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub enum SlicerSource {
+    Executable,
+    AppImage,
+    DesktopEntry,
+    Flatpak,
+    AppBundle,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct SlicerInstallation {
+    // Path to the program, or the application id for flatpak installs
+    pub executable: String,
+    pub version: Option<String>,
+    pub source: SlicerSource,
+}
+
+impl Slicer {
+    pub fn is_installed(&self) -> bool {
+        if let Slicer::Custom = self {
+            return true;
+        }
+
+        !self.get_installations().is_empty()
+    }
+
+    // File types the slicer can open. Zipped blobs are extracted before they are handed over, so only the plain types are listed.
+    // None means anything goes, as we don't know what a custom slicer accepts
+    pub fn get_supported_file_types(&self) -> Option<Vec<FileType>> {
+        let types = match self {
+            // PrusaSlicer's G-code viewer also opens the binary G-code it writes
+            Slicer::PrusaSlicer => vec![FileType::Stl, FileType::Obj, FileType::Step, FileType::Threemf, FileType::Gcode, FileType::Bgcode],
+            Slicer::SuperSlicer
+            | Slicer::OrcaSlicer
+            | Slicer::BambuStudio
+            | Slicer::CrealityPrint
+            | Slicer::ElegooSlicer => vec![FileType::Stl, FileType::Obj, FileType::Step, FileType::Threemf, FileType::Gcode],
+            Slicer::Cura => vec![FileType::Stl, FileType::Obj, FileType::Threemf, FileType::Gcode],
+            Slicer::IdeaMaker => vec![FileType::Stl, FileType::Obj, FileType::Threemf],
+            // Resin slicers
+            Slicer::Lychee => vec![FileType::Stl, FileType::Obj, FileType::Threemf],
+            Slicer::Chitubox => vec![FileType::Stl, FileType::Obj, FileType::Ctb],
+            Slicer::Custom => return None,
+        };
+
+        Some(types)
+    }
+
+    // Slicers with a command line interface that can export G-code without opening a window
+    pub fn supports_cli(&self) -> bool {
+        matches!(self, Slicer::PrusaSlicer | Slicer::SuperSlicer | Slicer::OrcaSlicer)
+    }
+
+    pub fn supports_file_type(&self, file_type: &FileType) -> bool {
+        match self.get_supported_file_types() {
+            Some(types) => types.contains(&file_type.from_zip()),
+            None => true,
+        }
+    }
+
+    // Refuses to open the slicer when any of the files is of a type it can't handle
+    pub fn ensure_supported(&self, paths: &[PathBuf]) -> Result<(), ServiceError> {
+        let unsupported: Vec<String> = paths
+            .iter()
+            .filter(|p| !self.supports_file_type(&FileType::from_pathbuf(p)))
+            .map(|p| p.file_name().map(|f| f.to_string_lossy().to_string()).unwrap_or_default())
+            .collect();
+
+        if !unsupported.is_empty() {
+            return Err(ServiceError::InternalError(format!(
+                "The selected slicer cannot open: {}",
+                unsupported.join(", ")
+            )));
+        }
+
+        Ok(())
+    }
+
+    // Uses the install chosen in the configuration, or the first one found if it is not available anymore
+    pub fn get_preferred_installation(&self, app_state: &AppState) -> Option<SlicerInstallation> {
+        let preferred = app_state.get_configuration().slicer_executable;
+        let installations = self.get_installations();
+
+        installations
+            .iter()
+            .find(|i| !preferred.is_empty() && i.executable == preferred)
+            .or(installations.first())
+            .cloned()
+    }
+}
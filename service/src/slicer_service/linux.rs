@@ -1,32 +1,47 @@
-use super::Slicer;
-use db::model::Model;
+use super::{Slicer, SlicerInstallation, SlicerSource};
 use crate::service_error::ServiceError;
 use crate::app_state::AppState;
-use crate::export_service::export_to_temp_folder;
-use crate::slicer_service::open_custom_slicer;
-use std::path::PathBuf;
+use crate::slicer_service::{open_custom_slicer, open_with_paths};
+use regex::Regex;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::sync::{LazyLock, Mutex};
+use std::time::SystemTime;
+
+// Running a slicer to ask for its version is slow, so the answer is kept until the program changes
+type VersionKey = (PathBuf, Option<SystemTime>);
+
+static VERSION_CACHE: LazyLock<Mutex<HashMap<VersionKey, Option<String>>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
 
 impl Slicer {
-    pub fn is_installed(&self) -> bool {
+    pub fn get_installations(&self) -> Vec<SlicerInstallation> {
         if let Slicer::Custom = self {
-            return true;
+            return vec![];
         }
 
-        let package = get_flatpak_slicer_package(&self);
+        let mut installations = vec![];
 
-        if package.is_empty() {
-            return false;
+        for executable in find_path_executables(self) {
+            push_unique(&mut installations, executable, SlicerSource::Executable, self);
         }
 
-        match Command::new("flatpak").arg("info").arg(package).output() {
-            Ok(output) => {
-                return output.status.success();
-            }
-            Err(_) => {
-                return false;
-            }
+        for appimage in find_appimages(self) {
+            push_unique(&mut installations, appimage, SlicerSource::AppImage, self);
+        }
+
+        for executable in find_desktop_entry_executables(self) {
+            push_unique(&mut installations, executable, SlicerSource::DesktopEntry, self);
+        }
+
+        if let Some(flatpak) = get_flatpak_installation(self) {
+            installations.push(flatpak);
         }
+
+        installations
     }
 
     pub async fn open(&self, paths: Vec<PathBuf>, app_state: &AppState) -> Result<(), ServiceError> {
@@ -34,12 +49,17 @@ impl Slicer {
             return open_custom_slicer(paths, app_state).await;
         }
 
-        if !self.is_installed() {
-            return Err(ServiceError::InternalError(String::from(
-                "Slicer not installed",
-            )));
-        }
-        
+        self.ensure_supported(&paths)?;
+
+        let installation = match self.get_preferred_installation(app_state) {
+            Some(installation) => installation,
+            None => {
+                return Err(ServiceError::InternalError(String::from(
+                    "Slicer not installed",
+                )));
+            }
+        };
+
         println!("Opening in slicer: {:?}", paths);
 
         if paths.len() == 0 {
@@ -48,10 +68,14 @@ impl Slicer {
             )));
         }
 
+        if installation.source != SlicerSource::Flatpak {
+            return open_with_paths(&installation.executable, paths);
+        }
+
         let _ = Command::new("flatpak")
             .arg("run")
             .arg("--file-forwarding")
-            .arg(get_flatpak_slicer_package(&self))
+            .arg(&installation.executable)
             .arg("@@")
             .args(paths)
             .arg("@@")
@@ -59,6 +83,21 @@ impl Slicer {
 
         Ok(())
     }
+
+    // Runs the slicer without a window, for its command line interface. Flatpak installs only get access to the given directory
+    pub fn get_cli_command(&self, installation: &SlicerInstallation, work_dir: &Path) -> Command {
+        if installation.source != SlicerSource::Flatpak {
+            return Command::new(&installation.executable);
+        }
+
+        let mut command = Command::new("flatpak");
+        command
+            .arg("run")
+            .arg(format!("--filesystem={}", work_dir.to_string_lossy()))
+            .arg(&installation.executable);
+
+        command
+    }
 }
 
 fn get_flatpak_slicer_package(slicer: &Slicer) -> String {
@@ -67,7 +106,284 @@ fn get_flatpak_slicer_package(slicer: &Slicer) -> String {
         Slicer::OrcaSlicer => "io.github.softfever.OrcaSlicer",
         Slicer::Cura => "com.ultimaker.cura",
         Slicer::BambuStudio => "com.bambulab.BambuStudio",
+        Slicer::SuperSlicer => "com.superslicer.SuperSlicer",
         _ => "",
     }
     .to_string()
 }
+
+// Names the slicer is installed as by distro packages and upstream tarballs
+fn get_binary_names(slicer: &Slicer) -> &'static [&'static str] {
+    match slicer {
+        Slicer::PrusaSlicer => &["prusa-slicer", "PrusaSlicer", "prusaslicer"],
+        Slicer::OrcaSlicer => &["orca-slicer", "OrcaSlicer", "orcaslicer"],
+        Slicer::Cura => &["cura", "UltiMaker-Cura", "ultimaker-cura"],
+        Slicer::BambuStudio => &["bambu-studio", "BambuStudio", "bambustudio"],
+        Slicer::SuperSlicer => &["superslicer", "SuperSlicer", "super-slicer"],
+        Slicer::CrealityPrint => &["CrealityPrint", "creality-print", "crealityprint"],
+        Slicer::ElegooSlicer => &["elegoo-slicer", "ElegooSlicer", "elegooslicer"],
+        Slicer::IdeaMaker => &["ideamaker", "ideaMaker"],
+        Slicer::Lychee => &["lychee", "lycheeslicer", "LycheeSlicer"],
+        Slicer::Chitubox => &["chitubox", "CHITUBOX"],
+        _ => &[],
+    }
+}
+
+// Lowercase fragments that identify the slicer in AppImage file names and desktop entry names
+fn get_name_patterns(slicer: &Slicer) -> &'static [&'static str] {
+    match slicer {
+        Slicer::PrusaSlicer => &["prusaslicer", "prusa-slicer", "prusa slicer"],
+        Slicer::OrcaSlicer => &["orcaslicer", "orca-slicer", "orca slicer", "orca_slicer"],
+        Slicer::Cura => &["cura"],
+        Slicer::BambuStudio => &["bambustudio", "bambu-studio", "bambu studio", "bambu_studio"],
+        Slicer::SuperSlicer => &["superslicer", "super-slicer", "super slicer", "super_slicer"],
+        Slicer::CrealityPrint => &["crealityprint", "creality-print", "creality print", "creality_print"],
+        Slicer::ElegooSlicer => &["elegooslicer", "elegoo-slicer", "elegoo slicer", "elegoo_slicer"],
+        Slicer::IdeaMaker => &["ideamaker"],
+        Slicer::Lychee => &["lychee"],
+        Slicer::Chitubox => &["chitubox"],
+        _ => &[],
+    }
+}
+
+fn matches_slicer_name(slicer: &Slicer, name: &str) -> bool {
+    let name = name.to_lowercase();
+    get_name_patterns(slicer).iter().any(|p| name.contains(p))
+}
+
+fn home_dir() -> Option<PathBuf> {
+    env::var_os("HOME").map(PathBuf::from)
+}
+
+fn is_executable(path: &Path) -> bool {
+    fs::metadata(path)
+        .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+fn push_unique(installations: &mut Vec<SlicerInstallation>, path: PathBuf, source: SlicerSource, slicer: &Slicer) {
+    // Symlinks in PATH and desktop entries often point at the same program
+    let path = fs::canonicalize(&path).unwrap_or(path);
+    let executable = path.to_string_lossy().to_string();
+
+    if installations.iter().any(|i| i.executable == executable) {
+        return;
+    }
+
+    installations.push(SlicerInstallation {
+        version: detect_version(slicer, &path),
+        executable,
+        source,
+    });
+}
+
+fn find_in_path(name: &str) -> Option<PathBuf> {
+    let path = env::var_os("PATH")?;
+
+    env::split_paths(&path)
+        .map(|dir| dir.join(name))
+        .find(|candidate| is_executable(candidate))
+}
+
+fn find_path_executables(slicer: &Slicer) -> Vec<PathBuf> {
+    get_binary_names(slicer)
+        .iter()
+        .filter_map(|name| find_in_path(name))
+        .collect()
+}
+
+fn get_appimage_dirs() -> Vec<PathBuf> {
+    let mut dirs = vec![PathBuf::from("/opt"), PathBuf::from("/usr/local/bin")];
+
+    if let Some(home) = home_dir() {
+        dirs.push(home.join("Applications"));
+        dirs.push(home.join("AppImages"));
+        dirs.push(home.join(".local/bin"));
+        dirs.push(home.join("bin"));
+    }
+
+    dirs
+}
+
+fn find_appimages(slicer: &Slicer) -> Vec<PathBuf> {
+    let mut appimages = vec![];
+
+    for dir in get_appimage_dirs() {
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let file_name = entry.file_name().to_string_lossy().to_string();
+
+            if file_name.to_lowercase().ends_with(".appimage")
+                && matches_slicer_name(slicer, &file_name)
+                && is_executable(&path)
+            {
+                appimages.push(path);
+            }
+        }
+    }
+
+    appimages
+}
+
+fn get_desktop_entry_dirs() -> Vec<PathBuf> {
+    let mut dirs = vec![];
+
+    if let Some(home) = home_dir() {
+        dirs.push(home.join(".local/share/applications"));
+    }
+
+    let data_dirs = env::var("XDG_DATA_DIRS").unwrap_or("/usr/local/share:/usr/share".into());
+
+    for dir in data_dirs.split(':').filter(|d| !d.is_empty()) {
+        dirs.push(PathBuf::from(dir).join("applications"));
+    }
+
+    dirs
+}
+
+// Returns the program of the Exec line, without arguments and field codes
+fn parse_desktop_entry(content: &str) -> Option<(String, String)> {
+    let mut in_main_section = false;
+    let mut name = None;
+    let mut exec = None;
+
+    for line in content.lines().map(|l| l.trim()) {
+        if line.starts_with('[') {
+            in_main_section = line == "[Desktop Entry]";
+            continue;
+        }
+
+        if !in_main_section {
+            continue;
+        }
+
+        if let Some(value) = line.strip_prefix("Name=") {
+            name = Some(value.to_string());
+        } else if let Some(value) = line.strip_prefix("Exec=") {
+            exec = Some(value.to_string());
+        }
+    }
+
+    let exec = exec?;
+    let program = if let Some(quoted) = exec.strip_prefix('"') {
+        quoted.split('"').next()?.to_string()
+    } else {
+        exec.split_whitespace().next()?.to_string()
+    };
+
+    Some((name?, program))
+}
+
+fn find_desktop_entry_executables(slicer: &Slicer) -> Vec<PathBuf> {
+    let mut executables = vec![];
+
+    for dir in get_desktop_entry_dirs() {
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+
+        for entry in entries.flatten() {
+            let path = entry.path();
+
+            if path.extension().and_then(|e| e.to_str()) != Some("desktop") {
+                continue;
+            }
+
+            let content = match fs::read_to_string(&path) {
+                Ok(content) => content,
+                Err(_) => continue,
+            };
+
+            let (name, program) = match parse_desktop_entry(&content) {
+                Some(entry) => entry,
+                None => continue,
+            };
+
+            // Flatpak exports its own desktop entries, those are picked up through flatpak info instead
+            if !matches_slicer_name(slicer, &name) || program.ends_with("flatpak") {
+                continue;
+            }
+
+            let program = PathBuf::from(&program);
+            let resolved = if program.is_absolute() {
+                Some(program).filter(|p| is_executable(p))
+            } else {
+                find_in_path(&program.to_string_lossy())
+            };
+
+            if let Some(resolved) = resolved {
+                executables.push(resolved);
+            }
+        }
+    }
+
+    executables
+}
+
+fn get_flatpak_installation(slicer: &Slicer) -> Option<SlicerInstallation> {
+    let package = get_flatpak_slicer_package(slicer);
+
+    if package.is_empty() {
+        return None;
+    }
+
+    let output = Command::new("flatpak").arg("info").arg(&package).output().ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    let info = String::from_utf8_lossy(&output.stdout);
+    let version = info
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("Version:"))
+        .map(|v| v.trim().to_string());
+
+    Some(SlicerInstallation {
+        executable: package,
+        version,
+        source: SlicerSource::Flatpak,
+    })
+}
+
+fn detect_version(slicer: &Slicer, path: &Path) -> Option<String> {
+    let version_regex = Regex::new(r"(\d+\.\d+(?:\.\d+)*)").unwrap();
+
+    // AppImages and upstream tarballs carry the version in their name, e.g. PrusaSlicer-2.8.1+linux-x64.AppImage
+    let file_name = path.file_name()?.to_string_lossy().to_string();
+
+    if let Some(captures) = version_regex.captures(&file_name) {
+        return Some(captures[1].to_string());
+    }
+
+    // Only PrusaSlicer and its SuperSlicer fork reliably print their version without opening a window
+    if let Slicer::PrusaSlicer | Slicer::SuperSlicer = slicer {
+        let modified = fs::metadata(path).and_then(|m| m.modified()).ok();
+        let key = (path.to_path_buf(), modified);
+
+        if let Some(version) = VERSION_CACHE.lock().unwrap().get(&key) {
+            return version.clone();
+        }
+
+        let version = Command::new(path)
+            .arg("--version")
+            .output()
+            .ok()
+            .and_then(|output| {
+                let stdout = String::from_utf8_lossy(&output.stdout);
+                version_regex.captures(&stdout).map(|c| c[1].to_string())
+            });
+
+        VERSION_CACHE.lock().unwrap().insert(key, version.clone());
+
+        return version;
+    }
+
+    None
+}
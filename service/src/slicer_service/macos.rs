@@ -1,19 +1,22 @@
-use super::Slicer;
+use super::{Slicer, SlicerInstallation, SlicerSource};
 use db::model::Model;
 use crate::service_error::ServiceError;
 use crate::app_state::AppState;
 use crate::export_service::export_to_temp_folder;
 use crate::slicer_service::open_custom_slicer;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 
 impl Slicer {
-    pub fn is_installed(&self) -> bool {
-        if let Slicer::Custom = self {
-            return true;
+    pub fn get_installations(&self) -> Vec<SlicerInstallation> {
+        match get_slicer_path(&self) {
+            Some(path) => vec![SlicerInstallation {
+                version: get_bundle_version(&path),
+                executable: path.to_string_lossy().to_string(),
+                source: SlicerSource::AppBundle,
+            }],
+            None => vec![],
         }
-
-        get_slicer_path(&self).is_some()
     }
 
     pub async fn open(&self, paths: Vec<PathBuf>, app_state: &AppState) -> Result<(), ServiceError> {
@@ -21,11 +24,16 @@ impl Slicer {
             return open_custom_slicer(paths, app_state).await;
         }
 
-        if !self.is_installed() {
-            return Err(ServiceError::InternalError(String::from(
-                "Slicer not installed",
-            )));
-        }
+        self.ensure_supported(&paths)?;
+
+        let installation = match self.get_preferred_installation(app_state) {
+            Some(installation) => installation,
+            None => {
+                return Err(ServiceError::InternalError(String::from(
+                    "Slicer not installed",
+                )));
+            }
+        };
 
         println!("Opening in slicer: {:?}", paths);
 
@@ -35,17 +43,24 @@ impl Slicer {
             )));
         }
 
-        let slicer_path = get_slicer_path(&self).unwrap();
-
         Command::new("open")
             .arg("-a")
-            .arg(slicer_path)
+            .arg(&installation.executable)
             .arg("--args")
             .args(paths)
             .spawn()?;
 
         Ok(())
     }
+
+    // Runs the slicer without a window, for its command line interface. `open -a` can't be used as it doesn't wait for the program
+    pub fn get_cli_command(&self, installation: &SlicerInstallation, _work_dir: &Path) -> Command {
+        let app_path = PathBuf::from(&installation.executable);
+        let binary = read_plist_string(&app_path, "CFBundleExecutable")
+            .unwrap_or_else(|| app_path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default());
+
+        Command::new(app_path.join("Contents/MacOS").join(binary))
+    }
 }
 
 fn get_slicer_path(slicer: &Slicer) -> Option<PathBuf> {
@@ -86,6 +101,41 @@ fn get_slicer_path(slicer: &Slicer) -> Option<PathBuf> {
             }
             return None;
         }
+        Slicer::SuperSlicer => first_existing(&["/Applications/SuperSlicer.app"]),
+        Slicer::CrealityPrint => first_existing(&[
+            "/Applications/CrealityPrint.app",
+            "/Applications/Creality Print.app",
+        ]),
+        Slicer::ElegooSlicer => first_existing(&["/Applications/ElegooSlicer.app"]),
+        Slicer::IdeaMaker => first_existing(&["/Applications/ideaMaker.app"]),
+        Slicer::Lychee => first_existing(&[
+            "/Applications/Lychee Slicer.app",
+            "/Applications/LycheeSlicer.app",
+        ]),
+        Slicer::Chitubox => first_existing(&[
+            "/Applications/CHITUBOX.app",
+            "/Applications/CHITUBOX Basic.app",
+            "/Applications/CHITUBOX Pro.app",
+        ]),
         _ => None,
     }
 }
+
+fn first_existing(paths: &[&str]) -> Option<PathBuf> {
+    paths.iter().map(PathBuf::from).find(|path| path.exists())
+}
+
+fn get_bundle_version(app_path: &PathBuf) -> Option<String> {
+    read_plist_string(app_path, "CFBundleShortVersionString")
+}
+
+// Reads a string value from the Info.plist of the app bundle
+fn read_plist_string(app_path: &PathBuf, key: &str) -> Option<String> {
+    let plist = std::fs::read_to_string(app_path.join("Contents/Info.plist")).ok()?;
+    let key_index = plist.find(&format!("<key>{}</key>", key))?;
+    let rest = &plist[key_index..];
+    let start = rest.find("<string>")? + "<string>".len();
+    let end = rest[start..].find("</string>")?;
+
+    Some(rest[start..start + end].trim().to_string())
+}
@@ -1,4 +1,4 @@
-use super::{Slicer, open_custom_slicer};
+use super::{Slicer, SlicerInstallation, SlicerSource, open_custom_slicer};
 use db::model::Model;
 use crate::service_error::ServiceError;
 use crate::app_state::AppState;
@@ -7,15 +7,19 @@ use crate::slicer_service::open_with_paths;
 use std::fs;
 use std::path::Path;
 use std::path::PathBuf;
+use std::process::Command;
 use winreg::*;
 
 impl Slicer {
-    pub fn is_installed(&self) -> bool {
-        if let Slicer::Custom = self {
-            return true;
+    pub fn get_installations(&self) -> Vec<SlicerInstallation> {
+        match get_slicer_path(&self) {
+            Some(path) => vec![SlicerInstallation {
+                executable: path.to_string_lossy().to_string(),
+                version: None,
+                source: SlicerSource::Executable,
+            }],
+            None => vec![],
         }
-
-        get_slicer_path(&self).is_some()
     }
 
     pub async fn open(&self, paths: Vec<PathBuf>, app_state: &AppState) -> Result<(), ServiceError> {
@@ -23,18 +27,38 @@ impl Slicer {
             return open_custom_slicer(paths, app_state).await;
         }
 
-        if !self.is_installed() {
-            return Err(ServiceError::InternalError(String::from(
-                "Slicer not installed",
-            )));
-        }
+        self.ensure_supported(&paths)?;
 
-        let slicer_pathbuf = get_slicer_path(&self).unwrap();
-        let slicer_path = slicer_pathbuf.to_str().unwrap();
+        let installation = match self.get_preferred_installation(app_state) {
+            Some(installation) => installation,
+            None => {
+                return Err(ServiceError::InternalError(String::from(
+                    "Slicer not installed",
+                )));
+            }
+        };
 
         println!("Opening in slicer: {:?}", paths);
 
-        open_with_paths(slicer_path, paths)
+        open_with_paths(&installation.executable, paths)
+    }
+
+    // Runs the slicer without a window, for its command line interface.
+    // PrusaSlicer and SuperSlicer ship a separate console build that waits for the slice to finish
+    pub fn get_cli_command(&self, installation: &SlicerInstallation, _work_dir: &Path) -> Command {
+        let executable = PathBuf::from(&installation.executable);
+
+        let console_name = match self {
+            Slicer::PrusaSlicer => Some("prusa-slicer-console.exe"),
+            Slicer::SuperSlicer => Some("superslicer_console.exe"),
+            _ => None,
+        };
+
+        let console = console_name
+            .and_then(|name| executable.parent().map(|dir| dir.join(name)))
+            .filter(|path| path.exists());
+
+        Command::new(console.unwrap_or(executable))
     }
 }
 
@@ -145,6 +169,56 @@ fn get_slicer_path(slicer: &Slicer) -> Option<PathBuf> {
 
             return None;
         }
+        Slicer::SuperSlicer => first_existing(&[
+            "C:\\Program Files\\SuperSlicer\\superslicer.exe",
+            "C:\\Program Files\\SuperSlicer\\SuperSlicer.exe",
+        ]),
+        Slicer::CrealityPrint => {
+            // Installs into a versioned folder, e.g. C:\Program Files\Creality\Creality Print 6.0
+            find_in_versioned_folder("C:\\Program Files\\Creality", "Creality Print", &["CrealityPrint.exe", "Creality Print.exe"])
+        }
+        Slicer::ElegooSlicer => first_existing(&[
+            "C:\\Program Files\\ElegooSlicer\\elegoo-slicer.exe",
+        ]),
+        Slicer::IdeaMaker => first_existing(&[
+            "C:\\Program Files\\Raise3D\\ideaMaker\\ideaMaker.exe",
+        ]),
+        Slicer::Lychee => first_existing(&[
+            "C:\\Program Files\\Mango 3D\\Lychee Slicer\\Lychee Slicer.exe",
+            "C:\\Program Files\\Lychee Slicer\\Lychee Slicer.exe",
+        ]),
+        Slicer::Chitubox => {
+            // Both the free and the pro edition install as CHITUBOX <edition/version>
+            find_in_versioned_folder("C:\\Program Files", "CHITUBOX", &["CHITUBOX.exe", "CHITUBOX_Basic.exe", "CHITUBOX Pro.exe"])
+        }
         _ => None,
     }
 }
+
+fn first_existing(paths: &[&str]) -> Option<PathBuf> {
+    paths.iter().map(PathBuf::from).find(|path| path.exists())
+}
+
+fn find_in_versioned_folder(parent: &str, folder_prefix: &str, executables: &[&str]) -> Option<PathBuf> {
+    let entries = fs::read_dir(parent).ok()?;
+
+    for entry in entries.flatten() {
+        if !entry.file_type().map(|ft| ft.is_dir()).unwrap_or(false) {
+            continue;
+        }
+
+        if !entry.file_name().to_string_lossy().starts_with(folder_prefix) {
+            continue;
+        }
+
+        for executable in executables {
+            let exe_path = Path::new(parent).join(entry.file_name()).join(executable);
+
+            if exe_path.exists() {
+                return Some(exe_path);
+            }
+        }
+    }
+
+    None
+}
@@ -0,0 +1,345 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use db::model::{FileType, GcodeMetadata, Model};
+use db::model_db;
+use tokio::task::spawn_blocking;
+
+use crate::app_state::AppState;
+use crate::export_service::{get_path_from_model, get_temp_dir};
+use crate::gcode_service;
+use crate::import_service;
+use crate::import_state::{ImportState, ImportStatus};
+use crate::service_error::ServiceError;
+use crate::slicer_service::{Slicer, SlicerInstallation};
+
+// Slices the model with the configured slicer and imports the G-code as a new model that points back at the source model
+pub async fn slice_model(
+    model: &Model,
+    profile_paths: &[PathBuf],
+    app_state: &AppState,
+    mut import_state: ImportState,
+) -> Result<(ImportState, GcodeMetadata), ServiceError> {
+    import_state.update_total_model_count(1);
+    import_state.update_status(ImportStatus::Slicing);
+
+    // Everything the slicer touches lives in one directory, so sandboxed installs only need access to that
+    let work_dir = get_temp_dir("slice");
+    let result = slice_model_in(model, profile_paths, &work_dir, app_state, import_state).await;
+    let _ = fs::remove_dir_all(&work_dir);
+
+    result
+}
+
+async fn slice_model_in(
+    model: &Model,
+    profile_paths: &[PathBuf],
+    work_dir: &Path,
+    app_state: &AppState,
+    mut import_state: ImportState,
+) -> Result<(ImportState, GcodeMetadata), ServiceError> {
+    let (gcode_path, estimate) = match slice_and_estimate(model, profile_paths, work_dir, app_state).await {
+        Ok(result) => result,
+        Err(e) => {
+            import_state.set_failure(e.to_string());
+            return Err(e);
+        }
+    };
+
+    let hash = import_service::hash_file_contents(&tokio::fs::read(&gcode_path).await?);
+    let existing_id = model_db::get_editable_model_id_via_sha256(&app_state.db, &import_state.user, &hash).await?;
+    let import_state = import_service::import_path(&gcode_path.to_string_lossy(), app_state, import_state).await?;
+
+    let user = &import_state.user;
+    let new_model_id = match get_created_model_id(&import_state, existing_id)? {
+        Some(id) => id,
+        None => return Ok((import_state, estimate)),
+    };
+
+    model_db::set_source_model(&app_state.db, user, new_model_id, Some(model.id)).await?;
+
+    if let Some(sliced_model) = model_db::get_models_via_ids(&app_state.db, user, vec![new_model_id]).await?.pop() {
+        let description = describe_slice(model, profile_paths, &estimate);
+
+        model_db::edit_model(
+            &app_state.db,
+            user,
+            new_model_id,
+            &model.name,
+            sliced_model.link.as_deref(),
+            Some(&description),
+            sliced_model.flags,
+            None,
+        )
+        .await?;
+    }
+
+    Ok((import_state, estimate))
+}
+
+// Identical G-code is deduplicated into the model that already has it, which has to keep its own name and source.
+// Returns the model the import created, or None when it found an existing one
+fn get_created_model_id(import_state: &ImportState, existing_id: Option<i64>) -> Result<Option<i64>, ServiceError> {
+    let imported_id = match import_state.imported_models.iter().flat_map(|set| set.model_ids.iter()).next() {
+        Some(id) => *id,
+        None => {
+            return Err(ServiceError::InternalError(String::from(
+                "Sliced G-code was not imported",
+            )));
+        }
+    };
+
+    match existing_id {
+        Some(_) => Ok(None),
+        None => Ok(Some(imported_id)),
+    }
+}
+
+async fn slice_and_estimate(model: &Model, profile_paths: &[PathBuf], work_dir: &Path, app_state: &AppState) -> Result<(PathBuf, GcodeMetadata), ServiceError> {
+    let gcode_path = run_slicer(model, profile_paths, work_dir, app_state).await?;
+    let estimate = gcode_service::read_metadata_from_path(&gcode_path).await?;
+
+    Ok((gcode_path, estimate))
+}
+
+async fn run_slicer(model: &Model, profile_paths: &[PathBuf], work_dir: &Path, app_state: &AppState) -> Result<PathBuf, ServiceError> {
+    let slicer = match app_state.get_configuration().slicer {
+        Some(slicer) if slicer.supports_cli() => slicer,
+        _ => {
+            return Err(ServiceError::InternalError(String::from(
+                "The selected slicer can't slice from the command line",
+            )));
+        }
+    };
+
+    let installation = match slicer.get_preferred_installation(app_state) {
+        Some(installation) => installation,
+        None => {
+            return Err(ServiceError::InternalError(String::from(
+                "Slicer not installed",
+            )));
+        }
+    };
+
+    let file_type = model.blob.to_file_type().from_zip();
+
+    if file_type.is_gcode() || !slicer.supports_file_type(&file_type) {
+        return Err(ServiceError::InternalError(String::from(
+            "This model can't be sliced",
+        )));
+    }
+
+    if profile_paths.is_empty() || profile_paths.iter().any(|p| !p.is_file()) {
+        return Err(ServiceError::InternalError(String::from(
+            "Slicer profile not found",
+        )));
+    }
+
+    let output_dir = work_dir.join("output");
+    fs::create_dir(&output_dir)?;
+
+    let mut profiles = Vec::with_capacity(profile_paths.len());
+
+    for profile_path in profile_paths {
+        let file_name = profile_path.file_name().unwrap_or_default();
+        let profile_copy = work_dir.join(file_name);
+        fs::copy(profile_path, &profile_copy)?;
+        profiles.push(profile_copy);
+    }
+
+    let work_dir = work_dir.to_path_buf();
+    let model_path = get_path_from_model(&work_dir, model, app_state, false).await?;
+
+    spawn_blocking(move || {
+        invoke_slicer_cli(&slicer, &installation, &work_dir, &model_path, &profiles, &output_dir)?;
+        find_gcode(&output_dir)
+    })
+    .await?
+}
+
+fn invoke_slicer_cli(
+    slicer: &Slicer,
+    installation: &SlicerInstallation,
+    work_dir: &Path,
+    model_path: &Path,
+    profiles: &[PathBuf],
+    output_dir: &Path,
+) -> Result<(), ServiceError> {
+    let mut command = slicer.get_cli_command(installation, work_dir);
+
+    match slicer {
+        Slicer::PrusaSlicer | Slicer::SuperSlicer => {
+            if profiles.iter().any(|p| !has_extension(p, "ini")) {
+                return Err(ServiceError::InternalError(String::from(
+                    "PrusaSlicer and SuperSlicer need .ini profiles",
+                )));
+            }
+
+            command.arg("--export-gcode");
+
+            for profile in profiles {
+                command.arg("--load").arg(profile);
+            }
+
+            let file_stem = model_path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+            command.arg("--output").arg(output_dir.join(format!("{}.gcode", file_stem)));
+        }
+        Slicer::OrcaSlicer => {
+            if profiles.iter().any(|p| !has_extension(p, "json")) {
+                return Err(ServiceError::InternalError(String::from(
+                    "OrcaSlicer needs .json profiles",
+                )));
+            }
+
+            let mut settings = vec![];
+            let mut filaments = vec![];
+
+            // Orca takes printer and process presets separately from filament presets
+            for profile in profiles {
+                let content = fs::read_to_string(profile)?;
+                let json: serde_json::Value = serde_json::from_str(&content)?;
+
+                match json.get("type").and_then(|t| t.as_str()) {
+                    Some("filament") => filaments.push(profile.to_string_lossy().to_string()),
+                    _ => settings.push(profile.to_string_lossy().to_string()),
+                }
+            }
+
+            command.arg("--slice").arg("0");
+
+            if !settings.is_empty() {
+                command.arg("--load-settings").arg(settings.join(";"));
+            }
+
+            if !filaments.is_empty() {
+                command.arg("--load-filaments").arg(filaments.join(";"));
+            }
+
+            // Loose meshes are not guaranteed to be on the plate, 3mf projects keep their own arrangement
+            if !has_extension(model_path, "3mf") {
+                command.arg("--arrange").arg("1");
+            }
+
+            command.arg("--outputdir").arg(output_dir);
+        }
+        _ => {
+            return Err(ServiceError::InternalError(String::from(
+                "The selected slicer can't slice from the command line",
+            )));
+        }
+    }
+
+    command.arg(model_path);
+
+    println!("Slicing: {:?}", command);
+
+    let output = command.output()?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let message = if stderr.trim().is_empty() { stdout } else { stderr };
+        // The last lines hold the actual error, everything before is progress output
+        let lines: Vec<&str> = message.lines().collect();
+        let last_lines = lines[lines.len().saturating_sub(5)..].join("\n");
+
+        return Err(ServiceError::InternalError(format!(
+            "Slicing failed: {}",
+            last_lines
+        )));
+    }
+
+    Ok(())
+}
+
+fn has_extension(path: &Path, extension: &str) -> bool {
+    path.extension()
+        .map(|e| e.to_string_lossy().eq_ignore_ascii_case(extension))
+        .unwrap_or(false)
+}
+
+fn find_gcode(output_dir: &Path) -> Result<PathBuf, ServiceError> {
+    for entry in fs::read_dir(output_dir)?.flatten() {
+        let path = entry.path();
+
+        if FileType::from_pathbuf(&path).is_gcode() {
+            return Ok(path);
+        }
+    }
+
+    Err(ServiceError::InternalError(String::from(
+        "Slicer did not produce any G-code",
+    )))
+}
+
+pub fn format_duration(seconds: i64) -> String {
+    let hours = seconds / 3600;
+    let minutes = (seconds % 3600) / 60;
+
+    if hours > 0 {
+        format!("{}h {}m", hours, minutes)
+    } else {
+        format!("{}m {}s", minutes, seconds % 60)
+    }
+}
+
+fn describe_slice(model: &Model, profile_paths: &[PathBuf], estimate: &GcodeMetadata) -> String {
+    let profiles = profile_paths
+        .iter()
+        .filter_map(|p| p.file_stem())
+        .map(|p| p.to_string_lossy().to_string())
+        .collect::<Vec<String>>()
+        .join(", ");
+
+    let mut lines = vec![format!("Sliced from {} with {}.", model.name, profiles)];
+
+    if let Some(seconds) = estimate.print_time_seconds {
+        lines.push(format!("Estimated print time: {}", format_duration(seconds)));
+    }
+
+    match (estimate.filament_used_mm, estimate.filament_used_g) {
+        (Some(mm), Some(g)) => lines.push(format!("Filament: {:.2} m, {:.2} g", mm / 1000.0, g)),
+        (Some(mm), None) => lines.push(format!("Filament: {:.2} m", mm / 1000.0)),
+        (None, Some(g)) => lines.push(format!("Filament: {:.2} g", g)),
+        (None, None) => {}
+    }
+
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use db::model::User;
+
+    fn import_state_with(model_ids: Vec<i64>) -> ImportState {
+        let mut import_state = ImportState::new(None, false, false, false, User::default());
+
+        for id in model_ids {
+            import_state.add_model_id_to_current_set(id);
+        }
+
+        import_state
+    }
+
+    #[test]
+    fn new_gcode_is_linked_to_the_source_model() {
+        let import_state = import_state_with(vec![7]);
+
+        assert_eq!(get_created_model_id(&import_state, None).unwrap(), Some(7));
+    }
+
+    #[test]
+    fn deduplicated_gcode_leaves_the_existing_model_alone() {
+        let import_state = import_state_with(vec![3]);
+
+        assert_eq!(get_created_model_id(&import_state, Some(3)).unwrap(), None);
+    }
+
+    #[test]
+    fn missing_import_is_an_error() {
+        let import_state = import_state_with(vec![]);
+
+        assert!(get_created_model_id(&import_state, None).is_err());
+    }
+}
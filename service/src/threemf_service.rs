@@ -1,31 +1,83 @@
-use std::{path::PathBuf, thread, u32};
+use std::{collections::{BTreeMap, HashMap}, path::PathBuf, thread, u32};
 
+use async_zip::{Compression, ZipEntryBuilder};
 use async_zip::tokio::read::seek::ZipFileReader;
+use async_zip::tokio::write::ZipFileWriter;
 use chrono::Utc;
 use db::model::{Model, User};
+use db::threemf_metadata_db;
 use indexmap::IndexMap;
-use itertools::join;
+use itertools::{Itertools, join};
 use regex::Regex;
 use serde::{Deserialize, Serialize};
-use stl_io::Vector;
-use tokio::{fs::File, io::BufReader};
+use tokio::{fs::File, io::{AsyncReadExt, BufReader}};
+use tokio_util::compat::FuturesAsyncReadCompatExt;
 
-use crate::{AppState, ServiceError, cleanse_evil_from_name, export_service, import_service, import_state::ImportState};
+use crate::{AppState, ServiceError, cleanse_evil_from_name, export_service, import_service, import_state::ImportState, mesh_service::{self, MeshFormat}};
 
-#[derive(Deserialize)]
+// The 3MF core metadata is written before the mesh data
+const CORE_METADATA_SCAN_BYTES: usize = 64 * 1024;
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
 pub struct ProjectSettingsConfig {
     pub nozzle_diameter: Vec<String>,
     pub layer_height: String,
     pub filament_type: Vec<String>,
     pub enable_support: String,
+    pub printer_settings_id: String,
+    pub print_settings_id: String,
+    pub filament_settings_id: Vec<String>,
+    pub filament_colour: Vec<String>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
 pub struct ThreemfMetadata {
     pub nozzle_diameter: Option<f32>,
     pub layer_height: Option<f32>,
     pub material_type: Option<String>,
     pub supports_enabled: Option<bool>,
+    pub title: Option<String>,
+    pub designer: Option<String>,
+    pub license: Option<String>,
+    pub creation_date: Option<String>,
+    pub printer_preset: Option<String>,
+    pub process_preset: Option<String>,
+    pub filament_presets: Vec<String>,
+    pub filament_colours: Vec<String>,
+    pub plates: Vec<ThreemfPlate>,
+    pub objects: Vec<ThreemfObject>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct ThreemfPlate {
+    pub index: u32,
+    pub name: Option<String>,
+    pub thumbnail_file: Option<String>,
+    pub object_names: Vec<String>,
+    // Only known once the project was sliced
+    pub print_time_seconds: Option<i64>,
+    pub weight_g: Option<f64>,
+    pub filaments: Vec<ThreemfFilament>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct ThreemfFilament {
+    pub id: u32,
+    pub material_type: Option<String>,
+    pub colour: Option<String>,
+    pub used_m: Option<f64>,
+    pub used_g: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct ThreemfObject {
+    pub id: u32,
+    pub name: Option<String>,
+    pub extruder: Option<u32>,
+    // Print settings that are overridden for just this object
+    pub settings: BTreeMap<String, String>,
 }
 
 async fn parse_project_settings_config(data: String) -> Result<ThreemfMetadata, ServiceError> {
@@ -49,16 +101,25 @@ async fn parse_project_settings_config(data: String) -> Result<ThreemfMetadata,
         layer_height,
         material_type: Some(filament_type),
         supports_enabled: enable_support,
+        printer_preset: Some(parsed_data.printer_settings_id).filter(|s| !s.is_empty()),
+        process_preset: Some(parsed_data.print_settings_id).filter(|s| !s.is_empty()),
+        filament_presets: parsed_data.filament_settings_id,
+        filament_colours: parsed_data.filament_colour,
+        ..Default::default()
     })
 }
 
+// PrusaSlicer lists multiple values as "a";"b" or a;b
+fn split_config_list(value: &str) -> Vec<String> {
+    value
+        .split(';')
+        .map(|v| v.trim().trim_matches('"').to_string())
+        .filter(|v| !v.is_empty())
+        .collect()
+}
+
 async fn parse_slicer_pe_config(data: String) -> Result<ThreemfMetadata, ServiceError> {
-    let mut threemf = ThreemfMetadata {
-        nozzle_diameter: None,
-        layer_height: None,
-        material_type: None,
-        supports_enabled: None,
-    };
+    let mut threemf = ThreemfMetadata::default();
 
     match data.lines().find(|line| line.starts_with("; nozzle_diameter = ")) {
         Some(line) => {
@@ -88,6 +149,18 @@ async fn parse_slicer_pe_config(data: String) -> Result<ThreemfMetadata, Service
         None => {}
     }
 
+    for line in data.lines() {
+        if let Some(value) = line.strip_prefix("; printer_settings_id = ") {
+            threemf.printer_preset = Some(value.trim().to_string()).filter(|s| !s.is_empty());
+        } else if let Some(value) = line.strip_prefix("; print_settings_id = ") {
+            threemf.process_preset = Some(value.trim().to_string()).filter(|s| !s.is_empty());
+        } else if let Some(value) = line.strip_prefix("; filament_settings_id = ") {
+            threemf.filament_presets = split_config_list(value);
+        } else if let Some(value) = line.strip_prefix("; filament_colour = ") {
+            threemf.filament_colours = split_config_list(value);
+        }
+    }
+
     match data.lines().find(|line| line.starts_with("; support_material =")) {
         Some(line) => {
             let value = line.trim_start_matches("; support_material =").trim();
@@ -143,111 +216,381 @@ pub async fn fetch_model_settings_config_from_3mf(threemf_path : PathBuf) -> Res
     return Err(ServiceError::InternalError("Failed to extract model settings".to_string()));
 }
 
+// Returns the project metadata of a 3MF model, reading the file only the first time
 pub async fn extract_metadata(model : &Model, app_state: &AppState) -> Result<ThreemfMetadata, ServiceError> {
     if !model.blob.filetype.contains("3mf") {
         return Err(ServiceError::InternalError("Model is not a 3MF file".to_string()));
     }
 
-    let temp_dir = std::env::temp_dir().join("meshorganiser_metadata_action");
-    if !temp_dir.exists() {
-        std::fs::create_dir(&temp_dir)?;
+    if let Some(json) = threemf_metadata_db::get_threemf_metadata_json(&app_state.db, model.blob.id).await? {
+        if let Ok(metadata) = serde_json::from_str::<ThreemfMetadata>(&json) {
+            return Ok(metadata);
+        }
     }
 
-    let theemf_path = export_service::get_path_from_model(&temp_dir, &model, app_state, true).await?;
-    
+    // 3MF files are never stored zipped, so they can be read in place
+    let theemf_path = export_service::get_model_path_for_blob(&model.blob, app_state);
+    let metadata = read_metadata(theemf_path).await?;
+
+    threemf_metadata_db::set_threemf_metadata_json(&app_state.db, model.blob.id, &serde_json::to_string(&metadata)?).await?;
+
+    Ok(metadata)
+}
+
+async fn read_metadata(theemf_path : PathBuf) -> Result<ThreemfMetadata, ServiceError> {
     let zip_file = File::open(theemf_path).await?;
     let mut buffered_reader = BufReader::new(zip_file);
     let mut zip = ZipFileReader::with_tokio(&mut buffered_reader).await?;
 
     let entries : Vec<_> = zip.file().entries().iter().cloned().collect();
+    let plate_json_regex = Regex::new(r"plate_(\d+)\.json$").unwrap();
+
+    let mut project_settings = None;
+    let mut slicer_pe_config = None;
+    let mut model_settings = None;
+    let mut slice_info = None;
+    let mut plate_jsons = Vec::new();
+    let mut core_model = None;
 
     for (i, entry) in entries.iter().enumerate() {
         let entry_filename = match entry.filename().as_str() {
-            Ok(name) => name,
+            Ok(name) => name.to_string(),
             Err(_) => continue,
         };
 
+        if entry_filename.ends_with("3dmodel.model") {
+            // Only the header is needed, the mesh data after it can be hundreds of megabytes
+            let mut reader = zip.reader_with_entry(i).await?.compat();
+            let mut head = vec![0u8; CORE_METADATA_SCAN_BYTES];
+            let mut read = 0;
+
+            while read < head.len() {
+                let count = reader.read(&mut head[read..]).await?;
+                if count == 0 {
+                    break;
+                }
+                read += count;
+            }
+
+            core_model = Some(String::from_utf8_lossy(&head[..read]).to_string());
+            continue;
+        }
+
+        let is_wanted = entry_filename.ends_with("project_settings.config")
+            || entry_filename.ends_with("Slic3r_PE.config")
+            || entry_filename.ends_with("model_settings.config")
+            || entry_filename.ends_with("Slic3r_PE_model.config")
+            || entry_filename.ends_with("slice_info.config")
+            || plate_json_regex.is_match(&entry_filename);
+
+        if !is_wanted {
+            continue;
+        }
+
+        let mut file = zip.reader_with_entry(i).await?;
+        let mut contents = String::new();
+        file.read_to_string_checked(&mut contents).await?;
+
         if entry_filename.ends_with("project_settings.config") {
-            let mut file = zip.reader_with_entry(i).await?;
-            let mut contents = String::new();
-            file.read_to_string_checked(&mut contents).await?;
-            return parse_project_settings_config(contents).await;
+            project_settings = Some(contents);
+        } else if entry_filename.ends_with("Slic3r_PE.config") {
+            slicer_pe_config = Some(contents);
+        } else if entry_filename.ends_with("slice_info.config") {
+            slice_info = Some(contents);
+        } else if let Some(captures) = plate_json_regex.captures(&entry_filename) {
+            plate_jsons.push((captures[1].parse::<u32>().unwrap_or(0), contents));
+        } else {
+            model_settings = Some(contents);
         }
+    }
 
-        if entry_filename.ends_with("Slic3r_PE.config") {
-            let mut file = zip.reader_with_entry(i).await?;
-            let mut contents = String::new();
-            file.read_to_string_checked(&mut contents).await?;
-            return parse_slicer_pe_config(contents).await;
+    let mut metadata = match (project_settings, slicer_pe_config) {
+        (Some(contents), _) => parse_project_settings_config(contents).await?,
+        (None, Some(contents)) => parse_slicer_pe_config(contents).await?,
+        (None, None) => ThreemfMetadata::default(),
+    };
+
+    if let Some(contents) = model_settings {
+        parse_objects_and_plates(&contents, &mut metadata);
+    }
+
+    for (index, contents) in plate_jsons {
+        parse_plate_json(index, &contents, &mut metadata);
+    }
+
+    if let Some(contents) = slice_info {
+        parse_slice_info(&contents, &mut metadata);
+    }
+
+    if let Some(contents) = core_model {
+        parse_core_metadata(&contents, &mut metadata);
+    }
+
+    metadata.plates.sort_by_key(|plate| plate.index);
+
+    Ok(metadata)
+}
+
+fn plate_mut(metadata: &mut ThreemfMetadata, index: u32) -> &mut ThreemfPlate {
+    match metadata.plates.iter().position(|plate| plate.index == index) {
+        Some(position) => &mut metadata.plates[position],
+        None => {
+            metadata.plates.push(ThreemfPlate { index, ..Default::default() });
+            metadata.plates.last_mut().unwrap()
         }
     }
+}
+
+// Reads <metadata key="..." value="..."/> pairs, Prusa adds a type="..." attribute in front of the key
+fn parse_metadata_pairs(data: &str) -> Vec<(String, String)> {
+    let re = Regex::new(r#"<metadata\b[^>]*?\bkey="([^"]*)"[^>]*?\bvalue="([^"]*)""#).unwrap();
 
-    return Err(ServiceError::InternalError("Failed to extract metadata".to_string()));
+    re.captures_iter(data)
+        .map(|captures| (captures[1].to_string(), unescape_xml(&captures[2])))
+        .collect()
 }
 
-fn extract_models_inner(theemf_path : PathBuf, temp_dir : &PathBuf, names_map : IndexMap<u32, String>) -> Result<(), ServiceError> {
-    let handle = std::fs::File::open(theemf_path)?;
-    let threemf_model = threemf::read(handle)?;
+fn parse_attributes(tag: &str) -> IndexMap<String, String> {
+    let re = Regex::new(r#"([\w:-]+)="([^"]*)""#).unwrap();
 
-    let objects: Vec<_> = threemf_model
-        .iter()
-        .flat_map(|model| &model.resources.object)
-        .filter(|obj| obj.mesh.is_some())
-        .collect();
+    re.captures_iter(tag)
+        .map(|captures| (captures[1].to_string(), unescape_xml(&captures[2])))
+        .collect()
+}
 
-    for obj in objects {
-        let mesh = obj.mesh.as_ref().unwrap();
-        
-        let obj_name = match names_map.get(&(obj.id as u32)) {
-            Some(name) => name.clone(),
-            None => obj.name.as_ref()
-                .map(|s| cleanse_evil_from_name(s))
-                .unwrap_or_else(|| format!("object_{}", obj.id))
+fn unescape_xml(value: &str) -> String {
+    value
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+// Bambu Studio / OrcaSlicer model_settings.config and PrusaSlicer Slic3r_PE_model.config
+fn parse_objects_and_plates(data: &str, metadata: &mut ThreemfMetadata) {
+    let object_regex = Regex::new(r#"(?s)<object\s+id="(\d+)"[^>]*>(.*?)</object>"#).unwrap();
+    let volume_regex = Regex::new(r"(?s)<part\b.*?</part>|<volume\b.*?</volume>").unwrap();
+
+    for captures in object_regex.captures_iter(data) {
+        let mut object = ThreemfObject {
+            id: captures[1].parse::<u32>().unwrap_or(u32::MAX),
+            ..Default::default()
         };
-        
-        let stl_path = temp_dir.join(format!("{}.stl", obj_name));
-
-        let mut triangles = Vec::new();
-        
-        for triangle in &mesh.triangles.triangle {
-            let v1 = &mesh.vertices.vertex[triangle.v1 as usize];
-            let v2 = &mesh.vertices.vertex[triangle.v2 as usize];
-            let v3 = &mesh.vertices.vertex[triangle.v3 as usize];
-            
-            let vertex1 = [v1.x as f32, v1.y as f32, v1.z as f32];
-            let vertex2 = [v2.x as f32, v2.y as f32, v2.z as f32];
-            let vertex3 = [v3.x as f32, v3.y as f32, v3.z as f32];
-            
-            let edge1 = [
-                vertex2[0] - vertex1[0],
-                vertex2[1] - vertex1[1],
-                vertex2[2] - vertex1[2],
-            ];
-            let edge2 = [
-                vertex3[0] - vertex1[0],
-                vertex3[1] - vertex1[1],
-                vertex3[2] - vertex1[2],
-            ];
-            let normal = [
-                edge1[1] * edge2[2] - edge1[2] * edge2[1],
-                edge1[2] * edge2[0] - edge1[0] * edge2[2],
-                edge1[0] * edge2[1] - edge1[1] * edge2[0],
-            ];
-            
-            triangles.push(stl_io::Triangle {
-                normal: Vector(normal),
-                vertices: [Vector(vertex1), Vector(vertex2), Vector(vertex3)],
-            });
+
+        // Parts and volumes carry their own settings, only the object level ones are collected
+        let object_data = volume_regex.replace_all(&captures[2], "");
+
+        for (key, value) in parse_metadata_pairs(&object_data) {
+            match key.as_str() {
+                "name" => object.name = Some(value),
+                "extruder" => object.extruder = value.parse::<u32>().ok(),
+                _ => { object.settings.insert(key, value); }
+            }
         }
 
-        let mut file = std::fs::File::create(stl_path)?;
-        stl_io::write_stl(&mut file, triangles.iter())?;
+        metadata.objects.push(object);
+    }
+
+    let plate_regex = Regex::new(r"(?s)<plate>(.*?)</plate>").unwrap();
+    let instance_regex = Regex::new(r"(?s)<model_instance>(.*?)</model_instance>").unwrap();
+
+    for captures in plate_regex.captures_iter(data) {
+        let plate_data = &captures[1];
+        let plate_settings = instance_regex.replace_all(plate_data, "");
+        let settings = parse_metadata_pairs(&plate_settings);
+
+        let index = match settings.iter().find(|(key, _)| key == "plater_id").and_then(|(_, value)| value.parse::<u32>().ok()) {
+            Some(index) => index,
+            None => continue,
+        };
+
+        let object_names: Vec<String> = instance_regex
+            .captures_iter(plate_data)
+            .filter_map(|instance| {
+                parse_metadata_pairs(&instance[1])
+                    .into_iter()
+                    .find(|(key, _)| key == "object_id")
+                    .and_then(|(_, value)| value.parse::<u32>().ok())
+            })
+            .filter_map(|object_id| metadata.objects.iter().find(|object| object.id == object_id))
+            .filter_map(|object| object.name.clone())
+            .collect();
+
+        let plate = plate_mut(metadata, index);
+
+        for (key, value) in settings {
+            match key.as_str() {
+                "plater_name" if !value.is_empty() => plate.name = Some(value),
+                "thumbnail_file" if !value.is_empty() => plate.thumbnail_file = Some(value),
+                _ => {}
+            }
+        }
+
+        plate.object_names = object_names;
+    }
+}
+
+// Metadata/plate_N.json, written by Bambu Studio and OrcaSlicer next to every plate thumbnail
+fn parse_plate_json(index: u32, data: &str, metadata: &mut ThreemfMetadata) {
+    let json = match serde_json::from_str::<serde_json::Value>(data) {
+        Ok(json) => json,
+        Err(_) => return,
+    };
+
+    let plate = plate_mut(metadata, index);
+
+    if plate.object_names.is_empty() {
+        plate.object_names = json["bbox_objects"]
+            .as_array()
+            .map(|objects| {
+                objects
+                    .iter()
+                    .filter_map(|object| object["name"].as_str())
+                    .map(|name| name.to_string())
+                    .unique()
+                    .collect()
+            })
+            .unwrap_or_default();
+    }
+
+    if plate.filaments.is_empty() {
+        let colours = json["filament_colors"].as_array().cloned().unwrap_or_default();
+        let ids = json["filament_ids"].as_array().cloned().unwrap_or_default();
+
+        plate.filaments = colours
+            .iter()
+            .enumerate()
+            .map(|(i, colour)| ThreemfFilament {
+                id: ids.get(i).and_then(|id| id.as_u64()).map(|id| id as u32 + 1).unwrap_or(i as u32 + 1),
+                colour: colour.as_str().map(|c| c.to_string()),
+                ..Default::default()
+            })
+            .collect();
+    }
+}
+
+// Metadata/slice_info.config, only contains estimates once the project has been sliced
+fn parse_slice_info(data: &str, metadata: &mut ThreemfMetadata) {
+    let plate_regex = Regex::new(r"(?s)<plate>(.*?)</plate>").unwrap();
+    let filament_regex = Regex::new(r"<filament\b([^>]*)/?>").unwrap();
+
+    for captures in plate_regex.captures_iter(data) {
+        let plate_data = &captures[1];
+        let settings = parse_metadata_pairs(plate_data);
+        let setting = |name: &str| settings.iter().find(|(key, _)| key == name).map(|(_, value)| value.clone());
+
+        let index = match setting("index").and_then(|value| value.parse::<u32>().ok()) {
+            Some(index) => index,
+            None => continue,
+        };
+
+        let print_time_seconds = setting("prediction").and_then(|value| value.parse::<i64>().ok());
+        let weight_g = setting("weight").and_then(|value| value.parse::<f64>().ok());
+
+        let filaments: Vec<ThreemfFilament> = filament_regex
+            .captures_iter(plate_data)
+            .map(|filament| {
+                let attributes = parse_attributes(&filament[1]);
+
+                ThreemfFilament {
+                    id: attributes.get("id").and_then(|id| id.parse::<u32>().ok()).unwrap_or(0),
+                    material_type: attributes.get("type").cloned(),
+                    colour: attributes.get("color").cloned(),
+                    used_m: attributes.get("used_m").and_then(|v| v.parse::<f64>().ok()),
+                    used_g: attributes.get("used_g").and_then(|v| v.parse::<f64>().ok()),
+                }
+            })
+            .collect();
+
+        let plate = plate_mut(metadata, index);
+        plate.print_time_seconds = print_time_seconds.or(plate.print_time_seconds);
+        plate.weight_g = weight_g.or(plate.weight_g);
+
+        if !filaments.is_empty() {
+            plate.filaments = filaments;
+        }
+    }
+}
+
+// 3MF core metadata, written as <metadata name="Title">...</metadata> at the start of 3D/3dmodel.model
+fn parse_core_metadata(data: &str, metadata: &mut ThreemfMetadata) {
+    let re = Regex::new(r#"<metadata\s+name="([^"]+)"[^>]*>([^<]*)</metadata>"#).unwrap();
+
+    for captures in re.captures_iter(data) {
+        let value = unescape_xml(captures[2].trim());
+
+        if value.is_empty() {
+            continue;
+        }
+
+        match &captures[1] {
+            "Title" => metadata.title = Some(value),
+            "Designer" => metadata.designer = Some(value),
+            "License" => metadata.license = Some(value),
+            "CreationDate" => metadata.creation_date = Some(value),
+            _ => {}
+        }
+    }
+}
+
+// Object and part extruders from model_settings.config / Slic3r_PE_model.config, parts without one use their object's
+fn parse_extruders(data: &str) -> HashMap<u32, u32> {
+    let object_regex = Regex::new(r#"(?s)<object\s+id="(\d+)"[^>]*>(.*?)</object>"#).unwrap();
+    let part_regex = Regex::new(r#"(?s)<part\s+id="(\d+)"[^>]*>(.*?)</part>"#).unwrap();
+    let mut extruders = HashMap::new();
+
+    let extruder_of = |data: &str| {
+        parse_metadata_pairs(data)
+            .into_iter()
+            .find(|(key, _)| key == "extruder")
+            .and_then(|(_, value)| value.parse::<u32>().ok())
+    };
+
+    for captures in object_regex.captures_iter(data) {
+        let object_id = captures[1].parse::<u32>().unwrap_or(u32::MAX);
+        let object_extruder = extruder_of(&part_regex.replace_all(&captures[2], ""));
+
+        if let Some(extruder) = object_extruder {
+            extruders.insert(object_id, extruder);
+        }
+
+        for part in part_regex.captures_iter(&captures[2]) {
+            let part_id = part[1].parse::<u32>().unwrap_or(u32::MAX);
+
+            if let Some(extruder) = extruder_of(&part[2]).or(object_extruder) {
+                extruders.insert(part_id, extruder);
+            }
+        }
+    }
+
+    extruders
+}
+
+fn extract_models_inner(theemf_path : PathBuf, temp_dir : &PathBuf, names_map : IndexMap<u32, String>, extruders : HashMap<u32, u32>, colours : Vec<String>, format : MeshFormat) -> Result<(), ServiceError> {
+    let meshes = mesh_service::read_3mf(&theemf_path)?;
+
+    for mut mesh in meshes {
+        mesh.name = match names_map.get(&mesh.object_id) {
+            Some(name) => cleanse_evil_from_name(name),
+            None => cleanse_evil_from_name(&mesh.name),
+        };
+
+        if mesh.name.is_empty() {
+            mesh.name = format!("object_{}", mesh.object_id);
+        }
+
+        mesh.extruder = extruders.get(&mesh.object_id).copied();
+
+        // Objects placed more than once end up as multiple files
+        let path = export_service::ensure_unique_file(temp_dir, &mesh.name, format.to_extension());
+        mesh_service::write_meshes(&[mesh], format, &colours, &path)?;
     }
 
     Ok(())
 }
 
-pub async fn extract_models(model : &Model, user: &User, app_state: &AppState) -> Result<ImportState, ServiceError> {
+pub async fn extract_models(model : &Model, user: &User, format: MeshFormat, app_state: &AppState) -> Result<ImportState, ServiceError> {
     if !model.blob.filetype.contains("3mf") {
         return Err(ServiceError::InternalError("Model is not a 3MF file".to_string()));
     }
@@ -268,9 +611,15 @@ pub async fn extract_models(model : &Model, user: &User, app_state: &AppState) -
     {
         let temp_dir = temp_dir.clone();
         let names_map = fetch_model_settings_config_from_3mf(theemf_path.clone()).await.unwrap_or_default();
+        let extruders = read_model_settings_config(theemf_path.clone()).await?
+            .map(|data| parse_extruders(&data))
+            .unwrap_or_default();
+        let colours = extract_metadata(model, app_state).await
+            .map(|metadata| metadata.filament_colours)
+            .unwrap_or_default();
 
         tokio::task::spawn_blocking(move || {
-            extract_models_inner(theemf_path, &temp_dir, names_map)
+            extract_models_inner(theemf_path, &temp_dir, names_map, extruders, colours, format)
         }).await??;
     }
 
@@ -278,4 +627,341 @@ pub async fn extract_models(model : &Model, user: &User, app_state: &AppState) -
     let result = import_service::import_path(temp_dir.to_str().unwrap(), app_state, import_state).await?;
 
     Ok(result)
-}
\ No newline at end of file
+}
+
+async fn read_model_settings_config(theemf_path : PathBuf) -> Result<Option<String>, ServiceError> {
+    let zip_file = File::open(theemf_path).await?;
+    let mut buffered_reader = BufReader::new(zip_file);
+    let mut zip = ZipFileReader::with_tokio(&mut buffered_reader).await?;
+
+    let entries : Vec<_> = zip.file().entries().iter().cloned().collect();
+
+    for (i, entry) in entries.iter().enumerate() {
+        let entry_filename = match entry.filename().as_str() {
+            Ok(name) => name,
+            Err(_) => continue,
+        };
+
+        if entry_filename.ends_with("model_settings.config") || entry_filename.ends_with("Slic3r_PE_model.config") {
+            let mut file = zip.reader_with_entry(i).await?;
+            let mut contents = String::new();
+            file.read_to_string_checked(&mut contents).await?;
+            return Ok(Some(contents));
+        }
+    }
+
+    Ok(None)
+}
+
+struct PlateLayout {
+    index: u32,
+    name: Option<String>,
+    object_ids: Vec<u32>,
+}
+
+// Bambu Studio and OrcaSlicer lay out plates in a grid with a gap of a fifth of the bed between them
+const PLATE_GAP_RATIO: f64 = 1.0 / 5.0;
+
+// Splits a Bambu Studio / OrcaSlicer project into one project per plate, and imports them as a new group
+pub async fn split_plates(model : &Model, user: &User, app_state: &AppState) -> Result<ImportState, ServiceError> {
+    if !model.blob.filetype.contains("3mf") {
+        return Err(ServiceError::InternalError("Model is not a 3MF file".to_string()));
+    }
+
+    let theemf_path = export_service::get_model_path_for_blob(&model.blob, app_state);
+    let entries = read_all_entries(theemf_path).await?;
+
+    let model_settings = entries
+        .iter()
+        .find(|(name, _)| name.ends_with("model_settings.config"))
+        .map(|(_, data)| String::from_utf8_lossy(data).to_string())
+        .ok_or_else(|| ServiceError::InternalError("3MF file has no plates".to_string()))?;
+
+    let plates = parse_plate_layouts(&model_settings);
+
+    if plates.iter().all(|plate| plate.object_ids.is_empty()) {
+        return Err(ServiceError::InternalError("3MF file has no plates".to_string()));
+    }
+
+    let bed_size = entries
+        .iter()
+        .find(|(name, _)| name.ends_with("project_settings.config"))
+        .and_then(|(_, data)| parse_bed_size(data))
+        .unwrap_or((256.0, 256.0));
+
+    let mut temp_dir = export_service::get_temp_dir("split");
+    let safe_model_name = cleanse_evil_from_name(&model.name);
+    temp_dir.push(&safe_model_name);
+    std::fs::create_dir(&temp_dir)?;
+
+    let column_count = plate_column_count(plates.len());
+
+    for (position, plate) in plates.iter().enumerate() {
+        if plate.object_ids.is_empty() {
+            continue;
+        }
+
+        let offset = (
+            (position % column_count) as f64 * bed_size.0 * (1.0 + PLATE_GAP_RATIO),
+            -((position / column_count) as f64) * bed_size.1 * (1.0 + PLATE_GAP_RATIO),
+        );
+
+        let plate_name = match &plate.name {
+            Some(name) => cleanse_evil_from_name(name),
+            None => format!("Plate {}", plate.index),
+        };
+
+        let plate_path = temp_dir.join(format!("{} - {}.3mf", safe_model_name, plate_name));
+        write_plate_project(&entries, plate, offset, &plate_path).await?;
+    }
+
+    let import_state = ImportState::new(model.link.clone(), false, true, false, user.clone());
+    let result = import_service::import_path(temp_dir.to_str().unwrap(), app_state, import_state).await?;
+
+    Ok(result)
+}
+
+async fn read_all_entries(theemf_path : PathBuf) -> Result<Vec<(String, Vec<u8>)>, ServiceError> {
+    let zip_file = File::open(theemf_path).await?;
+    let mut buffered_reader = BufReader::new(zip_file);
+    let mut zip = ZipFileReader::with_tokio(&mut buffered_reader).await?;
+
+    let entries : Vec<_> = zip.file().entries().iter().cloned().collect();
+    let mut result = Vec::new();
+
+    for (i, entry) in entries.iter().enumerate() {
+        let entry_filename = match entry.filename().as_str() {
+            Ok(name) => name.to_string(),
+            Err(_) => continue,
+        };
+
+        if entry.dir()? {
+            continue;
+        }
+
+        let mut file = zip.reader_with_entry(i).await?;
+        let mut contents = Vec::new();
+        file.read_to_end_checked(&mut contents).await?;
+        result.push((entry_filename, contents));
+    }
+
+    Ok(result)
+}
+
+fn parse_plate_layouts(data: &str) -> Vec<PlateLayout> {
+    let plate_regex = Regex::new(r"(?s)<plate>(.*?)</plate>").unwrap();
+    let instance_regex = Regex::new(r"(?s)<model_instance>(.*?)</model_instance>").unwrap();
+
+    let mut plates: Vec<PlateLayout> = plate_regex
+        .captures_iter(data)
+        .filter_map(|captures| {
+            let plate_data = &captures[1];
+            let settings = parse_metadata_pairs(&instance_regex.replace_all(plate_data, ""));
+            let setting = |name: &str| settings.iter().find(|(key, _)| key == name).map(|(_, value)| value.clone());
+
+            let index = setting("plater_id").and_then(|value| value.parse::<u32>().ok())?;
+
+            let object_ids = instance_regex
+                .captures_iter(plate_data)
+                .filter_map(|instance| {
+                    parse_metadata_pairs(&instance[1])
+                        .into_iter()
+                        .find(|(key, _)| key == "object_id")
+                        .and_then(|(_, value)| value.parse::<u32>().ok())
+                })
+                .unique()
+                .collect();
+
+            Some(PlateLayout {
+                index,
+                name: setting("plater_name").filter(|name| !name.is_empty()),
+                object_ids,
+            })
+        })
+        .collect();
+
+    plates.sort_by_key(|plate| plate.index);
+    plates
+}
+
+// Reads the bed size out of "printable_area": ["0x0", "256x0", "256x256", "0x256"]
+fn parse_bed_size(data: &[u8]) -> Option<(f64, f64)> {
+    let json = serde_json::from_slice::<serde_json::Value>(data).ok()?;
+
+    let points: Vec<(f64, f64)> = json["printable_area"]
+        .as_array()?
+        .iter()
+        .filter_map(|point| point.as_str())
+        .filter_map(|point| {
+            let (x, y) = point.split_once('x')?;
+            Some((x.trim().parse::<f64>().ok()?, y.trim().parse::<f64>().ok()?))
+        })
+        .collect();
+
+    let width = points.iter().map(|p| p.0).fold(f64::MIN, f64::max) - points.iter().map(|p| p.0).fold(f64::MAX, f64::min);
+    let depth = points.iter().map(|p| p.1).fold(f64::MIN, f64::max) - points.iter().map(|p| p.1).fold(f64::MAX, f64::min);
+
+    if points.is_empty() || width <= 0.0 || depth <= 0.0 {
+        return None;
+    }
+
+    Some((width, depth))
+}
+
+// Same rounding as the slicers: 2 plates are 2 columns, 5 plates are 3 columns
+fn plate_column_count(plate_count: usize) -> usize {
+    let value = (plate_count as f64).sqrt();
+    let rounded = value.round();
+
+    let columns = if value > rounded { rounded + 1.0 } else { rounded };
+    (columns as usize).max(1)
+}
+
+async fn write_plate_project(entries: &[(String, Vec<u8>)], plate: &PlateLayout, offset: (f64, f64), path: &PathBuf) -> Result<(), ServiceError> {
+    let plate_file_regex = Regex::new(r"^Metadata/(plate|plate_no_light|top|pick)_(\d+)\.(png|json)$").unwrap();
+    let object_file_regex = Regex::new(r"^3D/Objects/.+\.model$").unwrap();
+
+    let mut referenced_paths = Vec::new();
+    let mut files: Vec<(String, Vec<u8>)> = Vec::new();
+
+    // The root model decides which object files are still needed, so it's rewritten first
+    for (name, data) in entries.iter().filter(|(name, _)| name == "3D/3dmodel.model") {
+        let (model, paths) = rewrite_root_model(&String::from_utf8_lossy(data), &plate.object_ids, offset);
+        referenced_paths = paths;
+        files.push((name.clone(), model.into_bytes()));
+    }
+
+    for (name, data) in entries {
+        if name == "3D/3dmodel.model" {
+            continue;
+        }
+
+        if name.ends_with("model_settings.config") {
+            files.push((name.clone(), rewrite_model_settings(&String::from_utf8_lossy(data), plate).into_bytes()));
+        } else if name.ends_with(".model.rels") {
+            files.push((name.clone(), rewrite_relationships(&String::from_utf8_lossy(data), &referenced_paths).into_bytes()));
+        } else if object_file_regex.is_match(name) {
+            if referenced_paths.iter().any(|path| path.trim_start_matches('/') == name) {
+                files.push((name.clone(), data.clone()));
+            }
+        } else if let Some(captures) = plate_file_regex.captures(name) {
+            // The plate becomes the first and only plate of the new project
+            if captures[2].parse::<u32>().ok() == Some(plate.index) {
+                files.push((format!("Metadata/{}_1.{}", &captures[1], &captures[3]), data.clone()));
+            }
+        } else if name.ends_with(".gcode") || name.ends_with(".gcode.md5") || name.ends_with("slice_info.config") {
+            // Sliced results belong to the original plate numbering and are no longer valid
+            continue;
+        } else {
+            files.push((name.clone(), data.clone()));
+        }
+    }
+
+    let mut file = File::create(path).await?;
+    let mut writer = ZipFileWriter::with_tokio(&mut file);
+
+    for (name, data) in files {
+        let builder = ZipEntryBuilder::new(name.into(), Compression::Deflate);
+        writer.write_entry_whole(builder, &data).await?;
+    }
+
+    writer.close().await?;
+
+    Ok(())
+}
+
+// Keeps only the objects and build items of the plate, and moves them onto the first plate
+fn rewrite_root_model(data: &str, object_ids: &[u32], offset: (f64, f64)) -> (String, Vec<String>) {
+    let object_regex = Regex::new(r#"(?s)<object\s+id="(\d+)"[^>]*?(?:/>|>.*?</object>)"#).unwrap();
+    let path_regex = Regex::new(r#"p:path="([^"]+)""#).unwrap();
+    let item_regex = Regex::new(r#"<item\b[^>]*?\bobjectid="(\d+)"[^>]*/>"#).unwrap();
+    let transform_regex = Regex::new(r#"transform="([^"]*)""#).unwrap();
+
+    let is_kept = |id: &str| id.parse::<u32>().map(|id| object_ids.contains(&id)).unwrap_or(false);
+    let mut referenced_paths = Vec::new();
+
+    let data = object_regex.replace_all(data, |captures: &regex::Captures| {
+        if !is_kept(&captures[1]) {
+            return String::new();
+        }
+
+        for path in path_regex.captures_iter(&captures[0]) {
+            referenced_paths.push(path[1].to_string());
+        }
+
+        captures[0].to_string()
+    });
+
+    let data = item_regex.replace_all(&data, |captures: &regex::Captures| {
+        if !is_kept(&captures[1]) {
+            return String::new();
+        }
+
+        transform_regex.replace(&captures[0], |transform: &regex::Captures| {
+            let mut values: Vec<f64> = transform[1]
+                .split_whitespace()
+                .filter_map(|value| value.parse::<f64>().ok())
+                .collect();
+
+            if values.len() != 12 {
+                return transform[0].to_string();
+            }
+
+            values[9] -= offset.0;
+            values[10] -= offset.1;
+
+            format!("transform=\"{}\"", values.iter().map(|value| value.to_string()).join(" "))
+        }).to_string()
+    });
+
+    (data.to_string(), referenced_paths)
+}
+
+fn rewrite_model_settings(data: &str, plate: &PlateLayout) -> String {
+    let object_regex = Regex::new(r#"(?s)<object\s+id="(\d+)"[^>]*>.*?</object>"#).unwrap();
+    let plate_regex = Regex::new(r"(?s)<plate>.*?</plate>").unwrap();
+    let assemble_regex = Regex::new(r#"<assemble_item\b[^>]*?\bobject_id="(\d+)"[^>]*/>"#).unwrap();
+    let plater_id_regex = Regex::new(r#"(<metadata\s+key="plater_id"\s+value=")\d+(")"#).unwrap();
+    let plate_file_regex = Regex::new(&format!(r"\b(plate|plate_no_light|top|pick)_{}\.", plate.index)).unwrap();
+
+    let is_kept = |id: &str| id.parse::<u32>().map(|id| plate.object_ids.contains(&id)).unwrap_or(false);
+
+    let data = object_regex.replace_all(data, |captures: &regex::Captures| {
+        if is_kept(&captures[1]) { captures[0].to_string() } else { String::new() }
+    });
+
+    let data = assemble_regex.replace_all(&data, |captures: &regex::Captures| {
+        if is_kept(&captures[1]) { captures[0].to_string() } else { String::new() }
+    });
+
+    let data = plate_regex.replace_all(&data, |captures: &regex::Captures| {
+        let plate_data = &captures[0];
+        let index = parse_metadata_pairs(plate_data)
+            .into_iter()
+            .find(|(key, _)| key == "plater_id")
+            .and_then(|(_, value)| value.parse::<u32>().ok());
+
+        if index != Some(plate.index) {
+            return String::new();
+        }
+
+        let plate_data = plater_id_regex.replace(plate_data, "${1}1${2}");
+        plate_file_regex.replace_all(&plate_data, "${1}_1.").to_string()
+    });
+
+    data.to_string()
+}
+
+fn rewrite_relationships(data: &str, referenced_paths: &[String]) -> String {
+    let relationship_regex = Regex::new(r#"<Relationship\b[^>]*?\bTarget="([^"]+)"[^>]*/>"#).unwrap();
+
+    relationship_regex.replace_all(data, |captures: &regex::Captures| {
+        let target = &captures[1];
+
+        if !target.ends_with(".model") || referenced_paths.iter().any(|path| path == target) {
+            captures[0].to_string()
+        } else {
+            String::new()
+        }
+    }).to_string()
+}
@@ -1,17 +1,19 @@
 use std::{panic, path::PathBuf};
 
-use db::{blob_db, model::{Blob, FileType}};
+use db::{blob_db, model::{Blob, FileType}, thumbnail_hash_db};
 use image::imageops::FilterType::Triangle;
 use libmeshthumbnail::{extract_image, parse_model, render};
-use tokio::task::JoinSet;
+use tokio::task::{JoinSet, spawn_blocking};
 use vek::{Vec2, Vec3};
 
-use crate::{AppState, ServiceError, export_service::{get_image_path_for_blob, get_model_path_for_blob}, import_state::{ImportState, ImportStatus}};
+use crate::{AppState, ServiceError, bgcode_service, resin_service, export_service::{get_image_path_for_blob, get_model_path_for_blob, get_temp_dir}, import_state::{ImportState, ImportStatus}};
 pub use libmeshthumbnail::parse_model::{convert_step_path_to_stl, convert_step_to_stl};
 
 const IMAGE_WIDTH: usize = 400;
 const IMAGE_HEIGHT: usize = 400;
 const IMAGE_SIZE: Vec2<usize> = Vec2::new(IMAGE_WIDTH, IMAGE_HEIGHT);
+const HASH_WIDTH: u32 = 9;
+const HASH_HEIGHT: u32 = 8;
 
 fn render(model_path: &PathBuf, image_path: &PathBuf, color: Vec3<u8>, rotation: Vec3<f32>) -> Result<(), ServiceError> {
     let mesh = match parse_model::handle_parse(model_path) {
@@ -53,6 +55,24 @@ fn process(model_path: &PathBuf, image_path: &PathBuf, color: Vec3<u8>, rotation
         )));
     }
 
+    // Sliced resin files have no mesh to render, only their embedded preview
+    if extension.is_resin() {
+        if let Ok(Some(mut image)) = resin_service::read_preview(&model_path) {
+            image = image.resize_to_fill(IMAGE_WIDTH as u32, IMAGE_HEIGHT as u32, Triangle);
+            image.save(&image_path)?;
+            return Ok(());
+        }
+
+        return Err(ServiceError::InternalError(format!(
+            "No preview found in resin file at path {:?}",
+            model_path
+        )));
+    }
+
+    if extension.is_bgcode() {
+        return process_bgcode(model_path, image_path, color, rotation, prefer_gcode_thumbnail);
+    }
+
     if (prefer_3mf_thumbnail && extension.is_3mf())
         || (prefer_gcode_thumbnail && extension.is_gcode()) {
             if let Ok(Some(mut image)) = extract_image::handle_extract_image(&model_path) {
@@ -80,6 +100,98 @@ fn process(model_path: &PathBuf, image_path: &PathBuf, color: Vec3<u8>, rotation
     )))
 }
 
+// Binary G-code can only be rendered once it is decoded back into text G-code
+fn process_bgcode(model_path: &PathBuf, image_path: &PathBuf, color: Vec3<u8>, rotation: Vec3<f32>, prefer_gcode_thumbnail : bool) -> Result<(), ServiceError> {
+    if prefer_gcode_thumbnail {
+        if let Ok(Some(mut image)) = bgcode_service::read_thumbnail(model_path) {
+            image = image.resize_to_fill(IMAGE_WIDTH as u32, IMAGE_HEIGHT as u32, Triangle);
+            image.save(&image_path)?;
+            return Ok(());
+        }
+    }
+
+    let temp_dir = get_temp_dir("bgcode");
+    let gcode_path = temp_dir.join("model.gcode");
+
+    let rendered = bgcode_service::decode_to_file(model_path, &gcode_path)
+        .and_then(|_| render(&gcode_path, image_path, color, rotation));
+
+    let _ = std::fs::remove_dir_all(&temp_dir);
+
+    if rendered.is_ok() {
+        return Ok(());
+    }
+
+    if !prefer_gcode_thumbnail {
+        if let Ok(Some(mut image)) = bgcode_service::read_thumbnail(model_path) {
+            image = image.resize_to_fill(IMAGE_WIDTH as u32, IMAGE_HEIGHT as u32, Triangle);
+            image.save(&image_path)?;
+            return Ok(());
+        }
+    }
+
+    Err(ServiceError::InternalError(format!(
+        "Failed to generate thumbnail for model at path {:?}",
+        model_path
+    )))
+}
+
+// Difference hash of the thumbnail: the image is shrunk to 9x8 greyscale and each bit says whether a pixel is brighter than its right neighbour.
+// Transparent pixels count as white, so rendered and embedded thumbnails compare on the model and not the background.
+pub fn perceptual_hash(image_path: &PathBuf) -> Result<u64, ServiceError> {
+    let image = image::open(image_path)?.to_rgba8();
+    let small = image::imageops::resize(&image, HASH_WIDTH, HASH_HEIGHT, Triangle);
+
+    let brightness = |x: u32, y: u32| {
+        let [r, g, b, a] = small.get_pixel(x, y).0;
+        let alpha = a as f64 / 255.0;
+        let luma = 0.299 * r as f64 + 0.587 * g as f64 + 0.114 * b as f64;
+
+        luma * alpha + 255.0 * (1.0 - alpha)
+    };
+
+    let mut hash = 0u64;
+
+    for y in 0..HASH_HEIGHT {
+        for x in 0..HASH_WIDTH - 1 {
+            hash <<= 1;
+
+            if brightness(x, y) > brightness(x + 1, y) {
+                hash |= 1;
+            }
+        }
+    }
+
+    Ok(hash)
+}
+
+// Hashes thumbnails generated before thumbnails were hashed
+pub async fn hash_missing_thumbnails(app_state: &AppState) -> Result<(), ServiceError> {
+    let blobs = thumbnail_hash_db::get_blobs_without_thumbnail_hash(&app_state.db).await?;
+
+    for blob in blobs {
+        let image_path = get_image_path_for_blob(&blob, app_state);
+
+        if !image_path.exists() {
+            continue;
+        }
+
+        let hash = match spawn_blocking(move || perceptual_hash(&image_path)).await.map_err(ServiceError::from).and_then(|result| result) {
+            Ok(hash) => hash,
+            Err(e) => {
+                println!("Failed to hash thumbnail of blob {}: {}", blob.id, e);
+                continue;
+            }
+        };
+
+        if let Err(e) = thumbnail_hash_db::set_thumbnail_hash(&app_state.db, blob.id, hash).await {
+            println!("Failed to store thumbnail hash of blob {}: {}", blob.id, e);
+        }
+    }
+
+    Ok(())
+}
+
 pub async fn generate_all_thumbnails(
     app_state: &AppState,
     overwrite: bool,
@@ -123,15 +235,15 @@ pub async fn generate_thumbnails(
         (color & 0xFF) as u8,
     );
 
-    let paths: Vec<(PathBuf, PathBuf)> = models
+    let paths: Vec<(i64, PathBuf, PathBuf)> = models
         .iter()
         .map(|blob| {
             let model_path = get_model_path_for_blob(blob, app_state);
             let image_path = get_image_path_for_blob(blob, app_state);
 
-            (model_path, image_path)
+            (blob.id, model_path, image_path)
         })
-        .filter(|(_, image_path)| {
+        .filter(|(_, _, image_path)| {
             overwrite || !image_path.exists()
         })
         .collect();
@@ -140,13 +252,16 @@ pub async fn generate_thumbnails(
 
     let mut futures = JoinSet::new();
     let mut active = 0;
+    let mut hashes = Vec::new();
 
-    for (model_path, image_path) in paths {
+    for (blob_id, model_path, image_path) in paths {
         let color = color.clone();
         let rotation = rotation.clone();
         futures.spawn_blocking(move || {
             // Ignore errors for now
-            let _ = process(&model_path, &image_path, color, rotation, fallback_3mf_thumbnail, prefer_3mf_thumbnail, prefer_gcode_thumbnail);
+            process(&model_path, &image_path, color, rotation, fallback_3mf_thumbnail, prefer_3mf_thumbnail, prefer_gcode_thumbnail).ok()?;
+
+            perceptual_hash(&image_path).ok().map(|hash| (blob_id, hash))
         });
         active += 1;
 
@@ -155,7 +270,8 @@ pub async fn generate_thumbnails(
                 match res {
                     Err(err) if err.is_panic() => panic::resume_unwind(err.into_panic()),
                     Err(err) => panic!("{err}"),
-                    Ok(_) => {
+                    Ok(hash) => {
+                        hashes.extend(hash);
                         active -= 1;
                         import_state.update_finished_thumbnails_count(1);
                     }
@@ -164,7 +280,12 @@ pub async fn generate_thumbnails(
         }
     }
 
-    futures.join_all().await;
+    hashes.extend(futures.join_all().await.into_iter().flatten());
+
+    for (blob_id, hash) in hashes {
+        thumbnail_hash_db::set_thumbnail_hash(&app_state.db, blob_id, hash).await?;
+    }
+
     import_state.update_finished_thumbnails_count(import_state.model_count - import_state.finished_thumbnails_count);
     import_state.update_status(ImportStatus::FinishedThumbnails);
     Ok(())
@@ -0,0 +1,607 @@
+use std::collections::HashSet;
+use std::io::{SeekFrom, Write};
+use std::path::PathBuf;
+
+use async_zip::tokio::read::seek::ZipFileReader;
+use async_zip::tokio::write::ZipFileWriter;
+use async_zip::{Compression, ZipEntryBuilder};
+use chrono::{Datelike, Local, Timelike};
+use db::model::{FileType, Model};
+use flate2::Crc;
+use flate2::write::DeflateEncoder;
+use futures::AsyncWriteExt as FuturesAsyncWriteExt;
+use tokio::fs::File;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeekExt, AsyncWrite, AsyncWriteExt, BufReader, DuplexStream};
+use tokio_util::compat::{FuturesAsyncReadCompatExt, TokioAsyncReadCompatExt};
+
+use crate::app_state::AppState;
+use crate::conversion_service;
+use crate::export_service::{get_export_extension, get_model_path_for_blob, name_collection_of_models};
+use crate::mesh_service::MeshFormat;
+use crate::service_error::ServiceError;
+use crate::util::cleanse_evil_from_name;
+
+const PIPE_SIZE: usize = 256 * 1024;
+const LOCAL_HEADER_SIZE: u64 = 30;
+const DATA_DESCRIPTOR_SIZE: u64 = 16;
+const CENTRAL_HEADER_SIZE: u64 = 46;
+const END_OF_CENTRAL_DIRECTORY_SIZE: u64 = 22;
+// The crc and sizes follow the data in a descriptor, as they are only known once an entry is deflated. Names are utf-8
+const ENTRY_FLAGS: u16 = 0x0008 | 0x0800;
+const METHOD_STORED: u16 = 0;
+const METHOD_DEFLATED: u16 = 8;
+
+enum ZipStreamSource {
+    // Deflated up front, for small entries like metadata.json
+    Deflated { data: Vec<u8>, crc: u32 },
+    File(PathBuf),
+    // The single entry of a zipped blob, decompressed while streaming
+    ZippedFile(PathBuf),
+    // The compressed data of the single entry of a zipped blob, copied over as it is
+    Precompressed { path: PathBuf, offset: u64, crc: u32 },
+}
+
+struct ZipStreamEntry {
+    name: String,
+    size: u64,
+    method: u16,
+    // Unknown for entries that are deflated while streaming
+    compressed_size: Option<u64>,
+    source: ZipStreamSource,
+}
+
+pub struct ZipStream {
+    pub file_name: String,
+    // Only known when no entry has to be deflated while streaming and the archive fits a plain zip
+    pub content_length: Option<u64>,
+    pub reader: DuplexStream,
+}
+
+// 3MF and SL1 files are zips themselves, deflating them again only costs time
+fn is_compressed_format(name: &str) -> bool {
+    matches!(FileType::from_extension(name), FileType::Threemf | FileType::Sl1 | FileType::Sl1s)
+}
+
+fn bytes_entry(name: String, bytes: &[u8]) -> Result<ZipStreamEntry, ServiceError> {
+    let mut crc = Crc::new();
+    crc.update(bytes);
+
+    let mut encoder = DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(bytes)?;
+    let data = encoder.finish()?;
+
+    Ok(ZipStreamEntry {
+        name,
+        size: bytes.len() as u64,
+        method: METHOD_DEFLATED,
+        compressed_size: Some(data.len() as u64),
+        source: ZipStreamSource::Deflated { data, crc: crc.sum() },
+    })
+}
+
+async fn file_entry(name: String, path: PathBuf) -> Result<ZipStreamEntry, ServiceError> {
+    let size = tokio::fs::metadata(&path).await?.len();
+
+    let (method, compressed_size) = match is_compressed_format(&name) {
+        true => (METHOD_STORED, Some(size)),
+        false => (METHOD_DEFLATED, None),
+    };
+
+    Ok(ZipStreamEntry {
+        name,
+        size,
+        method,
+        compressed_size,
+        source: ZipStreamSource::File(path),
+    })
+}
+
+async fn zipped_file_entry(name: String, path: PathBuf) -> Result<ZipStreamEntry, ServiceError> {
+    let mut file = BufReader::new(File::open(&path).await?);
+    let zip = ZipFileReader::with_tokio(&mut file).await?;
+
+    let entry = match zip.file().entries().first() {
+        Some(entry) => entry.clone(),
+        None => return Err(ServiceError::InternalError(format!("{} is an empty archive", path.to_string_lossy()))),
+    };
+
+    let method = match entry.compression() {
+        Compression::Stored => METHOD_STORED,
+        Compression::Deflate => METHOD_DEFLATED,
+        _ => {
+            return Ok(ZipStreamEntry {
+                name,
+                size: entry.uncompressed_size(),
+                method: METHOD_DEFLATED,
+                compressed_size: None,
+                source: ZipStreamSource::ZippedFile(path),
+            });
+        }
+    };
+
+    // The data starts after the local header, whose extra field can differ from the one in the central directory
+    let mut header = [0u8; LOCAL_HEADER_SIZE as usize];
+    let mut file = file.into_inner();
+    file.seek(SeekFrom::Start(entry.header_offset())).await?;
+    file.read_exact(&mut header).await?;
+
+    let name_length = u16::from_le_bytes([header[26], header[27]]) as u64;
+    let extra_length = u16::from_le_bytes([header[28], header[29]]) as u64;
+
+    Ok(ZipStreamEntry {
+        name,
+        size: entry.uncompressed_size(),
+        method,
+        compressed_size: Some(entry.compressed_size()),
+        source: ZipStreamSource::Precompressed {
+            path,
+            offset: entry.header_offset() + LOCAL_HEADER_SIZE + name_length + extra_length,
+            crc: entry.crc32(),
+        },
+    })
+}
+
+// Streams the models as a zip, in the same layout as export_zip_to_temp_folder, without writing the archive to disk.
+// Conversions happen before this returns, so a failed conversion is still reported as an error instead of a broken download
+pub async fn stream_models_as_zip(
+    models: Vec<Model>,
+    format: Option<MeshFormat>,
+    app_state: &AppState,
+) -> Result<ZipStream, ServiceError> {
+    let configuration = app_state.get_configuration();
+    let file_name = format!("{}.zip", name_collection_of_models(&models));
+    let mut entries = Vec::with_capacity(models.len() + 1);
+    let mut names = HashSet::new();
+
+    if configuration.export_metadata {
+        names.insert(String::from("metadata.json"));
+        entries.push(bytes_entry(String::from("metadata.json"), &serde_json::to_vec_pretty(&models)?)?);
+    }
+
+    for model in &models {
+        let extension = get_export_extension(&model.blob, format);
+        let base_name = cleanse_evil_from_name(&model.name);
+        let mut name = format!("{}.{}", base_name, extension);
+        let mut counter = 1;
+
+        while !names.insert(name.clone()) {
+            name = format!("{}_{}.{}", base_name, counter, extension);
+            counter += 1;
+        }
+
+        let file_type = model.blob.to_file_type();
+
+        if let Some(format) = format.filter(|_| conversion_service::needs_conversion(&model.blob, format) && conversion_service::can_convert(&file_type)) {
+            let converted_path = conversion_service::get_converted_path(&model.blob, format, app_state).await?;
+            entries.push(file_entry(name, converted_path).await?);
+            continue;
+        }
+
+        let src_file_path = get_model_path_for_blob(&model.blob, app_state);
+
+        match file_type.is_zipped() {
+            true => entries.push(zipped_file_entry(name, src_file_path).await?),
+            false => entries.push(file_entry(name, src_file_path).await?),
+        }
+    }
+
+    let content_length = get_zip_length(&entries);
+    let (writer, reader) = tokio::io::duplex(PIPE_SIZE);
+
+    tokio::spawn(async move {
+        let result = match fits_plain_zip(&entries) {
+            true => write_zip(entries, writer).await,
+            false => write_zip64(entries, writer).await,
+        };
+
+        // The response ends early when this fails, which the client sees as a broken download
+        if let Err(e) = result {
+            println!("Failed to stream zip: {}", e);
+        }
+    });
+
+    Ok(ZipStream {
+        file_name,
+        content_length,
+        reader,
+    })
+}
+
+// Deflate can grow data it can't compress by a few bytes per block, this stays above zlib's own bound
+fn max_compressed_size(entry: &ZipStreamEntry) -> u64 {
+    entry.compressed_size.unwrap_or(entry.size + entry.size / 1024 + 64)
+}
+
+fn get_archive_length(entries: &[ZipStreamEntry], entry_size: impl Fn(&ZipStreamEntry) -> u64) -> u64 {
+    let entries_length: u64 = entries
+        .iter()
+        .map(|entry| LOCAL_HEADER_SIZE + DATA_DESCRIPTOR_SIZE + CENTRAL_HEADER_SIZE + 2 * entry.name.len() as u64 + entry_size(entry))
+        .sum();
+
+    entries_length + END_OF_CENTRAL_DIRECTORY_SIZE
+}
+
+// Whether write_zip can write the archive, it doesn't write the zip64 records larger archives need
+fn fits_plain_zip(entries: &[ZipStreamEntry]) -> bool {
+    entries.len() < u16::MAX as usize
+        && entries.iter().all(|entry| entry.size < u32::MAX as u64 && entry.name.len() <= u16::MAX as usize)
+        && get_archive_length(entries, max_compressed_size) < u32::MAX as u64
+}
+
+// Length of the archive written by write_zip, or None when an entry is deflated while streaming or it would need zip64
+fn get_zip_length(entries: &[ZipStreamEntry]) -> Option<u64> {
+    if !fits_plain_zip(entries) || entries.iter().any(|entry| entry.compressed_size.is_none()) {
+        return None;
+    }
+
+    Some(get_archive_length(entries, max_compressed_size))
+}
+
+struct CentralDirectoryEntry {
+    name: String,
+    method: u16,
+    size: u32,
+    compressed_size: u32,
+    crc: u32,
+    offset: u32,
+}
+
+// Writes the archive without zip64 records, check fits_plain_zip first
+async fn write_zip<W: AsyncWrite + Unpin>(entries: Vec<ZipStreamEntry>, mut writer: W) -> Result<(), ServiceError> {
+    let (time, date) = get_dos_time();
+    let mut offset: u64 = 0;
+    let mut central_directory = Vec::with_capacity(entries.len());
+
+    for entry in entries {
+        let mut header = Vec::with_capacity(LOCAL_HEADER_SIZE as usize + entry.name.len());
+        header.extend_from_slice(&0x04034b50u32.to_le_bytes());
+        header.extend_from_slice(&20u16.to_le_bytes());
+        header.extend_from_slice(&ENTRY_FLAGS.to_le_bytes());
+        header.extend_from_slice(&entry.method.to_le_bytes());
+        header.extend_from_slice(&time.to_le_bytes());
+        header.extend_from_slice(&date.to_le_bytes());
+        header.extend_from_slice(&0u32.to_le_bytes());
+        header.extend_from_slice(&0u32.to_le_bytes());
+        header.extend_from_slice(&0u32.to_le_bytes());
+        header.extend_from_slice(&(entry.name.len() as u16).to_le_bytes());
+        header.extend_from_slice(&0u16.to_le_bytes());
+        header.extend_from_slice(entry.name.as_bytes());
+        writer.write_all(&header).await?;
+
+        let (crc, compressed_size) = match entry.source {
+            ZipStreamSource::Deflated { data, crc } => {
+                writer.write_all(&data).await?;
+                (crc, data.len() as u64)
+            }
+            ZipStreamSource::File(path) => {
+                copy_with_crc(&mut BufReader::new(File::open(path).await?), &mut writer, entry.size, entry.method).await?
+            }
+            ZipStreamSource::ZippedFile(path) => {
+                let mut buffered_reader = BufReader::new(File::open(path).await?);
+                let mut zip = ZipFileReader::with_tokio(&mut buffered_reader).await?;
+                let file = zip.reader_with_entry(0).await?;
+
+                copy_with_crc(&mut file.compat(), &mut writer, entry.size, entry.method).await?
+            }
+            ZipStreamSource::Precompressed { path, offset, crc } => {
+                let compressed_size = entry.compressed_size.unwrap_or(0);
+                let mut file = File::open(path).await?;
+                file.seek(SeekFrom::Start(offset)).await?;
+
+                if tokio::io::copy(&mut BufReader::new(file).take(compressed_size), &mut writer).await? != compressed_size {
+                    return Err(ServiceError::InternalError(String::from("Zipped file is truncated")));
+                }
+
+                (crc, compressed_size)
+            }
+        };
+
+        let mut descriptor = Vec::with_capacity(DATA_DESCRIPTOR_SIZE as usize);
+        descriptor.extend_from_slice(&0x08074b50u32.to_le_bytes());
+        descriptor.extend_from_slice(&crc.to_le_bytes());
+        descriptor.extend_from_slice(&(compressed_size as u32).to_le_bytes());
+        descriptor.extend_from_slice(&(entry.size as u32).to_le_bytes());
+        writer.write_all(&descriptor).await?;
+
+        central_directory.push(CentralDirectoryEntry {
+            name: entry.name,
+            method: entry.method,
+            size: entry.size as u32,
+            compressed_size: compressed_size as u32,
+            crc,
+            offset: offset as u32,
+        });
+
+        offset += header.len() as u64 + compressed_size + DATA_DESCRIPTOR_SIZE;
+    }
+
+    let mut directory = Vec::new();
+
+    for entry in &central_directory {
+        directory.extend_from_slice(&0x02014b50u32.to_le_bytes());
+        directory.extend_from_slice(&20u16.to_le_bytes());
+        directory.extend_from_slice(&20u16.to_le_bytes());
+        directory.extend_from_slice(&ENTRY_FLAGS.to_le_bytes());
+        directory.extend_from_slice(&entry.method.to_le_bytes());
+        directory.extend_from_slice(&time.to_le_bytes());
+        directory.extend_from_slice(&date.to_le_bytes());
+        directory.extend_from_slice(&entry.crc.to_le_bytes());
+        directory.extend_from_slice(&entry.compressed_size.to_le_bytes());
+        directory.extend_from_slice(&entry.size.to_le_bytes());
+        directory.extend_from_slice(&(entry.name.len() as u16).to_le_bytes());
+        directory.extend_from_slice(&0u16.to_le_bytes());
+        directory.extend_from_slice(&0u16.to_le_bytes());
+        directory.extend_from_slice(&0u16.to_le_bytes());
+        directory.extend_from_slice(&0u16.to_le_bytes());
+        directory.extend_from_slice(&0u32.to_le_bytes());
+        directory.extend_from_slice(&entry.offset.to_le_bytes());
+        directory.extend_from_slice(entry.name.as_bytes());
+    }
+
+    let directory_size = directory.len() as u32;
+
+    directory.extend_from_slice(&0x06054b50u32.to_le_bytes());
+    directory.extend_from_slice(&0u16.to_le_bytes());
+    directory.extend_from_slice(&0u16.to_le_bytes());
+    directory.extend_from_slice(&(central_directory.len() as u16).to_le_bytes());
+    directory.extend_from_slice(&(central_directory.len() as u16).to_le_bytes());
+    directory.extend_from_slice(&directory_size.to_le_bytes());
+    directory.extend_from_slice(&(offset as u32).to_le_bytes());
+    directory.extend_from_slice(&0u16.to_le_bytes());
+
+    writer.write_all(&directory).await?;
+    writer.shutdown().await?;
+
+    Ok(())
+}
+
+// Copies exactly size bytes, so a file that changed since its size was taken can't corrupt the archive silently.
+// Returns the crc and the number of bytes written
+async fn copy_with_crc<R: AsyncRead + Unpin, W: AsyncWrite + Unpin>(reader: &mut R, writer: &mut W, size: u64, method: u16) -> Result<(u32, u64), ServiceError> {
+    let mut crc = Crc::new();
+    let mut buffer = vec![0u8; 64 * 1024];
+    let mut copied: u64 = 0;
+    let mut written: u64 = 0;
+    let mut encoder = match method {
+        METHOD_DEFLATED => Some(DeflateEncoder::new(Vec::new(), flate2::Compression::default())),
+        _ => None,
+    };
+
+    loop {
+        let read = reader.read(&mut buffer).await?;
+
+        if read == 0 {
+            break;
+        }
+
+        copied += read as u64;
+
+        if copied > size {
+            break;
+        }
+
+        crc.update(&buffer[..read]);
+
+        match encoder.as_mut() {
+            Some(encoder) => {
+                encoder.write_all(&buffer[..read])?;
+                writer.write_all(encoder.get_ref()).await?;
+                written += encoder.get_ref().len() as u64;
+                encoder.get_mut().clear();
+            }
+            None => {
+                writer.write_all(&buffer[..read]).await?;
+                written += read as u64;
+            }
+        }
+    }
+
+    if copied != size {
+        return Err(ServiceError::InternalError(String::from("File changed size while streaming it into a zip")));
+    }
+
+    if let Some(encoder) = encoder {
+        let rest = encoder.finish()?;
+        writer.write_all(&rest).await?;
+        written += rest.len() as u64;
+    }
+
+    Ok((crc.sum(), written))
+}
+
+// Archives too large for a plain zip are left to async_zip, which adds the zip64 records
+async fn write_zip64<W: AsyncWrite + Unpin>(entries: Vec<ZipStreamEntry>, writer: W) -> Result<(), ServiceError> {
+    let mut writer = ZipFileWriter::with_tokio(writer);
+
+    for entry in entries {
+        let compression = match entry.method {
+            METHOD_DEFLATED => Compression::Deflate,
+            _ => Compression::Stored,
+        };
+
+        let builder = ZipEntryBuilder::new(entry.name.into(), compression);
+
+        match entry.source {
+            ZipStreamSource::Deflated { data, crc } => {
+                let builder = builder.crc32(crc).uncompressed_size(entry.size);
+                let mut stream_writer = writer.write_entry_stream_precompressed(builder).await?;
+                stream_writer.write_all(&data).await?;
+                stream_writer.close().await?;
+            }
+            ZipStreamSource::File(path) => {
+                let mut stream_writer = writer.write_entry_stream(builder).await?;
+                let mut file = File::open(path).await?.compat();
+                futures::io::copy(&mut file, &mut stream_writer).await?;
+                stream_writer.close().await?;
+            }
+            ZipStreamSource::ZippedFile(path) => {
+                let mut stream_writer = writer.write_entry_stream(builder).await?;
+                let mut buffered_reader = BufReader::new(File::open(path).await?);
+                let mut zip = ZipFileReader::with_tokio(&mut buffered_reader).await?;
+                let mut file = zip.reader_with_entry(0).await?;
+
+                futures::io::copy(&mut file, &mut stream_writer).await?;
+                stream_writer.close().await?;
+            }
+            ZipStreamSource::Precompressed { path, offset, crc } => {
+                let builder = builder.crc32(crc).uncompressed_size(entry.size);
+                let mut stream_writer = writer.write_entry_stream_precompressed(builder).await?;
+                let mut file = File::open(path).await?;
+                file.seek(SeekFrom::Start(offset)).await?;
+
+                let mut file = BufReader::new(file).take(entry.compressed_size.unwrap_or(0)).compat();
+                futures::io::copy(&mut file, &mut stream_writer).await?;
+                stream_writer.close().await?;
+            }
+        }
+    }
+
+    writer.close().await?;
+
+    Ok(())
+}
+
+fn get_dos_time() -> (u16, u16) {
+    let now = Local::now();
+    let time = ((now.hour() << 11) | (now.minute() << 5) | (now.second() / 2)) as u16;
+    let date = (((now.year().max(1980) - 1980) << 9) as u32 | (now.month() << 5) | now.day()) as u16;
+
+    (time, date)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_zip::base::read::mem::ZipFileReader as MemZipFileReader;
+    use async_zip::base::write::ZipFileWriter as MemZipFileWriter;
+    use futures::executor::block_on;
+    use futures::AsyncReadExt as FuturesAsyncReadExt;
+    use std::future::Future;
+
+    fn run<F: Future>(future: F) -> F::Output {
+        tokio::runtime::Runtime::new().unwrap().block_on(future)
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("meshorganiser_zip_stream_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn zipped(name: &str, contents: &[u8], compression: Compression) -> Vec<u8> {
+        block_on(async {
+            let mut writer = MemZipFileWriter::new(futures::io::Cursor::new(Vec::new()));
+            writer.write_entry_whole(ZipEntryBuilder::new(name.to_string().into(), compression), contents).await.unwrap();
+            writer.close().await.unwrap().into_inner()
+        })
+    }
+
+    fn mesh(lines: usize) -> Vec<u8> {
+        (0..lines).map(|i| format!("vertex {} {} {}\n", i, i * 2, i * 3)).collect::<String>().into_bytes()
+    }
+
+    fn read_back(archive: Vec<u8>) -> Vec<(String, Compression, Vec<u8>)> {
+        block_on(async {
+            let zip = MemZipFileReader::new(archive).await.unwrap();
+            let mut entries = Vec::new();
+
+            for index in 0..zip.file().entries().len() {
+                let entry = zip.file().entries()[index].clone();
+                let mut contents = Vec::new();
+                zip.reader_with_entry(index).await.unwrap().read_to_end_checked(&mut contents).await.unwrap();
+                entries.push((entry.filename().as_str().unwrap().to_string(), entry.compression(), contents));
+            }
+
+            entries
+        })
+    }
+
+    #[test]
+    fn archive_has_the_computed_length_and_reads_back() {
+        let dir = temp_dir("layout");
+        let threemf = zipped("3D/3dmodel.model", &mesh(200), Compression::Deflate);
+        std::fs::write(dir.join("print.3mf"), &threemf).unwrap();
+        std::fs::write(dir.join("deflated.stl.zip"), zipped("deflated.stl", &mesh(300), Compression::Deflate)).unwrap();
+        std::fs::write(dir.join("stored.stl.zip"), zipped("stored.stl", &mesh(100), Compression::Stored)).unwrap();
+
+        let entries = run(async {
+            vec![
+                bytes_entry(String::from("metadata.json"), b"[{\"name\": \"model\"}]").unwrap(),
+                file_entry(String::from("print.3mf"), dir.join("print.3mf")).await.unwrap(),
+                zipped_file_entry(String::from("deflated.stl"), dir.join("deflated.stl.zip")).await.unwrap(),
+                zipped_file_entry(String::from("stored.stl"), dir.join("stored.stl.zip")).await.unwrap(),
+            ]
+        });
+
+        let length = get_zip_length(&entries).unwrap();
+        let mut archive = Vec::new();
+        run(write_zip(entries, &mut archive)).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(archive.len() as u64, length);
+
+        let entries = read_back(archive);
+        assert_eq!(entries.len(), 4);
+        assert_eq!(entries[0], (String::from("metadata.json"), Compression::Deflate, b"[{\"name\": \"model\"}]".to_vec()));
+        assert_eq!(entries[1], (String::from("print.3mf"), Compression::Stored, threemf));
+        assert_eq!(entries[2], (String::from("deflated.stl"), Compression::Deflate, mesh(300)));
+        assert_eq!(entries[3], (String::from("stored.stl"), Compression::Stored, mesh(100)));
+    }
+
+    #[test]
+    fn plain_files_are_deflated_while_streaming() {
+        let dir = temp_dir("deflate");
+        std::fs::write(dir.join("model.stl"), mesh(1000)).unwrap();
+
+        let entries = run(async { vec![file_entry(String::from("model.stl"), dir.join("model.stl")).await.unwrap()] });
+
+        assert_eq!(get_zip_length(&entries), None);
+
+        let mut archive = Vec::new();
+        run(write_zip(entries, &mut archive)).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!((archive.len() as u64) < mesh(1000).len() as u64);
+        assert_eq!(read_back(archive), vec![(String::from("model.stl"), Compression::Deflate, mesh(1000))]);
+    }
+
+    #[test]
+    fn zip64_archive_reads_back() {
+        let dir = temp_dir("zip64");
+        std::fs::write(dir.join("model.stl"), mesh(100)).unwrap();
+        std::fs::write(dir.join("part.stl.zip"), zipped("part.stl", &mesh(50), Compression::Deflate)).unwrap();
+
+        let entries = run(async {
+            vec![
+                bytes_entry(String::from("metadata.json"), b"[]").unwrap(),
+                file_entry(String::from("model.stl"), dir.join("model.stl")).await.unwrap(),
+                zipped_file_entry(String::from("part.stl"), dir.join("part.stl.zip")).await.unwrap(),
+            ]
+        });
+
+        let mut archive = Vec::new();
+        run(write_zip64(entries, &mut archive)).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let entries = read_back(archive);
+        assert_eq!(entries[0], (String::from("metadata.json"), Compression::Deflate, b"[]".to_vec()));
+        assert_eq!(entries[1], (String::from("model.stl"), Compression::Deflate, mesh(100)));
+        assert_eq!(entries[2], (String::from("part.stl"), Compression::Deflate, mesh(50)));
+    }
+
+    #[test]
+    fn file_that_changed_size_fails_the_stream() {
+        let dir = temp_dir("changed");
+        std::fs::write(dir.join("model.stl"), mesh(10)).unwrap();
+
+        let entries = run(async { vec![file_entry(String::from("model.stl"), dir.join("model.stl")).await.unwrap()] });
+        std::fs::write(dir.join("model.stl"), mesh(20)).unwrap();
+
+        let mut archive = Vec::new();
+        let result = run(write_zip(entries, &mut archive));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(result.is_err());
+    }
+}
@@ -2,6 +2,7 @@ mod blob_api;
 mod group_api;
 mod label_api;
 mod model_api;
+mod printer_api;
 mod resource_api;
 mod user_api;
 mod web_extensions_api;
@@ -10,6 +11,7 @@ pub use blob_api::*;
 pub use group_api::*;
 pub use label_api::*;
 pub use model_api::*;
+pub use printer_api::*;
 pub use resource_api::*;
 pub use user_api::*;
 pub use web_extensions_api::*;
\ No newline at end of file
@@ -7,12 +7,16 @@ use crate::error::ApplicationError;
 use crate::tauri_import_state::import_state_new_tauri;
 use db::blob_db;
 use db::model::FileType;
-use db::model::{Blob, ModelFlags, User};
+use db::model::{Blob, GcodeMetadata, ModelFlags, ResinMetadata, User};
 use db::model_db::{self, ModelFilterOptions, ModelOrderBy};
 use itertools::Itertools;
 use serde::Serialize;
 use service::export_service::{get_image_path_for_blob, get_model_path_for_blob};
 use service::import_state::ImportStatus;
+use service::mesh_analysis_service::{self, MeshReport};
+use service::mesh_repair_service::{self, MeshRepairReport};
+use service::similarity_service::{self, SimilarModel};
+use service::{duplicate_service, gcode_service, resin_service, slicing_service};
 use service::{export_service, import_service, thumbnail_service};
 use tauri::{AppHandle, State};
 
@@ -55,7 +59,7 @@ pub async fn add_model(
         .await?;
 
     let models_len = models.len();
-    let (_, paths) = export_service::export_to_temp_folder(models, &state.app_state, true, "open").await?;
+    let (_, paths) = export_service::export_to_temp_folder(models, &state.app_state, true, "open", None).await?;
 
     if open_in_slicer && models_len > 0 {
         if let Some(slicer) = &state.get_configuration().slicer {
@@ -68,6 +72,187 @@ pub async fn add_model(
     Ok(import_state)
 }
 
+#[derive(Serialize)]
+pub struct SliceModelResult {
+    pub import_state: ImportState,
+    pub estimate: GcodeMetadata,
+}
+
+#[tauri::command]
+pub async fn slice_model(
+    model_id: i64,
+    profile_paths: Vec<String>,
+    state: State<'_, TauriAppState>,
+    app_handle: AppHandle,
+) -> Result<SliceModelResult, ApplicationError> {
+    let user = state.get_current_user();
+    let model = match model_db::get_models_via_ids(&state.app_state.db, &user, vec![model_id]).await?.pop() {
+        Some(model) => model,
+        None => return Err(ApplicationError::InternalError("Model not found".into())),
+    };
+
+    let profile_paths: Vec<PathBuf> = profile_paths.iter().map(PathBuf::from).collect();
+    let import_state = import_state_new_tauri(None, false, false, false, &state, &app_handle);
+
+    let (mut import_state, estimate) =
+        slicing_service::slice_model(&model, &profile_paths, &state.app_state, import_state).await?;
+
+    let model_ids: Vec<i64> = import_state
+        .imported_models
+        .iter()
+        .flat_map(|f| f.model_ids.clone())
+        .collect();
+
+    let models = model_db::get_models_via_ids(&state.app_state.db, &user, model_ids).await?;
+    let blobs: Vec<&Blob> = models.iter().map(|m| &m.blob).collect();
+
+    thumbnail_service::generate_thumbnails(&blobs, &state.app_state, false, &mut import_state)
+        .await?;
+
+    import_state.update_status(ImportStatus::Finished);
+
+    Ok(SliceModelResult {
+        import_state,
+        estimate,
+    })
+}
+
+#[derive(Serialize)]
+pub struct RepairModelResult {
+    pub import_state: ImportState,
+    pub report: MeshRepairReport,
+}
+
+#[tauri::command]
+pub async fn repair_model(
+    model_id: i64,
+    state: State<'_, TauriAppState>,
+    app_handle: AppHandle,
+) -> Result<RepairModelResult, ApplicationError> {
+    let user = state.get_current_user();
+    let model = match model_db::get_models_via_ids(&state.app_state.db, &user, vec![model_id]).await?.pop() {
+        Some(model) => model,
+        None => return Err(ApplicationError::InternalError("Model not found".into())),
+    };
+
+    let import_state = import_state_new_tauri(None, false, false, false, &state, &app_handle);
+
+    let (mut import_state, report) =
+        mesh_repair_service::repair_model(&model, &state.app_state, import_state).await?;
+
+    let model_ids: Vec<i64> = import_state
+        .imported_models
+        .iter()
+        .flat_map(|f| f.model_ids.clone())
+        .collect();
+
+    let models = model_db::get_models_via_ids(&state.app_state.db, &user, model_ids).await?;
+    let blobs: Vec<&Blob> = models.iter().map(|m| &m.blob).collect();
+
+    thumbnail_service::generate_thumbnails(&blobs, &state.app_state, false, &mut import_state)
+        .await?;
+
+    import_state.update_status(ImportStatus::Finished);
+
+    Ok(RepairModelResult {
+        import_state,
+        report,
+    })
+}
+
+#[tauri::command]
+pub async fn get_gcode_metadata(
+    model_id: i64,
+    state: State<'_, TauriAppState>,
+) -> Result<GcodeMetadata, ApplicationError> {
+    let model = match model_db::get_models_via_ids(&state.app_state.db, &state.get_current_user(), vec![model_id]).await?.pop() {
+        Some(model) => model,
+        None => return Err(ApplicationError::InternalError("Model not found".into())),
+    };
+
+    let metadata = gcode_service::get_metadata(&model, &state.app_state).await?;
+
+    Ok(metadata)
+}
+
+#[tauri::command]
+pub async fn get_resin_metadata(
+    model_id: i64,
+    state: State<'_, TauriAppState>,
+) -> Result<ResinMetadata, ApplicationError> {
+    let model = match model_db::get_models_via_ids(&state.app_state.db, &state.get_current_user(), vec![model_id]).await?.pop() {
+        Some(model) => model,
+        None => return Err(ApplicationError::InternalError("Model not found".into())),
+    };
+
+    let metadata = resin_service::get_metadata(&model, &state.app_state).await?;
+
+    Ok(metadata)
+}
+
+#[tauri::command]
+pub async fn get_mesh_report(
+    model_id: i64,
+    state: State<'_, TauriAppState>,
+) -> Result<MeshReport, ApplicationError> {
+    let model = match model_db::get_models_via_ids(&state.app_state.db, &state.get_current_user(), vec![model_id]).await?.pop() {
+        Some(model) => model,
+        None => return Err(ApplicationError::InternalError("Model not found".into())),
+    };
+
+    let report = mesh_analysis_service::get_report(&model, &state.app_state).await?;
+
+    Ok(report)
+}
+
+#[tauri::command]
+pub async fn get_duplicate_models(
+    state: State<'_, TauriAppState>,
+) -> Result<Vec<Vec<db::model::Model>>, ApplicationError> {
+    let duplicates = duplicate_service::find_duplicates(&state.get_current_user(), &state.app_state).await?;
+
+    Ok(duplicates)
+}
+
+#[tauri::command]
+pub async fn merge_duplicate_models(
+    keep_model_id: i64,
+    duplicate_model_ids: Vec<i64>,
+    state: State<'_, TauriAppState>,
+) -> Result<(), ApplicationError> {
+    let user = state.get_current_user();
+    let model = match model_db::get_models_via_ids(&state.app_state.db, &user, vec![keep_model_id]).await?.pop() {
+        Some(model) => model,
+        None => return Err(ApplicationError::InternalError("Model not found".into())),
+    };
+
+    let duplicates = match duplicate_service::get_mergeable_duplicates(&model, &duplicate_model_ids, &user, &state.app_state).await? {
+        Some(duplicates) => duplicates,
+        None => return Err(ApplicationError::InternalError("Only duplicates of the model can be merged into it".into())),
+    };
+
+    duplicate_service::merge_duplicates(&model, &duplicates, &user, &state.app_state).await?;
+
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_similar_models(
+    model_id: i64,
+    limit: Option<usize>,
+    state: State<'_, TauriAppState>,
+) -> Result<Vec<SimilarModel>, ApplicationError> {
+    let user = state.get_current_user();
+    let model = match model_db::get_models_via_ids(&state.app_state.db, &user, vec![model_id]).await?.pop() {
+        Some(model) => model,
+        None => return Err(ApplicationError::InternalError("Model not found".into())),
+    };
+
+    let similar = similarity_service::find_similar_models(&model, &user, limit.unwrap_or(20), &state.app_state).await?;
+
+    Ok(similar)
+}
+
 #[tauri::command]
 pub async fn get_models(
     model_ids: Option<Vec<i64>>,
@@ -77,6 +262,11 @@ pub async fn get_models(
     order_by: Option<String>,
     text_search: Option<String>,
     model_flags: Option<ModelFlags>,
+    printer_model: Option<String>,
+    material_type: Option<String>,
+    nozzle_diameter: Option<f64>,
+    max_print_time_seconds: Option<i64>,
+    has_problems: Option<bool>,
     page: u32,
     page_size: u32,
     state: State<'_, TauriAppState>,
@@ -93,6 +283,11 @@ pub async fn get_models(
             model_flags,
             text_search,
             file_types,
+            printer_model,
+            material_type,
+            nozzle_diameter,
+            max_print_time_seconds,
+            has_problems,
             page,
             page_size,
         },
@@ -0,0 +1,121 @@
+use db::model::{PrintJob, Printer, PrinterType};
+use db::{model_db, printer_db};
+use service::printer_service;
+use tauri::State;
+
+use crate::{error::ApplicationError, tauri_app_state::TauriAppState};
+
+async fn get_printer(state: &TauriAppState, printer_id: i64) -> Result<Printer, ApplicationError> {
+    match printer_db::get_printer_by_id(&state.app_state.db, &state.get_current_user(), printer_id).await? {
+        Some(printer) => Ok(printer),
+        None => Err(ApplicationError::InternalError("Printer not found".into())),
+    }
+}
+
+#[tauri::command]
+pub async fn get_printers(
+    state: State<'_, TauriAppState>,
+) -> Result<Vec<Printer>, ApplicationError> {
+    let printers = printer_db::get_printers(&state.app_state.db, &state.get_current_user()).await?;
+
+    Ok(printers)
+}
+
+#[tauri::command]
+pub async fn add_printer(
+    printer_name: &str,
+    printer_type: PrinterType,
+    printer_base_url: &str,
+    printer_api_key: Option<&str>,
+    state: State<'_, TauriAppState>,
+) -> Result<i64, ApplicationError> {
+    let base_url = printer_service::normalize_base_url(printer_base_url)?;
+
+    let id = printer_db::add_printer(
+        &state.app_state.db,
+        &state.get_current_user(),
+        printer_name,
+        printer_type,
+        &base_url,
+        printer_api_key.unwrap_or(""),
+    )
+    .await?;
+
+    Ok(id)
+}
+
+#[tauri::command]
+pub async fn edit_printer(
+    printer_id: i64,
+    printer_name: &str,
+    printer_type: PrinterType,
+    printer_base_url: &str,
+    printer_api_key: Option<&str>,
+    state: State<'_, TauriAppState>,
+) -> Result<(), ApplicationError> {
+    let base_url = printer_service::normalize_base_url(printer_base_url)?;
+
+    printer_db::edit_printer(
+        &state.app_state.db,
+        &state.get_current_user(),
+        printer_id,
+        printer_name,
+        printer_type,
+        &base_url,
+        printer_api_key,
+    )
+    .await?;
+
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn delete_printer(
+    printer_id: i64,
+    state: State<'_, TauriAppState>,
+) -> Result<(), ApplicationError> {
+    printer_db::delete_printer(&state.app_state.db, &state.get_current_user(), printer_id).await?;
+
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn test_printer(
+    printer_id: i64,
+    state: State<'_, TauriAppState>,
+) -> Result<(), ApplicationError> {
+    let printer = get_printer(&state, printer_id).await?;
+
+    printer_service::test_connection(&printer).await?;
+
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_print_jobs(
+    printer_id: i64,
+    state: State<'_, TauriAppState>,
+) -> Result<Vec<PrintJob>, ApplicationError> {
+    let jobs = printer_db::get_print_jobs(&state.app_state.db, &state.get_current_user(), printer_id).await?;
+
+    Ok(jobs)
+}
+
+#[tauri::command]
+pub async fn send_model_to_printer(
+    printer_id: i64,
+    model_id: i64,
+    start_print: bool,
+    state: State<'_, TauriAppState>,
+) -> Result<i64, ApplicationError> {
+    let printer = get_printer(&state, printer_id).await?;
+
+    let model = match model_db::get_models_via_ids(&state.app_state.db, &state.get_current_user(), vec![model_id]).await?.pop() {
+        Some(model) => model,
+        None => return Err(ApplicationError::InternalError("Model not found".into())),
+    };
+
+    let id = printer_service::send_model_to_printer(&state.app_state, &printer, &model, start_print).await?;
+
+    Ok(id)
+}
@@ -21,13 +21,17 @@ use service::StoredConfiguration;
 use service::ThreemfMetadata;
 use service::export_service;
 use service::export_service::get_temp_dir;
+use service::folder_export_service::{self, FolderExportResult};
+use service::backup_service::{self, BackupManifest, RestoreOptions, RestoreResult};
 use service::import_state::ImportState;
+use service::mesh_service::MeshFormat;
 use service::stored_to_configuration;
-use service::{download_file_service, import_service, slicer_service::Slicer};
-use service::{threemf_service, thumbnail_service};
+use service::{download_file_service, import_service, slicer_service::{Slicer, SlicerInstallation}};
+use service::{duplicate_service, gcode_service, mesh_analysis_service, printer_service, threemf_service, thumbnail_service};
 use std::fs::File;
 use std::io::prelude::*;
 use std::{
+    collections::HashMap,
     path::PathBuf,
     sync::{Arc, Mutex},
     thread,
@@ -79,20 +83,29 @@ async fn set_configuration(
 pub struct SlicerEntry {
     slicer: Slicer,
     installed: bool,
+    installations: Vec<SlicerInstallation>,
 }
 
 #[tauri::command]
 async fn get_slicers() -> Result<Vec<SlicerEntry>, ApplicationError> {
-    Slicer::iter()
-        .map(|f| {
-            let installed = f.is_installed();
-
-            Ok(SlicerEntry {
-                slicer: f,
-                installed: installed,
+    // Finding installations runs external programs, which shouldn't hold up the async runtime
+    let slicers = tauri::async_runtime::spawn_blocking(|| {
+        Slicer::iter()
+            .map(|f| {
+                let installations = f.get_installations();
+                let installed = matches!(f, Slicer::Custom) || !installations.is_empty();
+
+                SlicerEntry {
+                    slicer: f,
+                    installed: installed,
+                    installations: installations,
+                }
             })
-        })
-        .collect()
+            .collect()
+    })
+    .await?;
+
+    Ok(slicers)
 }
 
 #[tauri::command]
@@ -111,6 +124,8 @@ async fn update_images(
 #[tauri::command]
 async fn open_in_slicer(
     model_ids: Vec<i64>,
+    format: Option<MeshFormat>,
+    combine: Option<bool>,
     state: State<'_, TauriAppState>,
 ) -> Result<(), ApplicationError> {
     let models =
@@ -118,7 +133,10 @@ async fn open_in_slicer(
             .await?;
 
     if let Some(slicer) = &state.get_configuration().slicer {
-        let (_, paths) = export_service::export_to_temp_folder(models, &state.app_state, true, "open").await?;
+        let paths = match combine.unwrap_or(false) {
+            true => vec![export_service::export_combined_3mf(models, &state.app_state).await?.1],
+            false => export_service::export_to_temp_folder(models, &state.app_state, true, "open", format).await?.1,
+        };
         slicer.open(paths, &state.app_state).await?;
     }
 
@@ -152,6 +170,8 @@ async fn download_file(
 async fn open_in_folder(
     model_ids: Vec<i64>,
     as_zip: bool,
+    format: Option<MeshFormat>,
+    combine: Option<bool>,
     state: State<'_, TauriAppState>,
 ) -> Result<(), ApplicationError> {
     let models =
@@ -159,9 +179,10 @@ async fn open_in_folder(
             .await?;
 
     let temp_dir = match as_zip {
-        true => export_service::export_zip_to_temp_folder(models, &state.app_state).await?.temp_dir,
+        _ if combine.unwrap_or(false) => export_service::export_combined_3mf(models, &state.app_state).await?.0,
+        true => export_service::export_zip_to_temp_folder(models, &state.app_state, format).await?.temp_dir,
         false => {
-            let (temp_dir, _) = export_service::export_to_temp_folder(models, &state.app_state, false, "export")
+            let (temp_dir, _) = export_service::export_to_temp_folder(models, &state.app_state, false, "export", format)
             .await?;
 
             temp_dir
@@ -173,6 +194,52 @@ async fn open_in_folder(
     Ok(())
 }
 
+#[tauri::command]
+async fn export_to_folder(
+    model_ids: Vec<i64>,
+    directory: String,
+    template: Option<String>,
+    format: Option<MeshFormat>,
+    state: State<'_, TauriAppState>,
+) -> Result<FolderExportResult, ApplicationError> {
+    let user = state.get_current_user();
+    let models = model_db::get_models_via_ids(&state.app_state.db, &user, model_ids).await?;
+    let template = template.unwrap_or(state.get_configuration().export_folder_template);
+    let directory = PathBuf::from(directory);
+
+    let result = folder_export_service::export_to_folder(&models, &directory, &template, format, &user, &state.app_state).await?;
+
+    service::open_folder_in_explorer(&directory);
+
+    Ok(result)
+}
+
+#[tauri::command]
+async fn create_backup(
+    path: String,
+    state: State<'_, TauriAppState>,
+) -> Result<BackupManifest, ApplicationError> {
+    let manifest = backup_service::create_backup(&PathBuf::from(path), &state.app_state).await?;
+
+    Ok(manifest)
+}
+
+#[tauri::command]
+async fn restore_backup(
+    path: String,
+    user_mapping: Option<HashMap<i64, i64>>,
+    state: State<'_, TauriAppState>,
+) -> Result<RestoreResult, ApplicationError> {
+    let _lock = state.app_state.import_mutex.lock().await;
+    let options = RestoreOptions {
+        user_mapping: user_mapping.unwrap_or_default(),
+    };
+
+    let result = backup_service::restore_backup(&PathBuf::from(path), options, &state.get_current_user(), &state.app_state).await?;
+
+    Ok(result)
+}
+
 #[tauri::command]
 async fn get_theemf_metadata(
     model_id: i64,
@@ -193,6 +260,7 @@ async fn get_theemf_metadata(
 #[tauri::command]
 async fn extract_threemf_models(
     model_id: i64,
+    format: Option<MeshFormat>,
     state: State<'_, TauriAppState>,
 ) -> Result<ModelGroupMeta, ApplicationError> {
     let model = model_db::get_models_via_ids(
@@ -202,10 +270,41 @@ async fn extract_threemf_models(
     )
     .await?;
 
-    let mut import_state =
-        threemf_service::extract_models(&model[0], &state.get_current_user(), &state.app_state)
+    let import_state =
+        threemf_service::extract_models(&model[0], &state.get_current_user(), format.unwrap_or_default(), &state.app_state)
             .await?;
 
+    finish_threemf_import(import_state, &state).await
+}
+
+#[tauri::command]
+async fn split_threemf_plates(
+    model_id: i64,
+    state: State<'_, TauriAppState>,
+) -> Result<ModelGroupMeta, ApplicationError> {
+    let model = model_db::get_models_via_ids(
+        &state.app_state.db,
+        &state.get_current_user(),
+        vec![model_id],
+    )
+    .await?;
+
+    if model.is_empty() {
+        return Err(ApplicationError::InternalError("Model not found".into()));
+    }
+
+    let import_state =
+        threemf_service::split_plates(&model[0], &state.get_current_user(), &state.app_state)
+            .await?;
+
+    finish_threemf_import(import_state, &state).await
+}
+
+// Generates thumbnails for the imported models and returns the group they were imported into
+async fn finish_threemf_import(
+    mut import_state: ImportState,
+    state: &State<'_, TauriAppState>,
+) -> Result<ModelGroupMeta, ApplicationError> {
     let model_ids: Vec<i64> = import_state
         .imported_models
         .iter()
@@ -654,9 +753,15 @@ pub fn run() {
                     tauri::async_runtime::spawn(async move {
                         let _ = group_db::delete_dead_groups(&app_state.db).await;
                         let _ = export_service::delete_dead_blobs(&app_state).await;
+                        let _ = gcode_service::extract_missing_metadata(&app_state).await;
+                        let _ = mesh_analysis_service::analyse_missing(&app_state).await;
+                        let _ = duplicate_service::fingerprint_missing(&app_state).await;
+                        let _ = thumbnail_service::hash_missing_thumbnails(&app_state).await;
                     });
                 }
 
+                tauri::async_runtime::spawn(printer_service::watch_printers(state.app_state.clone()));
+
                 state.configure_deep_links(&app.handle());
 
                 app.manage(state);
@@ -665,6 +770,21 @@ pub fn run() {
         })
         .invoke_handler(tauri::generate_handler![
             api::add_model,
+            api::slice_model,
+            api::get_printers,
+            api::add_printer,
+            api::edit_printer,
+            api::delete_printer,
+            api::test_printer,
+            api::get_print_jobs,
+            api::send_model_to_printer,
+            api::get_gcode_metadata,
+            api::get_resin_metadata,
+            api::get_mesh_report,
+            api::repair_model,
+            api::get_duplicate_models,
+            api::merge_duplicate_models,
+            api::get_similar_models,
             api::get_models,
             api::get_labels,
             api::edit_model,
@@ -677,6 +797,9 @@ pub fn run() {
             get_initial_state,
             download_file,
             open_in_folder,
+            export_to_folder,
+            create_backup,
+            restore_backup,
             api::set_label_on_models,
             api::remove_label_from_models,
             api::add_group,
@@ -719,6 +842,7 @@ pub fn run() {
             api::get_model_disk_space_usage,
             get_theemf_metadata,
             extract_threemf_models,
+            split_threemf_plates,
             api::download_files_and_open_in_folder,
             api::download_files_and_open_in_slicer,
             api::expand_paths,
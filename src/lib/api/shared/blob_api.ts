@@ -5,6 +5,12 @@ export enum FileType
     THREEMF = "3mf",
     STEP = "step.zip",
     GCODE = "gcode.zip",
+    BGCODE = "bgcode",
+    CTB = "ctb",
+    SL1 = "sl1",
+    SL1S = "sl1s",
+    GOO = "goo",
+    PWMX = "pwmx",
 }
 
 export interface Blob {
@@ -27,6 +33,18 @@ export function fileTypeToPlainFileExtension(fileType: FileType) : string {
             return ".step";
         case FileType.GCODE:
             return ".gcode";
+        case FileType.BGCODE:
+            return ".bgcode";
+        case FileType.CTB:
+            return ".ctb";
+        case FileType.SL1:
+            return ".sl1";
+        case FileType.SL1S:
+            return ".sl1s";
+        case FileType.GOO:
+            return ".goo";
+        case FileType.PWMX:
+            return ".pwmx";
     }
 }
 
@@ -55,6 +73,9 @@ export function createBlobInstance(id: number, sha256: string, filetype: string,
     };
 }
 
+// Mesh formats models can be converted to on export
+export type MeshFormat = "stl" | "obj" | "3mf" | "ply";
+
 export const IBlobApi = Symbol('IBlobApi');
 
 export interface IBlobApi {
@@ -62,6 +83,6 @@ export interface IBlobApi {
     getConvertedBlobBytes(blob : Blob, target : FileType) : Promise<Uint8Array>;
     getBlobThumbnailUrl(blob : Blob) : Promise<string>;
     // TODO: Move this to model at some point as it also includes data from the model serverside
-    getBlobDownloadUrl(blob : Blob) : Promise<string>;
-    getBlobsDownloadUrl(blobs : Blob[]) : Promise<string>;
+    getBlobDownloadUrl(blob : Blob, format? : MeshFormat) : Promise<string>;
+    getBlobsDownloadUrl(blobs : Blob[], format? : MeshFormat, combine? : boolean) : Promise<string>;
 }
\ No newline at end of file
@@ -63,4 +63,14 @@ export class DefaultDownloadApi implements IDownloadApi {
         link.click();
         link.remove();
     }
-}
\ No newline at end of file
+}
+
+// Lets the server stream the zip, so the browser doesn't have to hold every model in memory
+export class ServerZipDownloadApi extends DefaultDownloadApi {
+    async downloadModelsAsZip(models: Model[]): Promise<void> {
+        const link = document.createElement("a");
+        link.href = await this.blobApi.getBlobsDownloadUrl(models.map(m => m.blob));
+        link.click();
+        link.remove();
+    }
+}
@@ -1,9 +1,11 @@
+import type { MeshFormat } from "./blob_api";
 import type { Model } from "./model_api";
 
 export const ILocalApi = Symbol('ILocalApi');
 
 export interface ILocalApi {
-    openInFolder(models : Model[], asZip: boolean) : Promise<void>;
+    openInFolder(models : Model[], asZip: boolean, format?: MeshFormat, combine?: boolean) : Promise<void>;
+    exportToFolder?(models : Model[], format?: MeshFormat) : Promise<void>;
     getAppDataDir() : Promise<string>;
     openDataDirPicker() : Promise<string|null>;
     openCustomSlicerPicker() : Promise<string|null>;
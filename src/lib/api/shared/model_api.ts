@@ -29,6 +29,14 @@ export function stringArrayToModelFlags(flagList: string[]) : ModelFlags
     return flags;
 }
 
+export interface ModelPrint {
+    printedAt: Date;
+    durationSeconds: number|null;
+    state: "Uploaded" | "Printing" | "Finished" | "Failed" | "Cancelled";
+}
+
+export type MeshHealth = "Healthy" | "Warnings" | "Problems";
+
 export interface Model {
     id: number;
     name: string;
@@ -41,9 +49,12 @@ export interface Model {
     labels: LabelMeta[];
     flags: ModelFlags;
     uniqueGlobalId: string;
+    sourceModelId: number|null;
+    lastPrint: ModelPrint|null;
+    health: MeshHealth|null;
 }
 
-export function createModelInstance(id: number, name: string, blob: Blob, link: string|null, description: string|null, added: string, last_modified: string, group: GroupMeta|null, labels: LabelMeta[], flags: string[], unqiueGlobalId: string): Model {
+export function createModelInstance(id: number, name: string, blob: Blob, link: string|null, description: string|null, added: string, last_modified: string, group: GroupMeta|null, labels: LabelMeta[], flags: string[], unqiueGlobalId: string, sourceModelId: number|null = null, lastPrint: ModelPrint|null = null, health: MeshHealth|null = null): Model {
     return {
         id,
         name,
@@ -56,6 +67,9 @@ export function createModelInstance(id: number, name: string, blob: Blob, link:
         labels,
         flags: stringArrayToModelFlags(flags),
         uniqueGlobalId: unqiueGlobalId,
+        sourceModelId,
+        lastPrint,
+        health,
     };
 }
 
@@ -79,6 +93,7 @@ export interface ModelFilter
     textSearch: string|null;
     flags: ModelFlags|null;
     fileTypes: FileType[]|null;
+    hasProblems: boolean|null;
 }
 
 export function defaultModelFilter() : ModelFilter {
@@ -90,6 +105,7 @@ export function defaultModelFilter() : ModelFilter {
         textSearch: null,
         flags: null,
         fileTypes: null,
+        hasProblems: null,
     };
 }
 
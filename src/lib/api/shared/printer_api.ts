@@ -0,0 +1,64 @@
+import { toast } from "svelte-sonner";
+import type { Model } from "./model_api";
+
+export type PrinterType = "OctoPrint" | "Moonraker" | "PrusaLink";
+export const PrinterTypes = ["OctoPrint", "Moonraker", "PrusaLink"] as PrinterType[];
+
+export type PrintJobState = "Uploaded" | "Printing" | "Finished" | "Failed" | "Cancelled";
+
+export interface Printer {
+    id: number;
+    name: string;
+    printerType: PrinterType;
+    baseUrl: string;
+    createdAt: Date;
+}
+
+export interface PrintJob {
+    id: number;
+    printerId: number;
+    modelId: number|null;
+    fileName: string;
+    state: PrintJobState;
+    createdAt: Date;
+    startedAt: Date|null;
+    finishedAt: Date|null;
+    durationSeconds: number|null;
+}
+
+export const IPrinterApi = Symbol('IPrinterApi');
+
+export interface IPrinterApi {
+    getPrinters() : Promise<Printer[]>;
+    addPrinter(name: string, printerType: PrinterType, baseUrl: string, apiKey: string|null) : Promise<Printer>;
+    // An empty api key keeps the one that is already stored
+    editPrinter(printer: Printer, apiKey: string|null) : Promise<void>;
+    deletePrinter(printer: Printer) : Promise<void>;
+    testPrinter(printer: Printer) : Promise<void>;
+    getPrintJobs(printer: Printer) : Promise<PrintJob[]>;
+    sendModelToPrinter(printer: Printer, model: Model, startPrint: boolean) : Promise<number>;
+}
+
+export async function sendModelToPrinter(model : Model, printer : Printer, startPrint : boolean, printerApi : IPrinterApi|null) : Promise<void>
+{
+    if (!printerApi) {
+        return;
+    }
+
+    let promise = printerApi.sendModelToPrinter(printer, model, startPrint);
+
+    toast.promise(
+        promise,
+        {
+            loading: `Sending '${model.name}' to '${printer.name}'...`,
+            success: (_) => {
+                return startPrint
+                    ? `Started printing '${model.name}' on '${printer.name}'`
+                    : `Uploaded '${model.name}' to '${printer.name}'`;
+            },
+            error: `Failed to send '${model.name}' to '${printer.name}'`,
+        }
+    );
+
+    await promise;
+}
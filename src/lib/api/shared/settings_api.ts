@@ -39,6 +39,7 @@ export interface Configuration {
     max_size_model_step_preview: number; // in MB
     only_show_single_image_in_groups: boolean;
     custom_slicer_path : string;
+    slicer_executable : string;
     group_split_view: "no_split" | "split-left-right" | "split-top-bottom";
     label_exported_model_as_printed : boolean;
     theme : string;
@@ -53,6 +54,8 @@ export interface Configuration {
     thumbnail_rotation : [number, number, number];
     watch_downloads_folder: boolean;
     startup_page: StartupPages;
+    combined_3mf_bed_size : [number, number];
+    export_folder_template : string;
 }
 
 export function convertOrderOptionModelsToEnum(orderOption : OrderOptionModels) : ModelOrderBy {
@@ -130,6 +133,7 @@ export function configurationDefault() : Configuration
         max_size_model_step_preview: 30,
         only_show_single_image_in_groups: true,
         custom_slicer_path: "",
+        slicer_executable: "",
         group_split_view: "split-left-right",
         label_exported_model_as_printed: false,
         theme: "default",
@@ -144,6 +148,8 @@ export function configurationDefault() : Configuration
         thumbnail_rotation : [35, 30, 0],
         watch_downloads_folder: false,
         startup_page: "",
+        combined_3mf_bed_size : [256, 256],
+        export_folder_template : "{group}/{name}.{ext}",
     }
 }
 
@@ -1,21 +1,107 @@
 import { configuration } from "$lib/configuration.svelte";
-import type { IBlobApi } from "./blob_api";
+import type { IBlobApi, MeshFormat } from "./blob_api";
 import type { Model } from "./model_api";
 import { toast } from "svelte-sonner";
+import { updateSidebarState } from "$lib/sidebar_data.svelte";
+
+export interface SlicerInstallation
+{
+    executable : string,
+    version : string|null,
+    source : "Executable" | "AppImage" | "DesktopEntry" | "Flatpak" | "AppBundle",
+}
 
 export interface SlicerEntry
 {
     slicer : string,
     installed : boolean,
+    installations? : SlicerInstallation[],
 }
 
 export const ISlicerApi = Symbol('ISlicerApi');
 
 export interface ISlicerApi {
-    openInSlicer(models : Model[]) : Promise<void>;
+    openInSlicer(models : Model[], format? : MeshFormat, combine? : boolean) : Promise<void>;
     availableSlicers() : Promise<SlicerEntry[]>;
 }
 
+export interface SliceEstimate
+{
+    slicer : string|null,
+    slicer_version : string|null,
+    print_time_seconds : number|null,
+    filament_used_mm : number|null,
+    filament_used_g : number|null,
+    filament_cost : number|null,
+    nozzle_diameter : number|null,
+    layer_height : number|null,
+    printer_model : string|null,
+    material_type : string|null,
+}
+
+export interface SliceResult
+{
+    modelIds : number[],
+    estimate : SliceEstimate,
+}
+
+// Slicers that can export G-code from the command line without opening a window
+export const CliSlicers = ["PrusaSlicer", "SuperSlicer", "OrcaSlicer"];
+
+export const ISliceApi = Symbol('ISliceApi');
+
+export interface ISliceApi {
+    // Asks for the printer, print and filament profiles to slice with, null when cancelled
+    pickSliceProfiles() : Promise<string[]|null>;
+    sliceModel(model : Model, profilePaths : string[]) : Promise<SliceResult>;
+}
+
+function describeEstimate(estimate : SliceEstimate) : string
+{
+    let parts : string[] = [];
+
+    if (estimate.print_time_seconds !== null) {
+        let hours = Math.floor(estimate.print_time_seconds / 3600);
+        let minutes = Math.round((estimate.print_time_seconds % 3600) / 60);
+        parts.push(hours > 0 ? `${hours}h ${minutes}m` : `${minutes}m`);
+    }
+
+    if (estimate.filament_used_g !== null) {
+        parts.push(`${estimate.filament_used_g.toFixed(1)}g`);
+    }
+
+    return parts.length > 0 ? ` (${parts.join(", ")})` : "";
+}
+
+export async function sliceModel(model : Model, sliceApi : ISliceApi|null) : Promise<void>
+{
+    if (!sliceApi) {
+        return;
+    }
+
+    let profilePaths = await sliceApi.pickSliceProfiles();
+
+    if (!profilePaths || profilePaths.length === 0) {
+        return;
+    }
+
+    let promise = sliceApi.sliceModel(model, profilePaths);
+
+    toast.promise(
+        promise,
+        {
+            loading: `Slicing '${model.name}'...`,
+            success: (result) => {
+                return `Sliced '${model.name}'${describeEstimate(result.estimate)}`;
+            },
+            error: (e) => `Failed to slice '${model.name}': ${e}`,
+        }
+    );
+
+    await promise;
+    await updateSidebarState();
+}
+
 function slicerNameToDeepLink(slicerName: string): string | null {
     switch (slicerName) {
         case "PrusaSlicer":
@@ -26,6 +112,8 @@ function slicerNameToDeepLink(slicerName: string): string | null {
             return "bambustudio://open?file=";
         case "OrcaSlicer":
             return "orcaslicer://open?file=";
+        case "ElegooSlicer":
+            return "elegooslicer://open?file=";
         case "Mesh Organiser":
             return "meshorganiser://open?file=";
         default:
@@ -40,7 +128,7 @@ export class DefaultSlicerApi implements ISlicerApi {
         this.blobApi = blobApi;
     }
 
-    async openInSlicer(models: Model[]): Promise<void> {
+    async openInSlicer(models: Model[], format?: MeshFormat, combine?: boolean): Promise<void> {
         let modelUrl;
 
         console.log(models);
@@ -48,11 +136,14 @@ export class DefaultSlicerApi implements ISlicerApi {
         if (models.length === 0) {
             return;
         }
+        else if (combine) {
+            modelUrl = await this.blobApi.getBlobsDownloadUrl(models.map(m => m.blob), format, true);
+        }
         else if (models.length === 1) {
-            modelUrl = await this.blobApi.getBlobDownloadUrl(models[0].blob);
+            modelUrl = await this.blobApi.getBlobDownloadUrl(models[0].blob, format);
         }
         else if (models.length > 1) {
-            modelUrl = await this.blobApi.getBlobsDownloadUrl(models.map(m => m.blob));
+            modelUrl = await this.blobApi.getBlobsDownloadUrl(models.map(m => m.blob), format);
         }
 
         let deepLink = slicerNameToDeepLink(configuration.slicer ?? "OrcaSlicer");
@@ -1,6 +1,7 @@
 
 export enum ImportStatus {
     Idle = "Idle",
+    Slicing = "Slicing",
     ProcessingModels = "ProcessingModels",
     FinishedModels = "FinishedModels",
     ProcessingThumbnails = "ProcessingThumbnails",
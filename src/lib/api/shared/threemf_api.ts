@@ -1,6 +1,7 @@
 import { toast } from "svelte-sonner";
 import { IGroupApi, type GroupMeta } from "./group_api";
 import type { Model } from "./model_api";
+import type { MeshFormat } from "./blob_api";
 import { updateSidebarState } from "$lib/sidebar_data.svelte";
 import { goto } from "$app/navigation";
 
@@ -9,27 +10,75 @@ export interface ThreemfMetadata {
     layer_height: number | null;
     material_type: string | null;
     supports_enabled: boolean | null;
+    title: string | null;
+    designer: string | null;
+    license: string | null;
+    creation_date: string | null;
+    printer_preset: string | null;
+    process_preset: string | null;
+    filament_presets: string[];
+    filament_colours: string[];
+    plates: ThreemfPlate[];
+    objects: ThreemfObject[];
+}
+
+export interface ThreemfPlate {
+    index: number;
+    name: string | null;
+    thumbnail_file: string | null;
+    object_names: string[];
+    print_time_seconds: number | null;
+    weight_g: number | null;
+    filaments: ThreemfFilament[];
+}
+
+export interface ThreemfFilament {
+    id: number;
+    material_type: string | null;
+    colour: string | null;
+    used_m: number | null;
+    used_g: number | null;
+}
+
+export interface ThreemfObject {
+    id: number;
+    name: string | null;
+    extruder: number | null;
+    settings: Record<string, string>;
 }
 
 export const IThreemfApi = Symbol('IThreemfApi');
 
 export interface IThreemfApi {
     getThreemfMetadata(modelId: Model) : Promise<ThreemfMetadata|null>;
-    extractThreemfModels(modelId: Model) : Promise<GroupMeta>;
+    extractThreemfModels(modelId: Model, format: MeshFormat) : Promise<GroupMeta>;
+    splitThreemfPlates(modelId: Model) : Promise<GroupMeta>;
+}
+
+export async function extractThreemfModels(model : Model, format : MeshFormat, threemfApi : IThreemfApi|null, groupApi: IGroupApi|null) : Promise<void>
+{
+    if (!threemfApi || !groupApi) {
+        return;
+    }
+
+    await importFromThreemf(model, threemfApi.extractThreemfModels(model, format), `Extracting models from '${model.name}'...`, groupApi);
 }
 
-export async function extractThreemfModels(model : Model, threemfApi : IThreemfApi|null, groupApi: IGroupApi|null) : Promise<void>
+export async function splitThreemfPlates(model : Model, threemfApi : IThreemfApi|null, groupApi: IGroupApi|null) : Promise<void>
 {
     if (!threemfApi || !groupApi) {
         return;
     }
 
-    let promise = threemfApi.extractThreemfModels(model);
+    await importFromThreemf(model, threemfApi.splitThreemfPlates(model), `Splitting plates of '${model.name}'...`, groupApi);
+}
 
+async function importFromThreemf(model : Model, promise : Promise<GroupMeta>, loading : string, groupApi: IGroupApi) : Promise<void>
+{
     toast.promise(
         promise,
         {
-            loading: `Extracting models from '${model.name}'...`,
+            loading: loading,
             success: (newGroup) => {
                 return `Imported '${newGroup.name}''`;
             },
@@ -22,6 +22,8 @@ import { WebModelApi } from "../web/model";
 import { WebResourceApi } from "../web/resource";
 import { WebShareApi } from "../web/share";
 import { WebThreemfApi } from "../web/threemf";
+import { WebPrinterApi } from "../web/printer";
+import { IPrinterApi } from "../shared/printer_api";
 import { WebUserApi } from "../web/user";
 import { WebUserAdminApi } from "../web/user_admin";
 import { OnlineLocalApi } from "./local";
@@ -63,4 +65,8 @@ export async function initTauriOnlineAccountApi(user : User, hostUrl : string, a
     container.addSingleton(ISlicerApi, slicerApi);
     container.addSingleton(ITauriImportApi, tauriImportApi);
     container.addSingleton(IShareApi, shareApi);
+
+    if (user.permissions.admin) {
+        container.addSingleton(IPrinterApi, new WebPrinterApi(requestApi));
+    }
 }
\ No newline at end of file
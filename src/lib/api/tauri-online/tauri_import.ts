@@ -51,6 +51,8 @@ function fakeModels(modelIds: number[]) : Model[] {
             printed: false,
         },
         uniqueGlobalId: "",
+        sourceModelId: null,
+        lastPrint: null,
     }));
 }
 
@@ -30,14 +30,16 @@ import { updateState } from "$lib/update_data.svelte";
 import { toast } from "svelte-sonner";
 import { DiskUsageInfoApi } from "./disk_usage_info";
 import { IDiskUsageInfoApi } from "../shared/disk_usage_info_api";
-import { SlicerApi } from "./slicer";
+import { SliceApi, SlicerApi } from "./slicer";
 import { LocalApi } from "./local";
-import { DefaultSlicerApi, ISlicerApi } from "../shared/slicer_api";
+import { DefaultSlicerApi, ISliceApi, ISlicerApi } from "../shared/slicer_api";
 import { ILocalApi } from "../shared/local_api";
 import { UserApi } from "./user";
 import { IAdminUserApi, ISwitchUserApi, IUserApi, IUserManageSelfApi, type User } from "../shared/user_api";
 import { ThreemfApi } from "./threemf";
 import { IThreemfApi } from "../shared/threemf_api";
+import { PrinterApi } from "./printer";
+import { IPrinterApi } from "../shared/printer_api";
 import { ThumbnailApi } from "./thumbnail";
 import { IThumbnailApi } from "../shared/thumbnail_api";
 import { TauriUserSyncApi } from "./user_sync";
@@ -86,9 +88,11 @@ export async function initTauriLocalApis() : Promise<void> {
     const hostApi = new HostApi();
     const diskUsageInfoApi = new DiskUsageInfoApi();
     const slicerApi = new SlicerApi();
+    const sliceApi = new SliceApi();
     const localApi = new LocalApi(appDataDirPath, state.max_parallelism ?? 2);
     const userApi = new UserApi();
     const threemfApi = new ThreemfApi();
+    const printerApi = new PrinterApi();
     const thumbnailApi = new ThumbnailApi();
     const userSyncApi = new TauriUserSyncApi();
 
@@ -187,8 +191,10 @@ export async function initTauriLocalApis() : Promise<void> {
     container.addSingleton(ISidebarStateApi, sidebarApi);
     container.addSingleton(IDiskUsageInfoApi, diskUsageInfoApi);
     container.addSingleton(ISlicerApi, slicerApi);
+    container.addSingleton(ISliceApi, sliceApi);
     container.addSingleton(IThreemfApi, threemfApi);
     container.addSingleton(IThumbnailApi, thumbnailApi);
+    container.addSingleton(IPrinterApi, printerApi);
 
     await tauriImport.initImportListeners();
 }
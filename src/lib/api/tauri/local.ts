@@ -1,6 +1,7 @@
 import { invoke } from "@tauri-apps/api/core";
 import type { ILocalApi } from "../shared/local_api";
 import type { Model } from "../shared/model_api";
+import type { MeshFormat } from "../shared/blob_api";
 import { open } from "@tauri-apps/plugin-dialog";
 import { join } from "@tauri-apps/api/path";
 import { openPath } from "@tauri-apps/plugin-opener";
@@ -14,8 +15,21 @@ export class LocalApi implements ILocalApi {
         this.maxParallelism = maxParallelism;
     }
 
-    async openInFolder(models: Model[], asZip: boolean): Promise<void> {
-        await invoke("open_in_folder", { modelIds: models.map(m => m.id), asZip: asZip  });
+    async openInFolder(models: Model[], asZip: boolean, format?: MeshFormat, combine?: boolean): Promise<void> {
+        await invoke("open_in_folder", { modelIds: models.map(m => m.id), asZip: asZip, format: format ?? null, combine: combine ?? false });
+    }
+
+    async exportToFolder(models: Model[], format?: MeshFormat): Promise<void> {
+        const directory = await open({
+            multiple: false,
+            directory: true,
+        });
+
+        if (!directory) {
+            return;
+        }
+
+        await invoke("export_to_folder", { modelIds: models.map(m => m.id), directory: directory, template: null, format: format ?? null });
     }
 
     async getAppDataDir(): Promise<string> {
@@ -1,5 +1,5 @@
 import { invoke } from "@tauri-apps/api/core";
-import { createModelInstance, type Model, type IModelApi, type ModelFlags, type ModelOrderBy, type ModelFilter } from "../shared/model_api";
+import { createModelInstance, type MeshHealth, type Model, type ModelPrint, type IModelApi, type ModelFlags, type ModelOrderBy, type ModelFilter } from "../shared/model_api";
 import { parseRawBlob, type RawBlob } from "./blob";
 import { parseRawGroupMeta, type RawGroupMeta } from "./group";
 import { parseRawLabelMeta, type RawLabelMeta } from "./label";
@@ -42,6 +42,15 @@ export interface RawModel {
     labels: RawLabelMeta[];
     flags: string[];
     unique_global_id: string;
+    source_model_id?: number|null;
+    last_print?: RawModelPrint|null;
+    health?: MeshHealth|null;
+}
+
+export interface RawModelPrint {
+    printed_at: string;
+    duration_seconds: number|null;
+    state: ModelPrint["state"];
 }
 
 export function parseRawModel(raw: RawModel): Model {
@@ -56,7 +65,14 @@ export function parseRawModel(raw: RawModel): Model {
         raw.group ? parseRawGroupMeta(raw.group) : null,
         raw.labels.map(label => parseRawLabelMeta(label)),
         raw.flags,
-        raw.unique_global_id
+        raw.unique_global_id,
+        raw.source_model_id ?? null,
+        raw.last_print ? {
+            printedAt: new Date(raw.last_print.printed_at),
+            durationSeconds: raw.last_print.duration_seconds,
+            state: raw.last_print.state,
+        } : null,
+        raw.health ?? null
     );
 }
 
@@ -70,6 +86,7 @@ export class ModelApi implements IModelApi {
             textSearch: filter.textSearch,
             modelFlags: convertModelFlagsToRaw(filter.flags),
             fileTypes: filter.fileTypes,
+            hasProblems: filter.hasProblems,
             page: page,
             pageSize: pageSize,
         });
@@ -0,0 +1,87 @@
+import { invoke } from "@tauri-apps/api/core";
+import type { Model } from "../shared/model_api";
+import type { IPrinterApi, PrintJob, PrintJobState, Printer, PrinterType } from "../shared/printer_api";
+
+export interface RawPrinter {
+    id: number;
+    name: string;
+    printer_type: PrinterType;
+    base_url: string;
+    created_at: string;
+}
+
+export interface RawPrintJob {
+    id: number;
+    printer_id: number;
+    model_id: number|null;
+    file_name: string;
+    state: PrintJobState;
+    created_at: string;
+    started_at: string|null;
+    finished_at: string|null;
+    duration_seconds: number|null;
+}
+
+export function parseRawPrinter(raw: RawPrinter): Printer {
+    return {
+        id: raw.id,
+        name: raw.name,
+        printerType: raw.printer_type,
+        baseUrl: raw.base_url,
+        createdAt: new Date(raw.created_at),
+    };
+}
+
+export function parseRawPrintJob(raw: RawPrintJob): PrintJob {
+    return {
+        id: raw.id,
+        printerId: raw.printer_id,
+        modelId: raw.model_id,
+        fileName: raw.file_name,
+        state: raw.state,
+        createdAt: new Date(raw.created_at),
+        startedAt: raw.started_at ? new Date(raw.started_at) : null,
+        finishedAt: raw.finished_at ? new Date(raw.finished_at) : null,
+        durationSeconds: raw.duration_seconds,
+    };
+}
+
+export class PrinterApi implements IPrinterApi {
+    async getPrinters(): Promise<Printer[]> {
+        let printers = await invoke<RawPrinter[]>('get_printers');
+        return printers.map(parseRawPrinter);
+    }
+
+    async addPrinter(name: string, printerType: PrinterType, baseUrl: string, apiKey: string | null): Promise<Printer> {
+        let id = await invoke<number>('add_printer', { printerName: name, printerType: printerType, printerBaseUrl: baseUrl, printerApiKey: apiKey });
+        let printers = await this.getPrinters();
+        return printers.find(p => p.id === id)!;
+    }
+
+    async editPrinter(printer: Printer, apiKey: string | null): Promise<void> {
+        await invoke('edit_printer', {
+            printerId: printer.id,
+            printerName: printer.name,
+            printerType: printer.printerType,
+            printerBaseUrl: printer.baseUrl,
+            printerApiKey: apiKey ? apiKey : null,
+        });
+    }
+
+    async deletePrinter(printer: Printer): Promise<void> {
+        await invoke('delete_printer', { printerId: printer.id });
+    }
+
+    async testPrinter(printer: Printer): Promise<void> {
+        await invoke('test_printer', { printerId: printer.id });
+    }
+
+    async getPrintJobs(printer: Printer): Promise<PrintJob[]> {
+        let jobs = await invoke<RawPrintJob[]>('get_print_jobs', { printerId: printer.id });
+        return jobs.map(parseRawPrintJob);
+    }
+
+    async sendModelToPrinter(printer: Printer, model: Model, startPrint: boolean): Promise<number> {
+        return await invoke<number>('send_model_to_printer', { printerId: printer.id, modelId: model.id, startPrint: startPrint });
+    }
+}
@@ -1,13 +1,41 @@
 import { invoke } from "@tauri-apps/api/core";
+import { open } from "@tauri-apps/plugin-dialog";
 import type { Model } from "../shared/model_api";
-import type { ISlicerApi, SlicerEntry } from "../shared/slicer_api";
+import type { MeshFormat } from "../shared/blob_api";
+import type { ISliceApi, ISlicerApi, SliceEstimate, SliceResult, SlicerEntry } from "../shared/slicer_api";
+import type { ImportState } from "../shared/tauri_import_api";
+
+interface RawSliceResult {
+    import_state: ImportState;
+    estimate: SliceEstimate;
+}
 
 export class SlicerApi implements ISlicerApi {
-    async openInSlicer(models: Model[]): Promise<void> {
-        await invoke("open_in_slicer", { modelIds: models.map(m => m.id) });
+    async openInSlicer(models: Model[], format?: MeshFormat, combine?: boolean): Promise<void> {
+        await invoke("open_in_slicer", { modelIds: models.map(m => m.id), format: format ?? null, combine: combine ?? false });
     }
 
     async availableSlicers(): Promise<SlicerEntry[]> {
         return await invoke("get_slicers", {});
     }
-}
\ No newline at end of file
+}
+
+export class SliceApi implements ISliceApi {
+    async pickSliceProfiles(): Promise<string[] | null> {
+        return await open({
+            title: "Select slicer profiles",
+            multiple: true,
+            directory: false,
+            filters: [{ name: "Slicer profiles", extensions: ["ini", "json"] }],
+        });
+    }
+
+    async sliceModel(model: Model, profilePaths: string[]): Promise<SliceResult> {
+        let result = await invoke<RawSliceResult>("slice_model", { modelId: model.id, profilePaths: profilePaths });
+
+        return {
+            modelIds: result.import_state.imported_models.flatMap(set => set.model_ids),
+            estimate: result.estimate,
+        };
+    }
+}
@@ -190,7 +190,8 @@ export class TauriImportApi implements ITauriImportApi {
         let paths = event.paths.filter(p => {
             let lower = p.toLowerCase();
 
-            return lower.endsWith(".stl") || lower.endsWith(".obj") || lower.endsWith(".3mf") || lower.endsWith(".gcode") || lower.endsWith(".step");
+            return lower.endsWith(".stl") || lower.endsWith(".obj") || lower.endsWith(".3mf") || lower.endsWith(".gcode") || lower.endsWith(".bgcode") || lower.endsWith(".step")
+                || lower.endsWith(".ctb") || lower.endsWith(".sl1") || lower.endsWith(".sl1s") || lower.endsWith(".goo") || lower.endsWith(".pwmx");
         });
 
         if (paths.length <= 0) {
@@ -332,7 +333,7 @@ export class TauriImportApi implements ITauriImportApi {
             filters = [
                 {
                     name: "3D Models",
-                    extensions: ["stl", "obj", "3mf", "gcode", "step", "zip"],
+                    extensions: ["stl", "obj", "3mf", "gcode", "bgcode", "ctb", "sl1", "sl1s", "goo", "pwmx", "step", "zip"],
                 },
             ];
         }
@@ -1,12 +1,18 @@
 import { invoke } from "@tauri-apps/api/core";
+import type { MeshFormat } from "../shared/blob_api";
 import type { IThreemfApi, ThreemfMetadata } from "../shared/threemf_api";
 import type { Model } from "../shared/model_api";
 import type { GroupMeta } from "../shared/group_api";
 import { parseRawGroupMeta, type RawGroupMeta } from "./group";
 
 export class ThreemfApi implements IThreemfApi {
-    async extractThreemfModels(modelId: Model): Promise<GroupMeta> {
-        let groupMeta = await invoke<RawGroupMeta>('extract_threemf_models', { modelId: modelId.id });
+    async extractThreemfModels(modelId: Model, format: MeshFormat): Promise<GroupMeta> {
+        let groupMeta = await invoke<RawGroupMeta>('extract_threemf_models', { modelId: modelId.id, format: format });
+        return parseRawGroupMeta(groupMeta);
+    }
+
+    async splitThreemfPlates(modelId: Model): Promise<GroupMeta> {
+        let groupMeta = await invoke<RawGroupMeta>('split_threemf_plates', { modelId: modelId.id });
         return parseRawGroupMeta(groupMeta);
     }
 
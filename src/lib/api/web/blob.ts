@@ -1,4 +1,4 @@
-import { FileType, type Blob, type IBlobApi } from "../shared/blob_api";
+import { FileType, type Blob, type IBlobApi, type MeshFormat } from "../shared/blob_api";
 import { HttpMethod, type IServerRequestApi } from "../shared/server_request_api";
 import type { User } from "../shared/user_api";
 
@@ -13,13 +13,22 @@ export class WebBlobApi implements IBlobApi {
         this.hostUrl = hostUrl || document.location.origin;
     }
 
-    async getBlobDownloadUrl(blob: Blob): Promise<string> {
-        return this.hostUrl + `/api/v1/blobs/${blob.sha256}/download?user_hash=${this.user.syncUrl}&user_id=${this.user.id}`;
+    async getBlobDownloadUrl(blob: Blob, format?: MeshFormat): Promise<string> {
+        let formatParam = format ? `&format=${format}` : "";
+        return this.hostUrl + `/api/v1/blobs/${blob.sha256}/download?user_hash=${this.user.syncUrl}&user_id=${this.user.id}${formatParam}`;
     }
 
-    async getBlobsDownloadUrl(blobs: Blob[]): Promise<string> {
+    async getBlobsDownloadUrl(blobs: Blob[], format?: MeshFormat, combine?: boolean): Promise<string> {
         let data = blobs.map(b => b.sha256);
-        let zipDir = await this.requestApi.request<string>("/blobs/download", HttpMethod.POST, data);
+        let params = new URLSearchParams();
+        if (format) {
+            params.set("format", format);
+        }
+        if (combine) {
+            params.set("combine", "true");
+        }
+        let query = params.size > 0 ? `?${params.toString()}` : "";
+        let zipDir = await this.requestApi.request<string>("/blobs/download" + query, HttpMethod.POST, data);
         return this.hostUrl + `/api/v1/blobs/download/${zipDir}`;
     }
 
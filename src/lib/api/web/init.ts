@@ -16,7 +16,7 @@ import { WebSettingsApi } from "./settings";
 import { WebImportApi } from "./web_import";
 import { DefaultSlicerApi, ISlicerApi } from "../shared/slicer_api";
 import { DefaultSidebarStateApi, ISidebarStateApi } from "../shared/sidebar_state_api";
-import { DefaultDownloadApi, IDownloadApi } from "../shared/download_api";
+import { ServerZipDownloadApi, IDownloadApi } from "../shared/download_api";
 import { configuration, currentUser as globalCurrentUser, panicState } from "$lib/configuration.svelte";
 import { IBlobApi } from "../shared/blob_api";
 import { IDiskUsageInfoApi } from "../shared/disk_usage_info_api";
@@ -31,6 +31,8 @@ import { WebBrowserApi } from "./internal_browser_api";
 import { IInternalBrowserApi } from "../shared/internal_browser_api";
 import { WebThreemfApi } from "./threemf";
 import { IThreemfApi } from "../shared/threemf_api";
+import { WebPrinterApi } from "./printer";
+import { IPrinterApi } from "../shared/printer_api";
 import { WebUserAdminApi } from "./user_admin";
 import { WebShareApi } from "./share";
 import { IShareApi } from "../shared/share_api";
@@ -74,11 +76,12 @@ export async function initWebApi() : Promise<void> {
     const importApi = new WebImportApi(request);
     const slicer = new DefaultSlicerApi(blob);
     const sidebarApi = new DefaultSidebarStateApi();
-    const downloadApi = new DefaultDownloadApi(blob);
+    const downloadApi = new ServerZipDownloadApi(blob);
     const internalBrowserApi = new WebBrowserApi();
     const threemf = new WebThreemfApi(request);
     const userAdmin = new WebUserAdminApi(request, currentUser);
     const shareApi = new WebShareApi(request);
+    const printerApi = new WebPrinterApi(request);
 
     const config = await settings.getConfiguration();
     Object.assign(configuration, config);
@@ -102,6 +105,8 @@ export async function initWebApi() : Promise<void> {
 
     if (currentUser.permissions.admin) {
         container.addSingleton(IAdminUserApi, userAdmin);
+        // The server only lets admins set up printers
+        container.addSingleton(IPrinterApi, printerApi);
     }
 
     // Local user, shouldn't be used for anything other than account creation and recovery
@@ -18,6 +18,7 @@ export class WebModelApi implements IModelApi {
             order_by: filter.orderBy,
             text_search: filter.textSearch,
             file_types: filter.fileTypes,
+            has_problems: filter.hasProblems,
             page: page,
             page_size: pageSize,
             model_flags: convertModelFlagsToRaw(filter.flags)
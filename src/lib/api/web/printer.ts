@@ -0,0 +1,68 @@
+import type { Model } from "../shared/model_api";
+import type { IPrinterApi, PrintJob, Printer, PrinterType } from "../shared/printer_api";
+import { HttpMethod, type IServerRequestApi } from "../shared/server_request_api";
+import { parseRawPrinter, parseRawPrintJob, type RawPrinter, type RawPrintJob } from "../tauri/printer";
+
+interface PostIdResponse {
+    id: number;
+}
+
+export class WebPrinterApi implements IPrinterApi {
+    private requestApi : IServerRequestApi;
+
+    constructor(requestApi : IServerRequestApi) {
+        this.requestApi = requestApi;
+    }
+
+    async getPrinters(): Promise<Printer[]> {
+        let printers = await this.requestApi.request<RawPrinter[]>(`/printers`, HttpMethod.GET);
+        return printers.map(parseRawPrinter);
+    }
+
+    async addPrinter(name: string, printerType: PrinterType, baseUrl: string, apiKey: string | null): Promise<Printer> {
+        let data = {
+            printer_name: name,
+            printer_type: printerType,
+            printer_base_url: baseUrl,
+            printer_api_key: apiKey,
+        };
+
+        let response = await this.requestApi.request<PostIdResponse>(`/printers`, HttpMethod.POST, data);
+        let printers = await this.getPrinters();
+        return printers.find(p => p.id === response.id)!;
+    }
+
+    async editPrinter(printer: Printer, apiKey: string | null): Promise<void> {
+        let data = {
+            printer_name: printer.name,
+            printer_type: printer.printerType,
+            printer_base_url: printer.baseUrl,
+            printer_api_key: apiKey ? apiKey : null,
+        };
+
+        await this.requestApi.request<void>(`/printers/${printer.id}`, HttpMethod.PUT, data);
+    }
+
+    async deletePrinter(printer: Printer): Promise<void> {
+        await this.requestApi.request<void>(`/printers/${printer.id}`, HttpMethod.DELETE);
+    }
+
+    async testPrinter(printer: Printer): Promise<void> {
+        await this.requestApi.request<void>(`/printers/${printer.id}/test`, HttpMethod.POST);
+    }
+
+    async getPrintJobs(printer: Printer): Promise<PrintJob[]> {
+        let jobs = await this.requestApi.request<RawPrintJob[]>(`/printers/${printer.id}/jobs`, HttpMethod.GET);
+        return jobs.map(parseRawPrintJob);
+    }
+
+    async sendModelToPrinter(printer: Printer, model: Model, startPrint: boolean): Promise<number> {
+        let data = {
+            model_id: model.id,
+            start_print: startPrint,
+        };
+
+        let response = await this.requestApi.request<PostIdResponse>(`/printers/${printer.id}/jobs`, HttpMethod.POST, data);
+        return response.id;
+    }
+}
@@ -1,6 +1,7 @@
 import type { GroupMeta } from "../shared/group_api";
 import type { Model } from "../shared/model_api";
 import { HttpMethod, type IServerRequestApi } from "../shared/server_request_api";
+import type { MeshFormat } from "../shared/blob_api";
 import type { IThreemfApi, ThreemfMetadata } from "../shared/threemf_api";
 import { parseRawGroupMeta, type RawGroupMeta } from "../tauri/group";
 
@@ -21,8 +22,14 @@ export class WebThreemfApi implements IThreemfApi {
         }
     }
 
-    async extractThreemfModels(modelId: Model): Promise<GroupMeta> {
-        let groupMeta = await this.requestApi.request<RawGroupMeta>(`/models/${modelId.id}/3mf_extract`, HttpMethod.POST);
+    async extractThreemfModels(modelId: Model, format: MeshFormat): Promise<GroupMeta> {
+        let groupMeta = await this.requestApi.request<RawGroupMeta>(`/models/${modelId.id}/3mf_extract?format=${format}`, HttpMethod.POST);
+
+        return parseRawGroupMeta(groupMeta);
+    }
+
+    async splitThreemfPlates(modelId: Model): Promise<GroupMeta> {
+        let groupMeta = await this.requestApi.request<RawGroupMeta>(`/models/${modelId.id}/3mf_split`, HttpMethod.POST);
 
         return parseRawGroupMeta(groupMeta);
     }
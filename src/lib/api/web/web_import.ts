@@ -90,7 +90,7 @@ export class WebImportApi implements IWebImportApi {
     async openFilesForImporting(): Promise<void> {
         let input = document.createElement("input");
         input.type = "file";
-        input.accept = ".stl,.obj,.step,.3mf,.gcode,.zip";
+        input.accept = ".stl,.obj,.step,.3mf,.gcode,.bgcode,.ctb,.sl1,.sl1s,.goo,.pwmx,.zip";
         input.multiple = true;
         input.click();
 
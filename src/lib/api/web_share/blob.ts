@@ -1,4 +1,4 @@
-import type { Blob, FileType, IBlobApi } from "../shared/blob_api";
+import type { Blob, FileType, IBlobApi, MeshFormat } from "../shared/blob_api";
 import { HttpMethod, type IServerRequestApi } from "../shared/server_request_api";
 import type { Share } from "../shared/share_api";
 
@@ -15,12 +15,22 @@ export class WebShareBlobApi implements IBlobApi {
         throw new Error("Method not implemented.");
     }
 
-    getBlobsDownloadUrl(blobs: Blob[]): Promise<string> {
-        throw new Error("Cannot download multiple blobs from web share API");
+    async getBlobsDownloadUrl(blobs: Blob[], format?: MeshFormat, combine?: boolean): Promise<string> {
+        if (combine) {
+            throw new Error("Cannot combine blobs from web share API");
+        }
+
+        let params = new URLSearchParams();
+        blobs.forEach(b => params.append("sha256", b.sha256));
+        if (format) {
+            params.set("format", format);
+        }
+        return document.location.origin + `/api/v1/shares/${this.share.id}/download?${params.toString()}`;
     }
 
-    async getBlobDownloadUrl(blob: Blob): Promise<string> {
-        return document.location.origin + `/api/v1/blobs/${blob.sha256}/download?share_id=${this.share.id}`;
+    async getBlobDownloadUrl(blob: Blob, format?: MeshFormat): Promise<string> {
+        let formatParam = format ? `&format=${format}` : "";
+        return document.location.origin + `/api/v1/blobs/${blob.sha256}/download?share_id=${this.share.id}${formatParam}`;
     }
 
     async getBlobBytes(blob: Blob): Promise<Uint8Array> {
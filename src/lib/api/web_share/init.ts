@@ -1,7 +1,7 @@
 import { configuration, configurationMeta } from "$lib/configuration.svelte";
 import { getContainer, resetContainer } from "../dependency_injection";
 import { IBlobApi } from "../shared/blob_api";
-import { DefaultDownloadApi, IDownloadApi } from "../shared/download_api";
+import { ServerZipDownloadApi, IDownloadApi } from "../shared/download_api";
 import { IGroupApi } from "../shared/group_api";
 import { ILabelApi } from "../shared/label_api";
 import { IModelApi } from "../shared/model_api";
@@ -49,7 +49,7 @@ export async function initWebShareApi() : Promise<boolean> {
     const labelApi = new WebShareLabelApi();
     const modelApi = new WebShareModelApi(requestApi, share);
     const slicerApi = new DefaultSlicerApi(blobApi);
-    const downloadApi = new DefaultDownloadApi(blobApi);
+    const downloadApi = new ServerZipDownloadApi(blobApi);
     const resourceApi = new WebShareResourceApi();
 
     container.addSingleton(IBlobApi, blobApi);
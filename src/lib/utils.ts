@@ -85,6 +85,18 @@ export function fileTypeToDisplayName(fileType: FileType): string {
             return "STEP";
         case FileType.GCODE:
             return "GCODE";
+        case FileType.BGCODE:
+            return "BGCODE";
+        case FileType.CTB:
+            return "CTB";
+        case FileType.SL1:
+            return "SL1";
+        case FileType.SL1S:
+            return "SL1S";
+        case FileType.GOO:
+            return "GOO";
+        case FileType.PWMX:
+            return "PWMX";
         default:
             return "Unknown";
     }
@@ -99,7 +111,14 @@ export function fileTypeToColor(fileType: FileType): string {
         case FileType.OBJ:
             return "text-black bg-purple-400 hover:bg-purple-500";
         case FileType.GCODE:
+        case FileType.BGCODE:
             return "text-black bg-orange-400 hover:bg-orange-500";
+        case FileType.CTB:
+        case FileType.SL1:
+        case FileType.SL1S:
+        case FileType.GOO:
+        case FileType.PWMX:
+            return "text-black bg-yellow-300 hover:bg-yellow-400";
         default:
             return "text-black bg-gray-300 hover:bg-gray-400";
     }
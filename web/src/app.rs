@@ -1,12 +1,13 @@
 use std::{
-    env, fs::File, io::Write, path::PathBuf, sync::{Arc, Mutex}
+    env, fs::File, io::Write, net::SocketAddr, path::PathBuf, sync::{Arc, Mutex}
 };
 
 use axum::{
     Router,
-    extract::{DefaultBodyLimit, Request},
+    extract::{ConnectInfo, DefaultBodyLimit, Request, State},
+    http::{StatusCode, header::USER_AGENT},
     middleware::{self, Next},
-    response::Response,
+    response::{IntoResponse, Response},
 };
 use axum_login::{
     AuthManagerLayerBuilder,
@@ -14,38 +15,82 @@ use axum_login::{
 };
 use axum_messages::MessagesManagerLayer;
 use db::{
-    db_context::{self, DbContext}, group_db, model::User, user_db
+    db_context::{self, DbContext}, group_db, model::User, user_db, user_session_db
 };
-use service::{AppState, Configuration, StoredConfiguration, import_state::ImportState, stored_to_configuration, thumbnail_service};
+use service::{AppState, Configuration, StoredConfiguration, duplicate_service, gcode_service, import_state::ImportState, mesh_analysis_service, printer_service, stored_to_configuration, thumbnail_service};
 use time::{Duration, OffsetDateTime};
 use tokio::{fs, signal, task::AbortHandle};
 use tower_http::{compression::CompressionLayer, services::{ServeDir, ServeFile}};
-use tower_sessions::cookie::Key;
+use tower_sessions::cookie::{Key, SameSite};
 use tower_sessions_sqlx_store::SqliteStore;
 
 use crate::{
     controller::{
-        auth_controller, blob_controller, group_controller, label_controller, model_controller, page_controller, resource_controller, share_controller, threemf_controller, user_controller
+        auth_controller, blob_controller, group_controller, label_controller, model_controller, page_controller, resource_controller, share_controller, threemf_controller, user_controller, workspace_controller, printer_controller, gcode_controller, resin_controller, mesh_controller, backup_controller
     },
-    user::{AuthSession, Backend},
+    login_throttle::{LoginThrottle, get_client_ip},
+    oidc::{OidcClient, OidcConfig},
+    error::ApplicationError,
+    user::{AuthSession, Backend, SESSION_TRACKING_KEY},
     web_app_state::WebAppState, web_import_state::WebImportStateEmitter,
+    zip_downloads::ZipDownloads,
 };
 
 pub struct App {
     app_state: WebAppState,
-    session_store: SqliteStore,
 }
 
 fn expected_env_error_msg(var_name: &str) -> String {
     format!("Expected environment variable {} to be set", var_name)
 }
 
+// Returns false if the session was revoked by the user
+async fn track_session(
+    app_state: &WebAppState,
+    auth_session: &AuthSession,
+    user_id: i64,
+    user_agent: Option<&str>,
+    ip_address: &str,
+) -> Result<bool, ApplicationError> {
+    let db = &app_state.app_state.db;
+    let store_id = auth_session.session.id().map(|id| id.to_string());
+
+    let tracking_key = auth_session
+        .session
+        .get::<String>(SESSION_TRACKING_KEY)
+        .await
+        .map_err(|e| ApplicationError::InternalError(e.to_string()))?;
+
+    if let Some(tracking_key) = tracking_key {
+        return match user_session_db::get_session_via_id(db, &tracking_key).await? {
+            Some(session) if session.user_id == user_id => {
+                user_session_db::touch_session(db, &tracking_key, store_id.as_deref(), Some(ip_address)).await?;
+                Ok(true)
+            }
+            _ => Ok(false),
+        };
+    }
+
+    let tracking_key = user_session_db::add_session(db, user_id, store_id.as_deref(), user_agent, Some(ip_address)).await?;
+
+    auth_session
+        .session
+        .insert(SESSION_TRACKING_KEY, tracking_key)
+        .await
+        .map_err(|e| ApplicationError::InternalError(e.to_string()))?;
+
+    Ok(true)
+}
+
 async fn update_session_middleware(
-    auth_session: AuthSession,
+    State(app_state): State<WebAppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    mut auth_session: AuthSession,
     request: Request,
     next: Next,
 ) -> Response {
-    if auth_session.user.is_some() {
+    if let Some(user) = &auth_session.user {
+        let user_id = user.to_user().id;
         let expiry_date = auth_session.session.expiry_date();
         let now = OffsetDateTime::now_utc();
         let difference = expiry_date - now;
@@ -55,6 +100,22 @@ async fn update_session_middleware(
                 .session
                 .set_expiry(Some(Expiry::OnInactivity(Duration::days(7))));
         }
+
+        let ip_address = get_client_ip(request.headers(), &addr, app_state.trust_proxy_headers).to_string();
+        let user_agent = request
+            .headers()
+            .get(USER_AGENT)
+            .and_then(|h| h.to_str().ok())
+            .map(|h| h.to_string());
+
+        match track_session(&app_state, &auth_session, user_id, user_agent.as_deref(), &ip_address).await {
+            Ok(true) => {}
+            Ok(false) => {
+                let _ = auth_session.logout().await;
+                return StatusCode::UNAUTHORIZED.into_response();
+            }
+            Err(e) => return e.into_response(),
+        }
     }
 
     next.run(request).await
@@ -98,6 +159,15 @@ impl App {
         let db = db_context::setup_db(&sqlite_path, &sqlite_backup_dir).await;
         let db_clone = db.clone();
 
+        let session_store = SqliteStore::new(db_clone);
+        session_store.migrate().await?;
+
+        let trust_proxy_headers = env::var("TRUST_PROXY_HEADERS")
+            .map(|v| v.to_lowercase() == "true")
+            .unwrap_or(false);
+
+        let oidc = OidcConfig::from_env().map(|config| Arc::new(OidcClient::new(config)));
+
         let web_app_state = WebAppState {
             app_state: AppState {
                 db: Arc::new(db),
@@ -106,11 +176,13 @@ impl App {
                 import_mutex: Arc::new(tokio::sync::Mutex::new(())),
             },
             port: port,
+            session_store,
+            login_throttle: Arc::new(LoginThrottle::new()),
+            trust_proxy_headers,
+            oidc,
+            zip_downloads: Arc::new(ZipDownloads::new()),
         };
 
-        let session_store = SqliteStore::new(db_clone);
-        session_store.migrate().await?;
-
         let local_pass = match env::var("LOCAL_ACCOUNT_PASSWORD") {
             Ok(password) => password,
             Err(_) => {
@@ -144,7 +216,6 @@ impl App {
 
         Ok(Self {
             app_state: web_app_state,
-            session_store,
         })
     }
 
@@ -153,7 +224,7 @@ impl App {
         //
         // This uses `tower-sessions` to establish a layer that will provide the session
         // as a request extension.
-        let session_store = self.session_store;
+        let session_store = self.app_state.session_store.clone();
 
         let deletion_task = tokio::task::spawn(
             session_store
@@ -161,6 +232,18 @@ impl App {
                 .continuously_delete_expired(tokio::time::Duration::from_secs(60)),
         );
 
+        tokio::task::spawn(printer_service::watch_printers(self.app_state.app_state.clone()));
+
+        {
+            let app_state = self.app_state.app_state.clone();
+            tokio::task::spawn(async move {
+                let _ = gcode_service::extract_missing_metadata(&app_state).await;
+                let _ = mesh_analysis_service::analyse_missing(&app_state).await;
+                let _ = duplicate_service::fingerprint_missing(&app_state).await;
+                let _ = thumbnail_service::hash_missing_thumbnails(&app_state).await;
+            });
+        }
+
         // Generate a cryptographic key to sign the session cookie.
 
         let signing_key_path = self.app_state.get_signing_key_path();
@@ -176,8 +259,15 @@ impl App {
             }
         };
 
+        // The identity provider redirects back cross-site, which a strict cookie would not survive
+        let same_site = match self.app_state.oidc {
+            Some(_) => SameSite::Lax,
+            None => SameSite::Strict,
+        };
+
         let session_layer = SessionManagerLayer::new(session_store)
             .with_secure(false)
+            .with_same_site(same_site)
             .with_expiry(Expiry::OnInactivity(Duration::days(7)))
             .with_signed(key);
 
@@ -185,7 +275,7 @@ impl App {
         //
         // This combines the session layer with our backend to establish the auth
         // service which will provide the auth session as a request extension.
-        let backend = Backend::new(self.app_state.app_state.db.clone());
+        let backend = Backend::new(self.app_state.app_state.db.clone(), self.app_state.oidc.clone());
         let auth_layer = AuthManagerLayerBuilder::new(backend, session_layer).build();
 
         let serve_dir = ServeDir::new("www").not_found_service(ServeFile::new("www/index.html"));
@@ -203,8 +293,14 @@ impl App {
             .merge(threemf_controller::router())
             .merge(page_controller::router())
             .merge(share_controller::router())
-            .with_state(self.app_state)
-            .layer(middleware::from_fn(update_session_middleware))
+            .merge(workspace_controller::router())
+            .merge(printer_controller::router())
+            .merge(gcode_controller::router())
+            .merge(resin_controller::router())
+            .merge(mesh_controller::router())
+            .merge(backup_controller::router())
+            .with_state(self.app_state.clone())
+            .layer(middleware::from_fn_with_state(self.app_state, update_session_middleware))
             .layer(MessagesManagerLayer)
             .layer(auth_layer)
             .layer(DefaultBodyLimit::disable())
@@ -218,7 +314,7 @@ impl App {
         println!("Server running on port {}", port);
 
         // Ensure we use a shutdown signal to abort the deletion task.
-        axum::serve(listener, app.into_make_service())
+        axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
             .with_graceful_shutdown(shutdown_signal(deletion_task.abort_handle(), db))
             .await?;
 
@@ -1,7 +1,16 @@
-use crate::user::{Credentials, PasswordCredentials, TokenCredentials};
+use crate::login_throttle::{account_key, get_client_ip, ip_key};
+use crate::user::{Credentials, OidcCredentials, PENDING_OIDC_KEY, PENDING_TOTP_KEY, PasswordCredentials, SESSION_TRACKING_KEY, TokenCredentials, TotpCredentials};
 use crate::{user::AuthSession, web_app_state::WebAppState};
 use axum::{Router, http::StatusCode, response::IntoResponse, routing::{post, get}};
 use axum::Json;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
 
 pub fn router() -> Router<WebAppState> {
     Router::new().nest(
@@ -9,19 +18,98 @@ pub fn router() -> Router<WebAppState> {
         Router::new()
             .route("/login/password", post(post::password))
             .route("/login/token", post(post::token))
+            .route("/login/totp", post(post::totp))
+            .route("/login/oidc", get(get::oidc_login))
+            .route("/login/oidc/callback", get(get::oidc_callback))
             .route("/users/me", get(get::me))
             .route("/logout", post(post::logout)),
     )
 }
 
 mod get {
-    use axum::{extract::State, response::Response};
+    use axum::{extract::{Query, State}, response::{Redirect, Response}};
     use db::user_db;
+    use serde::Deserialize;
 
-    use crate::error::ApplicationError;
+    use crate::{error::ApplicationError, oidc::PendingOidcLogin};
 
     use super::*;
 
+    // How long the user has to finish logging in at the identity provider
+    const PENDING_OIDC_LIFETIME: u64 = 10 * 60;
+
+    #[derive(Deserialize)]
+    pub struct OidcCallbackParams {
+        pub code: Option<String>,
+        pub state: Option<String>,
+        pub error: Option<String>,
+    }
+
+    pub async fn oidc_login(
+        auth_session: AuthSession,
+        State(app_state): State<WebAppState>,
+    ) -> Result<Response, ApplicationError> {
+        let oidc = match &app_state.oidc {
+            Some(oidc) => oidc,
+            None => return Ok(StatusCode::NOT_FOUND.into_response()),
+        };
+
+        let (url, pending) = oidc.begin_login(PENDING_OIDC_LIFETIME).await?;
+
+        auth_session
+            .session
+            .insert(PENDING_OIDC_KEY, pending)
+            .await
+            .map_err(|e| ApplicationError::InternalError(e.to_string()))?;
+
+        Ok(Redirect::to(&url).into_response())
+    }
+
+    pub async fn oidc_callback(
+        mut auth_session: AuthSession,
+        Query(params): Query<OidcCallbackParams>,
+    ) -> Result<Response, ApplicationError> {
+        let pending = auth_session
+            .session
+            .remove::<PendingOidcLogin>(PENDING_OIDC_KEY)
+            .await
+            .map_err(|e| ApplicationError::InternalError(e.to_string()))?;
+
+        let pending = match pending {
+            Some(pending) if pending.expires_at > unix_now() => pending,
+            _ => return Ok((StatusCode::UNAUTHORIZED, "No pending login, start the login again").into_response()),
+        };
+
+        if let Some(error) = params.error {
+            return Ok((StatusCode::UNAUTHORIZED, format!("Identity provider returned an error: {}", error)).into_response());
+        }
+
+        let code = match (params.code, params.state) {
+            (Some(code), Some(state)) if state == pending.state => code,
+            _ => return Ok((StatusCode::UNAUTHORIZED, "Invalid login state").into_response()),
+        };
+
+        let creds = OidcCredentials {
+            code,
+            code_verifier: pending.code_verifier,
+            nonce: pending.nonce,
+        };
+
+        let user = match auth_session.authenticate(Credentials::Oidc(creds)).await {
+            Ok(Some(user)) => user,
+            Ok(None) => return Ok((StatusCode::UNAUTHORIZED, "Login through the identity provider failed").into_response()),
+            Err(_) => return Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+        };
+
+        if auth_session.login(&user).await.is_err() {
+            return Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response());
+        }
+
+        let _ = auth_session.session.remove::<String>(SESSION_TRACKING_KEY).await;
+
+        Ok(Redirect::to("/").into_response())
+    }
+
     pub async fn me(
         auth_session: AuthSession,
         State(app_state): State<WebAppState>,
@@ -43,12 +131,73 @@ mod get {
 }
 
 mod post {
+    use std::{net::SocketAddr, time::Duration};
+
+    use axum::{extract::{ConnectInfo, State}, http::{HeaderMap, header::RETRY_AFTER}, response::Response};
+    use axum_login::AuthUser as AxumAuthUser;
+    use db::{user_session_db, user_totp_db};
+    use serde::{Deserialize, Serialize};
+
+    use crate::user::AuthUser;
+
     use super::*;
 
+    // How long the second login step stays valid after the password was accepted
+    const PENDING_TOTP_LIFETIME: u64 = 5 * 60;
+
+    #[derive(Serialize, Deserialize)]
+    struct PendingTotpLogin {
+        user_id: i64,
+        expires_at: u64,
+    }
+
+    #[derive(Serialize)]
+    struct TotpRequiredResponse {
+        totp_required: bool,
+    }
+
+    #[derive(Deserialize)]
+    pub struct TotpLoginParams {
+        pub code: Option<String>,
+        pub recovery_code: Option<String>,
+    }
+
+
+    fn too_many_attempts(wait: Duration) -> Response {
+        let mut response = (StatusCode::TOO_MANY_REQUESTS, "Too many login attempts, try again later").into_response();
+        response.headers_mut().insert(
+            RETRY_AFTER,
+            (wait.as_secs() + 1).to_string().parse().unwrap(),
+        );
+
+        response
+    }
+
+    async fn login(auth_session: &mut AuthSession, user: &AuthUser) -> Response {
+        if auth_session.login(user).await.is_err() {
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+
+        // A fresh tracking entry gets created on the next request
+        let _ = auth_session.session.remove::<String>(SESSION_TRACKING_KEY).await;
+
+        StatusCode::NO_CONTENT.into_response()
+    }
+
     pub async fn password(
         mut auth_session: AuthSession,
+        State(app_state): State<WebAppState>,
+        ConnectInfo(addr): ConnectInfo<SocketAddr>,
+        headers: HeaderMap,
         Json(creds): Json<PasswordCredentials>,
     ) -> impl IntoResponse {
+        let ip = get_client_ip(&headers, &addr, app_state.trust_proxy_headers);
+        let throttle_keys = vec![ip_key(&ip), account_key(&creds.email)];
+
+        if let Err(wait) = app_state.login_throttle.begin_attempt(&throttle_keys) {
+            return too_many_attempts(wait);
+        }
+
         let user = match auth_session
             .authenticate(Credentials::Password(creds))
             .await
@@ -57,36 +206,123 @@ mod post {
             Ok(None) => {
                 return (StatusCode::UNAUTHORIZED, "Invalid username or password").into_response();
             }
+            Err(_) => {
+                app_state.login_throttle.release(&throttle_keys);
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+        };
+
+        // Only the account is cleared, so a valid login cannot reset the counter of an address
+        app_state.login_throttle.release(&throttle_keys[..1]);
+        app_state.login_throttle.register_success(&throttle_keys[1..]);
+
+        let user_totp = match user_totp_db::get_totp(&app_state.app_state.db, user.id()).await {
+            Ok(user_totp) => user_totp,
             Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
         };
 
-        if auth_session.login(&user).await.is_err() {
-            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        if user_totp.is_some_and(|t| t.enabled) {
+            let pending = PendingTotpLogin {
+                user_id: user.id(),
+                expires_at: unix_now() + PENDING_TOTP_LIFETIME,
+            };
+
+            if auth_session.session.insert(PENDING_TOTP_KEY, pending).await.is_err() {
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+
+            return (StatusCode::ACCEPTED, Json(TotpRequiredResponse { totp_required: true })).into_response();
         }
 
-        StatusCode::NO_CONTENT.into_response()
+        login(&mut auth_session, &user).await
+    }
+
+    pub async fn totp(
+        mut auth_session: AuthSession,
+        State(app_state): State<WebAppState>,
+        ConnectInfo(addr): ConnectInfo<SocketAddr>,
+        headers: HeaderMap,
+        Json(params): Json<TotpLoginParams>,
+    ) -> impl IntoResponse {
+        let pending = match auth_session.session.get::<PendingTotpLogin>(PENDING_TOTP_KEY).await {
+            Ok(Some(pending)) if pending.expires_at > unix_now() => pending,
+            Ok(_) => return (StatusCode::UNAUTHORIZED, "No pending login, sign in with your password first").into_response(),
+            Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        };
+
+        let ip = get_client_ip(&headers, &addr, app_state.trust_proxy_headers);
+        let throttle_keys = vec![ip_key(&ip), format!("totp:{}", pending.user_id)];
+
+        if let Err(wait) = app_state.login_throttle.begin_attempt(&throttle_keys) {
+            return too_many_attempts(wait);
+        }
+
+        let creds = TotpCredentials {
+            user_id: pending.user_id,
+            code: params.code,
+            recovery_code: params.recovery_code,
+        };
+
+        let user = match auth_session.authenticate(Credentials::Totp(creds)).await {
+            Ok(Some(user)) => user,
+            Ok(None) => {
+                return (StatusCode::UNAUTHORIZED, "Invalid authentication code").into_response();
+            }
+            Err(_) => {
+                app_state.login_throttle.release(&throttle_keys);
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+        };
+
+        app_state.login_throttle.release(&throttle_keys[..1]);
+        app_state.login_throttle.register_success(&throttle_keys[1..]);
+        let _ = auth_session.session.remove::<PendingTotpLogin>(PENDING_TOTP_KEY).await;
+
+        login(&mut auth_session, &user).await
     }
 
     pub async fn token(
         mut auth_session: AuthSession,
+        State(app_state): State<WebAppState>,
+        ConnectInfo(addr): ConnectInfo<SocketAddr>,
+        headers: HeaderMap,
         Json(creds): Json<TokenCredentials>,
     ) -> impl IntoResponse {
+        let ip = get_client_ip(&headers, &addr, app_state.trust_proxy_headers);
+        let throttle_keys = vec![ip_key(&ip)];
+
+        if let Err(wait) = app_state.login_throttle.begin_attempt(&throttle_keys) {
+            return too_many_attempts(wait);
+        }
+
         let user = match auth_session.authenticate(Credentials::Token(creds)).await {
             Ok(Some(user)) => user,
             Ok(None) => {
                 return (StatusCode::UNAUTHORIZED, "Invalid token").into_response();
             }
-            Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+            Err(_) => {
+                app_state.login_throttle.release(&throttle_keys);
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
         };
 
-        if auth_session.login(&user).await.is_err() {
-            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
-        }
+        app_state.login_throttle.release(&throttle_keys);
 
-        StatusCode::NO_CONTENT.into_response()
+        login(&mut auth_session, &user).await
     }
 
-    pub async fn logout(mut auth_session: AuthSession) -> impl IntoResponse {
+    pub async fn logout(
+        mut auth_session: AuthSession,
+        State(app_state): State<WebAppState>,
+    ) -> impl IntoResponse {
+        if let Some(user) = &auth_session.user {
+            let tracking_key = auth_session.session.get::<String>(SESSION_TRACKING_KEY).await;
+
+            if let Ok(Some(tracking_key)) = tracking_key {
+                let _ = user_session_db::delete_session(&app_state.app_state.db, user.to_user().id, &tracking_key).await;
+            }
+        }
+
         if auth_session.logout().await.is_err() {
             return StatusCode::INTERNAL_SERVER_ERROR.into_response();
         }
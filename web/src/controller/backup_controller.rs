@@ -0,0 +1,126 @@
+use crate::{
+    user::{AuthSession, Backend},
+    web_app_state::WebAppState,
+    zip_downloads::attachment_disposition,
+};
+use axum::extract::{Multipart, State};
+use axum::{
+    Json, Router,
+    body::Body,
+    http::header,
+    response::{IntoResponse, Response},
+    routing::{get, post},
+};
+use axum_login::login_required;
+use db::model::UserPermissions;
+use service::backup_service::{self, RestoreOptions};
+use service::export_service::get_temp_dir;
+use time::OffsetDateTime;
+use tokio::fs::File;
+use tokio_util::io::ReaderStream;
+
+use crate::error::ApplicationError;
+
+pub fn router() -> Router<WebAppState> {
+    Router::new().nest(
+        "/api/v1",
+        Router::new()
+            .route("/backup", get(get::get_backup))
+            .route("/backup/restore", post(post::restore_backup))
+            .route_layer(login_required!(Backend)),
+    )
+}
+
+mod get {
+    use super::*;
+
+    pub async fn get_backup(
+        auth_session: AuthSession,
+        State(app_state): State<WebAppState>,
+    ) -> Result<Response, ApplicationError> {
+        let user = auth_session.user.unwrap().to_user();
+
+        if !user.permissions.contains(UserPermissions::Admin) {
+            return Err(ApplicationError::InternalError(
+                "Insufficient permissions to back up the library.".into(),
+            ));
+        }
+
+        let filename = format!("meshorganiser_backup_{}.zip", OffsetDateTime::now_utc().date());
+        let reader = backup_service::stream_backup(&app_state.app_state).await?;
+
+        // Sent without a Content-Length, the archive is written while it streams so its size isn't known yet
+        let mut response = Body::from_stream(ReaderStream::new(reader)).into_response();
+        let headers = response.headers_mut();
+
+        if let Ok(disposition) = attachment_disposition(&filename).parse() {
+            headers.insert(header::CONTENT_DISPOSITION, disposition);
+        }
+
+        headers.insert(header::CONTENT_TYPE, "application/zip".parse().unwrap());
+
+        Ok(response)
+    }
+}
+
+mod post {
+    use tokio::io::AsyncWriteExt;
+
+    use super::*;
+
+    pub async fn restore_backup(
+        auth_session: AuthSession,
+        State(app_state): State<WebAppState>,
+        mut multipart: Multipart,
+    ) -> Result<Response, ApplicationError> {
+        let user = auth_session.user.unwrap().to_user();
+
+        if !user.permissions.contains(UserPermissions::Admin) {
+            return Err(ApplicationError::InternalError(
+                "Insufficient permissions to restore a backup.".into(),
+            ));
+        }
+
+        let temp_dir = get_temp_dir("restore");
+        let path = temp_dir.join("backup.zip");
+        let mut options = RestoreOptions::default();
+        let mut has_file = false;
+
+        while let Some(mut field) = multipart.next_field().await? {
+            // Optional JSON object mapping user ids in the backup to user ids in this library
+            if let Some("user_mapping") = field.name() {
+                options.user_mapping = serde_json::from_str(&field.text().await?)?;
+                continue;
+            }
+
+            if field.file_name().is_none() {
+                continue;
+            }
+
+            let mut file = File::create(&path).await?;
+
+            while let Some(chunk) = field.chunk().await? {
+                file.write_all(&chunk).await?;
+            }
+
+            file.flush().await?;
+            has_file = true;
+        }
+
+        drop(multipart);
+
+        if !has_file {
+            let _ = std::fs::remove_dir_all(&temp_dir);
+            return Ok((axum::http::StatusCode::BAD_REQUEST, "No backup uploaded").into_response());
+        }
+
+        let result = {
+            let _lock = app_state.app_state.import_mutex.lock().await;
+            backup_service::restore_backup(&path, options, &user, &app_state.app_state).await
+        };
+
+        let _ = std::fs::remove_dir_all(&temp_dir);
+
+        Ok(Json(result?).into_response())
+    }
+}
@@ -1,6 +1,7 @@
 use crate::{
     user::{AuthSession, Backend},
     web_app_state::WebAppState,
+    zip_downloads::zip_stream_response,
 };
 use axum::{Router, http::StatusCode, response::IntoResponse, routing::{get, post}};
 use axum_login::login_required;
@@ -13,11 +14,11 @@ use axum::{
 use axum_extra::extract::Query;
 use db::{model::{Blob, User}, model_db, user_db};
 use serde::Deserialize;
-use service::{cleanse_evil_from_name, export_service::get_model_path_for_blob};
+use service::{cleanse_evil_from_name, conversion_service, export_service::get_model_path_for_blob, mesh_service::MeshFormat};
 use tokio::{fs::File, io::BufReader};
 use tokio_util::{compat::FuturesAsyncReadCompatExt, io::ReaderStream};
 use axum::Json;
-use service::export_service;
+use service::{export_service, zip_stream_service};
 use crate::error::ApplicationError;
 
 pub fn router() -> Router<WebAppState> {
@@ -36,7 +37,6 @@ pub fn router() -> Router<WebAppState> {
 
 mod get {
     use db::model::FileType;
-    use service::thumbnail_service;
 
     use super::*;
 
@@ -45,6 +45,7 @@ mod get {
         pub user_id: Option<i64>,
         pub user_hash: Option<String>,
         pub share_id: Option<String>,
+        pub format: Option<MeshFormat>,
     }
 
     async fn extract_user_via_id_and_hash(
@@ -87,11 +88,14 @@ mod get {
         Query(params): Query<DownloadModelParams>,
     ) -> Response {
 
+        let format = params.format;
+
         let user = match params {
             DownloadModelParams {
                 user_id: Some(user_id),
                 user_hash: Some(user_hash),
                 share_id: None,
+                ..
             } => {
                 match extract_user_via_id_and_hash(&app_state, user_id, &user_hash).await {
                     Some(u) => u,
@@ -102,6 +106,7 @@ mod get {
                 user_id: None,
                 user_hash: None,
                 share_id: Some(share_id),
+                ..
             } => {
                 match extract_user_via_share_id(&app_state, &share_id).await {
                     Some(u) => u,
@@ -131,8 +136,12 @@ mod get {
             return StatusCode::NOT_FOUND.into_response();
         }
 
-        let filename = format!("{}.{}", cleanse_evil_from_name(&model.name).trim(), model.blob.to_file_type().from_zip().to_extension()).to_ascii_lowercase();
-        let mut response = get_blob_bytes_inner(&model.blob, model.blob.to_file_type(), &app_state)
+        let extension = match format {
+            Some(format) if conversion_service::needs_conversion(&model.blob, Some(format)) => format.to_extension().to_string(),
+            _ => model.blob.to_file_type().from_zip().to_extension(),
+        };
+        let filename = format!("{}.{}", cleanse_evil_from_name(&model.name).trim(), extension).to_ascii_lowercase();
+        let mut response = get_blob_bytes_inner(&model.blob, format, &app_state)
             .await;
 
         response.headers_mut().insert(
@@ -148,6 +157,7 @@ mod get {
     pub async fn get_model_bytes(
         auth_session: AuthSession,
         Path(model_id): Path<i64>,
+        Query(params): Query<GetBlobBytesParams>,
         State(app_state): State<WebAppState>,
     ) -> Response {
         let user = auth_session.user.unwrap().to_user();
@@ -166,13 +176,25 @@ mod get {
 
         let model = &model[0];
 
-        get_blob_bytes_inner(&model.blob, model.blob.to_file_type(), &app_state)
+        get_blob_bytes_inner(&model.blob, params.to_format(), &app_state)
             .await
     }
 
     #[derive(Deserialize)]
     pub struct GetBlobBytesParams {
         pub target_file_type: Option<String>,
+        pub format: Option<MeshFormat>,
+    }
+
+    impl GetBlobBytesParams {
+        // target_file_type predates format, and is still used to view STEP files as STL
+        fn to_format(&self) -> Option<MeshFormat> {
+            self.format.or_else(|| {
+                self.target_file_type
+                    .as_ref()
+                    .and_then(|target| MeshFormat::from_file_type(&FileType::from_extension(target)))
+            })
+        }
     }
 
     pub async fn get_blob_bytes(
@@ -194,9 +216,7 @@ mod get {
             _ => return StatusCode::NOT_FOUND.into_response(),
         };
 
-        let file_type = params.target_file_type.map(|target| FileType::from_extension(&target)).unwrap_or(blob.to_file_type()); 
-
-        get_blob_bytes_inner(&blob, file_type, &app_state)
+        get_blob_bytes_inner(&blob, params.to_format(), &app_state)
             .await
             .into_response()
     }
@@ -221,9 +241,29 @@ mod get {
 
     async fn get_blob_bytes_inner(
         blob: &Blob,
-        target: FileType,
+        format: Option<MeshFormat>,
         app_state: &WebAppState,
     ) -> Response {
+        if conversion_service::needs_conversion(blob, format) {
+            if !conversion_service::can_convert(&blob.to_file_type()) {
+                return StatusCode::BAD_REQUEST.into_response();
+            }
+
+            let converted_path = match conversion_service::get_converted_path(blob, format.unwrap(), &app_state.app_state).await {
+                Ok(p) => p,
+                Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+            };
+
+            let file = match File::open(converted_path).await {
+                Ok(f) => f,
+                Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+            };
+
+            let stream = ReaderStream::new(BufReader::new(file));
+
+            return Body::from_stream(stream).into_response();
+        }
+
         let src_file_path = get_model_path_for_blob(&blob, &app_state.app_state);
 
         let file = match File::open(src_file_path).await {
@@ -233,47 +273,48 @@ mod get {
 
         let buffered_reader = BufReader::new(file);
 
-        if blob.to_file_type() == target {
-            if blob.to_file_type().is_zipped() {
-                let archive = match ZipFileReader::with_tokio(buffered_reader).await {
-                    Ok(a) => a,
-                    Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
-                };
-                let file = match archive.into_entry(0).await {
-                    Ok(f) => f,
-                    Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
-                };
-
-                let stream = ReaderStream::new(file.compat());
-
-                return Body::from_stream(stream).into_response();
-            } else {
-                let stream = ReaderStream::new(buffered_reader);
-
-                return Body::from_stream(stream).into_response();
-            }
-        }
-        else if blob.to_file_type().is_step() && target.is_stl() {
-            let model_bytes = match export_service::get_bytes_from_blob(blob, &app_state.app_state).await {
-                Ok(b) => b,
+        if blob.to_file_type().is_zipped() {
+            let archive = match ZipFileReader::with_tokio(buffered_reader).await {
+                Ok(a) => a,
                 Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
             };
-
-            let converted_bytes = match thumbnail_service::convert_step_to_stl(&model_bytes) {
-                Ok(b) => b,
+            let file = match archive.into_entry(0).await {
+                Ok(f) => f,
                 Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
             };
 
-            return Response::new(Body::from(converted_bytes));
-        }
-        else {
-            return StatusCode::BAD_REQUEST.into_response();
+            let stream = ReaderStream::new(file.compat());
+
+            return Body::from_stream(stream).into_response();
         }
+
+        let stream = ReaderStream::new(buffered_reader);
+
+        Body::from_stream(stream).into_response()
     }
 
     pub async fn get_blobs_zip_download(
         Path(zip_dir): Path<String>,
+        State(app_state): State<WebAppState>,
     ) -> Result<Response, ApplicationError> {
+        if let Some(download) = app_state.zip_downloads.get(&zip_dir) {
+            let user = match user_db::get_user_by_id(&app_state.app_state.db, download.user_id).await? {
+                Some(u) => u,
+                None => return Ok(StatusCode::NOT_FOUND.into_response()),
+            };
+
+            let model_ids_len = download.model_ids.len();
+            let models = model_db::get_models_via_ids(&app_state.app_state.db, &user, download.model_ids).await?;
+
+            if models.len() != model_ids_len {
+                return Ok(StatusCode::NOT_FOUND.into_response());
+            }
+
+            let stream = zip_stream_service::stream_models_as_zip(models, download.format, &app_state.app_state).await?;
+
+            return Ok(zip_stream_response(stream));
+        }
+
         if !zip_dir.starts_with("meshorganiser_") {
             return Ok(StatusCode::BAD_REQUEST.into_response());
         }
@@ -294,7 +335,7 @@ mod get {
 
         let t = zip_file.file_name();
         let filename = t.to_string_lossy();
-        if !filename.ends_with(".zip") {
+        if !filename.ends_with(".zip") && !filename.ends_with(".3mf") {
             return Ok(StatusCode::NOT_FOUND.into_response());
         }
 
@@ -325,9 +366,16 @@ mod get {
 mod post {
     use super::*;
 
+    #[derive(Deserialize)]
+    pub struct BlobsZipDownloadParams {
+        pub format: Option<MeshFormat>,
+        pub combine: Option<bool>,
+    }
+
     pub async fn create_blobs_zip_download(
         auth_session: AuthSession,
         State(app_state): State<WebAppState>,
+        Query(params): Query<BlobsZipDownloadParams>,
         Json(blob_sha256s): Json<Vec<String>>,
     ) -> Result<Response, ApplicationError> {
         let mut model_ids = Vec::with_capacity(blob_sha256s.len());
@@ -352,8 +400,16 @@ mod post {
             return Ok(StatusCode::NOT_FOUND.into_response());
         }
         
-        let path = export_service::export_zip_to_temp_folder(models, &app_state.app_state).await?;
+        // Plain zips are streamed when the returned link is opened, combining needs the 3mf to be built up front
+        if !params.combine.unwrap_or(false) {
+            let model_ids = models.iter().map(|m| m.id).collect();
+            let token = app_state.zip_downloads.add(user.id, model_ids, params.format);
+
+            return Ok(Json(token).into_response());
+        }
+
+        let temp_dir = export_service::export_combined_3mf(models, &app_state.app_state).await?.0;
 
-        Ok(Json(path.temp_dir.file_name().unwrap().to_string_lossy().to_string()).into_response())
+        Ok(Json(temp_dir.file_name().unwrap().to_string_lossy().to_string()).into_response())
     }
 }
\ No newline at end of file
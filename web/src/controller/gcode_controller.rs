@@ -0,0 +1,50 @@
+use crate::{
+    user::{AuthSession, Backend},
+    web_app_state::WebAppState,
+};
+use axum::extract::{Path, State};
+use axum::{
+    Json, Router,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::get,
+};
+use axum_login::login_required;
+use db::model_db;
+use service::gcode_service;
+
+use crate::error::ApplicationError;
+
+pub fn router() -> Router<WebAppState> {
+    Router::new().nest(
+        "/api/v1",
+        Router::new()
+            .route(
+                "/models/{model_id}/gcode_metadata",
+                get(get::get_gcode_metadata),
+            )
+            .route_layer(login_required!(Backend)),
+    )
+}
+
+mod get {
+    use super::*;
+
+    pub async fn get_gcode_metadata(
+        auth_session: AuthSession,
+        Path(model_id): Path<i64>,
+        State(app_state): State<WebAppState>,
+    ) -> Result<Response, ApplicationError> {
+        let user = auth_session.user.unwrap().to_user();
+
+        let model = model_db::get_models_via_ids(&app_state.app_state.db, &user, vec![model_id]).await?;
+
+        if model.is_empty() {
+            return Ok((StatusCode::NOT_FOUND, "Model not found").into_response());
+        }
+
+        let gcode_metadata = gcode_service::get_metadata(&model[0], &app_state.app_state).await?;
+
+        Ok(Json(gcode_metadata).into_response())
+    }
+}
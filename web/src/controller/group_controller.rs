@@ -1,4 +1,5 @@
 use crate::error::ApplicationError;
+use crate::controller::workspace_controller::get_owner;
 use crate::user::Backend;
 use crate::{user::AuthSession, web_app_state::WebAppState};
 use axum::extract::Path;
@@ -241,6 +242,7 @@ mod post {
     #[derive(Deserialize)]
     pub struct PostGroupParams {
         pub group_name: String,
+        pub workspace_id: Option<i64>,
     }
 
     pub async fn add_group(
@@ -249,6 +251,7 @@ mod post {
         Json(params): Json<PostGroupParams>,
     ) -> Result<Response, ApplicationError> {
         let user = auth_session.user.unwrap().to_user();
+        let user = get_owner(&app_state, user, params.workspace_id).await?;
         let id =
             group_db::add_empty_group(&app_state.app_state.db, &user, &params.group_name, None)
                 .await?;
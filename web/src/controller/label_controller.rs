@@ -1,4 +1,5 @@
 use crate::error::ApplicationError;
+use crate::controller::workspace_controller::get_owner;
 use crate::user::Backend;
 use crate::{user::AuthSession, web_app_state::WebAppState};
 use axum::extract::Path;
@@ -97,6 +98,7 @@ mod post {
     pub struct PostLabelParams {
         pub label_name: String,
         pub label_color: i64,
+        pub workspace_id: Option<i64>,
     }
 
     pub async fn add_label(
@@ -105,6 +107,7 @@ mod post {
         Json(params): Json<PostLabelParams>,
     ) -> Result<Response, ApplicationError> {
         let user = auth_session.user.unwrap().to_user();
+        let user = get_owner(&app_state, user, params.workspace_id).await?;
         let id = label_db::add_label(
             &app_state.app_state.db,
             &user,
@@ -0,0 +1,149 @@
+use crate::{
+    user::{AuthSession, Backend},
+    web_app_state::WebAppState,
+};
+use axum::extract::{Path, State};
+use axum::{
+    Json, Router,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::{get, post},
+};
+use axum_login::login_required;
+use db::model_db;
+use service::{duplicate_service, mesh_analysis_service, mesh_repair_service};
+
+use crate::error::ApplicationError;
+
+pub fn router() -> Router<WebAppState> {
+    Router::new().nest(
+        "/api/v1",
+        Router::new()
+            .route(
+                "/models/{model_id}/mesh_report",
+                get(get::get_mesh_report),
+            )
+            .route(
+                "/models/{model_id}/repair",
+                post(post::repair_model),
+            )
+            .route(
+                "/models/duplicates",
+                get(get::get_duplicate_models),
+            )
+            .route(
+                "/models/{model_id}/merge",
+                post(post::merge_duplicate_models),
+            )
+            .route_layer(login_required!(Backend)),
+    )
+}
+
+mod get {
+    use db::model::Model;
+
+    use super::*;
+
+    pub async fn get_mesh_report(
+        auth_session: AuthSession,
+        Path(model_id): Path<i64>,
+        State(app_state): State<WebAppState>,
+    ) -> Result<Response, ApplicationError> {
+        let user = auth_session.user.unwrap().to_user();
+
+        let model = model_db::get_models_via_ids(&app_state.app_state.db, &user, vec![model_id]).await?;
+
+        if model.is_empty() {
+            return Ok((StatusCode::NOT_FOUND, "Model not found").into_response());
+        }
+
+        let report = mesh_analysis_service::get_report(&model[0], &app_state.app_state).await?;
+
+        Ok(Json(report).into_response())
+    }
+
+    pub async fn get_duplicate_models(
+        auth_session: AuthSession,
+        State(app_state): State<WebAppState>,
+    ) -> Result<Json<Vec<Vec<Model>>>, ApplicationError> {
+        let user = auth_session.user.unwrap().to_user();
+
+        let duplicates = duplicate_service::find_duplicates(&user, &app_state.app_state).await?;
+
+        Ok(Json(duplicates))
+    }
+}
+
+mod post {
+    use db::model::Blob;
+    use serde::Serialize;
+    use service::import_state::{ImportState, ImportStatus};
+    use service::mesh_repair_service::MeshRepairReport;
+    use service::thumbnail_service;
+
+    use crate::web_import_state::WebImportStateEmitter;
+
+    use super::*;
+
+    #[derive(Serialize)]
+    pub struct RepairModelResult {
+        pub import_state: ImportState,
+        pub report: MeshRepairReport,
+    }
+
+    pub async fn repair_model(
+        auth_session: AuthSession,
+        Path(model_id): Path<i64>,
+        State(app_state): State<WebAppState>,
+    ) -> Result<Response, ApplicationError> {
+        let user = auth_session.user.unwrap().to_user();
+
+        let model = model_db::get_models_via_ids(&app_state.app_state.db, &user, vec![model_id]).await?;
+
+        if model.is_empty() {
+            return Ok((StatusCode::NOT_FOUND, "Model not found").into_response());
+        }
+
+        let import_state = ImportState::new_with_emitter(None, false, false, false, user.clone(), Box::new(WebImportStateEmitter {}));
+        let (mut import_state, report) =
+            mesh_repair_service::repair_model(&model[0], &app_state.app_state, import_state).await?;
+
+        let model_ids: Vec<i64> = import_state
+            .imported_models
+            .iter()
+            .flat_map(|f| f.model_ids.clone())
+            .collect();
+
+        let models = model_db::get_models_via_ids(&app_state.app_state.db, &user, model_ids).await?;
+        let blobs: Vec<&Blob> = models.iter().map(|m| &m.blob).collect();
+
+        thumbnail_service::generate_thumbnails(&blobs, &app_state.app_state, false, &mut import_state).await?;
+
+        import_state.update_status(ImportStatus::Finished);
+
+        Ok(Json(RepairModelResult { import_state, report }).into_response())
+    }
+    pub async fn merge_duplicate_models(
+        auth_session: AuthSession,
+        Path(model_id): Path<i64>,
+        State(app_state): State<WebAppState>,
+        Json(duplicate_model_ids): Json<Vec<i64>>,
+    ) -> Result<Response, ApplicationError> {
+        let user = auth_session.user.unwrap().to_user();
+
+        let model = model_db::get_models_via_ids(&app_state.app_state.db, &user, vec![model_id]).await?;
+
+        if model.is_empty() {
+            return Ok((StatusCode::NOT_FOUND, "Model not found").into_response());
+        }
+
+        let duplicates = match duplicate_service::get_mergeable_duplicates(&model[0], &duplicate_model_ids, &user, &app_state.app_state).await? {
+            Some(duplicates) => duplicates,
+            None => return Ok((StatusCode::BAD_REQUEST, "Only duplicates of the model can be merged into it").into_response()),
+        };
+
+        duplicate_service::merge_duplicates(&model[0], &duplicates, &user, &app_state.app_state).await?;
+
+        Ok(StatusCode::NO_CONTENT.into_response())
+    }
+}
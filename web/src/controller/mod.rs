@@ -12,4 +12,10 @@ pub mod resource_controller;
 pub mod user_controller;
 pub mod threemf_controller;
 pub mod page_controller;
-pub mod share_controller;
\ No newline at end of file
+pub mod share_controller;
+pub mod workspace_controller;
+pub mod printer_controller;
+pub mod gcode_controller;
+pub mod resin_controller;
+pub mod mesh_controller;
+pub mod backup_controller;
\ No newline at end of file
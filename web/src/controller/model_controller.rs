@@ -24,7 +24,7 @@ use crate::error::ApplicationError;
 use db::blob_db;
 use db::model_db::{ModelFilterOptions, ModelOrderBy};
 use serde::Serialize;
-use service::export_service;
+use service::{export_service, similarity_service};
 
 pub fn router() -> Router<WebAppState> {
     Router::new().nest(
@@ -35,6 +35,7 @@ pub fn router() -> Router<WebAppState> {
             .route("/models", delete(delete::delete_models))
             .route("/models/count", get(get::get_model_count))
             .route("/models/disk_usage", get(get::get_model_disk_space_usage))
+            .route("/models/{model_id}/similar", get(get::get_similar_models))
             .route("/models/{model_id}", put(put::edit_model))
             .route("/models/{model_id}", delete(delete::delete_model))
             .route_layer(login_required!(Backend))
@@ -64,6 +65,11 @@ mod get {
         pub page_size: u32,
         #[serde(default)]
         pub file_types: Vec<FileType>,
+        pub printer_model: Option<String>,
+        pub material_type: Option<String>,
+        pub nozzle_diameter: Option<f64>,
+        pub max_print_time_seconds: Option<i64>,
+        pub has_problems: Option<bool>,
     }
 
     async fn get_models_inner(
@@ -88,6 +94,11 @@ mod get {
                 page: params.page,
                 page_size: params.page_size,
                 file_types: if params.file_types.is_empty() { None } else { Some(params.file_types) },
+                printer_model: params.printer_model,
+                material_type: params.material_type,
+                nozzle_diameter: params.nozzle_diameter,
+                max_print_time_seconds: params.max_print_time_seconds,
+                has_problems: params.has_problems,
             },
         )
         .await?;
@@ -168,6 +179,30 @@ mod get {
         })
         .into_response())
     }
+
+    #[derive(Deserialize)]
+    pub struct GetSimilarModelsParams {
+        pub limit: Option<usize>,
+    }
+
+    pub async fn get_similar_models(
+        auth_session: AuthSession,
+        Path(model_id): Path<i64>,
+        State(app_state): State<WebAppState>,
+        Query(params): Query<GetSimilarModelsParams>,
+    ) -> Result<Response, ApplicationError> {
+        let user = auth_session.user.unwrap().to_user();
+
+        let model = model_db::get_models_via_ids(&app_state.app_state.db, &user, vec![model_id]).await?;
+
+        if model.is_empty() {
+            return Ok((StatusCode::NOT_FOUND, "Model not found").into_response());
+        }
+
+        let similar = similarity_service::find_similar_models(&model[0], &user, params.limit.unwrap_or(20), &app_state.app_state).await?;
+
+        Ok(Json(similar).into_response())
+    }
 }
 
 mod put {
@@ -277,7 +312,7 @@ mod delete {
 }
 
 mod post {
-    use db::{model::Blob, random_hex_32};
+    use db::{model::{Blob, WorkspaceRole}, random_hex_32, workspace_db};
     use service::thumbnail_service;
     use tokio::io::AsyncWriteExt;
 
@@ -290,7 +325,7 @@ mod post {
         State(app_state): State<WebAppState>,
         mut multipart: Multipart,
     ) -> Result<Response, ApplicationError> {
-        let user = auth_session.user.unwrap().to_user();
+        let mut user = auth_session.user.unwrap().to_user();
         let mut paths = vec![];
 
         let temp_dir = std::env::temp_dir().join(format!(
@@ -302,6 +337,7 @@ mod post {
         std::fs::create_dir(&temp_dir)?;
 
         let mut link = None;
+        let mut workspace_id = None;
 
         while let Some(mut field) = multipart.next_field().await? {
             if let Some("source_url") = field.name() {
@@ -309,6 +345,11 @@ mod post {
                 continue;
             };
 
+            if let Some("workspace_id") = field.name() {
+                workspace_id = field.text().await?.parse::<i64>().ok();
+                continue;
+            };
+
             let file_name = match field.file_name() {
                 Some(name) => name.to_string(),
                 None => continue,
@@ -340,6 +381,16 @@ mod post {
             return Ok((StatusCode::BAD_REQUEST, "No files uploaded").into_response());
         }
 
+        // Importing into a workspace creates everything with the workspace as owner
+        if let Some(workspace_id) = workspace_id {
+            user = match workspace_db::get_workspace_identity(&app_state.app_state.db, &user, workspace_id, WorkspaceRole::Editor).await? {
+                Some(workspace) => workspace,
+                None => return Err(ApplicationError::InternalError(
+                    "Insufficient permissions to import into this workspace.".into(),
+                )),
+            };
+        }
+
         let mut model_ids: Vec<i64> = vec![];
 
         let mut import_state = ImportState::new_with_emitter(None, false, true, false, user.clone(), Box::new(WebImportStateEmitter {}));
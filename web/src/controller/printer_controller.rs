@@ -0,0 +1,202 @@
+use crate::error::ApplicationError;
+use crate::user::Backend;
+use crate::{user::AuthSession, web_app_state::WebAppState};
+use axum::extract::Path;
+use axum::{
+    Json, Router,
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::{delete, get, post, put},
+};
+use axum_login::login_required;
+use db::model::{Printer, PrinterType, User, UserPermissions};
+use db::{model_db, printer_db};
+use serde::{Deserialize, Serialize};
+use service::printer_service;
+
+pub fn router() -> Router<WebAppState> {
+    Router::new().nest(
+        "/api/v1",
+        Router::new()
+            .route("/printers", get(get::get_printers))
+            .route("/printers", post(post::add_printer))
+            .route("/printers/{printer_id}", put(put::edit_printer))
+            .route("/printers/{printer_id}", delete(delete::delete_printer))
+            .route("/printers/{printer_id}/test", post(post::test_printer))
+            .route("/printers/{printer_id}/jobs", get(get::get_print_jobs))
+            .route("/printers/{printer_id}/jobs", post(post::send_model_to_printer))
+            .route_layer(login_required!(Backend)),
+    )
+}
+
+async fn get_printer(app_state: &WebAppState, user: &User, printer_id: i64) -> Result<Printer, ApplicationError> {
+    match printer_db::get_printer_by_id(&app_state.app_state.db, user, printer_id).await? {
+        Some(printer) => Ok(printer),
+        None => Err(ApplicationError::InternalError("Printer not found".into())),
+    }
+}
+
+// The server sends requests to whatever url a printer is set up with, so only admins get to manage printers
+fn require_admin(user: &User) -> Result<(), ApplicationError> {
+    if !user.permissions.contains(UserPermissions::Admin) {
+        return Err(ApplicationError::InternalError(
+            "Insufficient permissions to manage printers.".into(),
+        ));
+    }
+
+    Ok(())
+}
+
+#[derive(Deserialize)]
+pub struct PrinterParams {
+    pub printer_name: String,
+    pub printer_type: PrinterType,
+    pub printer_base_url: String,
+    pub printer_api_key: Option<String>,
+}
+
+mod get {
+    use super::*;
+
+    pub async fn get_printers(
+        auth_session: AuthSession,
+        State(app_state): State<WebAppState>,
+    ) -> Result<Response, ApplicationError> {
+        let user = auth_session.user.unwrap().to_user();
+        let printers = printer_db::get_printers(&app_state.app_state.db, &user).await?;
+
+        Ok(Json(printers).into_response())
+    }
+
+    pub async fn get_print_jobs(
+        auth_session: AuthSession,
+        Path(printer_id): Path<i64>,
+        State(app_state): State<WebAppState>,
+    ) -> Result<Response, ApplicationError> {
+        let user = auth_session.user.unwrap().to_user();
+        let jobs = printer_db::get_print_jobs(&app_state.app_state.db, &user, printer_id).await?;
+
+        Ok(Json(jobs).into_response())
+    }
+}
+
+mod post {
+    use super::*;
+
+    #[derive(Serialize)]
+    pub struct PostPrinterResponse {
+        pub id: i64,
+    }
+
+    pub async fn add_printer(
+        auth_session: AuthSession,
+        State(app_state): State<WebAppState>,
+        Json(params): Json<PrinterParams>,
+    ) -> Result<Response, ApplicationError> {
+        let user = auth_session.user.unwrap().to_user();
+        require_admin(&user)?;
+        let base_url = printer_service::normalize_base_url(&params.printer_base_url)?;
+
+        let id = printer_db::add_printer(
+            &app_state.app_state.db,
+            &user,
+            &params.printer_name,
+            params.printer_type,
+            &base_url,
+            params.printer_api_key.as_deref().unwrap_or(""),
+        )
+        .await?;
+
+        Ok(Json(PostPrinterResponse { id }).into_response())
+    }
+
+    pub async fn test_printer(
+        auth_session: AuthSession,
+        Path(printer_id): Path<i64>,
+        State(app_state): State<WebAppState>,
+    ) -> Result<Response, ApplicationError> {
+        let user = auth_session.user.unwrap().to_user();
+        require_admin(&user)?;
+        let printer = get_printer(&app_state, &user, printer_id).await?;
+
+        printer_service::test_connection(&printer).await?;
+
+        Ok(StatusCode::NO_CONTENT.into_response())
+    }
+
+    #[derive(Deserialize)]
+    pub struct PostPrintJobParams {
+        pub model_id: i64,
+        pub start_print: bool,
+    }
+
+    #[derive(Serialize)]
+    pub struct PostPrintJobResponse {
+        pub id: i64,
+    }
+
+    pub async fn send_model_to_printer(
+        auth_session: AuthSession,
+        Path(printer_id): Path<i64>,
+        State(app_state): State<WebAppState>,
+        Json(params): Json<PostPrintJobParams>,
+    ) -> Result<Response, ApplicationError> {
+        let user = auth_session.user.unwrap().to_user();
+        let printer = get_printer(&app_state, &user, printer_id).await?;
+
+        let model = match model_db::get_models_via_ids(&app_state.app_state.db, &user, vec![params.model_id]).await?.pop() {
+            Some(model) => model,
+            None => return Ok(StatusCode::NOT_FOUND.into_response()),
+        };
+
+        let id = printer_service::send_model_to_printer(&app_state.app_state, &printer, &model, params.start_print).await?;
+
+        Ok(Json(PostPrintJobResponse { id }).into_response())
+    }
+}
+
+mod put {
+    use super::*;
+
+    pub async fn edit_printer(
+        auth_session: AuthSession,
+        Path(printer_id): Path<i64>,
+        State(app_state): State<WebAppState>,
+        Json(params): Json<PrinterParams>,
+    ) -> Result<Response, ApplicationError> {
+        let user = auth_session.user.unwrap().to_user();
+        require_admin(&user)?;
+        let base_url = printer_service::normalize_base_url(&params.printer_base_url)?;
+
+        printer_db::edit_printer(
+            &app_state.app_state.db,
+            &user,
+            printer_id,
+            &params.printer_name,
+            params.printer_type,
+            &base_url,
+            params.printer_api_key.as_deref(),
+        )
+        .await?;
+
+        Ok(StatusCode::NO_CONTENT.into_response())
+    }
+}
+
+mod delete {
+    use super::*;
+
+    pub async fn delete_printer(
+        auth_session: AuthSession,
+        Path(printer_id): Path<i64>,
+        State(app_state): State<WebAppState>,
+    ) -> Result<Response, ApplicationError> {
+        let user = auth_session.user.unwrap().to_user();
+        require_admin(&user)?;
+
+        printer_db::delete_printer(&app_state.app_state.db, &user, printer_id).await?;
+
+        Ok(StatusCode::NO_CONTENT.into_response())
+    }
+}
@@ -1,4 +1,5 @@
 use crate::error::ApplicationError;
+use crate::controller::workspace_controller::get_owner;
 use crate::user::Backend;
 use crate::{user::AuthSession, web_app_state::WebAppState};
 use axum::extract::Path;
@@ -68,6 +69,7 @@ mod post {
     #[derive(Deserialize)]
     pub struct PostResourceParams {
         pub resource_name: String,
+        pub workspace_id: Option<i64>,
     }
 
     pub async fn add_resource(
@@ -76,6 +78,7 @@ mod post {
         Json(params): Json<PostResourceParams>,
     ) -> Result<Response, ApplicationError> {
         let user = auth_session.user.unwrap().to_user();
+        let user = get_owner(&app_state, user, params.workspace_id).await?;
         let id =
             resource_db::add_resource(&app_state.app_state.db, &user, &params.resource_name, None)
                 .await?;
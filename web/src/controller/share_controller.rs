@@ -1,6 +1,7 @@
 use crate::{
     user::{AuthSession, Backend},
     web_app_state::WebAppState,
+    zip_downloads::zip_stream_response,
 };
 use axum::extract::Path;
 use axum::extract::State;
@@ -28,12 +29,15 @@ pub fn router() -> Router<WebAppState> {
             .route("/shares/{share_id}", delete(delete::delete_share))
             .route("/shares/{share_id}/models", put(put::set_model_ids_on_share))
             .route_layer(login_required!(Backend))
-            .route("/shares/{share_id}", get(get::get_share)),
+            .route("/shares/{share_id}", get(get::get_share))
+            .route("/shares/{share_id}/download", get(get::get_share_zip_download)),
     )
 }
 
 mod get {
-    use db::{model::ShareDto, user_db};
+    use axum_extra::extract::Query;
+    use db::{model::ShareDto, model_db, user_db};
+    use service::{mesh_service::MeshFormat, zip_stream_service};
 
     use super::*;
 
@@ -68,6 +72,44 @@ mod get {
 
         Ok(Json(share).into_response())
     }
+
+    #[derive(Deserialize)]
+    pub struct ShareZipDownloadParams {
+        pub format: Option<MeshFormat>,
+        // Limits the download to these blobs, all models of the share are downloaded when empty
+        #[serde(default)]
+        pub sha256: Vec<String>,
+    }
+
+    pub async fn get_share_zip_download(
+        Path(share_id): Path<String>,
+        State(app_state): State<WebAppState>,
+        Query(params): Query<ShareZipDownloadParams>,
+    ) -> Result<Response, ApplicationError> {
+        let share = match share_db::get_share_via_id(&app_state.app_state.db, &share_id).await {
+            Ok(s) => s,
+            Err(_) => return Ok(StatusCode::NOT_FOUND.into_response()),
+        };
+
+        let user = match user_db::get_user_by_id(&app_state.app_state.db, share.user_id).await? {
+            Some(u) => u,
+            None => return Ok(StatusCode::NOT_FOUND.into_response()),
+        };
+
+        let models = model_db::get_models_via_ids(&app_state.app_state.db, &user, share.model_ids).await?;
+        let models: Vec<_> = models
+            .into_iter()
+            .filter(|m| params.sha256.is_empty() || params.sha256.contains(&m.blob.sha256))
+            .collect();
+
+        if models.is_empty() {
+            return Ok(StatusCode::NOT_FOUND.into_response());
+        }
+
+        let stream = zip_stream_service::stream_models_as_zip(models, params.format, &app_state.app_state).await?;
+
+        Ok(zip_stream_response(stream))
+    }
 }
 
 mod post {
@@ -40,6 +40,10 @@ pub fn router() -> Router<WebAppState> {
                 "/models/{model_id}/3mf_extract",
                 post(post::extract_threemf_models),
             )
+            .route(
+                "/models/{model_id}/3mf_split",
+                post(post::split_threemf_plates),
+            )
             .route_layer(login_required!(Backend)),
     )
 }
@@ -68,17 +72,24 @@ mod get {
 }
 
 mod post {
-    use db::{model::{Blob, ModelGroupMeta}, random_hex_32, time_now};
-    use service::thumbnail_service;
+    use db::{model::{Blob, ModelGroupMeta, User}, random_hex_32, time_now};
+    use axum_extra::extract::Query;
+    use service::{mesh_service::MeshFormat, thumbnail_service};
 
     use crate::web_import_state::WebImportStateEmitter;
 
     use super::*;
 
+    #[derive(Deserialize)]
+    pub struct ExtractThreemfParams {
+        pub format: Option<MeshFormat>,
+    }
+
     pub async fn extract_threemf_models(
         auth_session: AuthSession,
         Path(model_id): Path<i64>,
         State(app_state): State<WebAppState>,
+        Query(params): Query<ExtractThreemfParams>,
     ) -> Result<Response, ApplicationError> {
         let user = auth_session.user.unwrap().to_user();
 
@@ -88,9 +99,37 @@ mod post {
             return Ok((StatusCode::NOT_FOUND, "Model not found").into_response());
         }
 
-        let mut import_state =
-            threemf_service::extract_models(&model[0], &user, &app_state.app_state).await?;
+        let import_state =
+            threemf_service::extract_models(&model[0], &user, params.format.unwrap_or_default(), &app_state.app_state).await?;
+
+        Ok(Json(finish_threemf_import(import_state, &user, &app_state).await?).into_response())
+    }
+
+    pub async fn split_threemf_plates(
+        auth_session: AuthSession,
+        Path(model_id): Path<i64>,
+        State(app_state): State<WebAppState>,
+    ) -> Result<Response, ApplicationError> {
+        let user = auth_session.user.unwrap().to_user();
+
+        let model = model_db::get_models_via_ids(&app_state.app_state.db, &user, vec![model_id]).await?;
+
+        if model.is_empty() {
+            return Ok((StatusCode::NOT_FOUND, "Model not found").into_response());
+        }
+
+        let import_state =
+            threemf_service::split_plates(&model[0], &user, &app_state.app_state).await?;
+
+        Ok(Json(finish_threemf_import(import_state, &user, &app_state).await?).into_response())
+    }
 
+    // Generates thumbnails for the imported models and returns the group they were imported into
+    async fn finish_threemf_import(
+        mut import_state: ImportState,
+        user: &User,
+        app_state: &WebAppState,
+    ) -> Result<ModelGroupMeta, ApplicationError> {
         import_state.set_emitter(Box::new(WebImportStateEmitter {}));
 
         let model_ids: Vec<i64> = import_state
@@ -99,18 +138,18 @@ mod post {
             .flat_map(|f| f.model_ids.clone())
             .collect();
 
-        let models = model_db::get_models_via_ids(&app_state.app_state.db, &user, model_ids).await?;
+        let models = model_db::get_models_via_ids(&app_state.app_state.db, user, model_ids).await?;
         let blobs: Vec<&Blob> = models.iter().map(|m| &m.blob).collect();
 
         thumbnail_service::generate_thumbnails(&blobs, &app_state.app_state, false, &mut import_state).await?;
 
-        Ok(Json(ModelGroupMeta {
+        Ok(ModelGroupMeta {
             id: import_state.imported_models[0].group_id.unwrap(),
             name: import_state.imported_models[0].group_name.clone().unwrap(),
             created: time_now(),
             last_modified: time_now(),
             resource_id: None,
             unique_global_id: random_hex_32(),
-        }).into_response())
+        })
     }
 }
\ No newline at end of file
@@ -7,13 +7,14 @@ use axum::{
     Json, Router,
 };
 use axum_login::login_required;
-use crate::user::Backend;
+use crate::user::{Backend, SESSION_TRACKING_KEY};
 use axum::extract::Path;
-use db::user_db;
+use db::{model::{UserSession, hash_recovery_code}, user_db, user_session_db, user_totp_db, workspace_db};
 use service::export_service;
 use serde::{Deserialize, Serialize};
 use crate::error::ApplicationError;
 use db::model::UserPermissions;
+use crate::totp;
 
 pub fn router() -> Router<WebAppState> {
     Router::new()
@@ -27,10 +28,45 @@ pub fn router() -> Router<WebAppState> {
                 .route("/users/{user_id}/token", delete(delete::generate_new_sync_token))
                 .route("/users/{user_id}/password", put(put::edit_user_password))
                 .route("/users/{user_id}/permissions", put(put::edit_user_permissions))
+                .route("/users/{user_id}/sessions", get(get::get_user_sessions))
+                .route("/users/{user_id}/sessions/{session_id}", delete(delete::delete_user_session))
+                .route("/users/{user_id}/totp", get(get::get_user_totp))
+                .route("/users/{user_id}/totp", post(post::begin_totp_enrolment))
+                .route("/users/{user_id}/totp", delete(delete::disable_user_totp))
+                .route("/users/{user_id}/totp/confirm", post(post::confirm_totp_enrolment))
+                .route("/users/{user_id}/totp/recovery_codes", post(post::regenerate_recovery_codes))
                 .route_layer(login_required!(Backend))
         )
 }
 
+// Checks a code against the enabled secret of a user and marks it as used
+async fn verify_totp_code(app_state: &WebAppState, user_id: i64, code: &str) -> Result<bool, ApplicationError> {
+    let user_totp = match user_totp_db::get_totp(&app_state.app_state.db, user_id).await? {
+        Some(user_totp) if user_totp.enabled => user_totp,
+        _ => return Ok(false),
+    };
+
+    match totp::verify(&user_totp.secret, code, user_totp.last_used_step) {
+        Some(step) => Ok(user_totp_db::set_totp_last_used_step(&app_state.app_state.db, user_id, step).await?),
+        None => Ok(false),
+    }
+}
+
+async fn store_new_recovery_codes(app_state: &WebAppState, user_id: i64) -> Result<Vec<String>, ApplicationError> {
+    let recovery_codes = totp::generate_recovery_codes();
+    let codes = recovery_codes.clone();
+    let hashes: Vec<String> = tokio::task::spawn_blocking(move || codes.iter().map(|c| hash_recovery_code(c)).collect()).await?;
+
+    user_totp_db::set_recovery_codes(&app_state.app_state.db, user_id, &hashes).await?;
+
+    Ok(recovery_codes)
+}
+
+#[derive(Serialize)]
+pub struct RecoveryCodesResponse {
+    pub recovery_codes: Vec<String>,
+}
+
 mod get {
     use super::*;
 
@@ -50,6 +86,75 @@ mod get {
 
         Ok(Json(users).into_response())
     }
+
+    #[derive(Serialize)]
+    pub struct UserSessionResponse {
+        #[serde(flatten)]
+        pub session: UserSession,
+        pub current: bool,
+    }
+
+    pub async fn get_user_sessions(
+        auth_session: AuthSession,
+        Path(user_id): Path<i64>,
+        State(app_state): State<WebAppState>,
+    ) -> Result<Response, ApplicationError> {
+        let user = auth_session.user.unwrap().to_user();
+
+        if !user.permissions.contains(UserPermissions::Admin) && user.id != user_id {
+            return Err(ApplicationError::InternalError(
+                "Insufficient permissions to view this user's sessions.".into(),
+            ));
+        }
+
+        let current_session = auth_session
+            .session
+            .get::<String>(SESSION_TRACKING_KEY)
+            .await
+            .ok()
+            .flatten();
+
+        user_session_db::delete_stale_sessions(&app_state.app_state.db).await?;
+        let sessions = user_session_db::get_sessions_for_user(&app_state.app_state.db, user_id).await?;
+
+        let sessions: Vec<UserSessionResponse> = sessions
+            .into_iter()
+            .map(|session| UserSessionResponse {
+                current: current_session.as_ref() == Some(&session.id),
+                session,
+            })
+            .collect();
+
+        Ok(Json(sessions).into_response())
+    }
+
+    #[derive(Serialize)]
+    pub struct UserTotpResponse {
+        pub enabled: bool,
+        pub recovery_codes_remaining: i64,
+    }
+
+    pub async fn get_user_totp(
+        auth_session: AuthSession,
+        Path(user_id): Path<i64>,
+        State(app_state): State<WebAppState>,
+    ) -> Result<Response, ApplicationError> {
+        let user = auth_session.user.unwrap().to_user();
+
+        if !user.permissions.contains(UserPermissions::Admin) && user.id != user_id {
+            return Err(ApplicationError::InternalError(
+                "Insufficient permissions to view this user's two-factor authentication.".into(),
+            ));
+        }
+
+        let user_totp = user_totp_db::get_totp(&app_state.app_state.db, user_id).await?;
+        let recovery_codes_remaining = user_totp_db::get_recovery_code_count(&app_state.app_state.db, user_id).await?;
+
+        Ok(Json(UserTotpResponse {
+            enabled: user_totp.is_some_and(|t| t.enabled),
+            recovery_codes_remaining,
+        }).into_response())
+    }
 }
 
 mod post {
@@ -80,6 +185,10 @@ mod post {
             ));
         }
 
+        if workspace_db::is_reserved_user_name(&params.user_name) {
+            return Ok((StatusCode::BAD_REQUEST, "This user name is reserved for workspaces").into_response());
+        }
+
         let id = user_db::add_user(
             &app_state.app_state.db,
             &params.user_name,
@@ -91,6 +200,100 @@ mod post {
 
         Ok(Json(PostUserResponse { id }).into_response())
     }
+
+    #[derive(Serialize)]
+    pub struct TotpEnrolmentResponse {
+        pub secret: String,
+        pub otpauth_uri: String,
+    }
+
+    pub async fn begin_totp_enrolment(
+        auth_session: AuthSession,
+        Path(user_id): Path<i64>,
+        State(app_state): State<WebAppState>,
+    ) -> Result<Response, ApplicationError> {
+        let user = auth_session.user.unwrap().to_user();
+
+        if user.id != user_id {
+            return Err(ApplicationError::InternalError(
+                "Two-factor authentication can only be set up by the user themselves.".into(),
+            ));
+        }
+
+        if user_totp_db::get_totp(&app_state.app_state.db, user_id).await?.is_some_and(|t| t.enabled) {
+            return Err(ApplicationError::InternalError(
+                "Two-factor authentication is already enabled.".into(),
+            ));
+        }
+
+        let secret = totp::generate_secret();
+        user_totp_db::set_pending_totp_secret(&app_state.app_state.db, user_id, &secret).await?;
+
+        Ok(Json(TotpEnrolmentResponse {
+            otpauth_uri: totp::otpauth_uri(&secret, &user.email),
+            secret,
+        }).into_response())
+    }
+
+    #[derive(Deserialize)]
+    pub struct TotpCodeParams {
+        pub code: String,
+    }
+
+    pub async fn confirm_totp_enrolment(
+        auth_session: AuthSession,
+        Path(user_id): Path<i64>,
+        State(app_state): State<WebAppState>,
+        Json(params): Json<TotpCodeParams>,
+    ) -> Result<Response, ApplicationError> {
+        let user = auth_session.user.unwrap().to_user();
+
+        if user.id != user_id {
+            return Err(ApplicationError::InternalError(
+                "Two-factor authentication can only be set up by the user themselves.".into(),
+            ));
+        }
+
+        let user_totp = match user_totp_db::get_totp(&app_state.app_state.db, user_id).await? {
+            Some(user_totp) if !user_totp.enabled => user_totp,
+            _ => return Err(ApplicationError::InternalError(
+                "No pending two-factor authentication setup.".into(),
+            )),
+        };
+
+        let step = match totp::verify(&user_totp.secret, &params.code, None) {
+            Some(step) => step,
+            None => return Ok((StatusCode::BAD_REQUEST, "Invalid authentication code").into_response()),
+        };
+
+        user_totp_db::enable_totp(&app_state.app_state.db, user_id, step).await?;
+        let recovery_codes = store_new_recovery_codes(&app_state, user_id).await?;
+
+        Ok(Json(RecoveryCodesResponse { recovery_codes }).into_response())
+    }
+
+    pub async fn regenerate_recovery_codes(
+        auth_session: AuthSession,
+        Path(user_id): Path<i64>,
+        State(app_state): State<WebAppState>,
+        Json(params): Json<TotpCodeParams>,
+    ) -> Result<Response, ApplicationError> {
+        let user = auth_session.user.unwrap().to_user();
+
+        if user.id != user_id {
+            return Err(ApplicationError::InternalError(
+                "Recovery codes can only be generated by the user themselves.".into(),
+            ));
+        }
+
+        if !verify_totp_code(&app_state, user_id, &params.code).await? {
+            return Ok((StatusCode::BAD_REQUEST, "Invalid authentication code").into_response());
+        }
+
+        let recovery_codes = store_new_recovery_codes(&app_state, user_id).await?;
+
+        Ok(Json(RecoveryCodesResponse { recovery_codes }).into_response())
+    }
 }
 
 mod put {
@@ -116,6 +319,10 @@ mod put {
             ));
         }
 
+        if workspace_db::is_reserved_user_name(&params.user_name) {
+            return Ok((StatusCode::BAD_REQUEST, "This user name is reserved for workspaces").into_response());
+        }
+
         user_db::edit_user_min(
             &app_state.app_state.db,
             user_id,
@@ -186,6 +393,10 @@ mod put {
 }
 
 mod delete {
+    use std::str::FromStr;
+
+    use tower_sessions::{SessionStore, session::Id};
+
     use super::*;
 
     pub async fn delete_user(
@@ -225,4 +436,67 @@ mod delete {
 
         Ok(StatusCode::NO_CONTENT.into_response())
     }
+
+    pub async fn delete_user_session(
+        auth_session: AuthSession,
+        Path((user_id, session_id)): Path<(i64, String)>,
+        State(app_state): State<WebAppState>,
+    ) -> Result<Response, ApplicationError> {
+        let user = auth_session.user.unwrap().to_user();
+
+        if !user.permissions.contains(UserPermissions::Admin) && user.id != user_id {
+            return Err(ApplicationError::InternalError(
+                "Insufficient permissions to revoke this user's session.".into(),
+            ));
+        }
+
+        let session = match user_session_db::get_session_via_id(&app_state.app_state.db, &session_id).await? {
+            Some(s) if s.user_id == user_id => s,
+            _ => return Ok(StatusCode::NOT_FOUND.into_response()),
+        };
+
+        user_session_db::delete_session(&app_state.app_state.db, user_id, &session.id).await?;
+
+        // Removing the stored session is best effort, the session middleware rejects it either way
+        if let Some(store_id) = session.store_id.and_then(|id| Id::from_str(&id).ok()) {
+            let _ = app_state.session_store.delete(&store_id).await;
+        }
+
+        Ok(StatusCode::NO_CONTENT.into_response())
+    }
+
+    #[derive(Deserialize)]
+    pub struct DisableTotpParams {
+        pub code: Option<String>,
+    }
+
+    pub async fn disable_user_totp(
+        auth_session: AuthSession,
+        Path(user_id): Path<i64>,
+        State(app_state): State<WebAppState>,
+        Json(params): Json<DisableTotpParams>,
+    ) -> Result<Response, ApplicationError> {
+        let user = auth_session.user.unwrap().to_user();
+
+        if !user.permissions.contains(UserPermissions::Admin) && user.id != user_id {
+            return Err(ApplicationError::InternalError(
+                "Insufficient permissions to disable this user's two-factor authentication.".into(),
+            ));
+        }
+
+        let enabled = user_totp_db::get_totp(&app_state.app_state.db, user_id).await?.is_some_and(|t| t.enabled);
+
+        // Admins can reset another account that lost its authenticator, users have to prove they still have it
+        if enabled && user.id == user_id {
+            let code = params.code.unwrap_or_default();
+
+            if !verify_totp_code(&app_state, user_id, &code).await? {
+                return Ok((StatusCode::BAD_REQUEST, "Invalid authentication code").into_response());
+            }
+        }
+
+        user_totp_db::delete_totp(&app_state.app_state.db, user_id).await?;
+
+        Ok(StatusCode::NO_CONTENT.into_response())
+    }
 }
@@ -0,0 +1,258 @@
+use crate::{user::AuthSession, web_app_state::WebAppState};
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::{delete, get, post, put},
+    Json, Router,
+};
+use axum_login::login_required;
+use crate::user::Backend;
+use axum::extract::Path;
+use db::{model::{User, WorkspaceRole}, user_db, workspace_db};
+use service::export_service;
+use serde::{Deserialize, Serialize};
+use crate::error::ApplicationError;
+
+pub fn router() -> Router<WebAppState> {
+    Router::new()
+        .nest(
+            "/api/v1",
+            Router::new()
+                .route("/workspaces", get(get::get_workspaces))
+                .route("/workspaces", post(post::add_workspace))
+                .route("/workspaces/{workspace_id}", put(put::edit_workspace))
+                .route("/workspaces/{workspace_id}", delete(delete::delete_workspace))
+                .route("/workspaces/{workspace_id}/members", get(get::get_workspace_members))
+                .route("/workspaces/{workspace_id}/members/{user_id}", put(put::set_workspace_member))
+                .route("/workspaces/{workspace_id}/members/{user_id}", delete(delete::remove_workspace_member))
+                .route("/workspaces/{workspace_id}/models", post(post::move_models_to_workspace))
+                .route("/workspaces/{workspace_id}/models", delete(delete::move_models_out_of_workspace))
+                .route_layer(login_required!(Backend))
+        )
+}
+
+async fn require_role(app_state: &WebAppState, user: &User, workspace_id: i64, min_role: WorkspaceRole) -> Result<User, ApplicationError> {
+    match workspace_db::get_workspace_identity(&app_state.app_state.db, user, workspace_id, min_role).await? {
+        Some(workspace) => Ok(workspace),
+        None => Err(ApplicationError::InternalError(
+            "Insufficient permissions for this workspace.".into(),
+        )),
+    }
+}
+
+// Entities are created in the user's own library, or in the given workspace when the user may edit it
+pub async fn get_owner(app_state: &WebAppState, user: User, workspace_id: Option<i64>) -> Result<User, ApplicationError> {
+    match workspace_id {
+        Some(workspace_id) => require_role(app_state, &user, workspace_id, WorkspaceRole::Editor).await,
+        None => Ok(user),
+    }
+}
+
+
+#[derive(Deserialize)]
+pub struct WorkspaceModelsParams {
+    pub model_ids: Vec<i64>,
+}
+
+mod get {
+    use super::*;
+
+    pub async fn get_workspaces(
+        auth_session: AuthSession,
+        State(app_state): State<WebAppState>,
+    ) -> Result<Response, ApplicationError> {
+        let user = auth_session.user.unwrap().to_user();
+
+        let workspaces = workspace_db::get_workspaces(&app_state.app_state.db, &user).await?;
+
+        Ok(Json(workspaces).into_response())
+    }
+
+    pub async fn get_workspace_members(
+        auth_session: AuthSession,
+        Path(workspace_id): Path<i64>,
+        State(app_state): State<WebAppState>,
+    ) -> Result<Response, ApplicationError> {
+        let user = auth_session.user.unwrap().to_user();
+        require_role(&app_state, &user, workspace_id, WorkspaceRole::Viewer).await?;
+
+        let members = workspace_db::get_workspace_members(&app_state.app_state.db, workspace_id).await?;
+
+        Ok(Json(members).into_response())
+    }
+}
+
+mod post {
+    use super::*;
+
+    #[derive(Deserialize)]
+    pub struct PostWorkspaceParams {
+        pub workspace_name: String,
+    }
+
+    #[derive(Serialize)]
+    pub struct PostWorkspaceResponse {
+        pub id: i64,
+    }
+
+    pub async fn add_workspace(
+        auth_session: AuthSession,
+        State(app_state): State<WebAppState>,
+        Json(params): Json<PostWorkspaceParams>,
+    ) -> Result<Response, ApplicationError> {
+        let user = auth_session.user.unwrap().to_user();
+
+        if workspace_db::is_workspace_name_taken(&app_state.app_state.db, &params.workspace_name, None).await? {
+            return Ok((StatusCode::CONFLICT, "This name is already in use").into_response());
+        }
+
+        let id = workspace_db::add_workspace(&app_state.app_state.db, &user, &params.workspace_name).await?;
+
+        Ok(Json(PostWorkspaceResponse { id }).into_response())
+    }
+
+    pub async fn move_models_to_workspace(
+        auth_session: AuthSession,
+        Path(workspace_id): Path<i64>,
+        State(app_state): State<WebAppState>,
+        Json(params): Json<WorkspaceModelsParams>,
+    ) -> Result<Response, ApplicationError> {
+        let user = auth_session.user.unwrap().to_user();
+        let workspace = require_role(&app_state, &user, workspace_id, WorkspaceRole::Editor).await?;
+
+        workspace_db::move_models_to_owner(&app_state.app_state.db, &user, &workspace, &params.model_ids).await?;
+
+        Ok(StatusCode::NO_CONTENT.into_response())
+    }
+}
+
+mod put {
+    use super::*;
+
+    #[derive(Deserialize)]
+    pub struct PutWorkspaceParams {
+        pub workspace_name: String,
+    }
+
+    pub async fn edit_workspace(
+        auth_session: AuthSession,
+        Path(workspace_id): Path<i64>,
+        State(app_state): State<WebAppState>,
+        Json(params): Json<PutWorkspaceParams>,
+    ) -> Result<Response, ApplicationError> {
+        let user = auth_session.user.unwrap().to_user();
+        require_role(&app_state, &user, workspace_id, WorkspaceRole::Editor).await?;
+
+        if workspace_db::is_workspace_name_taken(&app_state.app_state.db, &params.workspace_name, Some(workspace_id)).await? {
+            return Ok((StatusCode::CONFLICT, "This name is already in use").into_response());
+        }
+
+        workspace_db::edit_workspace(&app_state.app_state.db, workspace_id, &params.workspace_name).await?;
+
+        Ok(StatusCode::NO_CONTENT.into_response())
+    }
+
+    #[derive(Deserialize)]
+    pub struct PutWorkspaceMemberParams {
+        pub role: WorkspaceRole,
+    }
+
+    pub async fn set_workspace_member(
+        auth_session: AuthSession,
+        Path((workspace_id, user_id)): Path<(i64, i64)>,
+        State(app_state): State<WebAppState>,
+        Json(params): Json<PutWorkspaceMemberParams>,
+    ) -> Result<Response, ApplicationError> {
+        let user = auth_session.user.unwrap().to_user();
+        require_role(&app_state, &user, workspace_id, WorkspaceRole::Editor).await?;
+
+        let member = match user_db::get_user_by_id(&app_state.app_state.db, user_id).await? {
+            Some(member) if !member.permissions.contains(db::model::UserPermissions::Workspace) => member,
+            _ => return Ok(StatusCode::NOT_FOUND.into_response()),
+        };
+
+        let current_role = workspace_db::get_workspace_role(&app_state.app_state.db, &member, workspace_id).await?;
+
+        // Only owners can hand out or take away ownership
+        if params.role == WorkspaceRole::Owner || current_role == Some(WorkspaceRole::Owner) {
+            require_role(&app_state, &user, workspace_id, WorkspaceRole::Owner).await?;
+        }
+
+        // Keep at least one owner around, otherwise nobody can manage the workspace anymore
+        if current_role == Some(WorkspaceRole::Owner) && params.role < WorkspaceRole::Owner
+            && workspace_db::get_owner_count(&app_state.app_state.db, workspace_id).await? <= 1 {
+            return Err(ApplicationError::InternalError(
+                "A workspace needs at least one owner.".into(),
+            ));
+        }
+
+        workspace_db::set_workspace_member(&app_state.app_state.db, workspace_id, member.id, params.role).await?;
+
+        Ok(StatusCode::NO_CONTENT.into_response())
+    }
+}
+
+mod delete {
+    use super::*;
+
+    pub async fn delete_workspace(
+        auth_session: AuthSession,
+        Path(workspace_id): Path<i64>,
+        State(app_state): State<WebAppState>,
+    ) -> Result<Response, ApplicationError> {
+        let user = auth_session.user.unwrap().to_user();
+        require_role(&app_state, &user, workspace_id, WorkspaceRole::Owner).await?;
+
+        workspace_db::delete_workspace(&app_state.app_state.db, workspace_id).await?;
+
+        export_service::delete_dead_blobs(&app_state.app_state).await?;
+
+        Ok(StatusCode::NO_CONTENT.into_response())
+    }
+
+    pub async fn remove_workspace_member(
+        auth_session: AuthSession,
+        Path((workspace_id, user_id)): Path<(i64, i64)>,
+        State(app_state): State<WebAppState>,
+    ) -> Result<Response, ApplicationError> {
+        let user = auth_session.user.unwrap().to_user();
+
+        let role = workspace_db::get_workspace_role(&app_state.app_state.db, &User { id: user_id, ..Default::default() }, workspace_id).await?;
+
+        // Members can always leave, removing someone else requires editor rights, and removing an owner requires ownership
+        let required_role = match role {
+            _ if user.id == user_id => WorkspaceRole::Viewer,
+            Some(WorkspaceRole::Owner) => WorkspaceRole::Owner,
+            _ => WorkspaceRole::Editor,
+        };
+        require_role(&app_state, &user, workspace_id, required_role).await?;
+
+        if role == Some(WorkspaceRole::Owner)
+            && workspace_db::get_owner_count(&app_state.app_state.db, workspace_id).await? <= 1 {
+            return Err(ApplicationError::InternalError(
+                "A workspace needs at least one owner.".into(),
+            ));
+        }
+
+        workspace_db::remove_workspace_member(&app_state.app_state.db, workspace_id, user_id).await?;
+
+        Ok(StatusCode::NO_CONTENT.into_response())
+    }
+
+    pub async fn move_models_out_of_workspace(
+        auth_session: AuthSession,
+        Path(workspace_id): Path<i64>,
+        State(app_state): State<WebAppState>,
+        Json(params): Json<WorkspaceModelsParams>,
+    ) -> Result<Response, ApplicationError> {
+        let user = auth_session.user.unwrap().to_user();
+        // Taking models out moves them into a personal library, editors could otherwise empty a shared workspace into their own
+        let workspace = require_role(&app_state, &user, workspace_id, WorkspaceRole::Owner).await?;
+
+        // Only models that are currently in this workspace can be taken out of it
+        workspace_db::move_models_to_owner(&app_state.app_state.db, &workspace, &user, &params.model_ids).await?;
+
+        Ok(StatusCode::NO_CONTENT.into_response())
+    }
+}
@@ -0,0 +1,232 @@
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use axum::http::HeaderMap;
+
+// Failed attempts that are allowed before any delay kicks in
+const FREE_ACCOUNT_ATTEMPTS: u32 = 3;
+const FREE_IP_ATTEMPTS: u32 = 10;
+const BASE_DELAY: Duration = Duration::from_secs(1);
+const MAX_DELAY: Duration = Duration::from_secs(15 * 60);
+// Entries without a failure for this long are forgotten
+const FORGET_AFTER: Duration = Duration::from_secs(24 * 60 * 60);
+
+struct ThrottleEntry {
+    failures: u32,
+    last_failure: Instant,
+    blocked_until: Instant,
+}
+
+pub struct LoginThrottle {
+    entries: Mutex<HashMap<String, ThrottleEntry>>,
+}
+
+pub fn ip_key(ip: &IpAddr) -> String {
+    format!("ip:{}", ip)
+}
+
+pub fn account_key(email: &str) -> String {
+    format!("account:{}", email.trim().to_lowercase())
+}
+
+pub fn get_client_ip(headers: &HeaderMap, addr: &SocketAddr, trust_proxy_headers: bool) -> IpAddr {
+    if trust_proxy_headers {
+        let forwarded = headers
+            .get("x-forwarded-for")
+            .and_then(|h| h.to_str().ok())
+            .and_then(|h| h.split(',').next())
+            .and_then(|h| h.trim().parse::<IpAddr>().ok());
+
+        if let Some(ip) = forwarded {
+            return ip;
+        }
+
+        let real_ip = headers
+            .get("x-real-ip")
+            .and_then(|h| h.to_str().ok())
+            .and_then(|h| h.trim().parse::<IpAddr>().ok());
+
+        if let Some(ip) = real_ip {
+            return ip;
+        }
+    }
+
+    addr.ip()
+}
+
+fn get_delay(key: &str, failures: u32) -> Option<Duration> {
+    let free_attempts = if key.starts_with("ip:") {
+        FREE_IP_ATTEMPTS
+    } else {
+        FREE_ACCOUNT_ATTEMPTS
+    };
+
+    if failures <= free_attempts {
+        return None;
+    }
+
+    let exponent = (failures - free_attempts - 1).min(16);
+
+    Some(BASE_DELAY.saturating_mul(1 << exponent).min(MAX_DELAY))
+}
+
+impl LoginThrottle {
+    pub fn new() -> Self {
+        Self {
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Starts a login attempt, or returns how long the caller has to wait before another attempt is allowed.
+    /// The attempt counts as a failure until it is released, so parallel requests can't all get past the check.
+    pub fn begin_attempt(&self, keys: &[String]) -> Result<(), Duration> {
+        self.begin_attempt_at(keys, Instant::now())
+    }
+
+    fn begin_attempt_at(&self, keys: &[String], now: Instant) -> Result<(), Duration> {
+        let mut entries = self.entries.lock().unwrap();
+
+        let wait = keys.iter()
+            .filter_map(|key| entries.get(key))
+            .filter(|entry| entry.blocked_until > now)
+            .map(|entry| entry.blocked_until - now)
+            .max();
+
+        if let Some(wait) = wait {
+            return Err(wait);
+        }
+
+        entries.retain(|_, entry| now.duration_since(entry.last_failure) < FORGET_AFTER);
+
+        for key in keys {
+            let entry = entries.entry(key.clone()).or_insert(ThrottleEntry {
+                failures: 0,
+                last_failure: now,
+                blocked_until: now,
+            });
+
+            entry.failures += 1;
+            entry.last_failure = now;
+
+            if let Some(delay) = get_delay(key, entry.failures) {
+                entry.blocked_until = now + delay;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Takes back the failure an attempt was counted as, for attempts that didn't fail on the credentials
+    pub fn release(&self, keys: &[String]) {
+        let mut entries = self.entries.lock().unwrap();
+
+        for key in keys {
+            if let Some(entry) = entries.get_mut(key) {
+                entry.failures = entry.failures.saturating_sub(1);
+                entry.blocked_until = match get_delay(key, entry.failures) {
+                    Some(delay) => entry.last_failure + delay,
+                    None => entry.last_failure,
+                };
+            }
+        }
+    }
+
+    pub fn register_success(&self, keys: &[String]) {
+        let mut entries = self.entries.lock().unwrap();
+
+        for key in keys {
+            entries.remove(key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys() -> Vec<String> {
+        vec![ip_key(&"127.0.0.1".parse().unwrap()), account_key("User@Example.com")]
+    }
+
+    #[test]
+    fn account_is_locked_after_the_free_attempts() {
+        let throttle = LoginThrottle::new();
+        let now = Instant::now();
+
+        for _ in 0..FREE_ACCOUNT_ATTEMPTS + 1 {
+            assert!(throttle.begin_attempt_at(&keys(), now).is_ok());
+        }
+
+        assert_eq!(throttle.begin_attempt_at(&keys(), now), Err(BASE_DELAY));
+        // Another address can't get around the lock either
+        assert!(throttle.begin_attempt_at(&[ip_key(&"10.0.0.1".parse().unwrap()), account_key("user@example.com")], now).is_err());
+    }
+
+    #[test]
+    fn parallel_attempts_are_counted_before_they_finish() {
+        let throttle = LoginThrottle::new();
+        let now = Instant::now();
+
+        // None of these attempts has finished, yet the lock applies to the next one
+        let started = (0..10).filter(|_| throttle.begin_attempt_at(&keys(), now).is_ok()).count();
+
+        assert_eq!(started, FREE_ACCOUNT_ATTEMPTS as usize + 1);
+    }
+
+    #[test]
+    fn lockout_expires_and_grows() {
+        let throttle = LoginThrottle::new();
+        let now = Instant::now();
+
+        for _ in 0..FREE_ACCOUNT_ATTEMPTS + 1 {
+            throttle.begin_attempt_at(&keys(), now).unwrap();
+        }
+
+        let later = now + BASE_DELAY;
+        assert!(throttle.begin_attempt_at(&keys(), later).is_ok());
+        assert_eq!(throttle.begin_attempt_at(&keys(), later), Err(BASE_DELAY * 2));
+    }
+
+    #[test]
+    fn success_resets_the_account_but_not_the_address() {
+        let throttle = LoginThrottle::new();
+        let now = Instant::now();
+        let keys = keys();
+
+        for _ in 0..FREE_IP_ATTEMPTS {
+            throttle.begin_attempt_at(&keys[..1], now).unwrap();
+        }
+
+        for _ in 0..FREE_ACCOUNT_ATTEMPTS {
+            throttle.begin_attempt_at(&keys[1..], now).unwrap();
+        }
+
+        // The last attempt before the lock succeeds
+        throttle.begin_attempt_at(&keys[1..], now).unwrap();
+        throttle.register_success(&keys[1..]);
+
+        for _ in 0..FREE_ACCOUNT_ATTEMPTS + 1 {
+            assert!(throttle.begin_attempt_at(&keys[1..], now).is_ok());
+        }
+
+        assert!(throttle.begin_attempt_at(&keys[..1], now).is_ok());
+        assert!(throttle.begin_attempt_at(&keys[..1], now).is_err());
+    }
+
+    #[test]
+    fn released_attempts_are_not_counted() {
+        let throttle = LoginThrottle::new();
+        let now = Instant::now();
+
+        for _ in 0..10 {
+            throttle.begin_attempt_at(&keys(), now).unwrap();
+            throttle.release(&keys());
+        }
+
+        assert!(throttle.begin_attempt_at(&keys(), now).is_ok());
+    }
+}
@@ -8,9 +8,13 @@ use crate::{app::App, error::ApplicationError};
 mod app;
 mod controller;
 mod error;
+mod login_throttle;
+mod oidc;
+mod totp;
 mod user;
 mod web_app_state;
 mod web_import_state;
+mod zip_downloads;
 
 fn remove_temp_paths() -> Result<(), ApplicationError> {
     let threshold = std::time::Duration::from_secs(5 * 60);
@@ -0,0 +1,433 @@
+use std::env;
+
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use db::random_hex_32;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use tokio::sync::OnceCell;
+
+use crate::error::ApplicationError;
+
+#[derive(Debug, Clone)]
+pub struct OidcConfig {
+    pub issuer_url: String,
+    pub client_id: String,
+    pub client_secret: Option<String>,
+    pub redirect_url: String,
+    pub scopes: String,
+    pub auto_provision: bool,
+    pub admin_claim: Option<String>,
+    pub admin_claim_value: Option<String>,
+}
+
+impl OidcConfig {
+    /// Returns None when OIDC_ISSUER_URL is not set, which disables OIDC logins.
+    /// A half configured provider is logged and disabled as well, rather than keeping the server from starting.
+    pub fn from_env() -> Option<Self> {
+        let issuer_url = env::var("OIDC_ISSUER_URL").ok()?;
+
+        let (client_id, redirect_url) = match (env::var("OIDC_CLIENT_ID"), env::var("OIDC_REDIRECT_URL")) {
+            (Ok(client_id), Ok(redirect_url)) => (client_id, redirect_url),
+            _ => {
+                println!("OIDC_ISSUER_URL is set, but OIDC_CLIENT_ID or OIDC_REDIRECT_URL is missing. OIDC logins are disabled");
+                return None;
+            }
+        };
+
+        Some(Self {
+            issuer_url: issuer_url.trim_end_matches('/').to_string(),
+            client_id,
+            client_secret: env::var("OIDC_CLIENT_SECRET").ok().filter(|s| !s.is_empty()),
+            redirect_url,
+            scopes: env::var("OIDC_SCOPES").unwrap_or("openid email profile".into()),
+            auto_provision: env::var("OIDC_AUTO_PROVISION")
+                .map(|v| v.to_lowercase() == "true")
+                .unwrap_or(true),
+            admin_claim: env::var("OIDC_ADMIN_CLAIM").ok().filter(|s| !s.is_empty()),
+            admin_claim_value: env::var("OIDC_ADMIN_CLAIM_VALUE").ok().filter(|s| !s.is_empty()),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct ProviderMetadata {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    userinfo_endpoint: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    id_token: String,
+}
+
+// Everything the callback needs to finish a login that was started in this session
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingOidcLogin {
+    pub state: String,
+    pub nonce: String,
+    pub code_verifier: String,
+    pub expires_at: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct OidcIdentity {
+    pub email: String,
+    pub name: String,
+    pub is_admin: Option<bool>,
+}
+
+#[derive(Debug)]
+pub struct OidcClient {
+    pub config: OidcConfig,
+    http: reqwest::Client,
+    metadata: OnceCell<ProviderMetadata>,
+}
+
+fn oidc_error(message: impl Into<String>) -> ApplicationError {
+    ApplicationError::InternalError(format!("OIDC: {}", message.into()))
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+impl OidcClient {
+    pub fn new(config: OidcConfig) -> Self {
+        Self {
+            config,
+            http: reqwest::Client::new(),
+            metadata: OnceCell::new(),
+        }
+    }
+
+    // Discovery is done on first use, so the server still boots while the identity provider is unreachable
+    async fn metadata(&self) -> Result<&ProviderMetadata, ApplicationError> {
+        self.metadata
+            .get_or_try_init(|| async {
+                let url = format!("{}/.well-known/openid-configuration", self.config.issuer_url);
+                let body = self.http.get(&url).send().await
+                    .and_then(|r| r.error_for_status())
+                    .map_err(|e| oidc_error(format!("discovery failed: {}", e)))?
+                    .text().await
+                    .map_err(|e| oidc_error(e.to_string()))?;
+
+                let metadata: ProviderMetadata = serde_json::from_str(&body)?;
+
+                if metadata.issuer.trim_end_matches('/') != self.config.issuer_url {
+                    return Err(oidc_error("issuer in discovery document does not match OIDC_ISSUER_URL"));
+                }
+
+                Ok(metadata)
+            })
+            .await
+    }
+
+    /// Builds the authorization url and the state that has to be kept in the session until the callback.
+    pub async fn begin_login(&self, lifetime_secs: u64) -> Result<(String, PendingOidcLogin), ApplicationError> {
+        let metadata = self.metadata().await?;
+
+        let pending = PendingOidcLogin {
+            state: random_hex_32(),
+            nonce: random_hex_32(),
+            // 64 characters, within the 43-128 range required by RFC 7636
+            code_verifier: format!("{}{}", random_hex_32(), random_hex_32()),
+            expires_at: now_secs() + lifetime_secs,
+        };
+
+        let code_challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(pending.code_verifier.as_bytes()));
+        let separator = if metadata.authorization_endpoint.contains('?') { '&' } else { '?' };
+
+        let url = format!(
+            "{}{}response_type=code&client_id={}&redirect_uri={}&scope={}&state={}&nonce={}&code_challenge={}&code_challenge_method=S256",
+            metadata.authorization_endpoint,
+            separator,
+            urlencoding::encode(&self.config.client_id),
+            urlencoding::encode(&self.config.redirect_url),
+            urlencoding::encode(&self.config.scopes),
+            pending.state,
+            pending.nonce,
+            code_challenge,
+        );
+
+        Ok((url, pending))
+    }
+
+    /// Exchanges the authorization code and returns who logged in.
+    pub async fn finish_login(&self, code: &str, code_verifier: &str, nonce: &str) -> Result<Option<OidcIdentity>, ApplicationError> {
+        let metadata = self.metadata().await?;
+
+        let mut form = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", self.config.redirect_url.as_str()),
+            ("client_id", self.config.client_id.as_str()),
+            ("code_verifier", code_verifier),
+        ];
+
+        if let Some(secret) = &self.config.client_secret {
+            form.push(("client_secret", secret.as_str()));
+        }
+
+        let response = self.http.post(&metadata.token_endpoint).form(&form).send().await
+            .map_err(|e| oidc_error(e.to_string()))?;
+
+        if !response.status().is_success() {
+            return Ok(None);
+        }
+
+        let body = response.text().await.map_err(|e| oidc_error(e.to_string()))?;
+        let tokens: TokenResponse = serde_json::from_str(&body)?;
+
+        // The id token comes straight from the token endpoint over TLS, so per OIDC Core 3.1.3.7
+        // the claims are validated without checking the signature
+        let mut claims = match decode_id_token_claims(&tokens.id_token) {
+            Some(claims) => claims,
+            None => return Ok(None),
+        };
+
+        if !self.validate_claims(&claims, &metadata.issuer, nonce) {
+            return Ok(None);
+        }
+
+        if let Some(userinfo_endpoint) = &metadata.userinfo_endpoint {
+            let response = self.http.get(userinfo_endpoint).bearer_auth(&tokens.access_token).send().await
+                .map_err(|e| oidc_error(e.to_string()))?;
+
+            if response.status().is_success() {
+                let body = response.text().await.map_err(|e| oidc_error(e.to_string()))?;
+
+                // Userinfo must be about the same subject, otherwise it is ignored
+                if let Ok(Value::Object(userinfo)) = serde_json::from_str::<Value>(&body)
+                    && userinfo.get("sub") == claims.get("sub")
+                {
+                    for (key, value) in userinfo {
+                        claims.entry(key).or_insert(value);
+                    }
+                }
+            }
+        }
+
+        Ok(self.identity_from_claims(&claims))
+    }
+
+    fn validate_claims(&self, claims: &Map<String, Value>, issuer: &str, nonce: &str) -> bool {
+        let issuer_matches = claims.get("iss").and_then(|v| v.as_str()) == Some(issuer);
+
+        let audience_matches = match claims.get("aud") {
+            Some(Value::String(aud)) => aud == &self.config.client_id,
+            Some(Value::Array(auds)) => auds.iter().any(|a| a.as_str() == Some(&self.config.client_id)),
+            _ => false,
+        };
+
+        let not_expired = claims
+            .get("exp")
+            .and_then(|v| v.as_u64())
+            .is_some_and(|exp| exp > now_secs());
+
+        let nonce_matches = claims.get("nonce").and_then(|v| v.as_str()) == Some(nonce);
+
+        issuer_matches && audience_matches && not_expired && nonce_matches
+    }
+
+    fn identity_from_claims(&self, claims: &Map<String, Value>) -> Option<OidcIdentity> {
+        // Accounts are matched on email, so only addresses the provider explicitly verified are accepted
+        if claims.get("email_verified").and_then(|v| v.as_bool()) != Some(true) {
+            return None;
+        }
+
+        let email = claims.get("email").and_then(|v| v.as_str())?.to_string();
+
+        let name = ["preferred_username", "name", "nickname"]
+            .iter()
+            .find_map(|key| claims.get(*key).and_then(|v| v.as_str()))
+            .unwrap_or(&email)
+            .to_string();
+
+        let is_admin = self.config.admin_claim.as_ref().map(|claim| {
+            let expected = self.config.admin_claim_value.as_deref();
+
+            match (claims.get(claim), expected) {
+                (Some(Value::Bool(value)), None) => *value,
+                (Some(Value::String(value)), Some(expected)) => value == expected,
+                (Some(Value::Array(values)), Some(expected)) => values.iter().any(|v| v.as_str() == Some(expected)),
+                _ => false,
+            }
+        });
+
+        Some(OidcIdentity { email, name, is_admin })
+    }
+}
+
+fn decode_id_token_claims(id_token: &str) -> Option<Map<String, Value>> {
+    let payload = id_token.split('.').nth(1)?;
+    let bytes = URL_SAFE_NO_PAD.decode(payload.trim_end_matches('=')).ok()?;
+
+    match serde_json::from_slice::<Value>(&bytes).ok()? {
+        Value::Object(claims) => Some(claims),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Form, Json, Router, http::StatusCode, routing::{get, post}};
+    use serde_json::json;
+    use std::collections::HashMap;
+
+    const CLIENT_ID: &str = "mesh-client";
+    const NONCE: &str = "expected-nonce";
+    const CODE: &str = "valid-code";
+    const CODE_VERIFIER: &str = "verifier";
+
+    fn run<F: Future>(future: F) -> F::Output {
+        tokio::runtime::Runtime::new().unwrap().block_on(future)
+    }
+
+    // Stands in for an identity provider: discovery, token and userinfo endpoints.
+    // The id token claims are merged over a valid default set, so tests only list what they change
+    async fn start_provider(id_token_claims: Value, userinfo: Value) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let issuer = format!("http://{}", listener.local_addr().unwrap());
+
+        let mut claims = json!({
+            "iss": issuer,
+            "aud": CLIENT_ID,
+            "sub": "user-1",
+            "exp": now_secs() + 300,
+            "nonce": NONCE,
+        });
+        claims.as_object_mut().unwrap().extend(id_token_claims.as_object().unwrap().clone());
+
+        let id_token = format!(
+            "{}.{}.signature",
+            URL_SAFE_NO_PAD.encode(br#"{"alg":"RS256"}"#),
+            URL_SAFE_NO_PAD.encode(claims.to_string())
+        );
+
+        let discovery = json!({
+            "issuer": issuer,
+            "authorization_endpoint": format!("{}/authorize", issuer),
+            "token_endpoint": format!("{}/token", issuer),
+            "userinfo_endpoint": format!("{}/userinfo", issuer),
+        });
+
+        let app = Router::new()
+            .route("/.well-known/openid-configuration", get(move || {
+                let discovery = discovery.clone();
+                async move { Json(discovery) }
+            }))
+            .route("/token", post(move |Form(form): Form<HashMap<String, String>>| {
+                let id_token = id_token.clone();
+                async move {
+                    if form.get("code").map(String::as_str) != Some(CODE)
+                        || form.get("code_verifier").map(String::as_str) != Some(CODE_VERIFIER)
+                        || form.get("client_id").map(String::as_str) != Some(CLIENT_ID) {
+                        return Err(StatusCode::BAD_REQUEST);
+                    }
+
+                    Ok(Json(json!({ "access_token": "access-token", "id_token": id_token })))
+                }
+            }))
+            .route("/userinfo", get(move || {
+                let userinfo = userinfo.clone();
+                async move { Json(userinfo) }
+            }));
+
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        issuer
+    }
+
+    fn client(issuer: &str) -> OidcClient {
+        OidcClient::new(OidcConfig {
+            issuer_url: issuer.to_string(),
+            client_id: CLIENT_ID.into(),
+            client_secret: None,
+            redirect_url: "http://localhost/api/v1/login/oidc/callback".into(),
+            scopes: "openid email profile".into(),
+            auto_provision: true,
+            admin_claim: Some("groups".into()),
+            admin_claim_value: Some("admins".into()),
+        })
+    }
+
+    fn login(id_token_claims: Value, userinfo: Value, code: &str, nonce: &str) -> Option<OidcIdentity> {
+        run(async {
+            let issuer = start_provider(id_token_claims, userinfo).await;
+            client(&issuer).finish_login(code, CODE_VERIFIER, nonce).await.unwrap()
+        })
+    }
+
+    #[test]
+    fn login_reads_identity_and_merges_userinfo() {
+        let identity = login(
+            json!({ "email": "alice@example.com", "email_verified": true }),
+            json!({ "sub": "user-1", "preferred_username": "alice", "groups": ["users", "admins"] }),
+            CODE,
+            NONCE,
+        )
+        .unwrap();
+
+        assert_eq!(identity.email, "alice@example.com");
+        assert_eq!(identity.name, "alice");
+        assert_eq!(identity.is_admin, Some(true));
+    }
+
+    #[test]
+    fn userinfo_about_another_subject_is_ignored() {
+        let identity = login(
+            json!({ "email": "alice@example.com", "email_verified": true }),
+            json!({ "sub": "user-2", "preferred_username": "mallory", "groups": ["admins"] }),
+            CODE,
+            NONCE,
+        )
+        .unwrap();
+
+        assert_eq!(identity.name, "alice@example.com");
+        assert_eq!(identity.is_admin, Some(false));
+    }
+
+    #[test]
+    fn unverified_email_is_rejected() {
+        let userinfo = json!({ "sub": "user-1" });
+
+        assert!(login(json!({ "email": "alice@example.com" }), userinfo.clone(), CODE, NONCE).is_none());
+        assert!(login(json!({ "email": "alice@example.com", "email_verified": false }), userinfo, CODE, NONCE).is_none());
+    }
+
+    #[test]
+    fn invalid_id_token_claims_are_rejected() {
+        let userinfo = json!({ "sub": "user-1" });
+        let verified = json!({ "email": "alice@example.com", "email_verified": true });
+
+        assert!(login(verified.clone(), userinfo.clone(), CODE, "other-nonce").is_none());
+        assert!(login(json!({ "email": "alice@example.com", "email_verified": true, "aud": "other-client" }), userinfo.clone(), CODE, NONCE).is_none());
+        assert!(login(json!({ "email": "alice@example.com", "email_verified": true, "exp": now_secs() - 10 }), userinfo.clone(), CODE, NONCE).is_none());
+        assert!(login(json!({ "email": "alice@example.com", "email_verified": true, "iss": "https://evil.example.com" }), userinfo.clone(), CODE, NONCE).is_none());
+        assert!(login(verified, userinfo, "wrong-code", NONCE).is_none());
+    }
+
+    #[test]
+    fn begin_login_sends_pkce_challenge() {
+        run(async {
+            let issuer = start_provider(json!({}), json!({})).await;
+            let (url, pending) = client(&issuer).begin_login(600).await.unwrap();
+
+            let challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(pending.code_verifier.as_bytes()));
+
+            assert!(url.starts_with(&format!("{}/authorize?", issuer)));
+            assert!(url.contains(&format!("state={}", pending.state)));
+            assert!(url.contains(&format!("nonce={}", pending.nonce)));
+            assert!(url.contains(&format!("code_challenge={}&code_challenge_method=S256", challenge)));
+            assert!((43..=128).contains(&pending.code_verifier.len()));
+        });
+    }
+}
@@ -0,0 +1,209 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use hmac::{Hmac, Mac};
+use rand::Rng;
+use sha1::Sha1;
+
+// RFC 6238 defaults, which is what authenticator apps assume when the uri omits them
+const TIME_STEP: u64 = 30;
+const DIGITS: u32 = 6;
+const SECRET_LENGTH: usize = 20;
+// Accept codes from one step before and after the current one to account for clock drift
+const ALLOWED_DRIFT: i64 = 1;
+const RECOVERY_CODE_COUNT: usize = 10;
+const ISSUER: &str = "Mesh Organiser";
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+pub fn generate_secret() -> String {
+    let mut bytes = [0u8; SECRET_LENGTH];
+    rand::rng().fill(&mut bytes);
+    base32_encode(&bytes)
+}
+
+pub fn otpauth_uri(secret: &str, account: &str) -> String {
+    let label = format!("{}:{}", ISSUER, account);
+
+    format!(
+        "otpauth://totp/{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+        urlencoding::encode(&label),
+        secret,
+        urlencoding::encode(ISSUER),
+        DIGITS,
+        TIME_STEP
+    )
+}
+
+fn current_step() -> i64 {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+
+    (now / TIME_STEP) as i64
+}
+
+/// Returns the time step the code matched, so the caller can reject it when it is used again.
+pub fn verify(secret: &str, code: &str, last_used_step: Option<i64>) -> Option<i64> {
+    let code = code.trim().replace(' ', "");
+
+    if code.len() != DIGITS as usize || !code.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    let code: u32 = code.parse().ok()?;
+    let secret = base32_decode(secret)?;
+
+    verify_at_step(&secret, code, last_used_step, current_step())
+}
+
+fn verify_at_step(secret: &[u8], code: u32, last_used_step: Option<i64>, step: i64) -> Option<i64> {
+    (-ALLOWED_DRIFT..=ALLOWED_DRIFT)
+        .map(|offset| step + offset)
+        .filter(|candidate| *candidate >= 0)
+        .filter(|candidate| last_used_step.is_none_or(|last| *candidate > last))
+        .find(|candidate| hotp(secret, *candidate as u64, DIGITS) == code)
+}
+
+pub fn generate_recovery_codes() -> Vec<String> {
+    let mut rng = rand::rng();
+
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let mut bytes = [0u8; 5];
+            rng.fill(&mut bytes);
+            let code = hex::encode(bytes);
+            format!("{}-{}", &code[..5], &code[5..])
+        })
+        .collect()
+}
+
+// RFC 4226, dynamic truncation of an HMAC-SHA1 over the counter
+fn hotp(secret: &[u8], counter: u64, digits: u32) -> u32 {
+    let mut mac = Hmac::<Sha1>::new_from_slice(secret).expect("HMAC accepts keys of any size");
+    mac.update(&counter.to_be_bytes());
+    let hash = mac.finalize().into_bytes();
+
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+    let binary = ((hash[offset] as u32 & 0x7f) << 24)
+        | ((hash[offset + 1] as u32) << 16)
+        | ((hash[offset + 2] as u32) << 8)
+        | (hash[offset + 3] as u32);
+
+    binary % 10u32.pow(digits)
+}
+
+fn base32_encode(bytes: &[u8]) -> String {
+    let mut output = String::new();
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+
+    for byte in bytes {
+        buffer = (buffer << 8) | *byte as u32;
+        bits += 8;
+
+        while bits >= 5 {
+            bits -= 5;
+            output.push(BASE32_ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+        }
+    }
+
+    if bits > 0 {
+        output.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+
+    output
+}
+
+fn base32_decode(input: &str) -> Option<Vec<u8>> {
+    let mut output = Vec::new();
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+
+    for c in input.chars().filter(|c| *c != '=' && !c.is_whitespace()) {
+        let value = BASE32_ALPHABET
+            .iter()
+            .position(|a| *a as char == c.to_ascii_uppercase())? as u32;
+
+        buffer = (buffer << 5) | value;
+        bits += 5;
+
+        if bits >= 8 {
+            bits -= 8;
+            output.push(((buffer >> bits) & 0xff) as u8);
+        }
+    }
+
+    Some(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RFC_SECRET: &[u8] = b"12345678901234567890";
+
+    #[test]
+    fn hotp_matches_rfc4226_vectors() {
+        let expected = [755224, 287082, 359152, 969429, 338314, 254676, 287922, 162583, 399871, 520489];
+
+        for (counter, code) in expected.iter().enumerate() {
+            assert_eq!(hotp(RFC_SECRET, counter as u64, 6), *code);
+        }
+    }
+
+    #[test]
+    fn totp_matches_rfc6238_sha1_vectors() {
+        let expected = [
+            (59, 94287082),
+            (1111111109, 7081804),
+            (1111111111, 14050471),
+            (1234567890, 89005924),
+            (2000000000, 69279037),
+            (20000000000, 65353130),
+        ];
+
+        for (time, code) in expected {
+            assert_eq!(hotp(RFC_SECRET, time / TIME_STEP, 8), code);
+        }
+    }
+
+    #[test]
+    fn accepts_codes_within_one_step_of_drift() {
+        let step = 1234567890 / TIME_STEP as i64;
+
+        for offset in -ALLOWED_DRIFT..=ALLOWED_DRIFT {
+            let code = hotp(RFC_SECRET, (step + offset) as u64, DIGITS);
+            assert_eq!(verify_at_step(RFC_SECRET, code, None, step), Some(step + offset));
+        }
+
+        for offset in [-2, 2] {
+            let code = hotp(RFC_SECRET, (step + offset) as u64, DIGITS);
+            assert_eq!(verify_at_step(RFC_SECRET, code, None, step), None);
+        }
+    }
+
+    #[test]
+    fn rejects_codes_of_already_used_steps() {
+        let step = 1234567890 / TIME_STEP as i64;
+        let code = hotp(RFC_SECRET, step as u64, DIGITS);
+
+        assert_eq!(verify_at_step(RFC_SECRET, code, Some(step), step), None);
+        assert_eq!(verify_at_step(RFC_SECRET, code, Some(step - 1), step), Some(step));
+    }
+
+    #[test]
+    fn base32_round_trips() {
+        let secret = base32_decode(&base32_encode(RFC_SECRET)).unwrap();
+
+        assert_eq!(secret, RFC_SECRET);
+        assert_eq!(base32_encode(RFC_SECRET), "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+    }
+
+    #[test]
+    fn verify_rejects_malformed_codes() {
+        let secret = base32_encode(RFC_SECRET);
+
+        assert_eq!(verify(&secret, "12345", None), None);
+        assert_eq!(verify(&secret, "abcdef", None), None);
+    }
+}
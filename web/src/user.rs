@@ -1,12 +1,19 @@
 use std::sync::Arc;
 
 use axum_login::{AuthUser as AxumAuthUser, AuthnBackend, UserId};
-use db::{db_context::DbContext, model::User, user_db};
+use db::{db_context::DbContext, model::{User, UserPermissions, normalize_recovery_code}, random_hex_32, user_db, user_totp_db, workspace_db};
 use password_auth::verify_password;
 use serde::{Deserialize, Serialize};
 use tokio::task;
 
-use crate::error::ApplicationError;
+use crate::{error::ApplicationError, oidc::OidcClient, totp};
+
+// Session key that links a login session to its row in the user_sessions table
+pub const SESSION_TRACKING_KEY: &str = "mesh_session_key";
+// Session key holding a login that passed the password check but still needs a second factor
+pub const PENDING_TOTP_KEY: &str = "mesh_pending_totp";
+// Session key holding the state, nonce and PKCE verifier of an OIDC login until the provider redirects back
+pub const PENDING_OIDC_KEY: &str = "mesh_pending_oidc";
 
 #[derive(Clone, Serialize, Deserialize)]
 pub struct AuthUser {
@@ -61,6 +68,8 @@ impl AxumAuthUser for AuthUser {
 pub enum Credentials {
     Password(PasswordCredentials),
     Token(TokenCredentials),
+    Totp(TotpCredentials),
+    Oidc(OidcCredentials),
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -74,14 +83,29 @@ pub struct TokenCredentials {
     pub token: String,
 }
 
+#[derive(Debug, Clone, Deserialize)]
+pub struct TotpCredentials {
+    pub user_id: i64,
+    pub code: Option<String>,
+    pub recovery_code: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct OidcCredentials {
+    pub code: String,
+    pub code_verifier: String,
+    pub nonce: String,
+}
+
 #[derive(Debug, Clone)]
 pub struct Backend {
     db: Arc<DbContext>,
+    oidc: Option<Arc<OidcClient>>,
 }
 
 impl Backend {
-    pub fn new(db: Arc<DbContext>) -> Self {
-        Self { db }
+    pub fn new(db: Arc<DbContext>, oidc: Option<Arc<OidcClient>>) -> Self {
+        Self { db, oidc }
     }
 
     fn convert_user(user: User) -> AuthUser {
@@ -96,6 +120,24 @@ impl Backend {
             },
         }
     }
+
+    // Provisioned accounts take their name from the identity provider, which doesn't have to be unique here
+    async fn get_unused_user_name(db: &DbContext, name: &str) -> Result<String, ApplicationError> {
+        // Names in the workspace namespace are kept free for workspaces
+        let name = match workspace_db::is_reserved_user_name(name) {
+            true => name.replacen(':', " ", 1),
+            false => name.to_string(),
+        };
+        let mut candidate = name.clone();
+        let mut suffix = 2;
+
+        while user_db::get_user_by_name(db, &candidate).await?.is_some() {
+            candidate = format!("{} ({})", name, suffix);
+            suffix += 1;
+        }
+
+        Ok(candidate)
+    }
 }
 
 impl AuthnBackend for Backend {
@@ -133,6 +175,94 @@ impl AuthnBackend for Backend {
                     None => Ok(None),
                 }
             }
+            Credentials::Totp(totp_credentials) => {
+                let user = match user_db::get_user_by_id(&self.db, totp_credentials.user_id).await? {
+                    Some(user) => user,
+                    None => return Ok(None),
+                };
+
+                let user_totp = match user_totp_db::get_totp(&self.db, user.id).await? {
+                    Some(user_totp) if user_totp.enabled => user_totp,
+                    _ => return Ok(None),
+                };
+
+                if let Some(code) = &totp_credentials.code {
+                    if let Some(step) = totp::verify(&user_totp.secret, code, user_totp.last_used_step) {
+                        if user_totp_db::set_totp_last_used_step(&self.db, user.id, step).await? {
+                            return Ok(Some(Self::convert_user(user)));
+                        }
+                    }
+                }
+
+                if let Some(recovery_code) = &totp_credentials.recovery_code {
+                    let code = normalize_recovery_code(recovery_code);
+                    let recovery_codes = user_totp_db::get_recovery_codes(&self.db, user.id).await?;
+
+                    let recovery_code_id = task::spawn_blocking(move || {
+                        recovery_codes
+                            .into_iter()
+                            .find(|(_, hash)| verify_password(&code, hash).is_ok())
+                            .map(|(id, _)| id)
+                    })
+                    .await?;
+
+                    if let Some(recovery_code_id) = recovery_code_id {
+                        if user_totp_db::use_recovery_code(&self.db, recovery_code_id).await? {
+                            return Ok(Some(Self::convert_user(user)));
+                        }
+                    }
+                }
+
+                Ok(None)
+            }
+            // Second factors are left to the identity provider, so local TOTP is not asked for here
+            Credentials::Oidc(oidc_credentials) => {
+                let oidc = match &self.oidc {
+                    Some(oidc) => oidc,
+                    None => return Ok(None),
+                };
+
+                let identity = match oidc.finish_login(&oidc_credentials.code, &oidc_credentials.code_verifier, &oidc_credentials.nonce).await? {
+                    Some(identity) => identity,
+                    None => return Ok(None),
+                };
+
+                let user = match user_db::get_user_by_email(&self.db, &identity.email).await? {
+                    Some(user) => user,
+                    None if oidc.config.auto_provision => {
+                        // The random password means the account can only log in through the identity provider until one is set
+                        let name = Self::get_unused_user_name(&self.db, &identity.name).await?;
+                        let id = user_db::add_user(&self.db, &name, &identity.email, &random_hex_32()).await?;
+                        user_db::scramble_validity_token(&self.db, id).await?;
+
+                        match user_db::get_user_by_id(&self.db, id).await? {
+                            Some(user) => user,
+                            None => return Ok(None),
+                        }
+                    }
+                    None => return Ok(None),
+                };
+
+                if user.permissions.contains(UserPermissions::Workspace) {
+                    return Ok(None);
+                }
+
+                let user = match identity.is_admin {
+                    Some(is_admin) if is_admin != user.permissions.contains(UserPermissions::Admin) => {
+                        let mut permissions = UserPermissions::from_bits_truncate(user.permissions.bits());
+                        permissions.set(UserPermissions::Admin, is_admin);
+                        user_db::set_user_permissions(&self.db, user.id, UserPermissions::from_bits_truncate(permissions.bits())).await?;
+
+                        User {
+                            permissions,
+                            ..user
+                        }
+                    }
+                    _ => user,
+                };
+
+                Ok(Some(Self::convert_user(user)))
+            }
         }
     }
 
@@ -1,9 +1,18 @@
-use std::path::PathBuf;
+use std::{path::PathBuf, sync::Arc};
 
 use service::{AppState, Configuration};
+use tower_sessions_sqlx_store::SqliteStore;
+
+use crate::{login_throttle::LoginThrottle, oidc::OidcClient, zip_downloads::ZipDownloads};
+
 pub struct WebAppState {
     pub app_state: AppState,
     pub port: u16,
+    pub session_store: SqliteStore,
+    pub login_throttle: Arc<LoginThrottle>,
+    pub trust_proxy_headers: bool,
+    pub oidc: Option<Arc<OidcClient>>,
+    pub zip_downloads: Arc<ZipDownloads>,
 }
 
 impl WebAppState {
@@ -29,6 +38,11 @@ impl Clone for WebAppState {
         WebAppState {
             app_state: self.app_state.clone(),
             port: self.port,
+            session_store: self.session_store.clone(),
+            login_throttle: Arc::clone(&self.login_throttle),
+            trust_proxy_headers: self.trust_proxy_headers,
+            oidc: self.oidc.clone(),
+            zip_downloads: Arc::clone(&self.zip_downloads),
         }
     }
 }
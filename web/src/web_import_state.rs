@@ -11,6 +11,7 @@ impl ImportStateEmitter for WebImportStateEmitter {
             ImportStatus::FinishedModels => println!("Import Status: Finished Models"),
             ImportStatus::ProcessingModels => println!("Import Status: Processing Models"),
             ImportStatus::Idle => println!("Import Status: Idle"),
+            ImportStatus::Slicing => println!("Import Status: Slicing"),
             ImportStatus::FinishedThumbnails => println!("Import Status: Finished Thumbnails"),
         }
     }
@@ -0,0 +1,134 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use axum::{
+    body::Body,
+    http::header,
+    response::{IntoResponse, Response},
+};
+use db::random_hex_32;
+use service::{mesh_service::MeshFormat, zip_stream_service::ZipStream};
+use tokio_util::io::ReaderStream;
+
+// Links are fetched right away by the browser or a slicer, this only has to outlive retries
+const FORGET_AFTER: Duration = Duration::from_secs(60 * 60);
+const TOKEN_PREFIX: &str = "stream_";
+
+#[derive(Clone)]
+pub struct ZipDownload {
+    pub user_id: i64,
+    pub model_ids: Vec<i64>,
+    pub format: Option<MeshFormat>,
+    created: Instant,
+}
+
+// Zip downloads are requested with a POST but fetched through a plain link, the selection is kept here in between
+// so the archive can be streamed when the link is opened instead of being written to a temp dir up front
+pub struct ZipDownloads {
+    entries: Mutex<HashMap<String, ZipDownload>>,
+}
+
+impl ZipDownloads {
+    pub fn new() -> Self {
+        Self {
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn add(&self, user_id: i64, model_ids: Vec<i64>, format: Option<MeshFormat>) -> String {
+        let mut entries = self.entries.lock().unwrap();
+        let now = Instant::now();
+        let token = format!("{}{}", TOKEN_PREFIX, random_hex_32());
+
+        entries.retain(|_, download| now.duration_since(download.created) < FORGET_AFTER);
+        entries.insert(
+            token.clone(),
+            ZipDownload {
+                user_id,
+                model_ids,
+                format,
+                created: now,
+            },
+        );
+
+        token
+    }
+
+    pub fn get(&self, token: &str) -> Option<ZipDownload> {
+        if !token.starts_with(TOKEN_PREFIX) {
+            return None;
+        }
+
+        let entries = self.entries.lock().unwrap();
+
+        entries
+            .get(token)
+            .filter(|download| download.created.elapsed() < FORGET_AFTER)
+            .cloned()
+    }
+}
+
+// The quoted filename is an ascii fallback for old clients, filename* carries the full utf-8 name (RFC 6266)
+pub fn attachment_disposition(file_name: &str) -> String {
+    let mut fallback = String::with_capacity(file_name.len());
+
+    for c in file_name.chars() {
+        match c {
+            '"' | '\\' => {
+                fallback.push('\\');
+                fallback.push(c);
+            }
+            c if c.is_ascii() && !c.is_ascii_control() => fallback.push(c),
+            _ => fallback.push('_'),
+        }
+    }
+
+    format!(
+        "attachment; filename=\"{}\"; filename*=UTF-8''{}",
+        fallback,
+        urlencoding::encode(file_name)
+    )
+}
+
+pub fn zip_stream_response(stream: ZipStream) -> Response {
+    let mut response = Body::from_stream(ReaderStream::new(stream.reader)).into_response();
+    let headers = response.headers_mut();
+
+    if let Ok(disposition) = attachment_disposition(&stream.file_name).parse() {
+        headers.insert(header::CONTENT_DISPOSITION, disposition);
+    }
+
+    headers.insert(header::CONTENT_TYPE, "application/zip".parse().unwrap());
+
+    if let Some(content_length) = stream.content_length {
+        headers.insert(header::CONTENT_LENGTH, content_length.into());
+        // Keeps the compression layer from dropping the length, which lets the browser show download progress
+        headers.insert(header::CONTENT_ENCODING, "identity".parse().unwrap());
+    }
+
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quotes_and_backslashes_are_escaped() {
+        assert_eq!(
+            attachment_disposition("a \"b\" \\c.zip"),
+            "attachment; filename=\"a \\\"b\\\" \\\\c.zip\"; filename*=UTF-8''a%20%22b%22%20%5Cc.zip"
+        );
+    }
+
+    #[test]
+    fn non_ascii_names_are_kept_in_the_extended_filename() {
+        let disposition = attachment_disposition("Würfel\r\n.zip");
+
+        assert_eq!(disposition, "attachment; filename=\"W_rfel__.zip\"; filename*=UTF-8''W%C3%BCrfel%0D%0A.zip");
+        assert!(disposition.parse::<axum::http::HeaderValue>().is_ok());
+    }
+}